@@ -30,8 +30,9 @@ use autonomi::PointerAddress;
 use autonomi::ScratchpadAddress;
 
 use dweb::autonomi::args::max_fee_per_gas::MaxFeePerGasParam;
+use dweb::files::directory::{validate_version_tag, VersionManifest};
 use dweb::helpers::convert::*;
-use dweb::history::HistoryAddress;
+use dweb::history::{validate_branch_name, HistoryAddress};
 use dweb::token::ShowCost;
 use dweb::web::name::validate_dweb_name;
 
@@ -55,6 +56,17 @@ pub struct Opt {
     #[clap(long, conflicts_with("alpha"))]
     pub local: bool,
 
+    /// Connect to a network described by a network profile, such as a private testnet.
+    /// See 'dweb network --help'
+    #[clap(long, value_name = "NAME", conflicts_with_all(["alpha", "local"]))]
+    pub network: Option<String>,
+
+    /// Read settings from this file in addition to dweb.toml in the Autonomi client data
+    /// directory. Command line options and environment variables override settings from
+    /// either file. See 'dweb config show'.
+    #[clap(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
     /// Available sub commands
     #[command(subcommand)]
     pub cmd: Option<Subcommands>,
@@ -97,18 +109,17 @@ pub struct Opt {
     // TODO implement remaining CLI options:
     // TODO --wallet-path <path-to-wallet-dir>
     /// Show the cost of dweb API calls after each call in tokens, gas, both or none
-    #[clap(long, hide = true, default_value = "both")]
-    pub show_dweb_costs: ShowCost,
+    #[clap(long, hide = true)]
+    pub show_dweb_costs: Option<ShowCost>,
     /// Retry failed file uploads automatically after 1 minute pause.
     /// This will persistently retry any failed chunks until all data is successfully uploaded.
     /// 0 for no retries (same as with ant-cli, so different from --retry-api)
     #[arg(long)]
-    #[clap(default_value = "0")]
-    pub retry_file_uploads: u64,
+    pub retry_file_uploads: Option<u64>,
     //
-    /// Control API call retries (0 for unlimited tries - note the difference from --retry-failed)
-    #[clap(long, hide = true, default_value = "0")]
-    pub retry_api: u32,
+    /// Control API call tries (0 for unlimited tries, up to a time limit - note the difference from --retry-failed)
+    #[clap(long, hide = true)]
+    pub retry_api: Option<u32>,
     // Control API use of Pointers for versioned operations (e.g. for History and Registers).
     //
     // Using true can help accessing a History whose pointer has not updated.
@@ -165,6 +176,35 @@ pub enum Subcommands {
         command: ServerCommands,
     },
 
+    /// Manage network profiles, used to connect to networks such as a private testnet
+    ///
+    /// A network profile gives the peers used to join a network and its EVM payment settings.
+    /// Profiles are saved in dweb.toml in the Autonomi client data directory, and one can be
+    /// used with: 'dweb --network NAME <COMMAND>'
+    Network {
+        #[command(subcommand)]
+        command: NetworkCommands,
+    },
+
+    /// Show the settings used by dweb
+    ///
+    /// Settings are read from dweb.toml in the Autonomi client data directory, a file given
+    /// by --config, environment variables and command line options, each overriding those before.
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+
+    /// Install dweb as a service which starts the dweb server when you log in (Linux)
+    ///
+    /// Uses a systemd user service if available, or otherwise a supervisor script
+    /// started on login. Network options such as --local are used by the installed
+    /// service, so use: 'dweb --local service install' for a local testnet.
+    Service {
+        #[command(subcommand)]
+        command: ServiceCommands,
+    },
+
     /// Open a browser to view a website on Autonomi (requires 'dweb serve' running)
     ///
     /// You must have already started a server by typing 'dweb serve' in a different
@@ -284,6 +324,16 @@ pub enum Subcommands {
         /// You can either specify a path here or include the settings in <FILES-ROOT>/.dweb/dweb-settings.json
         #[clap(long = "dweb-settings", short = 'c', value_name = "JSON-FILE")]
         dweb_settings: Option<PathBuf>,
+        #[command(flatten)]
+        annotation: VersionAnnotationArgs,
+        /// Don't show the progress of the upload
+        #[clap(long = "no-progress", default_value = "false")]
+        no_progress: bool,
+        /// Upload the directory in a single operation. This makes fewer payments than the
+        /// default of paying for each file separately, but an interrupted upload can't resume
+        /// from the files already stored (see 'dweb publish-status')
+        #[clap(long, default_value = "false")]
+        batch: bool,
         /// Disable the AWV check when publishing a new website to allow for init of a new Autonomi network (during beta)
         #[clap(long, name = "is-new-network", hide = true, default_value = "false")]
         is_new_network: bool,
@@ -304,11 +354,143 @@ pub enum Subcommands {
         /// Defaults to use the name of the website directory (FILES-ROOT)
         #[clap(long, short = 'n')]
         name: Option<String>,
+        /// Publish to a named BRANCH of the History (such as 'staging') instead of the main line.
+        /// The branch is created if it doesn't exist, and is viewed using 'v--BRANCH' in place of
+        /// a version (or 'v3--BRANCH' for version 3 of the branch). See 'dweb promote'.
+        #[clap(long, value_name = "BRANCH", value_parser = str_to_branch_name)]
+        branch: Option<String>,
         /// Optional configuration when uploading content for a website. This can specify alternative
         /// default index file(s), redirects etc.
         /// You can either specify a path here or include the settings in <FILES-ROOT>/.dweb/dweb-settings.json
         #[clap(long = "dweb-settings", short = 'c', value_name = "JSON-FILE")]
         dweb_settings: Option<PathBuf>,
+        #[command(flatten)]
+        annotation: VersionAnnotationArgs,
+        /// Don't show the progress of the upload
+        #[clap(long = "no-progress", default_value = "false")]
+        no_progress: bool,
+        /// Upload the directory in a single operation. This makes fewer payments than the
+        /// default of paying for each file separately, but an interrupted upload can't resume
+        /// from the files already stored (see 'dweb publish-status')
+        #[clap(long, default_value = "false")]
+        batch: bool,
+    },
+
+    /// Show any interrupted publish which can be resumed.
+    ///
+    /// When a publish is interrupted, the files already stored are recorded so that
+    /// repeating 'dweb publish-new' or 'dweb publish-update' with the same FILES-ROOT
+    /// skips them and only pays for what is left.
+    #[allow(non_camel_case_types)]
+    Publish_status {
+        /// The directory being published. If omitted, all interrupted publishes are shown
+        #[clap(long = "files-root", value_name = "FILES-ROOT")]
+        files_root: Option<PathBuf>,
+    },
+
+    /// Discard the record of an interrupted publish, so that publishing FILES-ROOT starts again
+    #[allow(non_camel_case_types)]
+    Publish_abort {
+        /// The directory being published
+        #[clap(long = "files-root", value_name = "FILES-ROOT")]
+        files_root: PathBuf,
+    },
+
+    /// Upload a directory to private storage on Autonomi, keeping every version.
+    ///
+    /// Each upload is stored as a new version of a private History owned by you. Unlike
+    /// 'dweb publish-new', the files can only be read by you, or by those you share them
+    /// with using 'dweb share'. Pays using the default wallet.
+    Upload {
+        /// The root directory of the files to be uploaded
+        #[clap(long = "files-root", value_name = "FILES-ROOT")]
+        files_root: PathBuf,
+        /// The NAME of the private History, for use with 'dweb share' and 'dweb sync'.
+        /// Defaults to the name of the directory (FILES-ROOT)
+        #[clap(long, short = 'n')]
+        name: Option<String>,
+        /// Don't show the progress of the upload
+        #[clap(long = "no-progress", default_value = "false")]
+        no_progress: bool,
+        /// Upload the directory in a single operation. This makes fewer payments than the
+        /// default of paying for each file separately, but an interrupted upload can't resume
+        /// from the files already stored
+        #[clap(long, default_value = "false")]
+        batch: bool,
+    },
+
+    /// Share private storage created by 'dweb upload' or 'dweb sync'.
+    ///
+    /// By default shares the most recent version by printing its DATAMAP, which gives
+    /// access to every file in that version but nothing else. Use --path to share a
+    /// single file or directory, or --all-versions to share the whole History including
+    /// versions added later.
+    ///
+    /// Anyone you give a DATAMAP or HISTORY-KEY to can read the data, so share it only
+    /// with those you trust, and only over a private channel unless you want it public.
+    Share {
+        /// The NAME of the private History
+        #[clap(long, short = 'n')]
+        name: String,
+        /// The version to share. Defaults to the most recent
+        #[clap(long, short = 'v', name = "VERSION")]
+        version: Option<u64>,
+        /// A file or directory within the version to share. Sharing a directory stores
+        /// a new archive of the files beneath it, which is paid for using the default wallet
+        #[clap(long, value_name = "PATH", conflicts_with = "all_versions")]
+        path: Option<String>,
+        /// Share every version, by printing the HISTORY-ADDRESS and the HISTORY-KEY which decrypts it
+        #[clap(
+            long = "all-versions",
+            default_value = "false",
+            conflicts_with = "VERSION"
+        )]
+        all_versions: bool,
+        /// The host used in links. Defaults to "127.0.0.1"
+        /// This is only needed when not using defaults, so hidden to de-clutter the CLI help
+        #[clap(hide = true, long, value_name = "HOST", value_parser = parse_host)]
+        host: Option<String>,
+        /// The port used in links (on localhost by default)
+        /// This is only needed when not using defaults, so hidden to de-clutter the CLI help
+        #[clap(hide = true, long, value_name = "PORT", value_parser = parse_port_number)]
+        port: Option<u16>,
+    },
+
+    /// Sync a local directory with private storage on Autonomi in both directions.
+    ///
+    /// Files changed locally since the last sync are uploaded and published as a new
+    /// version, and files changed in the most recent version are downloaded. Files
+    /// changed in both places are reported as conflicts and left unchanged unless
+    /// --keep-local or --keep-remote is given. Pays using the default wallet.
+    ///
+    /// To sync from a History shared with you, use --from and --key. Changes are then
+    /// only downloaded.
+    Sync {
+        /// The local directory to sync
+        #[clap(long = "files-root", value_name = "FILES-ROOT")]
+        files_root: PathBuf,
+        /// The NAME of your private History. Defaults to the name of the directory (FILES-ROOT)
+        #[clap(long, short = 'n', conflicts_with = "from")]
+        name: Option<String>,
+        /// The HISTORY-ADDRESS of a History shared with you using 'dweb share --all-versions'
+        #[clap(long, value_name = "HISTORY-ADDRESS", value_parser = str_to_history_address, requires = "key")]
+        from: Option<HistoryAddress>,
+        /// The HISTORY-KEY of a History shared with you
+        #[clap(long, value_name = "HISTORY-KEY", requires = "from")]
+        key: Option<String>,
+        /// Resolve conflicts using the local file
+        #[clap(
+            long = "keep-local",
+            default_value = "false",
+            conflicts_with = "keep_remote"
+        )]
+        keep_local: bool,
+        /// Resolve conflicts using the file from private storage
+        #[clap(long = "keep-remote", default_value = "false")]
+        keep_remote: bool,
+        /// Show what would be changed without changing anything
+        #[clap(long = "dry-run", default_value = "false")]
+        dry_run: bool,
     },
 
     /// Download a file or directory. TODO: not yet implemented
@@ -392,6 +574,16 @@ pub enum Subcommands {
         #[clap(long = "graph-with-keys", short = 'k', default_value = "false")]
         graph_keys: bool,
 
+        /// For each entry in RANGE print the version annotations (message, author,
+        /// publish time and tags) recorded when the version was published
+        #[clap(
+            long = "annotations",
+            short = 'a',
+            default_value = "false",
+            requires = "entries_range"
+        )]
+        include_annotations: bool,
+
         #[command(flatten)]
         files_args: FilesArgs,
     },
@@ -415,6 +607,101 @@ pub enum Subcommands {
         graph_keys: bool,
     },
 
+    /// Publish a version of a branch as the next version of the main line.
+    ///
+    /// Use this to make content published with 'dweb publish-update --branch' live
+    /// once it has been checked.
+    #[allow(non_camel_case_types)]
+    Promote {
+        /// The NAME used when the website was first published.
+        #[clap(long, short = 'n')]
+        name: String,
+
+        /// The branch to promote
+        #[clap(long, value_name = "BRANCH", value_parser = str_to_branch_name)]
+        branch: String,
+
+        /// The version of the branch to promote. Defaults to the most recent
+        #[clap(long)]
+        version: Option<u64>,
+    },
+
+    /// Hand a website over to a History owned by a different key.
+    ///
+    /// Use this when a website passes to a new team, or if your key may have been
+    /// compromised. The new owner first publishes the website with their own key, then
+    /// you give the address of that History here. A final entry signed with your key is
+    /// added and the website can no longer be published from it. Anyone opening the
+    /// website follows the hand-over, with the new owner's versions numbered after yours.
+    #[allow(non_camel_case_types)]
+    Hand_over {
+        /// The NAME used when the website was first published.
+        #[clap(long, short = 'n')]
+        name: String,
+
+        /// The address of the History which continues the website
+        #[clap(long, value_name = "HISTORY-ADDRESS", value_parser = str_to_history_address)]
+        to: HistoryAddress,
+    },
+
+    /// Find and repair the most recent fork in a History.
+    ///
+    /// A History forks when two devices publish to it at the same time, after which only
+    /// one branch is read and new versions cannot be published. Without --branch this
+    /// prints the branches of the fork. With --branch a merge entry is added after the
+    /// head of that branch, making it the branch that continues. Versions on the other
+    /// branches remain on the network and are shown by 'dweb inspect-history'.
+    #[allow(non_camel_case_types)]
+    Repair_history {
+        /// The NAME used when the website was first published.
+        /// If none was given then, this will be the name of the website directory (FILES-ROOT)
+        #[clap(long, short = 'n')]
+        name: String,
+
+        /// The number of the branch to continue, as shown when run without this option
+        #[clap(long)]
+        branch: Option<usize>,
+
+        /// Shorten GraphEntry hex strings to the first six characters plus '..'
+        #[clap(long = "brief", short = 'b', default_value = "false")]
+        shorten_hex_strings: bool,
+    },
+
+    /// Create a new History from the values of an Autonomi Register.
+    ///
+    /// Each value in the Register is treated as the address of a website archive
+    /// and published in order as a version of a new History owned by you. The Register
+    /// is not modified. Afterwards the website can be updated using 'dweb publish-update'
+    /// with the same NAME.
+    ///
+    /// Note: a Register can also be viewed directly, without migration, by using its
+    /// address in place of a HISTORY-ADDRESS, although it cannot then be updated by dweb.
+    #[allow(non_camel_case_types)]
+    Migrate_register {
+        /// The address of a Register on Autonomi
+        #[clap(name = "REGISTER-ADDRESS", value_parser = str_to_history_address)]
+        register_address: HistoryAddress,
+
+        /// The NAME for the new History, used when publishing updates
+        #[clap(long, short = 'n')]
+        name: String,
+    },
+
+    /// Create an Autonomi Register holding the archive address of every version of a History.
+    ///
+    /// The Register is owned by you and its key is derived from REGISTER-NAME in the same
+    /// way as other Register based apps, so they can read and update it.
+    #[allow(non_camel_case_types)]
+    Export_register {
+        /// The HISTORY-ADDRESS of a History, or the NAME you used when publishing it
+        #[clap(name = "HISTORY-ADDRESS-OR-NAME")]
+        address_or_name: String,
+
+        /// The name used to derive the key of the new Register
+        #[clap(long = "register-name", short = 'r')]
+        register_name: String,
+    },
+
     /// Print information about a GraphEntry stored on Autonomi.
     ///
     /// Note: descendents are shown as public keys rather than addresses. This is for
@@ -493,6 +780,99 @@ pub enum Subcommands {
     },
 }
 
+#[derive(Subcommand, Debug)]
+pub enum ServiceCommands {
+    /// Install and start the dweb service, replacing any already installed
+    #[command(
+        after_help = "Server concurrency: set maximum number of parallel requests via --workers (default: DWEB_WORKERS if set, otherwise 12)"
+    )]
+    Install {
+        /// The host address on which the server will listen. Defaults to "127.0.0.1"
+        #[clap(long, value_name = "HOST", value_parser = parse_host)]
+        host: Option<String>,
+        /// The port that the server will listen on. Defaults to 5537
+        #[clap(long, value_name = "PORT", value_parser = parse_port_number)]
+        port: Option<u16>,
+        /// The maximum number of parallel requests handled by the server (sets DWEB_WORKERS)
+        #[clap(long, value_name = "WORKERS")]
+        workers: Option<usize>,
+        /// Write server output to 'dweb-server-PORT.log' in this directory instead of the
+        /// systemd journal (or with no systemd, the 'logs' directory of the Autonomi client
+        /// data directory)
+        #[clap(long, value_name = "LOGDIR")]
+        logdir: Option<PathBuf>,
+        /// Only write the service files without registering or starting the service
+        #[clap(long, default_value = "false")]
+        no_start: bool,
+    },
+
+    /// Stop the dweb service and remove it so it no longer starts on login
+    Uninstall {},
+
+    /// Show whether the dweb service is installed and running
+    Status {},
+}
+
+#[derive(Subcommand, Debug)]
+pub enum NetworkCommands {
+    /// List the network profiles
+    List {},
+
+    /// Add a network profile, replacing any with the same name
+    Add {
+        /// The name used to select the network with: 'dweb --network NAME'
+        name: String,
+        /// The multiaddr of a peer used to join the network (can be repeated)
+        #[clap(long = "peer", value_name = "MULTIADDR")]
+        peers: Vec<String>,
+        /// The URL of a list of peers used to join the network (can be repeated)
+        #[clap(long = "contacts-url", value_name = "URL")]
+        network_contacts_url: Vec<String>,
+        /// Look for peers on this machine (for a local testnet)
+        #[clap(long, default_value = "false")]
+        local: bool,
+        /// The EVM network used for payments, ignored if --rpc-url is given
+        #[clap(long, value_name = "EVM-NETWORK", value_parser = ["arbitrum-one", "arbitrum-sepolia-test"])]
+        evm_network: Option<String>,
+        /// The RPC URL of a custom EVM network
+        #[clap(long, value_name = "URL", requires_all(["payment_token_address", "data_payments_address"]))]
+        rpc_url: Option<String>,
+        /// The address of the payment token contract of a custom EVM network
+        #[clap(long, value_name = "ADDRESS", requires("rpc_url"))]
+        payment_token_address: Option<String>,
+        /// The address of the data payments contract of a custom EVM network
+        #[clap(long, value_name = "ADDRESS", requires("rpc_url"))]
+        data_payments_address: Option<String>,
+        /// The number of peers which must return a record
+        #[clap(long, value_name = "QUORUM", value_parser = ["one", "majority", "all"])]
+        get_quorum: Option<String>,
+        /// The number of peers which must store a record
+        #[clap(long, value_name = "QUORUM", value_parser = ["one", "majority", "all"])]
+        put_quorum: Option<String>,
+        /// Retries of failed network operations
+        #[clap(long, value_name = "RETRY", value_parser = ["none", "quick", "balanced", "persistent"])]
+        retry: Option<String>,
+    },
+
+    /// Remove a network profile
+    Remove {
+        /// The name of the network profile
+        name: String,
+    },
+
+    /// Set the network dweb connects to when --network, --local and --alpha are not given
+    Use {
+        /// The name of a network profile, or omit to use the public network
+        name: Option<String>,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum ConfigCommands {
+    /// Print the effective settings in dweb.toml format, including defaults
+    Show {},
+}
+
 #[derive(Subcommand, Debug)]
 #[clap(hide = true)]
 pub enum ServerCommands {
@@ -509,17 +889,18 @@ pub enum ServerCommands {
         /// This is only needed when not using the default
         #[clap(long, value_name = "PORT", value_parser = parse_port_number)]
         port: Option<u16>,
-        /// Keep thethe server in the foreground. Defaults to starting the server in the background
+        /// Keep the server in the foreground. Defaults to starting the server in the background
         #[clap(long, default_value = "false")]
         foreground: bool,
         /// Write server output to a file in the given directory which must exist. The logfile
         /// name will be 'dweb-server-PORT.log' where PORT is the port the server is listening
-        /// on.
+        /// on. In the background this defaults to the 'logs' directory of the Autonomi client
+        /// data directory.
         #[clap(long)]
         logdir: Option<String>,
     },
 
-    /// TODO
+    /// Stop a dweb server gracefully, once it has finished handling current requests
     Stop {
         /// Stop the server on the given PORT, or specify "all" to stop all dweb servers
         /// Note: if you stop the main dweb server this will also stop all the servers which that
@@ -528,7 +909,8 @@ pub enum ServerCommands {
         port_or_all: String,
     },
 
-    /// TODO
+    /// Show a dweb server's port, process, network and the listeners it has started for
+    /// each directory/website opened, with their History address and version
     Info {
         /// Get information about the specified server (on PORT), or specify "all" to get information on all servers
         #[clap(value_name = "PORT-OR-ALL")]
@@ -536,6 +918,38 @@ pub enum ServerCommands {
     },
 }
 
+/// Annotations recorded with a published version of a History
+#[derive(Args, Debug)]
+pub struct VersionAnnotationArgs {
+    /// A message describing this version, shown by 'dweb inspect-history' and /dweb-info
+    #[clap(long, short = 'm', value_name = "MESSAGE")]
+    pub message: Option<String>,
+
+    /// A label identifying who published this version
+    #[clap(long, value_name = "AUTHOR")]
+    pub author: Option<String>,
+
+    /// A tag for this version, which can be used instead of a version number (e.g. /dweb-open/v<TAG>/...).
+    /// Tags begin with a letter and may contain letters, digits, '-', '_' or '.'. Can be repeated.
+    #[clap(long = "tag", short = 't', value_name = "TAG", value_parser = str_to_version_tag)]
+    pub tags: Vec<String>,
+}
+
+impl VersionAnnotationArgs {
+    /// A VersionManifest for the new version, or None if no annotations were given
+    pub fn version_manifest(&self) -> Result<Option<VersionManifest>> {
+        if self.message.is_none() && self.author.is_none() && self.tags.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(VersionManifest::new(
+            self.message.clone(),
+            self.author.clone(),
+            self.tags.clone(),
+        )?))
+    }
+}
+
 #[derive(Args, Debug)]
 pub struct FilesArgs {
     /// Print the path of each file
@@ -616,6 +1030,16 @@ fn str_to_entries_range(s: &str) -> Result<EntriesRange> {
     Ok(EntriesRange { start, end })
 }
 
+fn str_to_version_tag(s: &str) -> Result<String> {
+    validate_version_tag(s)?;
+    Ok(s.to_string())
+}
+
+fn str_to_branch_name(s: &str) -> Result<String> {
+    validate_branch_name(s)?;
+    Ok(s.to_string())
+}
+
 // pub fn get_app_name() -> String {
 //     String::from(???)
 // }
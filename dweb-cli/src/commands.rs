@@ -2,6 +2,13 @@ pub(crate) mod cmd_browse;
 pub(crate) mod cmd_heal_history;
 pub(crate) mod cmd_inspect;
 pub(crate) mod cmd_name;
+pub(crate) mod cmd_network;
+pub(crate) mod cmd_private;
+pub(crate) mod cmd_publish;
+pub(crate) mod cmd_register;
+pub(crate) mod cmd_repair_history;
+pub(crate) mod cmd_service;
 pub(crate) mod helpers;
+pub(crate) mod progress;
 pub(crate) mod server;
 pub(crate) mod subcommands;
@@ -26,10 +26,12 @@ use autonomi::{GraphEntry, GraphEntryAddress, Pointer, PointerAddress};
 use autonomi::{Scratchpad, ScratchpadAddress};
 
 use dweb::client::DwebClient;
-use dweb::files::directory::Tree;
+use dweb::files::directory::{Tree, VersionManifest};
 use dweb::helpers::convert::tuple_from_address_or_name;
 use dweb::helpers::graph_entry::graph_entry_get;
-use dweb::history::{get_and_verify_pointer, pointer_address_from_history_address, History};
+use dweb::history::{
+    get_and_verify_pointer, pointer_address_from_history_address, History, HistoryFork,
+};
 
 use crate::cli_options::{EntriesRange, FilesArgs};
 
@@ -40,6 +42,7 @@ pub async fn handle_inspect_history(
     print_history_full: bool,
     entries_range: Option<EntriesRange>,
     include_files: bool,
+    include_annotations: bool,
     graph_keys: bool,
     shorten_hex_strings: bool,
     files_args: FilesArgs,
@@ -65,6 +68,19 @@ pub async fn handle_inspect_history(
         };
 
     print_history(&client, &history, print_history_full, shorten_hex_strings);
+    match history.detect_forks().await {
+        Ok(forks) => {
+            print_history_forks(
+                &client,
+                &forks,
+                print_history_full,
+                shorten_hex_strings,
+                &history,
+            )
+            .await?
+        }
+        Err(e) => println!("  forks          : failed to check for forks - {e}"),
+    }
     if let Some(entries_range) = entries_range {
         let size = history.num_entries();
         let first = if entries_range.start.is_some() {
@@ -114,11 +130,20 @@ pub async fn handle_inspect_history(
             .await?;
             let archive_address_hex = hex::encode(entry_iter.content);
             let archive_address = ArchiveAddress::from_hex(&archive_address_hex)?;
-            if include_files {
+            // Entry 0 holds the History type rather than a directory so has no annotations
+            if include_files || (include_annotations && index > 0) {
                 println!("    entry {index} - fetching content at {archive_address_hex}");
                 match Tree::from_archive_address(&client, archive_address).await {
-                    Ok(directory) => {
-                        let _ = print_files("      ", &directory, &files_args);
+                    Ok(mut directory) => {
+                        if include_annotations {
+                            print_version_manifest(
+                                "    ",
+                                directory.load_version_manifest(&client).await,
+                            );
+                        }
+                        if include_files {
+                            let _ = print_files("      ", &directory, &files_args);
+                        }
                     }
                     Err(e) => {
                         println!("Failed to get website directory from network");
@@ -196,6 +221,12 @@ fn print_history(
 
     println!("  type           : {type_string}",);
     println!("  size           : {}", history.num_entries());
+    if history.version_offset() > 0 {
+        println!(
+            "  succeeds       : {} versions of earlier Histories (handed over)",
+            history.version_offset()
+        );
+    }
 
     if full {
         println!("  pointer address: {pointer_string}");
@@ -204,12 +235,125 @@ fn print_history(
     }
 }
 
+async fn print_history_forks(
+    client: &DwebClient,
+    forks: &Vec<HistoryFork>,
+    full: bool,
+    shorten_hex_strings: bool,
+    history: &History<Tree>,
+) -> Result<()> {
+    let unrepaired = forks.iter().filter(|fork| !fork.is_repaired()).count();
+    if forks.is_empty() {
+        println!("  forks          : none");
+        return Ok(());
+    }
+    println!(
+        "  forks          : {} ({unrepaired} not repaired)",
+        forks.len()
+    );
+    for fork in forks {
+        print_history_fork(client, "    ", fork, full, shorten_hex_strings, history).await?;
+    }
+    if unrepaired > 0 {
+        println!(
+            "  Use 'dweb repair-history' to choose which branch of the most recent fork continues"
+        );
+    }
+    Ok(())
+}
+
+/// Print each branch of a forked History
+pub async fn print_history_fork(
+    client: &DwebClient,
+    indent: &str,
+    fork: &HistoryFork,
+    full: bool,
+    shorten_hex_strings: bool,
+    history: &History<Tree>,
+) -> Result<()> {
+    let state = if fork.is_repaired() {
+        "repaired"
+    } else {
+        "not repaired"
+    };
+    println!(
+        "{indent}fork at entry {} ({state}), address {}",
+        fork.entry_index,
+        fork.address.to_hex()
+    );
+
+    let canonical = fork.canonical_branch();
+    let entry_indent = format!("{indent}    ");
+    for (branch_index, branch) in fork.branches.iter().enumerate() {
+        let followed = if branch_index == canonical {
+            " (followed)"
+        } else {
+            ""
+        };
+        println!(
+            "{indent}  branch {branch_index}{followed}, {} entries:",
+            branch.num_entries()
+        );
+        let mut index = fork.entry_index;
+        for entry in branch.entries.iter() {
+            println!("{indent}    entry {index:4.}:");
+            print_graphentry(
+                client,
+                &entry_indent,
+                entry,
+                false,
+                full,
+                shorten_hex_strings,
+                Some(history),
+            )
+            .await?;
+            index = index + 1;
+        }
+        if let Some(merge_entry) = &branch.merge_entry {
+            println!("{indent}    entry {index:4.} (merge):");
+            print_graphentry(
+                client,
+                &entry_indent,
+                merge_entry,
+                false,
+                full,
+                shorten_hex_strings,
+                Some(history),
+            )
+            .await?;
+        }
+    }
+    Ok(())
+}
+
+fn print_version_manifest(indent: &str, version_manifest: Option<&VersionManifest>) {
+    let manifest = match version_manifest {
+        Some(manifest) => manifest,
+        None => {
+            println!("{indent}annotations: none");
+            return;
+        }
+    };
+
+    println!("{indent}annotations:");
+    println!(
+        "{indent}  message    : {}",
+        manifest.message.clone().unwrap_or_default()
+    );
+    println!(
+        "{indent}  author     : {}",
+        manifest.author.clone().unwrap_or_default()
+    );
+    println!("{indent}  published  : {}", manifest.published_string());
+    println!("{indent}  tags       : {}", manifest.tags.join(", "));
+}
+
 /// Implement 'inspect-pointer' subcommand
 pub async fn handle_inspect_pointer(
     client: DwebClient,
     pointer_address: PointerAddress,
 ) -> Result<()> {
-    let pointer = match client.client.pointer_get(&pointer_address).await {
+    let pointer = match client.backend.pointer_get(&pointer_address).await {
         Ok(pointer) => pointer,
         Err(e) => {
             let message = format!("Failed to get Pointer from network - {e}");
@@ -235,7 +379,7 @@ pub async fn handle_inspect_scratchpad(
     scratchpad_address: ScratchpadAddress,
     data_as_text: bool,
 ) -> Result<()> {
-    let scratchpad = match client.client.scratchpad_get(&scratchpad_address).await {
+    let scratchpad = match client.backend.scratchpad_get(&scratchpad_address).await {
         Ok(scratchpad) => scratchpad,
         Err(e) => {
             let message = format!("Failed to get Scratchpad from network - {e}");
@@ -276,7 +420,7 @@ pub async fn handle_inspect_graphentry(
     full: bool,
     shorten_hex_strings: bool,
 ) -> Result<()> {
-    let graph_entry = graph_entry_get(&client.client, &graph_entry_address, false).await?;
+    let graph_entry = graph_entry_get(client.backend.as_ref(), &graph_entry_address, false).await?;
 
     print_graphentry(
         &client,
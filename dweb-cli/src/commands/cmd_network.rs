@@ -0,0 +1,101 @@
+/*
+ Copyright (c) 2025 Mark Hughes
+
+ This program is free software: you can redistribute it and/or modify
+ it under the terms of the GNU Affero General Public License as published by
+ the Free Software Foundation, either version 3 of the License, or
+ (at your option) any later version.
+
+ This program is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY; without even the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU Affero General Public License for more details.
+
+ You should have received a copy of the GNU Affero General Public License
+ along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use color_eyre::{eyre::eyre, Result};
+
+use dweb::config::{edit_default_config_file, DwebConfig, NetworkProfile};
+
+/// List the network profiles, marking the one used by default
+pub fn handle_list(config: &DwebConfig) -> Result<()> {
+    if config.networks.is_empty() {
+        println!("No network profiles. See 'dweb network add --help'");
+        return Ok(());
+    }
+
+    for (name, profile) in &config.networks {
+        let marker = if config.network.name.as_ref() == Some(name) {
+            "*"
+        } else {
+            " "
+        };
+        let evm_network = match &profile.rpc_url {
+            Some(rpc_url) => format!("custom EVM at {rpc_url}"),
+            None => profile
+                .evm_network
+                .clone()
+                .unwrap_or("arbitrum-one".to_string()),
+        };
+        let local = if profile.is_local() { ", local" } else { "" };
+        println!(
+            "{marker} {name}: {} peers, {evm_network}{local}",
+            profile.peers.len()
+        );
+    }
+    Ok(())
+}
+
+/// Add or replace a network profile in dweb.toml
+pub fn handle_add(mut profile: NetworkProfile) -> Result<()> {
+    profile.validate()?;
+    let name = profile.name.clone();
+    let path = edit_default_config_file(|config| {
+        config.networks.insert(profile.name.clone(), profile);
+        Ok(())
+    })?;
+    println!("Network '{name}' saved in {path:?}");
+    println!("To use it: dweb --network {name} <COMMAND>");
+    Ok(())
+}
+
+/// Remove a network profile from dweb.toml
+pub fn handle_remove(name: &String) -> Result<()> {
+    let path = edit_default_config_file(|config| {
+        if config.networks.remove(name).is_none() {
+            return Err(eyre!("No network profile named '{name}'"));
+        }
+        if config.network.name.as_ref() == Some(name) {
+            config.network.name = None;
+        }
+        Ok(())
+    })?;
+    println!("Network '{name}' removed from {path:?}");
+    Ok(())
+}
+
+/// Set the network used by default in dweb.toml
+pub fn handle_use(name: Option<String>) -> Result<()> {
+    let path = edit_default_config_file(|config| {
+        if let Some(name) = &name {
+            if !config.networks.contains_key(name) {
+                return Err(eyre!("No network profile named '{name}'"));
+            }
+        }
+        config.network.name = name.clone();
+        if name.is_some() {
+            config.network.local = None;
+            config.network.alpha = None;
+        }
+        Ok(())
+    })?;
+    match name {
+        Some(name) => println!("dweb will connect to network '{name}' by default ({path:?})"),
+        None => {
+            println!("dweb will connect to the network given by settings or options ({path:?})")
+        }
+    }
+    Ok(())
+}
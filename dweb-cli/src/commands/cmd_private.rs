@@ -0,0 +1,256 @@
+/*
+Copyright (c) 2025- Mark Hughes
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+use std::path::PathBuf;
+
+use color_eyre::{eyre::eyre, Result};
+
+use autonomi::SecretKey;
+
+use dweb::client::DwebClient;
+use dweb::files::directory::{canonicalise_path, osstr_to_string};
+use dweb::files::private_history::{
+    store_private_archive, sync_private_directory, upload_private_directory, ConflictResolution,
+    PrivateHistory, SyncAction,
+};
+use dweb::helpers::upload_progress::UploadProgressReporter;
+use dweb::history::HistoryAddress;
+use dweb::token::{show_spend_return_value, Spends};
+use dweb::web::request::make_main_server_url;
+
+use crate::commands::progress::show_upload_progress;
+
+/// Implement 'upload' subcommand
+///
+/// Uploads files_root as a new version of the private History NAME, creating it if necessary.
+pub async fn handle_upload(
+    client: DwebClient,
+    app_secret_key: SecretKey,
+    files_root: PathBuf,
+    name: Option<String>,
+    progress: bool,
+) -> Result<()> {
+    let name = name_or_directory_name(name, &files_root)?;
+    let private_history = PrivateHistory::owned(app_secret_key, &name)?;
+    let spends = Spends::new(&client, Some(&"Upload cost: ")).await?;
+
+    println!("Uploading {files_root:?} to private History '{name}'...");
+    let progress = progress.then(UploadProgressReporter::new);
+    let progress_bar = progress.as_ref().map(show_upload_progress);
+    let result =
+        upload_private_directory(&client, &files_root, &private_history, progress.as_ref()).await;
+    if let Some(progress_bar) = progress_bar {
+        let _ = progress_bar.await;
+    }
+    let (cost, version) = match result {
+        Ok(result) => show_spend_return_value(&spends, Ok(result)).await?,
+        Err(e) => {
+            let message = format!("Failed to upload {files_root:?} - {e}");
+            println!("{message}");
+            return show_spend_return_value(&spends, Err(eyre!(message))).await;
+        }
+    };
+
+    println!(
+        "Uploaded version {version} of private History '{name}' at HISTORY-ADDRESS: {}",
+        private_history.history_address.to_hex()
+    );
+    println!("Cost: {cost}");
+    println!("To share it use: dweb share --name \"{name}\"");
+    Ok(())
+}
+
+/// Implement 'share' subcommand
+///
+/// Prints a datamap and link giving access to a version, or a file or directory within it,
+/// or with all_versions the HISTORY-ADDRESS and HISTORY-KEY giving access to every version.
+pub async fn handle_share(
+    client: DwebClient,
+    app_secret_key: SecretKey,
+    name: String,
+    version: Option<u64>,
+    path: Option<String>,
+    all_versions: bool,
+    host: Option<String>,
+    port: Option<u16>,
+) -> Result<()> {
+    let private_history = PrivateHistory::owned(app_secret_key, &name)?;
+    if all_versions {
+        println!(
+            "HISTORY-ADDRESS: {}",
+            private_history.history_address.to_hex()
+        );
+        println!("HISTORY-KEY: {}", private_history.history_key.to_hex());
+        println!("Anyone with both can read every version of '{name}', including future versions, using:");
+        println!(
+            "  dweb sync --files-root <DIRECTORY> --from <HISTORY-ADDRESS> --key <HISTORY-KEY>"
+        );
+        return Ok(());
+    }
+
+    let private_version = match private_history
+        .get_version(&client, version.unwrap_or(0))
+        .await?
+    {
+        Some(private_version) => private_version,
+        None => return Err(eyre!("No private History called '{name}'")),
+    };
+    let version = private_version.version;
+    let host = host.as_ref();
+
+    let path = match path {
+        Some(path) => canonicalise_path(&path),
+        None => {
+            let datamap = private_version.archive_datamap.to_hex();
+            println!("Sharing version {version} of '{name}'");
+            print_directory_share(&datamap, host, port);
+            return Ok(());
+        }
+    };
+
+    let file = private_version
+        .archive
+        .private_archive
+        .map()
+        .iter()
+        .find(|(file_path, _)| canonicalise_path(&file_path.to_string_lossy().to_string()) == path)
+        .map(|(_, (datamap, _))| datamap.to_hex());
+    if let Some(datamap) = file {
+        println!("Sharing {path} from version {version} of '{name}'");
+        println!("DATAMAP: {datamap}");
+        let url_path = format!("{}/data/{datamap}", dweb::api::DWEB_ANT_API_ROUTE);
+        println!("Link: {}", make_main_server_url(host, port, &url_path));
+        return Ok(());
+    }
+
+    // A directory is shared as a new archive of the files beneath it
+    let subtree = private_version.archive.subtree(&path)?;
+    let spends = Spends::new(&client, Some(&"Share cost: ")).await?;
+    let (cost, datamap) = match store_private_archive(&client, &subtree).await {
+        Ok(result) => show_spend_return_value(&spends, Ok(result)).await?,
+        Err(e) => {
+            let message = format!("Failed to store archive of {path} - {e}");
+            println!("{message}");
+            return show_spend_return_value(&spends, Err(eyre!(message))).await;
+        }
+    };
+    println!("Sharing {path}/ from version {version} of '{name}'");
+    print_directory_share(&datamap.to_hex(), host, port);
+    println!("Cost: {cost}");
+    Ok(())
+}
+
+/// Implement 'sync' subcommand
+///
+/// Syncs files_root with the private History NAME, or with a History shared using
+/// 'dweb share --all-versions' if history_address and history_key are given.
+pub async fn handle_sync(
+    client: DwebClient,
+    app_secret_key: SecretKey,
+    files_root: PathBuf,
+    name: Option<String>,
+    history_address: Option<HistoryAddress>,
+    history_key: Option<String>,
+    resolution: ConflictResolution,
+    dry_run: bool,
+) -> Result<()> {
+    let private_history = match (history_address, history_key) {
+        (Some(history_address), Some(history_key)) => match SecretKey::from_hex(&history_key) {
+            Ok(history_key) => PrivateHistory::shared(history_address, history_key),
+            Err(e) => return Err(eyre!("Invalid HISTORY-KEY - {e}")),
+        },
+        _ => {
+            let name = name_or_directory_name(name, &files_root)?;
+            PrivateHistory::owned(app_secret_key, &name)?
+        }
+    };
+
+    let spends = Spends::new(&client, Some(&"Sync cost: ")).await?;
+    let result =
+        sync_private_directory(&client, &files_root, &private_history, resolution, dry_run).await;
+    let result = match result {
+        Ok(result) => show_spend_return_value(&spends, Ok(result)).await?,
+        Err(e) => {
+            let message = format!("Failed to sync {files_root:?} - {e}");
+            println!("{message}");
+            return show_spend_return_value(&spends, Err(eyre!(message))).await;
+        }
+    };
+
+    let mut conflicts = 0;
+    for action in &result.actions {
+        match action {
+            SyncAction::Upload(path) => println!("  upload    {path}"),
+            SyncAction::RemoveRemote(path) => println!("  remove    {path} (from History)"),
+            SyncAction::Download(path) => println!("  download  {path}"),
+            SyncAction::RemoveLocal(path) => println!("  delete    {path} (local)"),
+            SyncAction::Conflict(path) => {
+                conflicts = conflicts + 1;
+                println!("  CONFLICT  {path} (changed locally and in History)");
+            }
+        }
+    }
+
+    if dry_run {
+        println!("Dry run: {} changes not made", result.actions.len());
+    } else if result.published {
+        println!(
+            "Synced {files_root:?} and published version {}",
+            result.version.unwrap_or(0)
+        );
+        println!("Cost: {}", result.cost);
+    } else if result.actions.is_empty() {
+        println!("{files_root:?} is up to date");
+    } else {
+        println!("Synced {files_root:?}");
+    }
+    if conflicts > 0 {
+        println!(
+            "{conflicts} conflicts were not synced. Use --keep-local or --keep-remote to resolve them"
+        );
+    }
+    Ok(())
+}
+
+fn print_directory_share(datamap: &str, host: Option<&String>, port: Option<u16>) {
+    println!("DATAMAP: {datamap}");
+    let url_path = format!("{}/archive/{datamap}", dweb::api::DWEB_ANT_API_ROUTE);
+    println!(
+        "List files: {}",
+        make_main_server_url(host, port, &url_path)
+    );
+    let url_path = format!("{}/file/{datamap}/<PATH>", dweb::api::DWEB_API_ROUTE);
+    println!(
+        "Get a file: {}",
+        make_main_server_url(host, port, &url_path)
+    );
+}
+
+fn name_or_directory_name(name: Option<String>, files_root: &PathBuf) -> Result<String> {
+    let name = match name {
+        Some(name) => Some(name),
+        None => match files_root.file_name() {
+            Some(osstr) => osstr_to_string(osstr),
+            None => None,
+        },
+    };
+    match name {
+        Some(name) => Ok(name),
+        None => Err(eyre!(
+            "Unable to use {files_root:?} as a NAME - please specify one with --name"
+        )),
+    }
+}
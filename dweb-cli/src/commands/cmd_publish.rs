@@ -0,0 +1,177 @@
+/*
+Copyright (c) 2025- Mark Hughes
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+use std::path::PathBuf;
+
+use blsttc::SecretKey;
+use color_eyre::{eyre::eyre, Result};
+
+use dweb::cache::publish_journal::PublishJournal;
+use dweb::client::DwebClient;
+use dweb::files::directory::Tree;
+use dweb::history::{History, HistoryAddress};
+
+/// Implement 'publish-status' subcommand
+///
+/// Shows the journal of an interrupted publish of files_root, or if None, of all interrupted publishes.
+pub fn handle_publish_status(files_root: Option<PathBuf>) -> Result<()> {
+    let journals = match files_root {
+        Some(files_root) => match PublishJournal::load(&files_root)? {
+            Some(journal) => vec![journal],
+            None => {
+                println!("No interrupted publish of {files_root:?}");
+                return Ok(());
+            }
+        },
+        None => PublishJournal::load_all()?,
+    };
+
+    if journals.is_empty() {
+        println!("No interrupted publishes");
+        return Ok(());
+    }
+
+    for journal in journals {
+        let data_type = if journal.is_public {
+            "public"
+        } else {
+            "private"
+        };
+        println!("FILES-ROOT: {:?}", journal.files_root);
+        if let Some(name) = &journal.history_name {
+            println!("  History created: '{name}'");
+        }
+        println!(
+            "  {} files stored as {data_type} data, cost {} attos",
+            journal.files_completed(),
+            journal.cost_in_attos()
+        );
+        if journal.files_with_receipts() > 0 {
+            println!(
+                "  {} files paid for but not yet stored",
+                journal.files_with_receipts()
+            );
+        }
+        if journal.batch_paid {
+            println!("  paid for a batched upload which did not complete");
+        }
+        println!(
+            "  started: {}, last updated: {}",
+            format_time(journal.started),
+            format_time(journal.updated)
+        );
+    }
+    println!(
+        "To resume, repeat 'dweb publish-new' or 'dweb publish-update' with the same FILES-ROOT"
+    );
+    println!("To discard, use 'dweb publish-abort --files-root <FILES-ROOT>'");
+    Ok(())
+}
+
+/// Implement 'publish-abort' subcommand
+///
+/// Deletes the journal so the next publish of files_root starts again.
+pub fn handle_publish_abort(files_root: PathBuf) -> Result<()> {
+    let journal = match PublishJournal::load(&files_root)? {
+        Some(journal) => journal,
+        None => return Err(eyre!("No interrupted publish of {files_root:?}")),
+    };
+
+    PublishJournal::remove(&files_root)?;
+    println!("Discarded the interrupted publish of {files_root:?}");
+    if journal.files_with_receipts() > 0 {
+        println!(
+            "Note: payments made for {} files which were not stored will not be re-used",
+            journal.files_with_receipts()
+        );
+    }
+    if journal.batch_paid {
+        println!("Note: the payment made for the batched upload will still be re-used by the next upload");
+    }
+    Ok(())
+}
+
+/// Implement 'promote' subcommand
+///
+/// Publishes a version of a branch as the next version of the main line of the History.
+pub async fn handle_promote(
+    client: DwebClient,
+    app_secret_key: SecretKey,
+    name: String,
+    branch: String,
+    version: Option<u64>,
+) -> Result<()> {
+    println!("Getting History from network...");
+    let mut history = History::<Tree>::from_name(
+        client.clone(),
+        app_secret_key.clone(),
+        name.clone(),
+        false,
+        0,
+    )
+    .await
+    .map_err(|e| eyre!("Failed to get History '{name}' - {e}"))?;
+    let mut branch_history = History::<Tree>::from_name_and_branch(
+        client,
+        app_secret_key.clone(),
+        name.clone(),
+        Some(branch.clone()),
+        false,
+        0,
+    )
+    .await
+    .map_err(|e| eyre!("Failed to get branch '{branch}' of History '{name}' - {e}"))?;
+
+    let (cost, version) = history
+        .promote_branch(app_secret_key, &mut branch_history, version)
+        .await?;
+    println!("Branch '{branch}' promoted to version {version} of '{name}' (cost {cost} ANT)");
+    println!("History address: {}", history.history_address().to_hex());
+    Ok(())
+}
+
+pub async fn handle_hand_over(
+    client: DwebClient,
+    app_secret_key: SecretKey,
+    name: String,
+    successor: HistoryAddress,
+) -> Result<()> {
+    println!("Getting History from network...");
+    let mut history = History::<Tree>::from_name(
+        client.clone(),
+        app_secret_key.clone(),
+        name.clone(),
+        false,
+        0,
+    )
+    .await
+    .map_err(|e| eyre!("Failed to get History '{name}' - {e}"))?;
+
+    let cost = history.hand_over(app_secret_key, successor).await?;
+    println!(
+        "History '{name}' with {} versions handed over to {successor} (cost {cost} ANT)",
+        history.num_versions().unwrap_or(0)
+    );
+    println!("History address: {}", history.history_address().to_hex());
+    Ok(())
+}
+
+fn format_time(seconds_since_epoch: u64) -> String {
+    match chrono::DateTime::from_timestamp(seconds_since_epoch as i64, 0) {
+        Some(time) => time.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+        None => format!("{seconds_since_epoch}"),
+    }
+}
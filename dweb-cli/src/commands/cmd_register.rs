@@ -0,0 +1,119 @@
+/*
+Copyright (c) 2025- Mark Hughes
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+use color_eyre::{eyre::eyre, Result};
+
+use autonomi::register::RegisterAddress;
+use autonomi::SecretKey;
+
+use dweb::client::DwebClient;
+use dweb::files::directory::Tree;
+use dweb::history::{History, HistoryAddress};
+use dweb::token::{show_spend_return_value, Spends};
+
+/// Implement 'migrate-register' subcommand
+///
+/// Creates a new History owned by the app secret key and replays each value
+/// of the Register into it as a version. The Register is not modified.
+pub async fn handle_migrate_register(
+    client: DwebClient,
+    app_secret_key: SecretKey,
+    register_address: HistoryAddress,
+    name: String,
+) -> Result<()> {
+    let register_address = RegisterAddress::new(register_address.owner());
+    let spends = Spends::new(&client, Some(&"Migrate register cost: ")).await?;
+
+    println!(
+        "Migrating Register {} to History '{name}'...",
+        register_address.to_hex()
+    );
+    let result = History::<Tree>::create_from_register(
+        client.clone(),
+        register_address,
+        name.clone(),
+        app_secret_key,
+    )
+    .await;
+
+    let (cost, history) = match result {
+        Ok(result) => show_spend_return_value(&spends, Ok(result)).await?,
+        Err(e) => {
+            let message = format!("Failed to migrate Register to History '{name}' - {e}");
+            println!("{message}");
+            return show_spend_return_value(&spends, Err(eyre!(message))).await;
+        }
+    };
+
+    println!(
+        "History '{name}' created with {} versions at HISTORY-ADDRESS: {}",
+        history.num_versions().unwrap_or(0),
+        history.history_address().to_hex()
+    );
+    println!("Cost: {cost}");
+    println!("To update it use: dweb publish-update --name \"{name}\" --files-root <FILES-ROOT>");
+    Ok(())
+}
+
+/// Implement 'export-register' subcommand
+///
+/// Creates an Autonomi Register holding the archive address of each version
+/// of a History, in order, so Register based apps can read and update it.
+pub async fn handle_export_register(
+    client: DwebClient,
+    app_secret_key: SecretKey,
+    address_or_name: String,
+    register_name: String,
+) -> Result<()> {
+    let history_address =
+        History::<Tree>::parse_history_address_or_name(app_secret_key.clone(), &address_or_name);
+
+    println!("Getting History from network...");
+    let history =
+        match History::<Tree>::from_history_address(client.clone(), history_address, true, 0).await
+        {
+            Ok(history) => history,
+            Err(e) => {
+                let message = format!("Failed to get History for '{address_or_name}' - {e}");
+                println!("{message}");
+                return Err(eyre!(message));
+            }
+        };
+
+    let spends = Spends::new(&client, Some(&"Export register cost: ")).await?;
+    println!(
+        "Exporting {} versions to Register '{register_name}'...",
+        history.num_versions().unwrap_or(0)
+    );
+    let (cost, register_address) = match history
+        .export_to_register(app_secret_key, &register_name)
+        .await
+    {
+        Ok(result) => show_spend_return_value(&spends, Ok(result)).await?,
+        Err(e) => {
+            let message = format!("Failed to export History to Register - {e}");
+            println!("{message}");
+            return show_spend_return_value(&spends, Err(eyre!(message))).await;
+        }
+    };
+
+    println!(
+        "Register '{register_name}' created at REGISTER-ADDRESS: {}",
+        register_address.to_hex()
+    );
+    println!("Cost: {cost}");
+    Ok(())
+}
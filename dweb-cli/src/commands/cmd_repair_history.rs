@@ -0,0 +1,91 @@
+/*
+Copyright (c) 2025 Mark Hughes
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+use blsttc::SecretKey;
+use color_eyre::{eyre::eyre, Result};
+
+use dweb::client::DwebClient;
+use dweb::files::directory::Tree;
+use dweb::history::History;
+
+use super::cmd_inspect::print_history_fork;
+
+/// Implement 'repair-history' subcommand
+///
+/// This command walks the History to find the most recent fork that has not been
+/// repaired and prints its branches. If a branch is given it repairs the fork by adding
+/// a merge entry which makes that branch the one that continues.
+pub async fn handle_repair_history(
+    client: DwebClient,
+    app_secret_key: SecretKey,
+    name: String,
+    branch: Option<usize>,
+    shorten_hex_strings: bool,
+) -> Result<()> {
+    println!("Getting History from network...");
+    let mut history = match History::<Tree>::from_name(
+        client.clone(),
+        app_secret_key.clone(),
+        name.clone(),
+        true,
+        0,
+    )
+    .await
+    {
+        Ok(history) => history,
+        Err(e) => {
+            let message = format!("Failed to repair history for '{name}' - {e}");
+            println!("{message}");
+            return Err(eyre!(message));
+        }
+    };
+
+    println!("Checking History for forks...");
+    let forks = history.detect_forks().await?;
+    let fork = match forks.iter().rev().find(|fork| !fork.is_repaired()) {
+        Some(fork) => fork.clone(),
+        None => {
+            println!("\nNo unrepaired fork found - no changes were applied.");
+            return Ok(());
+        }
+    };
+
+    print_history_fork(&client, "  ", &fork, false, shorten_hex_strings, &history).await?;
+
+    let branch = match branch {
+        Some(branch) => branch,
+        None => {
+            println!(
+                "\nTo repair the fork, repeat this command with --branch followed by the number of the branch to continue."
+            );
+            return Ok(());
+        }
+    };
+
+    match history.repair_fork(app_secret_key, &fork, branch).await {
+        Ok((cost, version)) => {
+            println!(
+                "\nFork repaired, branch {branch} continues and is now version {version} (cost {cost} ANT)"
+            );
+            Ok(())
+        }
+        Err(e) => {
+            let message = format!("Failed to repair fork - {e}");
+            println!("{message}");
+            Err(eyre!(message))
+        }
+    }
+}
@@ -0,0 +1,518 @@
+/*
+Copyright (c) 2025- Mark Hughes
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+use color_eyre::{eyre::eyre, Result};
+
+use dweb::autonomi::access::data_dir::get_client_data_dir_path;
+use dweb::web::server::{request_server_info, request_server_stop, DWEB_SERVER_LOGFILE};
+use dweb::web::{DEFAULT_HTTP_PORT, LOCALHOST_STR};
+
+const SYSTEMD_UNIT_NAME: &str = "dweb.service";
+const SUPERVISOR_SCRIPT_NAME: &str = "dweb-service.sh";
+const AUTOSTART_ENTRY_NAME: &str = "dweb-service.desktop";
+const SERVER_LOGS_DIRECTORY: &str = "logs";
+
+/// Seconds before a server which failed is restarted
+const RESTART_DELAY_SECS: u64 = 10;
+
+// Variables in the supervisor script which identify the server it runs
+const SCRIPT_HOST_VARIABLE: &str = "DWEB_SERVICE_HOST";
+const SCRIPT_PORT_VARIABLE: &str = "DWEB_SERVICE_PORT";
+
+/// The settings of an installed dweb service
+pub struct ServiceSettings {
+    /// The dweb executable which runs the server
+    pub executable: PathBuf,
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub local: bool,
+    pub alpha: bool,
+    /// The name of a network profile (see 'dweb network')
+    pub network: Option<String>,
+    /// Sets DWEB_WORKERS for the server
+    pub workers: Option<usize>,
+    /// Server output is appended to this file, or if None sent to the systemd journal
+    pub logfile: Option<PathBuf>,
+}
+
+impl ServiceSettings {
+    fn host(&self) -> String {
+        self.host.clone().unwrap_or(LOCALHOST_STR.to_string())
+    }
+
+    fn port(&self) -> u16 {
+        self.port.unwrap_or(DEFAULT_HTTP_PORT)
+    }
+
+    /// Arguments for the dweb executable to run the server in the foreground
+    fn server_args(&self) -> Vec<String> {
+        let mut args = Vec::new();
+        if let Some(network) = &self.network {
+            args.extend(["--network".to_string(), network.clone()]);
+        } else if self.local {
+            args.push("--local".to_string());
+        } else if self.alpha {
+            args.push("--alpha".to_string());
+        }
+        args.extend(["server", "start", "--foreground"].map(String::from));
+        args.extend(["--host".to_string(), self.host()]);
+        args.extend(["--port".to_string(), self.port().to_string()]);
+        args
+    }
+
+    fn environment(&self) -> Vec<(String, String)> {
+        let mut environment = Vec::new();
+        if let Some(workers) = self.workers {
+            environment.push(("DWEB_WORKERS".to_string(), workers.to_string()));
+        }
+        if let Some(logfile) = &self.logfile {
+            let logfile = logfile.to_string_lossy().to_string();
+            environment.push((DWEB_SERVER_LOGFILE.to_string(), logfile));
+        }
+        environment
+    }
+}
+
+/// Generate a systemd user unit which runs the dweb server
+pub fn systemd_unit(settings: &ServiceSettings) -> String {
+    let mut exec_start = vec![systemd_quote(&settings.executable.to_string_lossy())];
+    exec_start.extend(settings.server_args().iter().map(|arg| systemd_quote(arg)));
+
+    let mut unit = String::new();
+    unit.push_str("[Unit]\n");
+    unit.push_str("Description=dweb server for Autonomi websites and APIs\n");
+    unit.push_str("After=network-online.target\n\n");
+    unit.push_str("[Service]\n");
+    unit.push_str("Type=simple\n");
+    unit.push_str(&format!("ExecStart={}\n", exec_start.join(" ")));
+    for (name, value) in settings.environment() {
+        unit.push_str(&format!(
+            "Environment={}\n",
+            systemd_quote(&format!("{name}={value}"))
+        ));
+    }
+    if let Some(logfile) = &settings.logfile {
+        let logfile = systemd_escape(&logfile.to_string_lossy());
+        unit.push_str(&format!("StandardOutput=append:{logfile}\n"));
+        unit.push_str(&format!("StandardError=append:{logfile}\n"));
+    }
+    unit.push_str("Restart=on-failure\n");
+    unit.push_str(&format!("RestartSec={RESTART_DELAY_SECS}\n\n"));
+    unit.push_str("[Install]\n");
+    unit.push_str("WantedBy=default.target\n");
+    unit
+}
+
+/// Generate a shell script which runs the dweb server, restarting it if it fails
+///
+/// The script ends when the server is stopped normally (e.g. by 'dweb server stop').
+pub fn supervisor_script(settings: &ServiceSettings) -> String {
+    let mut command = vec![shell_quote(&settings.executable.to_string_lossy())];
+    command.extend(settings.server_args().iter().map(|arg| shell_quote(arg)));
+    let mut command = command.join(" ");
+    if let Some(logfile) = &settings.logfile {
+        let logfile = shell_quote(&logfile.to_string_lossy());
+        command.push_str(&format!(" >>{logfile} 2>&1"));
+    }
+
+    let mut script = String::new();
+    script.push_str("#!/bin/sh\n");
+    script.push_str("# Runs the dweb server. Generated by 'dweb service install'\n");
+    script.push_str(&format!(
+        "{SCRIPT_HOST_VARIABLE}={}\n",
+        shell_quote(&settings.host())
+    ));
+    script.push_str(&format!("{SCRIPT_PORT_VARIABLE}={}\n", settings.port()));
+    for (name, value) in settings.environment() {
+        script.push_str(&format!("export {name}={}\n", shell_quote(&value)));
+    }
+    script.push_str(&format!("until {command}; do\n"));
+    script.push_str(&format!("    sleep {RESTART_DELAY_SECS}\n"));
+    script.push_str("done\n");
+    script
+}
+
+fn autostart_entry(script: &Path) -> String {
+    let mut entry = String::new();
+    entry.push_str("[Desktop Entry]\n");
+    entry.push_str("Type=Application\n");
+    entry.push_str("Name=dweb\n");
+    entry.push_str("Comment=dweb server for Autonomi websites and APIs\n");
+    entry.push_str(&format!(
+        "Exec=/bin/sh {}\n",
+        systemd_quote(&script.to_string_lossy())
+    ));
+    entry.push_str("Terminal=false\n");
+    entry
+}
+
+/// Implement 'service install'
+pub async fn handle_install(
+    local: bool,
+    alpha: bool,
+    network: Option<String>,
+    host: Option<String>,
+    port: Option<u16>,
+    workers: Option<usize>,
+    logdir: Option<PathBuf>,
+    no_start: bool,
+) -> Result<()> {
+    check_is_linux()?;
+    let workers = match workers {
+        Some(workers) => Some(workers),
+        None => std::env::var("DWEB_WORKERS")
+            .ok()
+            .and_then(|workers| workers.trim().parse::<usize>().ok()),
+    };
+    let mut settings = ServiceSettings {
+        executable: std::env::current_exe()?,
+        host,
+        port,
+        local,
+        alpha,
+        network,
+        workers,
+        logfile: None,
+    };
+
+    let use_systemd = has_systemd();
+    let logdir = match logdir {
+        Some(logdir) => Some(logdir),
+        None if !use_systemd => Some(get_client_data_dir_path()?.join(SERVER_LOGS_DIRECTORY)),
+        None => None,
+    };
+    if let Some(logdir) = logdir {
+        std::fs::create_dir_all(&logdir)?;
+        let logdir = logdir.canonicalize()?;
+        settings.logfile = Some(logdir.join(format!("dweb-server-{}.log", settings.port())));
+    }
+
+    if use_systemd {
+        let unit_path = systemd_unit_path()?;
+        write_file(&unit_path, &systemd_unit(&settings))?;
+        println!("Wrote systemd user service {unit_path:?}");
+        if !no_start {
+            systemctl(&["daemon-reload"])?;
+            systemctl(&["enable", SYSTEMD_UNIT_NAME])?;
+            systemctl(&["restart", SYSTEMD_UNIT_NAME])?;
+            println!("The dweb service is running and will start when you log in");
+        }
+    } else {
+        let script_path = supervisor_script_path()?;
+        write_file(&script_path, &supervisor_script(&settings))?;
+        let autostart_path = autostart_entry_path()?;
+        write_file(&autostart_path, &autostart_entry(&script_path))?;
+        println!("systemd is not available, so wrote supervisor script {script_path:?}");
+        println!("and login autostart entry {autostart_path:?}");
+        if !no_start {
+            if request_server_info(settings.host.as_ref(), settings.port)
+                .await
+                .is_ok()
+            {
+                println!(
+                    "A dweb server is already running on port {}. The service will be used when you next log in",
+                    settings.port()
+                );
+            } else {
+                start_supervisor_script(&script_path)?;
+                println!("The dweb service is running and will start when you log in");
+            }
+        }
+    }
+
+    if let Some(logfile) = &settings.logfile {
+        println!("Server output: {logfile:?}");
+    }
+    Ok(())
+}
+
+/// Implement 'service uninstall'
+pub async fn handle_uninstall() -> Result<()> {
+    check_is_linux()?;
+    let mut was_installed = false;
+
+    let unit_path = systemd_unit_path()?;
+    if unit_path.exists() {
+        was_installed = true;
+        if has_systemd() {
+            systemctl(&["disable", "--now", SYSTEMD_UNIT_NAME])?;
+        }
+        std::fs::remove_file(&unit_path)?;
+        if has_systemd() {
+            systemctl(&["daemon-reload"])?;
+        }
+        println!("Removed systemd user service {unit_path:?}");
+    }
+
+    let script_path = supervisor_script_path()?;
+    if script_path.exists() {
+        was_installed = true;
+        let (host, port) = script_host_and_port(&std::fs::read_to_string(&script_path)?);
+        // The script ends once the server stops normally
+        if request_server_stop(host.as_ref(), port).await.is_ok() {
+            println!(
+                "Stopped dweb server on port {}",
+                port.unwrap_or(DEFAULT_HTTP_PORT)
+            );
+        }
+        std::fs::remove_file(&script_path)?;
+        println!("Removed supervisor script {script_path:?}");
+    }
+
+    let autostart_path = autostart_entry_path()?;
+    if autostart_path.exists() {
+        std::fs::remove_file(&autostart_path)?;
+        println!("Removed login autostart entry {autostart_path:?}");
+    }
+
+    if !was_installed {
+        println!("The dweb service is not installed");
+    }
+    Ok(())
+}
+
+/// Implement 'service status'
+pub async fn handle_status() -> Result<()> {
+    check_is_linux()?;
+
+    let unit_path = systemd_unit_path()?;
+    if unit_path.exists() {
+        println!("Installed as systemd user service {unit_path:?}");
+        if has_systemd() {
+            let _ = Command::new("systemctl")
+                .args(["--user", "status", SYSTEMD_UNIT_NAME, "--no-pager"])
+                .status();
+        }
+        return Ok(());
+    }
+
+    let script_path = supervisor_script_path()?;
+    if script_path.exists() {
+        println!("Installed as supervisor script {script_path:?}");
+        let (host, port) = script_host_and_port(&std::fs::read_to_string(&script_path)?);
+        match request_server_info(host.as_ref(), port).await {
+            Ok(info) => println!(
+                "dweb server is running on {}:{} (pid {})",
+                info.host, info.port, info.pid
+            ),
+            Err(_) => println!(
+                "dweb server is not running on port {}",
+                port.unwrap_or(DEFAULT_HTTP_PORT)
+            ),
+        }
+        return Ok(());
+    }
+
+    println!("The dweb service is not installed. Use 'dweb service install' to install it");
+    Ok(())
+}
+
+// The host and port recorded in a supervisor script
+fn script_host_and_port(script: &str) -> (Option<String>, Option<u16>) {
+    let mut host = None;
+    let mut port = None;
+    for line in script.lines() {
+        if let Some(value) = line.strip_prefix(&format!("{SCRIPT_HOST_VARIABLE}=")) {
+            host = Some(value.trim_matches('\'').to_string());
+        } else if let Some(value) = line.strip_prefix(&format!("{SCRIPT_PORT_VARIABLE}=")) {
+            port = value.parse::<u16>().ok();
+        }
+    }
+    (host, port)
+}
+
+fn check_is_linux() -> Result<()> {
+    if !cfg!(target_os = "linux") {
+        return Err(eyre!(
+            "'dweb service' is only available on Linux. Use 'dweb server start' to start a server in the background"
+        ));
+    }
+    Ok(())
+}
+
+// True if there is a systemd user instance to register the service with
+fn has_systemd() -> bool {
+    if !Path::new("/run/systemd/system").exists() {
+        return false;
+    }
+    match Command::new("systemctl")
+        .args(["--user", "show-environment"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+    {
+        Ok(status) => status.success(),
+        Err(_) => false,
+    }
+}
+
+fn systemctl(args: &[&str]) -> Result<()> {
+    let status = match Command::new("systemctl").arg("--user").args(args).status() {
+        Ok(status) => status,
+        Err(e) => return Err(eyre!("Failed to run systemctl - {e}")),
+    };
+    if !status.success() {
+        return Err(eyre!(
+            "'systemctl --user {}' failed with {status}",
+            args.join(" ")
+        ));
+    }
+    Ok(())
+}
+
+fn start_supervisor_script(script_path: &Path) -> Result<()> {
+    let mut command = Command::new("/bin/sh");
+    command
+        .arg(script_path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+
+    // Detach from the terminal so the service is not stopped when it closes
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+
+    match command.spawn() {
+        Ok(_) => Ok(()),
+        Err(e) => Err(eyre!("Failed to start {script_path:?} - {e}")),
+    }
+}
+
+fn write_file(path: &Path, contents: &str) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    if let Err(e) = std::fs::write(path, contents) {
+        return Err(eyre!("Failed to write {path:?} - {e}"));
+    }
+    Ok(())
+}
+
+fn config_directory() -> Result<PathBuf> {
+    if let Ok(config_home) = std::env::var("XDG_CONFIG_HOME") {
+        if !config_home.is_empty() {
+            return Ok(PathBuf::from(config_home));
+        }
+    }
+    match std::env::var("HOME") {
+        Ok(home) => Ok(PathBuf::from(home).join(".config")),
+        Err(_) => Err(eyre!(
+            "Unable to locate your config directory as HOME is not set"
+        )),
+    }
+}
+
+fn systemd_unit_path() -> Result<PathBuf> {
+    Ok(config_directory()?
+        .join("systemd")
+        .join("user")
+        .join(SYSTEMD_UNIT_NAME))
+}
+
+fn autostart_entry_path() -> Result<PathBuf> {
+    Ok(config_directory()?
+        .join("autostart")
+        .join(AUTOSTART_ENTRY_NAME))
+}
+
+fn supervisor_script_path() -> Result<PathBuf> {
+    Ok(get_client_data_dir_path()?.join(SUPERVISOR_SCRIPT_NAME))
+}
+
+// Escape the characters which systemd expands in unit file values
+fn systemd_escape(value: &str) -> String {
+    value.replace('%', "%%").replace('$', "$$")
+}
+
+// Quote a word for ExecStart= or Environment= if needed
+fn systemd_quote(word: &str) -> String {
+    let word = systemd_escape(word);
+    if !word.is_empty()
+        && !word
+            .chars()
+            .any(|c| c.is_whitespace() || c == '"' || c == '\'' || c == '\\')
+    {
+        return word;
+    }
+    format!("\"{}\"", word.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+fn shell_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> ServiceSettings {
+        ServiceSettings {
+            executable: PathBuf::from("/home/me/.cargo/bin/dweb"),
+            host: Some("0.0.0.0".to_string()),
+            port: Some(6000),
+            local: true,
+            alpha: false,
+            network: None,
+            workers: Some(24),
+            logfile: Some(PathBuf::from("/home/me/dweb logs/dweb-server-6000.log")),
+        }
+    }
+
+    #[test]
+    fn systemd_unit_carries_settings() {
+        let unit = systemd_unit(&settings());
+        assert!(unit.contains(
+            "ExecStart=/home/me/.cargo/bin/dweb --local server start --foreground --host 0.0.0.0 --port 6000\n"
+        ));
+        assert!(unit.contains("Environment=DWEB_WORKERS=24\n"));
+        assert!(unit.contains(
+            "Environment=\"DWEB_SERVER_LOGFILE=/home/me/dweb logs/dweb-server-6000.log\"\n"
+        ));
+        assert!(unit.contains("StandardOutput=append:/home/me/dweb logs/dweb-server-6000.log\n"));
+        assert!(unit.contains("WantedBy=default.target\n"));
+
+        let mut settings = settings();
+        settings.logfile = None;
+        settings.local = false;
+        let unit = systemd_unit(&settings);
+        assert!(!unit.contains("StandardOutput"));
+        assert!(!unit.contains("--local"));
+    }
+
+    #[test]
+    fn supervisor_script_restarts_the_server() {
+        let script = supervisor_script(&settings());
+        assert!(script.contains("export DWEB_WORKERS='24'\n"));
+        assert!(script.contains(
+            "until '/home/me/.cargo/bin/dweb' '--local' 'server' 'start' '--foreground' '--host' '0.0.0.0' '--port' '6000' >>'/home/me/dweb logs/dweb-server-6000.log' 2>&1; do\n"
+        ));
+        assert_eq!(
+            script_host_and_port(&script),
+            (Some("0.0.0.0".to_string()), Some(6000))
+        );
+    }
+
+    #[test]
+    fn words_are_quoted() {
+        assert_eq!(systemd_quote("plain"), "plain");
+        assert_eq!(systemd_quote("100%"), "100%%");
+        assert_eq!(systemd_quote("a \"b\""), "\"a \\\"b\\\"\"");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+    }
+}
@@ -0,0 +1,80 @@
+/*
+Copyright (c) 2025- Mark Hughes
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+use std::io::Write;
+
+use actix_web::rt::task::JoinHandle;
+
+use dweb::helpers::upload_progress::{UploadProgress, UploadProgressReporter, UploadStage};
+
+const BAR_WIDTH: u64 = 30;
+
+/// Render upload progress as a progress bar on stderr until the upload finishes
+///
+/// Await the returned handle after the upload to ensure the final state is shown.
+pub fn show_upload_progress(reporter: &UploadProgressReporter) -> JoinHandle<()> {
+    let mut receiver = reporter.subscribe();
+    actix_web::rt::spawn(async move {
+        loop {
+            let progress = receiver.borrow_and_update().clone();
+            eprint!("\r{}\x1b[K", progress_line(&progress));
+            let _ = std::io::stderr().flush();
+            if progress.is_finished() || receiver.changed().await.is_err() {
+                eprintln!();
+                return;
+            }
+        }
+    })
+}
+
+fn progress_line(progress: &UploadProgress) -> String {
+    let stage = match progress.stage {
+        UploadStage::Starting => "Starting",
+        UploadStage::UploadingFiles => "Uploading",
+        UploadStage::StoringArchive => "Storing archive",
+        UploadStage::UpdatingHistory => "Updating History",
+        UploadStage::Complete => "Complete",
+        UploadStage::Failed => "Failed",
+    };
+
+    let filled = if progress.bytes_total > 0 {
+        (progress.bytes_uploaded * BAR_WIDTH / progress.bytes_total).min(BAR_WIDTH)
+    } else if progress.stage == UploadStage::Complete {
+        BAR_WIDTH
+    } else {
+        0
+    };
+    let bar = format!(
+        "{}{}",
+        "#".repeat(filled as usize),
+        "-".repeat((BAR_WIDTH - filled) as usize)
+    );
+
+    let mut line = format!(
+        "{stage:<16} [{bar}] {}/{} files, {}/~{} chunks, {}/{} bytes, cost {} attos",
+        progress.files_done,
+        progress.files_total,
+        progress.chunks_done,
+        progress.chunks_total,
+        progress.bytes_uploaded,
+        progress.bytes_total,
+        progress.cost_in_attos
+    );
+    if progress.retries > 0 {
+        line = line + &format!(", {} retries", progress.retries);
+    }
+    line
+}
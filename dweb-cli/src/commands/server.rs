@@ -16,10 +16,21 @@
  along with this program. If not, see <https://www.gnu.org/licenses/>.
 */
 
+use std::fs::OpenOptions;
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::time::Duration;
+
 use color_eyre::{eyre::eyre, Result};
 
+use dweb::autonomi::access::data_dir::get_client_data_dir_path;
 use dweb::client::{ApiControl, DwebClient, DwebClientConfig};
+use dweb::helpers::is_main_dweb_server_running;
 use dweb::web::name::register_builtin_names;
+use dweb::web::server::{
+    request_server_info, request_server_stop, ServerInfo, ServerLock, DWEB_SERVER_LOGFILE,
+};
+use dweb::web::LOCALHOST_STR;
 
 pub(crate) async fn connect_and_announce(
     local_network: bool,
@@ -29,9 +40,11 @@ pub(crate) async fn connect_and_announce(
     api_control: ApiControl,
     announce: bool,
 ) -> (DwebClient, bool) {
+    let network_profile = dweb::config::config().network_profile();
     let client = dweb::client::DwebClient::initialise_and_connect(&DwebClientConfig {
         local_network,
         alpha_network,
+        network_profile: network_profile.clone(),
         host,
         port,
         client: None,
@@ -42,7 +55,9 @@ pub(crate) async fn connect_and_announce(
     .expect("Failed to connect to Autonomi Network");
 
     if announce {
-        if local_network {
+        if let Some(profile) = network_profile {
+            println!("-> network '{}' {}", profile.name, client.network);
+        } else if local_network {
             println!("-> local network: {}", client.network);
         } else if alpha_network {
             println!("-> alpha network {}", client.network);
@@ -51,11 +66,23 @@ pub(crate) async fn connect_and_announce(
         };
     };
 
-    (client, local_network)
+    let is_local = client.is_local;
+    (client, is_local)
 }
 
 use dweb_server::DwebService;
 
+/// Default directory for the logfiles of servers started in the background
+const SERVER_LOGS_DIRECTORY: &str = "logs";
+
+/// How long 'dweb server start' waits for a background server to respond
+const START_TIMEOUT_SECS: u64 = 60;
+
+/// How long 'dweb server stop' waits for a server to release its port
+const STOP_TIMEOUT_SECS: u64 = 15;
+
+const POLL_INTERVAL_MILLIS: u64 = 500;
+
 pub(crate) async fn start_in_foreground(
     local: bool,
     alpha: bool,
@@ -64,28 +91,420 @@ pub(crate) async fn start_in_foreground(
     port: Option<u16>,
     logdir: Option<String>,
 ) -> Result<bool> {
+    let port = port.unwrap_or(dweb::web::DEFAULT_HTTP_PORT);
+    check_port_available(port)?;
+
+    // Output can only be redirected for a child process, so run the server as one and wait for it
+    if let Some(logdir) = logdir {
+        let (mut child, logfile) = spawn_server(port, PathBuf::from(logdir))?;
+        println!(
+            "Started dweb server on port {port} (pid {}), logging to {logfile:?}",
+            child.id()
+        );
+        let status = child.wait()?;
+        if !status.success() {
+            return Err(eyre!("dweb server on port {port} exited with {status}"));
+        }
+        return Ok(true);
+    }
+
     register_builtin_names(local);
 
     // Start the main server (for port based browsing), which will handle /dweb-open URLs  opened by 'dweb open'
 
-    let port = port.unwrap_or(dweb::web::DEFAULT_HTTP_PORT);
+    let lock_host = host.clone().unwrap_or(LOCALHOST_STR.to_string());
+    let logfile = std::env::var(DWEB_SERVER_LOGFILE).ok().map(PathBuf::from);
+    ServerLock::new(&lock_host, port, logfile).save()?;
+
     let client_config = DwebClientConfig {
+        local_network: local,
+        alpha_network: alpha,
+        network_profile: dweb::config::config().network_profile(),
         host,
         port: Some(port),
+        api_control,
         ..Default::default()
     };
 
     let mut dweb_service = DwebService::new(client_config);
     dweb_service.start_blocking(port).await;
+    remove_own_lock(port);
     Ok(true)
 }
 
+/// Start a main server in a detached process with its output sent to a logfile in logdir
+///
+/// The server is started by running this executable with the same options plus '--foreground'.
 pub async fn start_in_background(
-    local: bool,
-    alpha: bool,
-    api_control: ApiControl,
     host: Option<String>,
     port: Option<u16>,
     logdir: Option<String>,
-) {
+) -> Result<bool> {
+    let port = port.unwrap_or(dweb::web::DEFAULT_HTTP_PORT);
+    check_port_available(port)?;
+
+    let logdir = match logdir {
+        Some(logdir) => PathBuf::from(logdir),
+        None => {
+            let logdir = get_client_data_dir_path()?.join(SERVER_LOGS_DIRECTORY);
+            std::fs::create_dir_all(&logdir)?;
+            logdir
+        }
+    };
+    let (mut child, logfile) = spawn_server(port, logdir)?;
+    println!(
+        "Starting dweb server on port {port} (pid {}), logging to {logfile:?}",
+        child.id()
+    );
+
+    let mut waited = Duration::ZERO;
+    while waited < Duration::from_secs(START_TIMEOUT_SECS) {
+        if let Some(status) = child.try_wait()? {
+            return Err(eyre!(
+                "dweb server exited with {status} - see {logfile:?} for details"
+            ));
+        }
+        if request_server_info(host.as_ref(), Some(port)).await.is_ok() {
+            println!("dweb server is running. Use 'dweb server stop {port}' to stop it");
+            return Ok(true);
+        }
+        actix_web::rt::time::sleep(Duration::from_millis(POLL_INTERVAL_MILLIS)).await;
+        waited += Duration::from_millis(POLL_INTERVAL_MILLIS);
+    }
+
+    println!("dweb server has not responded yet, it may still be connecting to the network.");
+    println!("Use 'dweb server info {port}' to check, or see {logfile:?}");
+    Ok(true)
+}
+
+/// Implement 'server stop' for a PORT or "all" servers recorded by 'dweb server start'
+pub async fn stop_servers(port_or_all: &str) -> Result<()> {
+    for port in parse_port_or_all(port_or_all)? {
+        stop_server(port).await?;
+    }
+    Ok(())
+}
+
+/// Implement 'server info' for a PORT or "all" servers recorded by 'dweb server start'
+pub async fn show_server_info(port_or_all: &str) -> Result<()> {
+    let ports = parse_port_or_all(port_or_all)?;
+    if ports.is_empty() {
+        println!("No dweb servers are running");
+    }
+
+    for port in ports {
+        let lock = ServerLock::load(port)?;
+        let host = lock.as_ref().map(|lock| lock.host.clone());
+        match request_server_info(host.as_ref(), Some(port)).await {
+            Ok(info) => print_server_info(&info, lock.as_ref()),
+            Err(e) => {
+                if lock.is_some() && !is_main_dweb_server_running(port) {
+                    println!("No dweb server is running on port {port} (removing its stale lock)");
+                    ServerLock::remove(port)?;
+                } else {
+                    println!("Unable to get information from port {port} - {e}");
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn print_server_info(info: &ServerInfo, lock: Option<&ServerLock>) {
+    let kind = if info.is_main_server {
+        "main server"
+    } else {
+        "listener"
+    };
+    println!(
+        "dweb {kind} on {}:{} (pid {}, dweb v{}, network {})",
+        info.host, info.port, info.pid, info.dweb_version, info.network
+    );
+    if let Some(logfile) = lock.and_then(|lock| lock.logfile.as_ref()) {
+        println!("  logfile: {logfile:?}");
+    }
+    if info.is_main_server {
+        println!("  listeners: {}", info.listeners.len());
+    }
+    for listener in &info.listeners {
+        let version = match listener.version {
+            Some(version) if version > 0 => format!("version {version}"),
+            _ => "most recent version".to_string(),
+        };
+        let idle = match listener.idle_secs {
+            Some(idle_secs) => format!(" (idle {idle_secs}s)"),
+            None => String::new(),
+        };
+        match &listener.history_address {
+            Some(history_address) => println!(
+                "    port {}: {version} of HISTORY-ADDRESS {history_address}{idle}",
+                listener.port
+            ),
+            None => println!(
+                "    port {}: ARCHIVE-ADDRESS {}{idle}",
+                listener.port, listener.archive_address
+            ),
+        }
+    }
+}
+
+async fn stop_server(port: u16) -> Result<()> {
+    let lock = ServerLock::load(port)?;
+    let host = lock.as_ref().map(|lock| lock.host.clone());
+    match request_server_stop(host.as_ref(), Some(port)).await {
+        Ok(()) => println!("Stopping dweb server on port {port}..."),
+        Err(e) => match &lock {
+            // Fall back to the process recorded when it was started
+            Some(lock) if is_main_dweb_server_running(port) => {
+                println!("DEBUG stop request failed - {e}");
+                // The pid may have been reused since the lock was saved
+                if !is_dweb_process(lock.pid) {
+                    return Err(eyre!(
+                        "Unable to stop dweb server on port {port} - pid {} is not a dweb server",
+                        lock.pid
+                    ));
+                }
+                println!("Stopping dweb server on port {port} (pid {})...", lock.pid);
+                kill_process(lock.pid)?;
+            }
+            Some(_) => {
+                println!("No dweb server is running on port {port} (removing its stale lock)");
+                ServerLock::remove(port)?;
+                return Ok(());
+            }
+            None => return Err(eyre!("Unable to stop dweb server on port {port} - {e}")),
+        },
+    };
+
+    let mut waited = Duration::ZERO;
+    while is_main_dweb_server_running(port) {
+        if waited >= Duration::from_secs(STOP_TIMEOUT_SECS) {
+            return Err(eyre!("dweb server on port {port} has not stopped"));
+        }
+        actix_web::rt::time::sleep(Duration::from_millis(POLL_INTERVAL_MILLIS)).await;
+        waited += Duration::from_millis(POLL_INTERVAL_MILLIS);
+    }
+    if lock.is_some() {
+        ServerLock::remove(port)?;
+    }
+    println!("Stopped dweb server on port {port}");
+    Ok(())
+}
+
+// The port given, or for "all" the port of every recorded server plus the default port if in use
+fn parse_port_or_all(port_or_all: &str) -> Result<Vec<u16>> {
+    if port_or_all != "all" {
+        return match port_or_all.parse::<u16>() {
+            Ok(port) => Ok(vec![port]),
+            Err(_) => Err(eyre!("Expected a PORT or \"all\" but got '{port_or_all}'")),
+        };
+    }
+
+    let mut ports: Vec<u16> = ServerLock::list()?.iter().map(|lock| lock.port).collect();
+    let default_port = dweb::web::DEFAULT_HTTP_PORT;
+    if !ports.contains(&default_port) && is_main_dweb_server_running(default_port) {
+        ports.push(default_port);
+    }
+    Ok(ports)
+}
+
+fn check_port_available(port: u16) -> Result<()> {
+    if is_main_dweb_server_running(port) {
+        return Err(eyre!(
+            "A server is already running on port {port}. See 'dweb server info {port}'"
+        ));
+    }
+    if ServerLock::load(port)?.is_some() {
+        println!("DEBUG removing stale lock for port {port}");
+        ServerLock::remove(port)?;
+    }
+    Ok(())
+}
+
+// Only remove the lock if it is still ours
+fn remove_own_lock(port: u16) {
+    if let Ok(Some(lock)) = ServerLock::load(port) {
+        if lock.pid == std::process::id() {
+            let _ = ServerLock::remove(port);
+        }
+    }
+}
+
+// Run this executable with the current arguments to start a server in the foreground,
+// with output appended to 'dweb-server-PORT.log' in logdir
+fn spawn_server(port: u16, logdir: PathBuf) -> Result<(Child, PathBuf)> {
+    if !logdir.is_dir() {
+        return Err(eyre!("Log directory {logdir:?} does not exist"));
+    }
+    let logfile = logdir.join(format!("dweb-server-{port}.log"));
+    let log = match OpenOptions::new().create(true).append(true).open(&logfile) {
+        Ok(log) => log,
+        Err(e) => return Err(eyre!("Unable to open logfile {logfile:?} - {e}")),
+    };
+
+    let mut command = Command::new(std::env::current_exe()?);
+    command
+        .args(foreground_server_args(std::env::args().skip(1).collect()))
+        .env(DWEB_SERVER_LOGFILE, &logfile)
+        .stdin(Stdio::null())
+        .stdout(log.try_clone()?)
+        .stderr(log);
+
+    // Detach from the terminal so the server is not stopped when it closes
+    #[cfg(unix)]
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+
+    match command.spawn() {
+        Ok(child) => Ok((child, logfile)),
+        Err(e) => Err(eyre!("Failed to start dweb server - {e}")),
+    }
+}
+
+// The arguments for 'server start' in the foreground without --logdir
+fn foreground_server_args(args: Vec<String>) -> Vec<String> {
+    let mut server_args = Vec::new();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--logdir" {
+            args.next();
+        } else if !arg.starts_with("--logdir=") && arg != "--foreground" {
+            server_args.push(arg);
+        }
+    }
+    server_args.push("--foreground".to_string());
+    server_args
+}
+
+// True if the process command is this executable, such as a server started by 'dweb server start'
+fn is_dweb_process(pid: u32) -> bool {
+    let executable = match std::env::current_exe().ok().and_then(|path| {
+        path.file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+    }) {
+        Some(executable) => executable,
+        None => return false,
+    };
+    match process_command(pid) {
+        Some(command) => command_runs_executable(&command, &executable),
+        None => false,
+    }
+}
+
+fn command_runs_executable(command: &str, executable: &str) -> bool {
+    command
+        .split_whitespace()
+        .next()
+        .and_then(|program| std::path::Path::new(program.trim_matches('"')).file_stem())
+        .is_some_and(|stem| stem.to_string_lossy() == executable)
+}
+
+#[cfg(unix)]
+fn process_command(pid: u32) -> Option<String> {
+    let output = Command::new("ps")
+        .args(["-p", &pid.to_string(), "-o", "args="])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(not(unix))]
+fn process_command(pid: u32) -> Option<String> {
+    // CSV output begins with the quoted image name, e.g. "dweb.exe","1234",...
+    let output = Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {pid}"), "/FO", "CSV", "/NH"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let image = stdout.split(',').next()?.trim().trim_matches('"');
+    if image.is_empty() {
+        return None;
+    }
+    Some(image.to_string())
+}
+
+#[cfg(unix)]
+fn kill_process(pid: u32) -> Result<()> {
+    let status = Command::new("kill").arg(pid.to_string()).status()?;
+    if !status.success() {
+        return Err(eyre!("Failed to stop process {pid}"));
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn kill_process(pid: u32) -> Result<()> {
+    let status = Command::new("taskkill")
+        .args(["/PID", &pid.to_string()])
+        .status()?;
+    if !status.success() {
+        return Err(eyre!("Failed to stop process {pid}"));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strings(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn foreground_args_drop_logdir() {
+        assert_eq!(
+            foreground_server_args(strings(&[
+                "server",
+                "start",
+                "--logdir",
+                "/tmp/logs",
+                "--port",
+                "8080"
+            ])),
+            strings(&["server", "start", "--port", "8080", "--foreground"])
+        );
+        assert_eq!(
+            foreground_server_args(strings(&["server", "start", "--logdir=/tmp/logs"])),
+            strings(&["server", "start", "--foreground"])
+        );
+        assert_eq!(
+            foreground_server_args(strings(&["server", "start", "--foreground"])),
+            strings(&["server", "start", "--foreground"])
+        );
+    }
+
+    #[test]
+    fn port_or_all_is_parsed() {
+        assert_eq!(parse_port_or_all("8080").unwrap(), vec![8080]);
+        assert!(parse_port_or_all("70000").is_err());
+        assert!(parse_port_or_all("every").is_err());
+        assert!(parse_port_or_all("").is_err());
+    }
+
+    #[test]
+    fn command_is_matched_to_executable() {
+        assert!(command_runs_executable(
+            "/usr/local/bin/dweb server start --foreground",
+            "dweb"
+        ));
+        assert!(command_runs_executable("dweb.exe", "dweb"));
+        assert!(!command_runs_executable("/usr/bin/python3 dweb", "dweb"));
+        assert!(!command_runs_executable("", "dweb"));
+    }
+
+    #[test]
+    fn own_process_is_recognised() {
+        // The test binary is not named dweb, so check against its own name
+        let executable = std::env::current_exe().unwrap();
+        let executable = executable.file_stem().unwrap().to_string_lossy();
+        let command = process_command(std::process::id()).unwrap();
+        assert!(command_runs_executable(&command, &executable));
+        assert!(is_dweb_process(std::process::id()));
+    }
 }
@@ -20,34 +20,45 @@ use color_eyre::{eyre::eyre, Report, Result};
 use autonomi::AttoTokens;
 
 use dweb::client::{ApiControl, DwebClientConfig};
+use dweb::config::{default_config_file, DwebConfig, NetworkProfile, StrategyProfile};
+use dweb::files::private_history::ConflictResolution;
+use dweb::helpers::upload_progress::UploadProgressReporter;
 use dweb::history::HistoryAddress;
 use dweb::storage::{publish_or_update_files, report_content_published_or_updated};
 use dweb::token::{show_spend_return_value, Spends};
 use dweb::web::request::{main_server_request, make_main_server_url};
 use dweb::web::{LOCALHOST_STR, SERVER_PORTS_MAIN_PORT};
 
-use crate::cli_options::{Opt, ServerCommands, Subcommands};
+use crate::cli_options::{
+    ConfigCommands, NetworkCommands, Opt, ServerCommands, ServiceCommands, Subcommands,
+};
+use crate::commands::progress::show_upload_progress;
 use crate::commands::server::connect_and_announce;
 
 // Returns true if command complete, false to start the browser
 pub async fn cli_commands(opt: Opt) -> Result<bool> {
-    let api_control = ApiControl {
-        file_retries: opt.retry_file_uploads,
-        api_tries: opt.retry_api,
-        use_public_archive: opt.use_old_archive,
-        ignore_pointers: opt.ignore_pointers,
-        max_fee_per_gas: opt.transaction_opt.max_fee_per_gas,
-        disable_single_node_payment: opt.disable_single_node_payment,
-        ..Default::default()
+    let config = match load_config(&opt) {
+        Ok(config) => config,
+        Err(e) => {
+            println!("{e:?}");
+            return Err(e);
+        }
     };
+    dweb::config::set_config(config.clone())?;
+
+    let local = config.local();
+    let alpha = config.alpha();
+    let mut api_control = config.api_control();
+    api_control.max_fee_per_gas = opt.transaction_opt.max_fee_per_gas;
 
     match opt.cmd {
         Some(Subcommands::Serve { host, port }) => {
             let client_config = DwebClientConfig {
-                local_network: opt.local,
-                alpha_network: opt.alpha,
-                host,
-                port,
+                local_network: local,
+                alpha_network: alpha,
+                network_profile: config.network_profile(),
+                host: host.or(config.server.host.clone()),
+                port: port.or(config.server.port),
                 api_control,
                 ..DwebClientConfig::default()
             };
@@ -68,35 +79,140 @@ pub async fn cli_commands(opt: Opt) -> Result<bool> {
                 foreground,
                 logdir,
             } => {
-                if foreground {
-                    let _ = super::server::start_in_foreground(
-                        opt.local,
-                        opt.alpha,
+                let host = host.or(config.server.host.clone());
+                let port = port.or(config.server.port);
+                let result = if foreground {
+                    super::server::start_in_foreground(
+                        local,
+                        alpha,
                         api_control,
                         host,
                         port,
                         logdir,
                     )
-                    .await;
+                    .await
                 } else {
-                    let _ = super::server::start_in_background(
-                        opt.local,
-                        opt.alpha,
-                        api_control,
-                        host,
-                        port,
-                        logdir,
-                    );
+                    super::server::start_in_background(host, port, logdir).await
+                };
+                match result {
+                    Ok(_) => return Ok(true),
+                    Err(e) => {
+                        println!("{e:?}");
+                        return Err(e);
+                    }
                 }
             }
 
-            ServerCommands::Stop { port_or_all } => { // TODO
+            ServerCommands::Stop { port_or_all } => {
+                match super::server::stop_servers(&port_or_all).await {
+                    Ok(()) => return Ok(true),
+                    Err(e) => {
+                        println!("{e:?}");
+                        return Err(e);
+                    }
+                }
             }
 
-            ServerCommands::Info { port_or_all } => { // TODO
+            ServerCommands::Info { port_or_all } => {
+                match super::server::show_server_info(&port_or_all).await {
+                    Ok(()) => return Ok(true),
+                    Err(e) => {
+                        println!("{e:?}");
+                        return Err(e);
+                    }
+                }
             }
         },
 
+        Some(Subcommands::Network { command }) => {
+            let result = match command {
+                NetworkCommands::List {} => crate::commands::cmd_network::handle_list(&config),
+                NetworkCommands::Add {
+                    name,
+                    peers,
+                    network_contacts_url,
+                    local,
+                    evm_network,
+                    rpc_url,
+                    payment_token_address,
+                    data_payments_address,
+                    get_quorum,
+                    put_quorum,
+                    retry,
+                } => crate::commands::cmd_network::handle_add(NetworkProfile {
+                    name,
+                    peers,
+                    network_contacts_url,
+                    local: local.then_some(true),
+                    evm_network,
+                    rpc_url,
+                    payment_token_address,
+                    data_payments_address,
+                    strategy: StrategyProfile {
+                        get_quorum,
+                        put_quorum,
+                        retry,
+                    },
+                }),
+                NetworkCommands::Remove { name } => {
+                    crate::commands::cmd_network::handle_remove(&name)
+                }
+                NetworkCommands::Use { name } => crate::commands::cmd_network::handle_use(name),
+            };
+            match result {
+                Ok(()) => return Ok(true),
+                Err(e) => {
+                    println!("{e:?}");
+                    return Err(e);
+                }
+            }
+        }
+
+        Some(Subcommands::Config { command }) => match command {
+            ConfigCommands::Show {} => match show_config(&config, opt.config.as_ref()) {
+                Ok(()) => return Ok(true),
+                Err(e) => {
+                    println!("{e:?}");
+                    return Err(e);
+                }
+            },
+        },
+
+        Some(Subcommands::Service { command }) => {
+            let result = match command {
+                ServiceCommands::Install {
+                    host,
+                    port,
+                    workers,
+                    logdir,
+                    no_start,
+                } => {
+                    crate::commands::cmd_service::handle_install(
+                        local,
+                        alpha,
+                        config.network.name.clone(),
+                        host,
+                        port,
+                        workers,
+                        logdir,
+                        no_start,
+                    )
+                    .await
+                }
+                ServiceCommands::Uninstall {} => {
+                    crate::commands::cmd_service::handle_uninstall().await
+                }
+                ServiceCommands::Status {} => crate::commands::cmd_service::handle_status().await,
+            };
+            match result {
+                Ok(()) => return Ok(true),
+                Err(e) => {
+                    println!("{e:?}");
+                    return Err(e);
+                }
+            }
+        }
+
         // TODO consider detecting if the relevant server is running and if not starting automatically
         Some(Subcommands::Open {
             address_name_or_link,
@@ -157,8 +273,8 @@ pub async fn cli_commands(opt: Opt) -> Result<bool> {
 
         Some(Subcommands::Estimate { files_root }) => {
             let (client, _) =
-                connect_and_announce(opt.local, opt.alpha, None, None, api_control, true).await;
-            match client.client.file_cost(&files_root).await {
+                connect_and_announce(local, alpha, None, None, api_control, true).await;
+            match client.network_client()?.file_cost(&files_root).await {
                 Ok(tokens) => println!("Cost estimate: {tokens}"),
                 Err(e) => println!("Unable to estimate cost: {e}"),
             }
@@ -168,22 +284,35 @@ pub async fn cli_commands(opt: Opt) -> Result<bool> {
             files_root,
             name,
             dweb_settings,
+            annotation,
+            no_progress,
+            batch,
             is_new_network: _,
         }) => {
+            let version_manifest = annotation.version_manifest()?;
             let app_secret_key = dweb::helpers::get_app_secret_key()?;
+            api_control.upload_file_by_file = !batch;
             let (client, _) =
-                connect_and_announce(opt.local, opt.alpha, None, None, api_control, true).await;
+                connect_and_announce(local, alpha, None, None, api_control, true).await;
             let spends = Spends::new(&client, Some(&"Publish new cost: ")).await?;
-            let (cost, name, history_address, version) = match publish_or_update_files(
+            let progress = (!no_progress).then(UploadProgressReporter::new);
+            let progress_bar = progress.as_ref().map(show_upload_progress);
+            let result = publish_or_update_files(
                 &client,
                 &files_root,
                 app_secret_key,
                 name,
+                None,
                 dweb_settings,
+                version_manifest,
                 true,
+                progress.as_ref(),
             )
-            .await
-            {
+            .await;
+            if let Some(progress_bar) = progress_bar {
+                let _ = progress_bar.await;
+            }
+            let (cost, name, history_address, version) = match result {
                 Ok(result) => {
                     show_spend_return_value::<(AttoTokens, String, HistoryAddress, u64)>(
                         &spends, result,
@@ -210,11 +339,17 @@ pub async fn cli_commands(opt: Opt) -> Result<bool> {
         Some(Subcommands::Publish_update {
             files_root,
             name,
+            branch,
             dweb_settings,
+            annotation,
+            no_progress,
+            batch,
         }) => {
+            let version_manifest = annotation.version_manifest()?;
             let app_secret_key = dweb::helpers::get_app_secret_key()?;
+            api_control.upload_file_by_file = !batch;
             let (client, _) =
-                connect_and_announce(opt.local, opt.alpha, None, None, api_control, true).await;
+                connect_and_announce(local, alpha, None, None, api_control, true).await;
             let spends = Spends::new(&client, Some(&"Publish update cost: ")).await?;
 
             let name = if name.is_none() {
@@ -252,16 +387,24 @@ pub async fn cli_commands(opt: Opt) -> Result<bool> {
                 ));
             };
 
-            let (cost, name, history_address, version) = match publish_or_update_files(
+            let progress = (!no_progress).then(UploadProgressReporter::new);
+            let progress_bar = progress.as_ref().map(show_upload_progress);
+            let result = publish_or_update_files(
                 &client,
                 &files_root,
                 app_secret_key,
                 Some(name),
+                branch,
                 dweb_settings,
+                version_manifest,
                 false,
+                progress.as_ref(),
             )
-            .await
-            {
+            .await;
+            if let Some(progress_bar) = progress_bar {
+                let _ = progress_bar.await;
+            }
+            let (cost, name, history_address, version) = match result {
                 Ok(result) => {
                     show_spend_return_value::<(AttoTokens, String, HistoryAddress, u64)>(
                         &spends, result,
@@ -286,16 +429,126 @@ pub async fn cli_commands(opt: Opt) -> Result<bool> {
             );
         }
 
-        Some(Subcommands::Wallet_info {}) => {
-            let (client, _) = connect_and_announce(
-                opt.local,
-                opt.alpha,
-                None,
-                None,
-                ApiControl::default(),
-                true,
+        Some(Subcommands::Publish_status { files_root }) => {
+            match crate::commands::cmd_publish::handle_publish_status(files_root) {
+                Ok(()) => return Ok(true),
+                Err(e) => {
+                    println!("{e:?}");
+                    return Err(e);
+                }
+            }
+        }
+
+        Some(Subcommands::Publish_abort { files_root }) => {
+            match crate::commands::cmd_publish::handle_publish_abort(files_root) {
+                Ok(()) => return Ok(true),
+                Err(e) => {
+                    println!("{e:?}");
+                    return Err(e);
+                }
+            }
+        }
+
+        Some(Subcommands::Upload {
+            files_root,
+            name,
+            no_progress,
+            batch,
+        }) => {
+            let app_secret_key = dweb::helpers::get_app_secret_key()?;
+            api_control.upload_file_by_file = !batch;
+            let (client, _) =
+                connect_and_announce(local, alpha, None, None, api_control, true).await;
+            match crate::commands::cmd_private::handle_upload(
+                client,
+                app_secret_key,
+                files_root,
+                name,
+                !no_progress,
             )
-            .await;
+            .await
+            {
+                Ok(()) => return Ok(true),
+                Err(e) => {
+                    println!("{e:?}");
+                    return Err(e);
+                }
+            }
+        }
+
+        Some(Subcommands::Share {
+            name,
+            version,
+            path,
+            all_versions,
+            host,
+            port,
+        }) => {
+            let app_secret_key = dweb::helpers::get_app_secret_key()?;
+            let (client, _) =
+                connect_and_announce(local, alpha, None, None, api_control, true).await;
+            match crate::commands::cmd_private::handle_share(
+                client,
+                app_secret_key,
+                name,
+                version,
+                path,
+                all_versions,
+                host,
+                port,
+            )
+            .await
+            {
+                Ok(()) => return Ok(true),
+                Err(e) => {
+                    println!("{e:?}");
+                    return Err(e);
+                }
+            }
+        }
+
+        Some(Subcommands::Sync {
+            files_root,
+            name,
+            from,
+            key,
+            keep_local,
+            keep_remote,
+            dry_run,
+        }) => {
+            let resolution = if keep_local {
+                ConflictResolution::KeepLocal
+            } else if keep_remote {
+                ConflictResolution::KeepRemote
+            } else {
+                ConflictResolution::Report
+            };
+            let app_secret_key = dweb::helpers::get_app_secret_key()?;
+            let (client, _) =
+                connect_and_announce(local, alpha, None, None, api_control, true).await;
+            match crate::commands::cmd_private::handle_sync(
+                client,
+                app_secret_key,
+                files_root,
+                name,
+                from,
+                key,
+                resolution,
+                dry_run,
+            )
+            .await
+            {
+                Ok(()) => return Ok(true),
+                Err(e) => {
+                    println!("{e:?}");
+                    return Err(e);
+                }
+            }
+        }
+
+        Some(Subcommands::Wallet_info {}) => {
+            let (client, _) =
+                connect_and_announce(local, alpha, None, None, ApiControl::default(), true).await;
             let tokens = client.wallet.balance_of_tokens().await?;
             let gas = client.wallet.balance_of_gas_tokens().await?;
             let network = client.network.identifier();
@@ -312,6 +565,7 @@ pub async fn cli_commands(opt: Opt) -> Result<bool> {
             entries_range,
             shorten_hex_strings,
             include_files,
+            include_annotations,
             graph_keys,
             files_args,
         }) => {
@@ -320,13 +574,14 @@ pub async fn cli_commands(opt: Opt) -> Result<bool> {
                 ..Default::default()
             };
             let (client, _) =
-                connect_and_announce(opt.local, opt.alpha, None, None, api_control, true).await;
+                connect_and_announce(local, alpha, None, None, api_control, true).await;
             match crate::commands::cmd_inspect::handle_inspect_history(
                 client,
                 &address_or_name,
                 print_history_full,
                 entries_range,
                 include_files,
+                include_annotations,
                 graph_keys,
                 shorten_hex_strings,
                 files_args,
@@ -341,6 +596,75 @@ pub async fn cli_commands(opt: Opt) -> Result<bool> {
             }
         }
 
+        Some(Subcommands::Promote {
+            name,
+            branch,
+            version,
+        }) => {
+            let app_secret_key = dweb::helpers::get_app_secret_key()?;
+            let (client, _) =
+                connect_and_announce(local, alpha, None, None, api_control, true).await;
+            match crate::commands::cmd_publish::handle_promote(
+                client,
+                app_secret_key,
+                name,
+                branch,
+                version,
+            )
+            .await
+            {
+                Ok(()) => return Ok(true),
+                Err(e) => {
+                    println!("{e:?}");
+                    return Err(e);
+                }
+            }
+        }
+
+        Some(Subcommands::Hand_over { name, to }) => {
+            let app_secret_key = dweb::helpers::get_app_secret_key()?;
+            let (client, _) =
+                connect_and_announce(local, alpha, None, None, api_control, true).await;
+            match crate::commands::cmd_publish::handle_hand_over(client, app_secret_key, name, to)
+                .await
+            {
+                Ok(()) => return Ok(true),
+                Err(e) => {
+                    println!("{e:?}");
+                    return Err(e);
+                }
+            }
+        }
+
+        Some(Subcommands::Repair_history {
+            name,
+            branch,
+            shorten_hex_strings,
+        }) => {
+            let api_control = ApiControl {
+                ignore_pointers: true,
+                ..Default::default()
+            };
+            let app_secret_key = dweb::helpers::get_app_secret_key()?;
+            let (client, _) =
+                connect_and_announce(local, alpha, None, None, api_control, true).await;
+            match crate::commands::cmd_repair_history::handle_repair_history(
+                client,
+                app_secret_key,
+                name,
+                branch,
+                shorten_hex_strings,
+            )
+            .await
+            {
+                Ok(()) => return Ok(true),
+                Err(e) => {
+                    println!("{e:?}");
+                    return Err(e);
+                }
+            }
+        }
+
         Some(Subcommands::Heal_history {
             name,
             shorten_hex_strings,
@@ -352,7 +676,7 @@ pub async fn cli_commands(opt: Opt) -> Result<bool> {
             };
             let app_secret_key = dweb::helpers::get_app_secret_key()?;
             let (client, _) =
-                connect_and_announce(opt.local, opt.alpha, None, None, api_control, true).await;
+                connect_and_announce(local, alpha, None, None, api_control, true).await;
             match crate::commands::cmd_heal_history::handle_heal_history(
                 client,
                 app_secret_key,
@@ -370,6 +694,52 @@ pub async fn cli_commands(opt: Opt) -> Result<bool> {
             }
         }
 
+        Some(Subcommands::Migrate_register {
+            register_address,
+            name,
+        }) => {
+            let app_secret_key = dweb::helpers::get_app_secret_key()?;
+            let (client, _) =
+                connect_and_announce(local, alpha, None, None, api_control, true).await;
+            match crate::commands::cmd_register::handle_migrate_register(
+                client,
+                app_secret_key,
+                register_address,
+                name,
+            )
+            .await
+            {
+                Ok(()) => return Ok(true),
+                Err(e) => {
+                    println!("{e:?}");
+                    return Err(e);
+                }
+            }
+        }
+
+        Some(Subcommands::Export_register {
+            address_or_name,
+            register_name,
+        }) => {
+            let app_secret_key = dweb::helpers::get_app_secret_key()?;
+            let (client, _) =
+                connect_and_announce(local, alpha, None, None, api_control, true).await;
+            match crate::commands::cmd_register::handle_export_register(
+                client,
+                app_secret_key,
+                address_or_name,
+                register_name,
+            )
+            .await
+            {
+                Ok(()) => return Ok(true),
+                Err(e) => {
+                    println!("{e:?}");
+                    return Err(e);
+                }
+            }
+        }
+
         Some(Subcommands::Inspect_graphentry {
             graph_entry_address,
             print_full,
@@ -379,7 +749,7 @@ pub async fn cli_commands(opt: Opt) -> Result<bool> {
                 ..Default::default()
             };
             let (client, _) =
-                connect_and_announce(opt.local, opt.alpha, None, None, api_control, true).await;
+                connect_and_announce(local, alpha, None, None, api_control, true).await;
             match crate::commands::cmd_inspect::handle_inspect_graphentry(
                 client,
                 graph_entry_address,
@@ -397,15 +767,8 @@ pub async fn cli_commands(opt: Opt) -> Result<bool> {
         }
 
         Some(Subcommands::Inspect_pointer { pointer_address }) => {
-            let (client, _) = connect_and_announce(
-                opt.local,
-                opt.alpha,
-                None,
-                None,
-                ApiControl::default(),
-                true,
-            )
-            .await;
+            let (client, _) =
+                connect_and_announce(local, alpha, None, None, ApiControl::default(), true).await;
             match crate::commands::cmd_inspect::handle_inspect_pointer(client, pointer_address)
                 .await
             {
@@ -421,15 +784,8 @@ pub async fn cli_commands(opt: Opt) -> Result<bool> {
             scratchpad_address,
             data_as_text,
         }) => {
-            let (client, _) = connect_and_announce(
-                opt.local,
-                opt.alpha,
-                None,
-                None,
-                ApiControl::default(),
-                true,
-            )
-            .await;
+            let (client, _) =
+                connect_and_announce(local, alpha, None, None, ApiControl::default(), true).await;
             match crate::commands::cmd_inspect::handle_inspect_scratchpad(
                 client,
                 scratchpad_address,
@@ -449,15 +805,8 @@ pub async fn cli_commands(opt: Opt) -> Result<bool> {
             archive_address,
             files_args,
         }) => {
-            let (client, _) = connect_and_announce(
-                opt.local,
-                opt.alpha,
-                None,
-                None,
-                ApiControl::default(),
-                true,
-            )
-            .await;
+            let (client, _) =
+                connect_and_announce(local, alpha, None, None, ApiControl::default(), true).await;
             match crate::commands::cmd_inspect::handle_inspect_files(
                 client,
                 archive_address,
@@ -546,3 +895,40 @@ pub async fn cli_commands(opt: Opt) -> Result<bool> {
     }
     Ok(true)
 }
+
+/// Settings from the config files and environment, overridden by command line options
+fn load_config(opt: &Opt) -> Result<DwebConfig> {
+    let mut flags = DwebConfig::default();
+    // --local and --alpha also override the network given by a lower layer
+    if opt.local || opt.alpha {
+        flags.network.local = Some(opt.local);
+        flags.network.alpha = Some(opt.alpha);
+    }
+    flags.network.name = opt.network.clone();
+    flags.api.retry_file_uploads = opt.retry_file_uploads;
+    flags.api.retry_api = opt.retry_api;
+    flags.api.show_dweb_costs = opt.show_dweb_costs;
+    flags.api.use_old_archive = opt.use_old_archive.then_some(true);
+    flags.api.ignore_pointers = opt.ignore_pointers.then_some(true);
+    flags.api.disable_single_node_payment = opt.disable_single_node_payment.then_some(true);
+
+    let mut config = DwebConfig::load(opt.config.as_ref())?.merge(flags);
+    if opt.local || opt.alpha {
+        config.network.name = None;
+    }
+    config.check_network_profile()?;
+    Ok(config)
+}
+
+fn show_config(config: &DwebConfig, config_file: Option<&std::path::PathBuf>) -> Result<()> {
+    match default_config_file() {
+        Ok(path) if path.exists() => println!("# Using settings from {path:?}"),
+        Ok(path) => println!("# No settings file at {path:?}"),
+        Err(_) => {}
+    }
+    if let Some(config_file) = config_file {
+        println!("# Using settings from {config_file:?}");
+    }
+    println!("{}", config.with_defaults().to_toml()?);
+    Ok(())
+}
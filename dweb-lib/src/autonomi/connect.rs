@@ -0,0 +1,117 @@
+// Copyright 2024 MaidSafe.net limited.
+//
+// This SAFE Network Software is licensed to you under The General Public License (GPL), version 3.
+// Unless required by applicable law or agreed to in writing, the SAFE Network Software distributed
+// under the GPL Licence is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY
+// KIND, either express or implied. Please review the Licences for the specific language governing
+// permissions and limitations relating to use of the SAFE Network Software.
+
+// Adapted for dweb to connect using a NetworkProfile (see crate::config)
+
+use autonomi::client::config::ClientOperatingStrategy;
+use autonomi::networking::{Quorum, RetryStrategy};
+use autonomi::{Client, ClientConfig, InitialPeersConfig, Multiaddr, Network};
+use color_eyre::eyre::{Result, bail, eyre};
+
+use crate::config::NetworkProfile;
+
+pub async fn connect_to_network_with_config(profile: &NetworkProfile) -> Result<Client> {
+    let config = client_config_for_profile(profile)?;
+    let res = Client::init_with_config(config).await;
+
+    match res {
+        Ok(client) => {
+            println!("Connected to network '{}'", profile.name);
+            Ok(client)
+        }
+        Err(e) => {
+            println!("Failed to connect to network '{}': {e}", profile.name);
+            bail!("Failed to connect to the network: {e}")
+        }
+    }
+}
+
+/// The Autonomi ClientConfig for a network profile
+pub fn client_config_for_profile(profile: &NetworkProfile) -> Result<ClientConfig> {
+    profile.validate()?;
+
+    let mut addrs = Vec::new();
+    for peer in &profile.peers {
+        match peer.parse::<Multiaddr>() {
+            Ok(addr) => addrs.push(addr),
+            Err(e) => return Err(eyre!("Invalid peer '{peer}' - {e}")),
+        }
+    }
+
+    let init_peers_config = InitialPeersConfig {
+        first: false,
+        addrs,
+        network_contacts_url: profile.network_contacts_url.clone(),
+        local: profile.is_local(),
+        ..Default::default()
+    };
+
+    Ok(ClientConfig {
+        init_peers_config,
+        evm_network: evm_network_for_profile(profile),
+        strategy: operating_strategy_for_profile(profile),
+        ..Default::default()
+    })
+}
+
+fn evm_network_for_profile(profile: &NetworkProfile) -> Network {
+    match (
+        &profile.rpc_url,
+        &profile.payment_token_address,
+        &profile.data_payments_address,
+    ) {
+        (Some(rpc_url), Some(payment_token_address), Some(data_payments_address)) => {
+            Network::new_custom(rpc_url, payment_token_address, data_payments_address)
+        }
+        _ => match profile.evm_network.as_deref() {
+            Some("arbitrum-sepolia-test") => Network::ArbitrumSepoliaTest,
+            _ => Network::ArbitrumOne,
+        },
+    }
+}
+
+fn operating_strategy_for_profile(profile: &NetworkProfile) -> ClientOperatingStrategy {
+    let mut strategy = ClientOperatingStrategy::default();
+    for operation in [
+        &mut strategy.chunks,
+        &mut strategy.graph_entry,
+        &mut strategy.pointer,
+        &mut strategy.scratchpad,
+    ] {
+        if let Some(quorum) = parse_quorum(profile.strategy.get_quorum.as_deref()) {
+            operation.get_quorum = quorum;
+        }
+        if let Some(quorum) = parse_quorum(profile.strategy.put_quorum.as_deref()) {
+            operation.put_quorum = quorum;
+        }
+        if let Some(retry) = parse_retry(profile.strategy.retry.as_deref()) {
+            operation.get_retry = retry;
+            operation.put_retry = retry;
+        }
+    }
+    strategy
+}
+
+fn parse_quorum(quorum: Option<&str>) -> Option<Quorum> {
+    match quorum {
+        Some("one") => Some(Quorum::One),
+        Some("majority") => Some(Quorum::Majority),
+        Some("all") => Some(Quorum::All),
+        _ => None,
+    }
+}
+
+fn parse_retry(retry: Option<&str>) -> Option<RetryStrategy> {
+    match retry {
+        Some("none") => Some(RetryStrategy::None),
+        Some("quick") => Some(RetryStrategy::Quick),
+        Some("balanced") => Some(RetryStrategy::Balanced),
+        Some("persistent") => Some(RetryStrategy::Persistent),
+        _ => None,
+    }
+}
@@ -2,5 +2,6 @@ pub mod access;
 // pub mod actions;
 // pub(crate) mod exit_code;
 pub mod args;
+pub mod connect;
 pub mod exit_code;
 pub mod wallet;
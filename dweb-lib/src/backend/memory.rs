@@ -0,0 +1,534 @@
+/*
+ Copyright (c) 2025 Mark Hughes
+
+ This program is free software: you can redistribute it and/or modify
+ it under the terms of the GNU Affero General Public License as published by
+ the Free Software Foundation, either version 3 of the License, or
+ (at your option) any later version.
+
+ This program is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY; without even the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU Affero General Public License for more details.
+
+ You should have received a copy of the GNU Affero General Public License
+ along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! An in-memory DwebBackend for tests, which simulates costs, latency and failures.
+//!
+//! Values are checked as the network would check them: graph entries and pointers can't be
+//! replaced except by a pointer with a higher counter, and two scratchpads stored with
+//! the same counter are returned as a fork. Tests can also create forks directly using
+//! insert_graph_entry_fork() and insert_scratchpad_fork().
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use xor_name::XorName;
+
+use autonomi::chunk::DataMapChunk;
+use autonomi::client::payment::PaymentOption;
+use autonomi::data::DataAddress;
+use autonomi::pointer::PointerTarget;
+use autonomi::{
+    Amount, AttoTokens, Bytes, Chunk, ChunkAddress, GraphEntry, GraphEntryAddress, Pointer,
+    PointerAddress, Scratchpad, ScratchpadAddress, SecretKey, Wallet,
+};
+
+use super::{BackendError, BackendResult, DwebBackend};
+
+/// Starting balance of the simulated wallet (1000 tokens and 1 ETH of gas)
+const DEFAULT_BALANCE_ATTOS: u64 = 1_000_000_000_000_000_000;
+
+/// The behaviour of a MemoryBackend
+#[derive(Clone, Debug)]
+pub struct MemorySettings {
+    /// Token cost of each paid put, in attos
+    pub cost_per_put: u64,
+    /// Gas used by each paid put, in attos
+    pub gas_per_put: u64,
+    /// Delay added to every operation
+    pub latency: Duration,
+    /// Probability (0.0 to 1.0) that an operation fails
+    pub failure_rate: f64,
+}
+
+impl Default for MemorySettings {
+    fn default() -> Self {
+        MemorySettings {
+            cost_per_put: 1,
+            gas_per_put: 1,
+            latency: Duration::ZERO,
+            failure_rate: 0.0,
+        }
+    }
+}
+
+#[derive(Default)]
+struct MemoryStore {
+    chunks: HashMap<String, Chunk>,
+    public_data: HashMap<String, Bytes>,
+    private_data: HashMap<String, Bytes>,
+    pointers: HashMap<String, Pointer>,
+    graph_entries: HashMap<String, Vec<GraphEntry>>,
+    scratchpads: HashMap<String, Vec<Scratchpad>>,
+
+    /// Operations which will fail regardless of failure_rate
+    fail_next: u32,
+    /// Number of paid puts
+    paid_puts: u64,
+    tokens_spent: u64,
+    gas_spent: u64,
+}
+
+/// Storage held in memory, for testing without a network
+#[derive(Default)]
+pub struct MemoryBackend {
+    settings: MemorySettings,
+    store: Mutex<MemoryStore>,
+}
+
+impl MemoryBackend {
+    pub fn new() -> MemoryBackend {
+        MemoryBackend::default()
+    }
+
+    pub fn with_settings(settings: MemorySettings) -> MemoryBackend {
+        MemoryBackend {
+            settings,
+            store: Mutex::new(MemoryStore::default()),
+        }
+    }
+
+    /// Make the next count operations fail
+    pub fn fail_next(&self, count: u32) {
+        if let Ok(mut store) = self.store.lock() {
+            store.fail_next = count;
+        }
+    }
+
+    /// The number of paid puts made so far
+    pub fn paid_puts(&self) -> u64 {
+        match self.store.lock() {
+            Ok(store) => store.paid_puts,
+            Err(_) => 0,
+        }
+    }
+
+    /// Store a second GraphEntry at the address of entry, as happens when two devices
+    /// extend a graph at the same time
+    pub fn insert_graph_entry_fork(&self, entry: GraphEntry) {
+        if let Ok(mut store) = self.store.lock() {
+            store
+                .graph_entries
+                .entry(entry.address().to_hex())
+                .or_default()
+                .push(entry);
+        }
+    }
+
+    /// Store a second Scratchpad with the same counter as any already stored at its address
+    pub fn insert_scratchpad_fork(&self, scratchpad: Scratchpad) {
+        if let Ok(mut store) = self.store.lock() {
+            store
+                .scratchpads
+                .entry(scratchpad.address().to_hex())
+                .or_default()
+                .push(scratchpad);
+        }
+    }
+
+    /// Wait for the simulated latency and decide whether the operation fails
+    async fn operation(&self, operation: &str) -> BackendResult<()> {
+        if !self.settings.latency.is_zero() {
+            tokio::time::sleep(self.settings.latency).await;
+        }
+
+        let mut store = self.lock()?;
+        if store.fail_next > 0 {
+            store.fail_next -= 1;
+            return Err(BackendError::Failed(format!(
+                "{operation} failed (simulated)"
+            )));
+        }
+        if self.settings.failure_rate > 0.0 && rand::random::<f64>() < self.settings.failure_rate
+        {
+            return Err(BackendError::Failed(format!(
+                "{operation} failed (simulated)"
+            )));
+        }
+        Ok(())
+    }
+
+    fn lock(&self) -> BackendResult<std::sync::MutexGuard<'_, MemoryStore>> {
+        self.store
+            .lock()
+            .map_err(|_| BackendError::Failed("in-memory store is poisoned".to_string()))
+    }
+
+    /// Record the cost of a put and return it
+    fn pay(&self, store: &mut MemoryStore) -> AttoTokens {
+        store.paid_puts += 1;
+        store.tokens_spent += self.settings.cost_per_put;
+        store.gas_spent += self.settings.gas_per_put;
+        AttoTokens::from_u64(self.settings.cost_per_put)
+    }
+}
+
+/// A DataMapChunk which identifies private data stored in memory
+fn memory_datamap(data: &Bytes) -> BackendResult<DataMapChunk> {
+    let name = XorName::from_content(data);
+    let marker = [b"dweb-memory-datamap:".as_slice(), name.0.as_slice()].concat();
+    DataMapChunk::from_hex(&hex::encode(marker))
+        .map_err(|e| BackendError::Failed(format!("failed to make datamap - {e}")))
+}
+
+fn not_found(what: &str, address: String) -> BackendError {
+    BackendError::NotFound(format!("{what} at {address}"))
+}
+
+#[async_trait]
+impl DwebBackend for MemoryBackend {
+    fn name(&self) -> String {
+        "in-memory".to_string()
+    }
+
+    async fn chunk_get(&self, address: &ChunkAddress) -> BackendResult<Chunk> {
+        self.operation("chunk_get()").await?;
+        let store = self.lock()?;
+        match store.chunks.get(&address.to_hex()) {
+            Some(chunk) => Ok(chunk.clone()),
+            None => Err(not_found("chunk", address.to_hex())),
+        }
+    }
+
+    async fn chunk_put(
+        &self,
+        chunk: &Chunk,
+        _payment_option: PaymentOption,
+    ) -> BackendResult<(AttoTokens, ChunkAddress)> {
+        self.operation("chunk_put()").await?;
+        let mut store = self.lock()?;
+        let address = *chunk.address();
+        let cost = self.pay(&mut store);
+        store.chunks.insert(address.to_hex(), chunk.clone());
+        Ok((cost, address))
+    }
+
+    async fn data_get_public(&self, address: &DataAddress) -> BackendResult<Bytes> {
+        self.operation("data_get_public()").await?;
+        let store = self.lock()?;
+        match store.public_data.get(&address.to_hex()) {
+            Some(data) => Ok(data.clone()),
+            None => Err(not_found("data", address.to_hex())),
+        }
+    }
+
+    async fn data_put_public(
+        &self,
+        data: Bytes,
+        _payment_option: PaymentOption,
+    ) -> BackendResult<(AttoTokens, DataAddress)> {
+        self.operation("data_put_public()").await?;
+        let name = XorName::from_content(&data);
+        let address = DataAddress::from_hex(&hex::encode(name.0))
+            .map_err(|e| BackendError::Failed(format!("failed to make address - {e}")))?;
+        let mut store = self.lock()?;
+        let cost = self.pay(&mut store);
+        store.public_data.insert(address.to_hex(), data);
+        Ok((cost, address))
+    }
+
+    async fn data_get(&self, datamap: &DataMapChunk) -> BackendResult<Bytes> {
+        self.operation("data_get()").await?;
+        let store = self.lock()?;
+        match store.private_data.get(&datamap.to_hex()) {
+            Some(data) => Ok(data.clone()),
+            None => Err(not_found("private data", datamap.to_hex())),
+        }
+    }
+
+    async fn data_put(
+        &self,
+        data: Bytes,
+        _payment_option: PaymentOption,
+    ) -> BackendResult<(AttoTokens, DataMapChunk)> {
+        self.operation("data_put()").await?;
+        let datamap = memory_datamap(&data)?;
+        let mut store = self.lock()?;
+        let cost = self.pay(&mut store);
+        store.private_data.insert(datamap.to_hex(), data);
+        Ok((cost, datamap))
+    }
+
+    async fn pointer_get(&self, address: &PointerAddress) -> BackendResult<Pointer> {
+        self.operation("pointer_get()").await?;
+        let store = self.lock()?;
+        match store.pointers.get(&address.to_hex()) {
+            Some(pointer) => Ok(pointer.clone()),
+            None => Err(not_found("pointer", address.to_hex())),
+        }
+    }
+
+    async fn pointer_check_existence(&self, address: &PointerAddress) -> BackendResult<bool> {
+        self.operation("pointer_check_existence()").await?;
+        Ok(self.lock()?.pointers.contains_key(&address.to_hex()))
+    }
+
+    async fn pointer_put(
+        &self,
+        pointer: Pointer,
+        _payment_option: PaymentOption,
+    ) -> BackendResult<(AttoTokens, PointerAddress)> {
+        self.operation("pointer_put()").await?;
+        let address = pointer.address();
+        let mut store = self.lock()?;
+        if let Some(existing) = store.pointers.get(&address.to_hex()) {
+            if existing.counter() >= pointer.counter() {
+                return Err(BackendError::Failed(format!(
+                    "pointer_put() counter {} is not above {}",
+                    pointer.counter(),
+                    existing.counter()
+                )));
+            }
+        }
+        let cost = self.pay(&mut store);
+        store.pointers.insert(address.to_hex(), pointer);
+        Ok((cost, address))
+    }
+
+    async fn pointer_create(
+        &self,
+        owner: &SecretKey,
+        target: PointerTarget,
+        _payment_option: PaymentOption,
+    ) -> BackendResult<(AttoTokens, PointerAddress)> {
+        self.operation("pointer_create()").await?;
+        let pointer = Pointer::new(owner, 0, target);
+        let address = pointer.address();
+        let mut store = self.lock()?;
+        if store.pointers.contains_key(&address.to_hex()) {
+            return Err(BackendError::AlreadyExists(address.to_hex()));
+        }
+        let cost = self.pay(&mut store);
+        store.pointers.insert(address.to_hex(), pointer);
+        Ok((cost, address))
+    }
+
+    async fn pointer_update(&self, owner: &SecretKey, target: PointerTarget) -> BackendResult<()> {
+        self.operation("pointer_update()").await?;
+        let address = PointerAddress::new(owner.public_key());
+        let mut store = self.lock()?;
+        let counter = match store.pointers.get(&address.to_hex()) {
+            Some(existing) => existing.counter() + 1,
+            None => return Err(not_found("pointer", address.to_hex())),
+        };
+        store
+            .pointers
+            .insert(address.to_hex(), Pointer::new(owner, counter, target));
+        Ok(())
+    }
+
+    async fn graph_entry_get(&self, address: &GraphEntryAddress) -> BackendResult<GraphEntry> {
+        self.operation("graph_entry_get()").await?;
+        let store = self.lock()?;
+        match store.graph_entries.get(&address.to_hex()) {
+            Some(entries) if entries.len() > 1 => Err(BackendError::GraphEntryFork(entries.clone())),
+            Some(entries) if entries.len() == 1 => Ok(entries[0].clone()),
+            _ => Err(not_found("graph entry", address.to_hex())),
+        }
+    }
+
+    async fn graph_entry_check_existence(
+        &self,
+        address: &GraphEntryAddress,
+    ) -> BackendResult<bool> {
+        self.operation("graph_entry_check_existence()").await?;
+        Ok(self.lock()?.graph_entries.contains_key(&address.to_hex()))
+    }
+
+    async fn graph_entry_put(
+        &self,
+        entry: GraphEntry,
+        _payment_option: PaymentOption,
+    ) -> BackendResult<(AttoTokens, GraphEntryAddress)> {
+        self.operation("graph_entry_put()").await?;
+        let address = entry.address();
+        let mut store = self.lock()?;
+        if store.graph_entries.contains_key(&address.to_hex()) {
+            return Err(BackendError::AlreadyExists(address.to_hex()));
+        }
+        let cost = self.pay(&mut store);
+        store.graph_entries.insert(address.to_hex(), vec![entry]);
+        Ok((cost, address))
+    }
+
+    async fn scratchpad_get(&self, address: &ScratchpadAddress) -> BackendResult<Scratchpad> {
+        self.operation("scratchpad_get()").await?;
+        let store = self.lock()?;
+        match store.scratchpads.get(&address.to_hex()) {
+            Some(scratchpads) if scratchpads.len() > 1 => {
+                Err(BackendError::ScratchpadFork(scratchpads.clone()))
+            }
+            Some(scratchpads) if scratchpads.len() == 1 => Ok(scratchpads[0].clone()),
+            _ => Err(not_found("scratchpad", address.to_hex())),
+        }
+    }
+
+    async fn scratchpad_check_existence(
+        &self,
+        address: &ScratchpadAddress,
+    ) -> BackendResult<bool> {
+        self.operation("scratchpad_check_existence()").await?;
+        Ok(self.lock()?.scratchpads.contains_key(&address.to_hex()))
+    }
+
+    async fn scratchpad_put(
+        &self,
+        scratchpad: Scratchpad,
+        _payment_option: PaymentOption,
+    ) -> BackendResult<(AttoTokens, ScratchpadAddress)> {
+        self.operation("scratchpad_put()").await?;
+        let address = *scratchpad.address();
+        let mut store = self.lock()?;
+        let highest = store
+            .scratchpads
+            .get(&address.to_hex())
+            .and_then(|scratchpads| scratchpads.iter().map(|s| s.counter()).max());
+        if let Some(highest) = highest {
+            if scratchpad.counter() <= highest {
+                return Err(BackendError::Failed(format!(
+                    "scratchpad_put() counter {} is not above {highest}",
+                    scratchpad.counter()
+                )));
+            }
+        }
+        let cost = self.pay(&mut store);
+        store.scratchpads.insert(address.to_hex(), vec![scratchpad]);
+        Ok((cost, address))
+    }
+
+    async fn scratchpad_create(
+        &self,
+        owner: &SecretKey,
+        content_type: u64,
+        data: &Bytes,
+        _payment_option: PaymentOption,
+    ) -> BackendResult<(AttoTokens, ScratchpadAddress)> {
+        self.operation("scratchpad_create()").await?;
+        let scratchpad = Scratchpad::new(owner, content_type, data, 0);
+        let address = *scratchpad.address();
+        let mut store = self.lock()?;
+        if store.scratchpads.contains_key(&address.to_hex()) {
+            return Err(BackendError::AlreadyExists(address.to_hex()));
+        }
+        let cost = self.pay(&mut store);
+        store.scratchpads.insert(address.to_hex(), vec![scratchpad]);
+        Ok((cost, address))
+    }
+
+    async fn scratchpad_update(
+        &self,
+        owner: &SecretKey,
+        content_type: u64,
+        data: &Bytes,
+    ) -> BackendResult<()> {
+        self.operation("scratchpad_update()").await?;
+        let address = ScratchpadAddress::new(owner.public_key());
+        let mut store = self.lock()?;
+        let counter = match store.scratchpads.get(&address.to_hex()) {
+            Some(scratchpads) if scratchpads.len() > 1 => {
+                return Err(BackendError::ScratchpadFork(scratchpads.clone()));
+            }
+            Some(scratchpads) if scratchpads.len() == 1 => scratchpads[0].counter() + 1,
+            _ => return Err(not_found("scratchpad", address.to_hex())),
+        };
+        let scratchpad = Scratchpad::new(owner, content_type, data, counter);
+        store.scratchpads.insert(address.to_hex(), vec![scratchpad]);
+        Ok(())
+    }
+
+    async fn balance_of_tokens(&self, _wallet: &Wallet) -> BackendResult<Amount> {
+        let spent = self.lock()?.tokens_spent;
+        Ok(Amount::from(DEFAULT_BALANCE_ATTOS.saturating_sub(spent)))
+    }
+
+    async fn balance_of_gas_tokens(&self, _wallet: &Wallet) -> BackendResult<Amount> {
+        let spent = self.lock()?.gas_spent;
+        Ok(Amount::from(DEFAULT_BALANCE_ATTOS.saturating_sub(spent)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn puts_are_paid_and_forks_reported() {
+        let backend = MemoryBackend::new();
+        let wallet = Wallet::new_with_random_wallet(autonomi::Network::ArbitrumSepoliaTest);
+        let payment = PaymentOption::from(&wallet);
+
+        let (cost, address) = backend
+            .data_put_public(Bytes::from("hello"), payment.clone())
+            .await
+            .unwrap();
+        assert_eq!(cost, AttoTokens::from_u64(1));
+        assert_eq!(
+            backend.data_get_public(&address).await.unwrap(),
+            Bytes::from("hello")
+        );
+        let (_, datamap) = backend
+            .data_put(Bytes::from("secret"), payment.clone())
+            .await
+            .unwrap();
+        assert_eq!(backend.data_get(&datamap).await.unwrap(), Bytes::from("secret"));
+
+        let owner = SecretKey::random();
+        let content = Bytes::from("one");
+        let (_, address) = backend
+            .scratchpad_create(&owner, 0, &content, payment.clone())
+            .await
+            .unwrap();
+        backend
+            .scratchpad_update(&owner, 0, &Bytes::from("two"))
+            .await
+            .unwrap();
+        assert_eq!(backend.scratchpad_get(&address).await.unwrap().counter(), 1);
+        backend.insert_scratchpad_fork(Scratchpad::new(&owner, 0, &Bytes::from("three"), 1));
+        assert!(matches!(
+            backend.scratchpad_get(&address).await,
+            Err(BackendError::ScratchpadFork(forks)) if forks.len() == 2
+        ));
+
+        assert_eq!(backend.paid_puts(), 3);
+        assert!(backend.balance_of_tokens(&wallet).await.unwrap() < Amount::from(DEFAULT_BALANCE_ATTOS));
+    }
+
+    #[actix_web::test]
+    async fn failures_are_simulated() {
+        let backend = MemoryBackend::new();
+        let wallet = Wallet::new_with_random_wallet(autonomi::Network::ArbitrumSepoliaTest);
+        let owner = SecretKey::random();
+        let target = PointerTarget::ChunkAddress(ChunkAddress::new(XorName::random(
+            &mut rand::thread_rng(),
+        )));
+
+        backend.fail_next(1);
+        assert!(
+            backend
+                .pointer_create(&owner, target.clone(), PaymentOption::from(&wallet))
+                .await
+                .is_err()
+        );
+        let (_, address) = backend
+            .pointer_create(&owner, target.clone(), PaymentOption::from(&wallet))
+            .await
+            .unwrap();
+        backend.pointer_update(&owner, target).await.unwrap();
+        assert_eq!(backend.pointer_get(&address).await.unwrap().counter(), 1);
+    }
+}
@@ -0,0 +1,232 @@
+/*
+ Copyright (c) 2025 Mark Hughes
+
+ This program is free software: you can redistribute it and/or modify
+ it under the terms of the GNU Affero General Public License as published by
+ the Free Software Foundation, either version 3 of the License, or
+ (at your option) any later version.
+
+ This program is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY; without even the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU Affero General Public License for more details.
+
+ You should have received a copy of the GNU Affero General Public License
+ along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! The storage operations used by dweb, implemented by an Autonomi network or in memory.
+//!
+//! DwebClient holds a DwebBackend which is used for reading and writing chunks, data,
+//! archives, pointers, graph entries and scratchpads. When connected to Autonomi this is
+//! the autonomi::Client (see network.rs). For tests, MemoryBackend (see memory.rs) keeps
+//! everything in memory and can simulate costs, latency and failures, so that History
+//! publishing, pointer healing and server routes can be exercised without a network.
+//!
+//! Operations which only make sense on a real network, such as uploading a directory in
+//! batches with payment receipts, still use the autonomi::Client via
+//! DwebClient::network_client().
+
+pub mod memory;
+pub mod network;
+
+use std::fmt;
+
+use async_trait::async_trait;
+
+use autonomi::chunk::DataMapChunk;
+use autonomi::client::payment::PaymentOption;
+use autonomi::data::DataAddress;
+use autonomi::files::archive_public::ArchiveAddress;
+use autonomi::files::{PrivateArchive, PublicArchive};
+use autonomi::pointer::PointerTarget;
+use autonomi::{
+    Amount, AttoTokens, Bytes, Chunk, ChunkAddress, GraphEntry, GraphEntryAddress, Pointer,
+    PointerAddress, Scratchpad, ScratchpadAddress, SecretKey, Wallet,
+};
+
+pub use memory::MemoryBackend;
+
+/// Why a storage operation failed
+///
+/// Forks are returned with the conflicting values so that callers can choose how to resolve them.
+#[derive(Debug, Clone)]
+pub enum BackendError {
+    /// Nothing is stored at the address
+    NotFound(String),
+    /// A value which can't be replaced is already stored at the address (as hex)
+    AlreadyExists(String),
+    /// More than one GraphEntry is stored at the address
+    GraphEntryFork(Vec<GraphEntry>),
+    /// More than one Scratchpad with the highest counter is stored at the address
+    ScratchpadFork(Vec<Scratchpad>),
+    /// Payment couldn't be made, such as insufficient funds or a wallet on the wrong network
+    Payment(String),
+    /// The request can never succeed, such as an undecodable datamap or the wrong kind of record
+    InvalidInput(String),
+    /// Any other failure, such as a network error or failure to get quotes
+    Failed(String),
+}
+
+impl fmt::Display for BackendError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BackendError::NotFound(what) => write!(f, "not found: {what}"),
+            BackendError::AlreadyExists(address) => write!(f, "already exists at {address}"),
+            BackendError::GraphEntryFork(entries) => {
+                write!(f, "forked GraphEntry, {} entries found", entries.len())
+            }
+            BackendError::ScratchpadFork(scratchpads) => {
+                write!(f, "forked Scratchpad, {} versions found", scratchpads.len())
+            }
+            BackendError::Payment(message) => write!(f, "payment failed: {message}"),
+            BackendError::InvalidInput(message) => write!(f, "invalid input: {message}"),
+            BackendError::Failed(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for BackendError {}
+
+impl BackendError {
+    /// True if trying the operation again will fail in the same way
+    ///
+    /// A value which already exists, a fork, a failed payment or invalid input won't
+    /// change by retrying. Anything missing may not have reached enough nodes yet, and
+    /// other failures may be temporary, so these are not permanent (see
+    /// helpers::retry::classify_error()).
+    pub fn is_permanent(&self) -> bool {
+        match self {
+            BackendError::AlreadyExists(_)
+            | BackendError::GraphEntryFork(_)
+            | BackendError::ScratchpadFork(_)
+            | BackendError::Payment(_)
+            | BackendError::InvalidInput(_) => true,
+            BackendError::NotFound(_) | BackendError::Failed(_) => false,
+        }
+    }
+}
+
+pub type BackendResult<T> = std::result::Result<T, BackendError>;
+
+/// Storage used by dweb
+///
+/// Payment is made according to the PaymentOption, and the cost paid returned along
+/// with the address of what was stored.
+#[async_trait]
+pub trait DwebBackend: Send + Sync {
+    /// A short description for messages, such as "Autonomi" or "in-memory"
+    fn name(&self) -> String;
+
+    async fn chunk_get(&self, address: &ChunkAddress) -> BackendResult<Chunk>;
+    async fn chunk_put(
+        &self,
+        chunk: &Chunk,
+        payment_option: PaymentOption,
+    ) -> BackendResult<(AttoTokens, ChunkAddress)>;
+
+    async fn data_get_public(&self, address: &DataAddress) -> BackendResult<Bytes>;
+    async fn data_put_public(
+        &self,
+        data: Bytes,
+        payment_option: PaymentOption,
+    ) -> BackendResult<(AttoTokens, DataAddress)>;
+    async fn data_get(&self, datamap: &DataMapChunk) -> BackendResult<Bytes>;
+    async fn data_put(
+        &self,
+        data: Bytes,
+        payment_option: PaymentOption,
+    ) -> BackendResult<(AttoTokens, DataMapChunk)>;
+
+    async fn pointer_get(&self, address: &PointerAddress) -> BackendResult<Pointer>;
+    async fn pointer_check_existence(&self, address: &PointerAddress) -> BackendResult<bool>;
+    async fn pointer_put(
+        &self,
+        pointer: Pointer,
+        payment_option: PaymentOption,
+    ) -> BackendResult<(AttoTokens, PointerAddress)>;
+    async fn pointer_create(
+        &self,
+        owner: &SecretKey,
+        target: PointerTarget,
+        payment_option: PaymentOption,
+    ) -> BackendResult<(AttoTokens, PointerAddress)>;
+    /// Update an existing pointer, increasing its counter (free)
+    async fn pointer_update(&self, owner: &SecretKey, target: PointerTarget) -> BackendResult<()>;
+
+    async fn graph_entry_get(&self, address: &GraphEntryAddress) -> BackendResult<GraphEntry>;
+    async fn graph_entry_check_existence(
+        &self,
+        address: &GraphEntryAddress,
+    ) -> BackendResult<bool>;
+    async fn graph_entry_put(
+        &self,
+        entry: GraphEntry,
+        payment_option: PaymentOption,
+    ) -> BackendResult<(AttoTokens, GraphEntryAddress)>;
+
+    async fn scratchpad_get(&self, address: &ScratchpadAddress) -> BackendResult<Scratchpad>;
+    async fn scratchpad_check_existence(
+        &self,
+        address: &ScratchpadAddress,
+    ) -> BackendResult<bool>;
+    async fn scratchpad_put(
+        &self,
+        scratchpad: Scratchpad,
+        payment_option: PaymentOption,
+    ) -> BackendResult<(AttoTokens, ScratchpadAddress)>;
+    async fn scratchpad_create(
+        &self,
+        owner: &SecretKey,
+        content_type: u64,
+        data: &Bytes,
+        payment_option: PaymentOption,
+    ) -> BackendResult<(AttoTokens, ScratchpadAddress)>;
+    /// Update an existing scratchpad, increasing its counter (free)
+    async fn scratchpad_update(
+        &self,
+        owner: &SecretKey,
+        content_type: u64,
+        data: &Bytes,
+    ) -> BackendResult<()>;
+
+    /// The token balance of wallet, used to report the cost of operations
+    async fn balance_of_tokens(&self, wallet: &Wallet) -> BackendResult<Amount>;
+    /// The gas balance of wallet, used to report the cost of operations
+    async fn balance_of_gas_tokens(&self, wallet: &Wallet) -> BackendResult<Amount>;
+
+    /// Archives are stored as data so these need not be implemented by a backend
+    async fn archive_get_public(&self, address: &ArchiveAddress) -> BackendResult<PublicArchive> {
+        let bytes = self.data_get_public(address).await?;
+        PublicArchive::from_bytes(bytes)
+            .map_err(|e| BackendError::Failed(format!("invalid PublicArchive - {e}")))
+    }
+
+    async fn archive_put_public(
+        &self,
+        archive: &PublicArchive,
+        payment_option: PaymentOption,
+    ) -> BackendResult<(AttoTokens, ArchiveAddress)> {
+        let bytes = archive
+            .to_bytes()
+            .map_err(|e| BackendError::Failed(format!("failed to serialise archive - {e}")))?;
+        self.data_put_public(bytes, payment_option).await
+    }
+
+    async fn archive_get(&self, datamap: &DataMapChunk) -> BackendResult<PrivateArchive> {
+        let bytes = self.data_get(datamap).await?;
+        PrivateArchive::from_bytes(bytes)
+            .map_err(|e| BackendError::Failed(format!("invalid PrivateArchive - {e}")))
+    }
+
+    async fn archive_put(
+        &self,
+        archive: &PrivateArchive,
+        payment_option: PaymentOption,
+    ) -> BackendResult<(AttoTokens, DataMapChunk)> {
+        let bytes = archive
+            .to_bytes()
+            .map_err(|e| BackendError::Failed(format!("failed to serialise archive - {e}")))?;
+        self.data_put(bytes, payment_option).await
+    }
+}
@@ -0,0 +1,290 @@
+/*
+ Copyright (c) 2025 Mark Hughes
+
+ This program is free software: you can redistribute it and/or modify
+ it under the terms of the GNU Affero General Public License as published by
+ the Free Software Foundation, either version 3 of the License, or
+ (at your option) any later version.
+
+ This program is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY; without even the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU Affero General Public License for more details.
+
+ You should have received a copy of the GNU Affero General Public License
+ along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! DwebBackend for an Autonomi network, implemented by delegating to autonomi::Client
+
+use async_trait::async_trait;
+
+use autonomi::chunk::DataMapChunk;
+use autonomi::client::payment::PayError;
+use autonomi::client::payment::PaymentOption;
+use autonomi::client::{GetError, PutError};
+use autonomi::data::DataAddress;
+use autonomi::files::UploadError;
+use autonomi::graph::GraphError;
+use autonomi::pointer::PointerError;
+use autonomi::pointer::PointerTarget;
+use autonomi::scratchpad::ScratchpadError;
+use autonomi::{
+    Amount, AttoTokens, Bytes, Chunk, ChunkAddress, Client, GraphEntry, GraphEntryAddress,
+    Pointer, PointerAddress, Scratchpad, ScratchpadAddress, SecretKey, Wallet,
+};
+
+use super::{BackendError, BackendResult, DwebBackend};
+
+fn get_error(e: GetError) -> BackendError {
+    match e {
+        GetError::RecordNotFound => BackendError::NotFound(e.to_string()),
+        GetError::InvalidDataMap(_)
+        | GetError::UnrecognizedDataMap(_)
+        | GetError::RecordKindMismatch(_)
+        | GetError::TooLargeForMemory(_) => BackendError::InvalidInput(e.to_string()),
+        e => BackendError::Failed(e.to_string()),
+    }
+}
+
+/// Failure to get quotes (PayError::Cost) may be temporary, but not a wallet error
+/// such as insufficient funds
+fn pay_error(e: &PayError) -> BackendError {
+    match e {
+        PayError::EvmWalletNetworkMismatch | PayError::EvmWalletError(_) => {
+            BackendError::Payment(e.to_string())
+        }
+        e => failed(e),
+    }
+}
+
+fn put_error(e: PutError) -> BackendError {
+    match &e {
+        PutError::PayError(pay) => pay_error(pay),
+        PutError::Wallet(_) => BackendError::Payment(e.to_string()),
+        _ => failed(&e),
+    }
+}
+
+/// Classify an error from uploading files with the autonomi::Client
+pub fn upload_error(e: &UploadError) -> BackendError {
+    match e {
+        UploadError::PutError(PutError::PayError(pay)) => pay_error(pay),
+        UploadError::PutError(PutError::Wallet(_)) => BackendError::Payment(e.to_string()),
+        e => failed(e),
+    }
+}
+
+fn graph_error(e: GraphError) -> BackendError {
+    match e {
+        GraphError::Fork(entries) => BackendError::GraphEntryFork(entries),
+        GraphError::AlreadyExists(address) => BackendError::AlreadyExists(address.to_hex()),
+        GraphError::Pay(_) | GraphError::Wallet(_) | GraphError::InvalidQuote => {
+            BackendError::Payment(e.to_string())
+        }
+        e => BackendError::Failed(e.to_string()),
+    }
+}
+
+fn pointer_error(e: PointerError) -> BackendError {
+    match e {
+        PointerError::Pay(_) | PointerError::Wallet(_) | PointerError::InvalidQuote => {
+            BackendError::Payment(e.to_string())
+        }
+        e => BackendError::Failed(e.to_string()),
+    }
+}
+
+fn scratchpad_error(e: ScratchpadError) -> BackendError {
+    match e {
+        ScratchpadError::Fork(scratchpads) => BackendError::ScratchpadFork(scratchpads),
+        ScratchpadError::Pay(_) | ScratchpadError::Wallet(_) | ScratchpadError::InvalidQuote => {
+            BackendError::Payment(e.to_string())
+        }
+        e => BackendError::Failed(e.to_string()),
+    }
+}
+
+fn failed(e: impl std::fmt::Display) -> BackendError {
+    BackendError::Failed(e.to_string())
+}
+
+#[async_trait]
+impl DwebBackend for Client {
+    fn name(&self) -> String {
+        format!("Autonomi ({})", self.evm_network().identifier())
+    }
+
+    async fn chunk_get(&self, address: &ChunkAddress) -> BackendResult<Chunk> {
+        Client::chunk_get(self, address).await.map_err(get_error)
+    }
+
+    async fn chunk_put(
+        &self,
+        chunk: &Chunk,
+        payment_option: PaymentOption,
+    ) -> BackendResult<(AttoTokens, ChunkAddress)> {
+        Client::chunk_put(self, chunk, payment_option)
+            .await
+            .map_err(put_error)
+    }
+
+    async fn data_get_public(&self, address: &DataAddress) -> BackendResult<Bytes> {
+        Client::data_get_public(self, address)
+            .await
+            .map_err(get_error)
+    }
+
+    async fn data_put_public(
+        &self,
+        data: Bytes,
+        payment_option: PaymentOption,
+    ) -> BackendResult<(AttoTokens, DataAddress)> {
+        Client::data_put_public(self, data, payment_option)
+            .await
+            .map_err(put_error)
+    }
+
+    async fn data_get(&self, datamap: &DataMapChunk) -> BackendResult<Bytes> {
+        Client::data_get(self, datamap).await.map_err(get_error)
+    }
+
+    async fn data_put(
+        &self,
+        data: Bytes,
+        payment_option: PaymentOption,
+    ) -> BackendResult<(AttoTokens, DataMapChunk)> {
+        Client::data_put(self, data, payment_option)
+            .await
+            .map_err(put_error)
+    }
+
+    async fn pointer_get(&self, address: &PointerAddress) -> BackendResult<Pointer> {
+        match Client::pointer_get(self, address).await {
+            Ok(pointer) => Ok(pointer),
+            // PointerError doesn't distinguish a missing pointer from other failures
+            Err(e) => match Client::pointer_check_existence(self, address).await {
+                Ok(false) => Err(BackendError::NotFound(format!(
+                    "pointer at {} - {e}",
+                    address.to_hex()
+                ))),
+                _ => Err(pointer_error(e)),
+            },
+        }
+    }
+
+    async fn pointer_check_existence(&self, address: &PointerAddress) -> BackendResult<bool> {
+        Client::pointer_check_existence(self, address)
+            .await
+            .map_err(failed)
+    }
+
+    async fn pointer_put(
+        &self,
+        pointer: Pointer,
+        payment_option: PaymentOption,
+    ) -> BackendResult<(AttoTokens, PointerAddress)> {
+        Client::pointer_put(self, pointer, payment_option)
+            .await
+            .map_err(pointer_error)
+    }
+
+    async fn pointer_create(
+        &self,
+        owner: &SecretKey,
+        target: PointerTarget,
+        payment_option: PaymentOption,
+    ) -> BackendResult<(AttoTokens, PointerAddress)> {
+        Client::pointer_create(self, owner, target, payment_option)
+            .await
+            .map_err(pointer_error)
+    }
+
+    async fn pointer_update(&self, owner: &SecretKey, target: PointerTarget) -> BackendResult<()> {
+        match Client::pointer_update(self, owner, target).await {
+            Ok(_) => Ok(()),
+            Err(e) => Err(pointer_error(e)),
+        }
+    }
+
+    async fn graph_entry_get(&self, address: &GraphEntryAddress) -> BackendResult<GraphEntry> {
+        Client::graph_entry_get(self, address)
+            .await
+            .map_err(graph_error)
+    }
+
+    async fn graph_entry_check_existence(
+        &self,
+        address: &GraphEntryAddress,
+    ) -> BackendResult<bool> {
+        Client::graph_entry_check_existence(self, address)
+            .await
+            .map_err(graph_error)
+    }
+
+    async fn graph_entry_put(
+        &self,
+        entry: GraphEntry,
+        payment_option: PaymentOption,
+    ) -> BackendResult<(AttoTokens, GraphEntryAddress)> {
+        Client::graph_entry_put(self, entry, payment_option)
+            .await
+            .map_err(graph_error)
+    }
+
+    async fn scratchpad_get(&self, address: &ScratchpadAddress) -> BackendResult<Scratchpad> {
+        Client::scratchpad_get(self, address)
+            .await
+            .map_err(scratchpad_error)
+    }
+
+    async fn scratchpad_check_existence(
+        &self,
+        address: &ScratchpadAddress,
+    ) -> BackendResult<bool> {
+        Client::scratchpad_check_existence(self, address)
+            .await
+            .map_err(scratchpad_error)
+    }
+
+    async fn scratchpad_put(
+        &self,
+        scratchpad: Scratchpad,
+        payment_option: PaymentOption,
+    ) -> BackendResult<(AttoTokens, ScratchpadAddress)> {
+        Client::scratchpad_put(self, scratchpad, payment_option)
+            .await
+            .map_err(scratchpad_error)
+    }
+
+    async fn scratchpad_create(
+        &self,
+        owner: &SecretKey,
+        content_type: u64,
+        data: &Bytes,
+        payment_option: PaymentOption,
+    ) -> BackendResult<(AttoTokens, ScratchpadAddress)> {
+        Client::scratchpad_create(self, owner, content_type, data, payment_option)
+            .await
+            .map_err(scratchpad_error)
+    }
+
+    async fn scratchpad_update(
+        &self,
+        owner: &SecretKey,
+        content_type: u64,
+        data: &Bytes,
+    ) -> BackendResult<()> {
+        Client::scratchpad_update(self, owner, content_type, data)
+            .await
+            .map_err(scratchpad_error)
+    }
+
+    async fn balance_of_tokens(&self, wallet: &Wallet) -> BackendResult<Amount> {
+        wallet.balance_of_tokens().await.map_err(failed)
+    }
+
+    async fn balance_of_gas_tokens(&self, wallet: &Wallet) -> BackendResult<Amount> {
+        wallet.balance_of_gas_tokens().await.map_err(failed)
+    }
+}
@@ -19,19 +19,20 @@
 //! requests and to provide a local DNS based on DWEB-NAME, where each DWEB-NAME corresponds
 //! do a Tree history (History<Tree>).
 
-use std::sync::{LazyLock, Mutex};
-
-use schnellru::{ByLength, LruMap};
+use std::sync::LazyLock;
 
 use autonomi::client::files::archive_public::ArchiveAddress;
 
+use crate::cache::single_flight::SingleFlightCache;
+use crate::config::config;
 use crate::files::directory::Tree;
 use crate::history::HistoryAddress;
 use crate::web::name::DwebHost;
 
-// TODO: tune these values
-const DWEB_NAMES_CAPACITY: u32 = 1000; // When exceeded, DWEB-NAMES will be forgotten and new versions inaccessible
-const VERSIONS_CAPACITY: u32 = 1000; // When exceeded, particular versions will be dropped but will remain accessible so long as the DWEB-NAME is cached
+// Capacities are set by [cache] dweb_names and versions in dweb.toml (see crate::config)
+//
+// When dweb_names is exceeded, DWEB-NAMES will be forgotten and new versions inaccessible
+// When versions is exceeded, particular versions will be dropped but will remain accessible so long as the DWEB-NAME is cached
 
 // Note:
 // I considered using DWEB-NAME.www-dweb.au as the key to avoid clashes of the same
@@ -46,15 +47,9 @@ const VERSIONS_CAPACITY: u32 = 1000; // When exceeded, particular versions will
 ///
 /// Entry:   DirectoryVersionWithName
 ///
-// TODO use Mutex here because LazyLock.get_mut() is a Nightly Rust feature (01/2025)
-pub static DIRECTORY_VERSIONS_WITH_NAME: LazyLock<Mutex<LruMap<String, DirectoryVersionWithName>>> =
+pub static DIRECTORY_VERSIONS_WITH_NAME: LazyLock<SingleFlightCache<DirectoryVersionWithName>> =
     LazyLock::new(|| {
-        Mutex::<LruMap<String, DirectoryVersionWithName>>::new(LruMap::<
-            String,
-            DirectoryVersionWithName,
-        >::new(ByLength::new(
-            VERSIONS_CAPACITY,
-        )))
+        SingleFlightCache::new("DIRECTORY_VERSIONS_WITH_NAME", config().versions_capacity())
     });
 
 /// HISTORY_NAMES is a cache which acts like local DNS, providing a lookup of DWEB-NAME
@@ -67,12 +62,8 @@ pub static DIRECTORY_VERSIONS_WITH_NAME: LazyLock<Mutex<LruMap<String, Directory
 /// This cache is populated by a successful API call to create a DWEB-NAME, so long as a
 /// a History can initialise using a supplied HISTORY-ADDRESS.
 ///
-// TODO use Mutex here because LazyLock.get_mut() is a Nightly Rust feature (01/2025)
-pub static HISTORY_NAMES: LazyLock<Mutex<LruMap<String, HistoryAddress>>> = LazyLock::new(|| {
-    Mutex::<LruMap<String, HistoryAddress>>::new(LruMap::<String, HistoryAddress>::new(
-        ByLength::new(DWEB_NAMES_CAPACITY),
-    ))
-});
+pub static HISTORY_NAMES: LazyLock<SingleFlightCache<HistoryAddress>> =
+    LazyLock::new(|| SingleFlightCache::new("HISTORY_NAMES", config().dweb_names_capacity()));
 
 #[derive(Clone)]
 pub struct DirectoryVersionWithName {
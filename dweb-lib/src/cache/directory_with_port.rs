@@ -23,21 +23,23 @@
 //! and subsequent requests to be served by the correct listener.
 
 use std::fmt::{self, Display, Formatter};
-use std::sync::{LazyLock, Mutex};
+use std::sync::LazyLock;
 
 use color_eyre::eyre::{eyre, Result};
-use schnellru::{ByLength, LruMap};
 
 use autonomi::client::files::archive_public::ArchiveAddress;
 
 use crate::cache::directory_with_name::HISTORY_NAMES;
+use crate::cache::single_flight::SingleFlightCache;
 use crate::client::DwebClient;
+use crate::config::config;
 use crate::files::directory::Tree;
 use crate::helpers::convert::*;
 use crate::history::HistoryAddress;
+use crate::web::server::ListenerInfo;
 
-// TODO: tune cache size values
-const WITH_PORT_CAPACITY: u32 = u16::MAX as u32; // When exceeded, port servers will be forgotten and new versions inaccessible
+// Capacity is set by [cache] directories_with_port in dweb.toml (see crate::config)
+// When exceeded, port servers will be forgotten and new versions inaccessible
 
 /// A cache of DirectoryVersionWithPort
 ///
@@ -46,9 +48,8 @@ const WITH_PORT_CAPACITY: u32 = u16::MAX as u32; // When exceeded, port servers
 /// Entry:   DirectoryVersionWithPort
 ///
 
-// Port configuration for the deterministic port algorithm
-const DETERMINISTIC_PORT_RANGE: u64 = 20_000;
-const DETERMINISTIC_PORT_BASE: u64 = 30_000;
+// The deterministic port algorithm uses [server] listener_port_base and listener_port_range
+// in dweb.toml (see crate::config)
 
 pub fn key_for_directory_versions_with_port(archive_address: ArchiveAddress) -> String {
     format!("{}", archive_address.to_hex()).to_ascii_lowercase()
@@ -63,23 +64,20 @@ pub fn key_for_directory_versions_with_port(archive_address: ArchiveAddress) ->
 //     format!("{address}-v{version_str}")
 // }
 
-// TODO use Mutex here because LazyLock.get_mut() is a Nightly Rust feature (01/2025)
-pub static DIRECTORY_VERSIONS_WITH_PORT: LazyLock<Mutex<LruMap<String, DirectoryVersionWithPort>>> =
+pub static DIRECTORY_VERSIONS_WITH_PORT: LazyLock<SingleFlightCache<DirectoryVersionWithPort>> =
     LazyLock::new(|| {
-        Mutex::<LruMap<String, DirectoryVersionWithPort>>::new(LruMap::<
-            String,
-            DirectoryVersionWithPort,
-        >::new(ByLength::new(
-            WITH_PORT_CAPACITY,
-        )))
+        SingleFlightCache::new(
+            "DIRECTORY_VERSIONS_WITH_PORT",
+            config().with_port_capacity(),
+        )
     });
 
 /// Generates a deterministic port number from an archive address.
 /// The algorithm:
 /// 1. Converts the archive address to its hex representation (always 64 hex characters)
 /// 2. Takes the last 16 hex characters and converts them to a number
-/// 3. Takes the number modulo DETERMINISTIC_PORT_RANGE
-/// 4. Adds DETERMINISTIC_PORT_BASE as a base to get a port in the range DETERMINISTIC_PORT_BASE + DETERMINISTIC_PORT_RANGE
+/// 3. Takes the number modulo listener_port_range
+/// 4. Adds listener_port_base as a base to get a port in the range listener_port_base + listener_port_range
 fn deterministic_port_from_archive_address(archive_address: ArchiveAddress) -> u16 {
     let hex_string = archive_address.to_hex();
 
@@ -90,8 +88,10 @@ fn deterministic_port_from_archive_address(archive_address: ArchiveAddress) -> u
     // Convert hex string to number
     let number = u64::from_str_radix(hex_suffix, 16).unwrap_or(0);
 
-    // Take modulo DETERMINISTIC_PORT_RANGE and add DETERMINISTIC_PORT_BASE
-    ((number % DETERMINISTIC_PORT_RANGE) + DETERMINISTIC_PORT_BASE) as u16
+    // Take modulo listener_port_range and add listener_port_base
+    let range = config().listener_port_range() as u64;
+    let base = config().listener_port_base() as u64;
+    ((number % range) + base).min(u16::MAX as u64) as u16
 }
 
 #[derive(Clone)]
@@ -141,6 +141,25 @@ impl DirectoryVersionWithPort {
             is_fixed_webname: false,
         }
     }
+
+    /// Describe the listener serving this directory version, for reporting
+    pub fn listener_info(&self) -> ListenerInfo {
+        ListenerInfo {
+            port: self.port,
+            history_address: self.history_address.map(|address| address.to_hex()),
+            version: self.version,
+            archive_address: self.archive_address.to_hex(),
+            idle_secs: None,
+        }
+    }
+}
+
+/// Forget the DirectoryVersionWithPort for archive_address, so that a new listener will be
+/// started the next time it is opened. Call this when its listener is stopped.
+pub fn forget_directory_version_with_port(
+    archive_address: ArchiveAddress,
+) -> Option<DirectoryVersionWithPort> {
+    DIRECTORY_VERSIONS_WITH_PORT.remove(&key_for_directory_versions_with_port(archive_address))
 }
 
 /// Look-up the DirectoryVersionWithPort for a given address/version combination in the cache
@@ -157,14 +176,12 @@ pub async fn lookup_or_create_directory_version_with_port(
 
     // If the address appears to be a name, try using that to get the history address
     if history_address.is_none() && archive_address.is_none() {
-        if let Ok(lock) = &mut HISTORY_NAMES.lock() {
-            let cached_address = lock.get(address_or_name);
-            if cached_address.is_none() {
+        match HISTORY_NAMES.get(address_or_name) {
+            Some(cached_address) => history_address = Some(cached_address),
+            None => {
                 return Err(eyre!(
                     "Unrecognised DWEB-NAME or invalid address: '{address_or_name}'"
                 ));
-            } else {
-                history_address = Some(*cached_address.unwrap());
             }
         };
     };
@@ -207,15 +224,28 @@ pub async fn lookup_or_create_directory_version_with_port(
         archive_address.unwrap()
     };
 
-    // Try the cache
+    // Try the cache, or else create and add to cache. Concurrent requests share one fetch
     let key = key_for_directory_versions_with_port(archive_address);
-    if let Ok(lock) = &mut DIRECTORY_VERSIONS_WITH_PORT.lock() {
-        if let Some(directory_version) = lock.get(&key) {
-            return Ok((directory_version.clone(), true));
-        };
-    };
+    let mut was_created = false;
+    let created_slot = &mut was_created;
+    let directory_version = DIRECTORY_VERSIONS_WITH_PORT
+        .get_or_fetch(&key, || async move {
+            *created_slot = true;
+            create_directory_version_with_port(client, history_address, version, archive_address)
+                .await
+        })
+        .await?;
+
+    Ok((directory_version, !was_created))
+}
 
-    // Not in the cache, so create and add to cache
+// Fetch the Tree and allocate a port for a new DirectoryVersionWithPort
+async fn create_directory_version_with_port(
+    client: &DwebClient,
+    history_address: Option<HistoryAddress>,
+    version: Option<u64>,
+    archive_address: ArchiveAddress,
+) -> Result<DirectoryVersionWithPort> {
     let directory_tree = match Tree::from_archive_address(client, archive_address).await {
         Ok(directory_tree) => directory_tree,
         Err(e) => {
@@ -249,24 +279,11 @@ pub async fn lookup_or_create_directory_version_with_port(
         }
     };
 
-    let directory_version = DirectoryVersionWithPort::new(
+    Ok(DirectoryVersionWithPort::new(
         port,
         history_address,
         version,
         archive_address,
         directory_tree,
-    );
-
-    // Add it to the cache
-    if let Ok(lock) = &mut DIRECTORY_VERSIONS_WITH_PORT.lock() {
-        let key = key_for_directory_versions_with_port(archive_address);
-        if lock.insert(key, directory_version.clone()) {
-            return Ok((directory_version, false));
-        } else {
-            let msg = format!("Failed to add new DirectoryVersionWithPort to the cache");
-            println!("DEBUG {msg}");
-            return Err(eyre!(msg));
-        }
-    }
-    Ok((directory_version, false))
+    ))
 }
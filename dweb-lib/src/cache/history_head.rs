@@ -0,0 +1,289 @@
+/*
+ Copyright (c) 2025 Mark Hughes
+
+ This program is free software: you can redistribute it and/or modify
+ it under the terms of the GNU Affero General Public License as published by
+ the Free Software Foundation, either version 3 of the License, or
+ (at your option) any later version.
+
+ This program is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY; without even the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU Affero General Public License for more details.
+
+ You should have received a copy of the GNU Affero General Public License
+ along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! A persisted record of the most recent entry seen for each History.
+//!
+//! Each GraphEntry holds the randomly chosen derivation of the next, so the head of a
+//! History can only be found by following the graph one network round trip at a time.
+//! When a Pointer is stale that walk starts a long way behind the head, which is why the
+//! server has preferred to trust the Pointer, and so could miss recent versions.
+//!
+//! Whenever the head of a History is found it is recorded here, and later lookups begin
+//! from whichever is further along, the Pointer or this hint. Because GraphEntries are
+//! immutable a hint can be behind the head but never wrong, so the walk is only as long
+//! as the number of versions published since the History was last seen.
+//!
+//! Hints are kept in memory and saved to HISTORY_HEADS_FILE in the dweb data directory
+//! so they survive restarts. Saving is done in the background shortly after a change so
+//! that async callers are not blocked on the file system, and several changes made
+//! together are saved at once. See set_history_heads_file_path() to save elsewhere or
+//! not at all (e.g. for tests using a MemoryBackend).
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use autonomi::GraphEntryAddress;
+
+use crate::helpers::file::get_dweb_data_dir_path;
+use crate::history::{HistoryAddress, HistoryDerivation};
+
+const HISTORY_HEADS_FILE: &str = "history-heads.json";
+
+// How long to wait after a change before saving, so that changes made together are saved once
+const SAVE_DELAY: Duration = Duration::from_millis(500);
+
+/// The most recent entry known for a History
+#[derive(Clone, Copy, Serialize, Deserialize)]
+pub struct HistoryHeadHint {
+    /// Index of the entry in the graph (0 is the root entry)
+    pub entry_index: u64,
+    pub entry_address: GraphEntryAddress,
+}
+
+/// HISTORY_HEADS holds the most recent entry seen for each History.
+///
+/// Key:     see history_head_key()
+///
+/// Entry:   HistoryHeadHint
+///
+/// Populated from the file at HISTORY_HEADS_FILE_PATH on first use.
+pub static HISTORY_HEADS: LazyLock<Mutex<HashMap<String, HistoryHeadHint>>> = LazyLock::new(|| {
+    Mutex::new(load_history_heads(&history_heads_file_path()).unwrap_or_default())
+});
+
+/// Where the hints are saved, or None to keep them in memory only
+static HISTORY_HEADS_FILE_PATH: LazyLock<Mutex<Option<PathBuf>>> =
+    LazyLock::new(|| Mutex::new(default_history_heads_file_path()));
+
+// Set while a save is waiting to be made
+static SAVE_PENDING: AtomicBool = AtomicBool::new(false);
+
+/// Save hints to path instead of HISTORY_HEADS_FILE in the dweb data directory, or if
+/// None keep them in memory only. Hints are loaded on first use, so to load from path
+/// this must be called before any History is read.
+pub fn set_history_heads_file_path(path: Option<PathBuf>) {
+    match HISTORY_HEADS_FILE_PATH.lock() {
+        Ok(mut file_path) => *file_path = path,
+        Err(e) => println!("DEBUG set_history_heads_file_path() failed to lock - {e}"),
+    }
+}
+
+/// A Register is read using the same address as a History owned by the same key, so the
+/// derivation is part of the key
+fn history_head_key(history_address: &HistoryAddress, derivation: HistoryDerivation) -> String {
+    format!("{derivation:?}:{}", history_address.to_hex())
+}
+
+/// Get the most recent entry seen for a History, if any
+pub fn get_history_head_hint(
+    history_address: &HistoryAddress,
+    derivation: HistoryDerivation,
+) -> Option<HistoryHeadHint> {
+    match HISTORY_HEADS.lock() {
+        Ok(heads) => heads
+            .get(&history_head_key(history_address, derivation))
+            .copied(),
+        Err(e) => {
+            println!("DEBUG get_history_head_hint() failed to lock HISTORY_HEADS - {e}");
+            None
+        }
+    }
+}
+
+/// Record an entry seen for a History if it is more recent than the current hint
+///
+/// The hints are saved in the background when changed. Failure to save is reported but not an error.
+pub fn update_history_head_hint(
+    history_address: &HistoryAddress,
+    derivation: HistoryDerivation,
+    entry_index: u64,
+    entry_address: GraphEntryAddress,
+) {
+    {
+        let mut heads = match HISTORY_HEADS.lock() {
+            Ok(heads) => heads,
+            Err(e) => {
+                println!("DEBUG update_history_head_hint() failed to lock HISTORY_HEADS - {e}");
+                return;
+            }
+        };
+        if !update_heads(
+            &mut heads,
+            history_head_key(history_address, derivation),
+            entry_index,
+            entry_address,
+        ) {
+            return;
+        }
+    }
+
+    println!(
+        "DEBUG update_history_head_hint() {} entry_index: {entry_index}",
+        history_address.to_hex()
+    );
+    schedule_save();
+}
+
+// Insert the hint unless there is one at least as recent. Returns true if inserted
+fn update_heads(
+    heads: &mut HashMap<String, HistoryHeadHint>,
+    key: String,
+    entry_index: u64,
+    entry_address: GraphEntryAddress,
+) -> bool {
+    if let Some(hint) = heads.get(&key) {
+        if hint.entry_index >= entry_index {
+            return false;
+        }
+    }
+    heads.insert(
+        key,
+        HistoryHeadHint {
+            entry_index,
+            entry_address,
+        },
+    );
+    true
+}
+
+// Save after SAVE_DELAY on a blocking thread, unless a save is already waiting
+fn schedule_save() {
+    if SAVE_PENDING.swap(true, Ordering::AcqRel) {
+        return;
+    }
+    match tokio::runtime::Handle::try_current() {
+        Ok(handle) => {
+            handle.spawn_blocking(|| {
+                std::thread::sleep(SAVE_DELAY);
+                save_now();
+            });
+        }
+        Err(_) => save_now(),
+    }
+}
+
+fn save_now() {
+    // Cleared first so that a change made while saving schedules another save
+    SAVE_PENDING.store(false, Ordering::Release);
+    let path = match history_heads_file_path() {
+        Some(path) => path,
+        None => return,
+    };
+    let heads = match HISTORY_HEADS.lock() {
+        Ok(heads) => heads.clone(),
+        Err(e) => {
+            println!("DEBUG save_now() failed to lock HISTORY_HEADS - {e}");
+            return;
+        }
+    };
+    if let Err(e) = save_history_heads(&path, &heads) {
+        println!("DEBUG failed to save {} - {e}", path.to_string_lossy());
+    }
+}
+
+fn default_history_heads_file_path() -> Option<PathBuf> {
+    if cfg!(test) {
+        return None;
+    }
+    match get_dweb_data_dir_path() {
+        Ok(dir) => Some(dir.join(HISTORY_HEADS_FILE)),
+        Err(e) => {
+            println!("DEBUG unable to save {HISTORY_HEADS_FILE} - {e}");
+            None
+        }
+    }
+}
+
+fn history_heads_file_path() -> Option<PathBuf> {
+    match HISTORY_HEADS_FILE_PATH.lock() {
+        Ok(path) => path.clone(),
+        Err(_) => None,
+    }
+}
+
+fn load_history_heads(path: &Option<PathBuf>) -> Result<HashMap<String, HistoryHeadHint>> {
+    let path = match path {
+        Some(path) if path.exists() => path,
+        _ => return Ok(HashMap::new()),
+    };
+    let reader = BufReader::new(File::open(path)?);
+    Ok(serde_json::from_reader(reader)?)
+}
+
+fn save_history_heads(path: &PathBuf, heads: &HashMap<String, HistoryHeadHint>) -> Result<()> {
+    let writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer(writer, heads)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use blsttc::SecretKey;
+
+    fn entry_address() -> GraphEntryAddress {
+        GraphEntryAddress::new(SecretKey::random().public_key())
+    }
+
+    #[test]
+    fn hints_only_move_forward() {
+        let mut heads = HashMap::new();
+        let key = "Trove:history".to_string();
+        assert!(update_heads(&mut heads, key.clone(), 3, entry_address()));
+        assert!(!update_heads(&mut heads, key.clone(), 3, entry_address()));
+        assert!(!update_heads(&mut heads, key.clone(), 2, entry_address()));
+        assert!(update_heads(&mut heads, key.clone(), 4, entry_address()));
+        assert_eq!(heads.get(&key).unwrap().entry_index, 4);
+    }
+
+    #[test]
+    fn histories_and_registers_have_separate_hints() {
+        let address = HistoryAddress::new(SecretKey::random().public_key());
+        update_history_head_hint(&address, HistoryDerivation::Trove, 5, entry_address());
+        update_history_head_hint(&address, HistoryDerivation::Register, 2, entry_address());
+
+        let trove = get_history_head_hint(&address, HistoryDerivation::Trove).unwrap();
+        let register = get_history_head_hint(&address, HistoryDerivation::Register).unwrap();
+        assert_eq!(trove.entry_index, 5);
+        assert_eq!(register.entry_index, 2);
+    }
+
+    #[test]
+    fn hints_are_saved_and_loaded() {
+        let path =
+            std::env::temp_dir().join(format!("dweb-history-heads-{}.json", rand::random::<u64>()));
+        let mut heads = HashMap::new();
+        let entry = entry_address();
+        update_heads(&mut heads, "Trove:history".to_string(), 7, entry);
+        save_history_heads(&path, &heads).unwrap();
+
+        let loaded = load_history_heads(&Some(path.clone())).unwrap();
+        let hint = loaded.get("Trove:history").unwrap();
+        assert_eq!(hint.entry_index, 7);
+        assert_eq!(hint.entry_address, entry);
+        assert!(load_history_heads(&None).unwrap().is_empty());
+        let _ = std::fs::remove_file(path);
+    }
+}
@@ -30,3 +30,18 @@ pub mod directory;
 pub mod directory_with_name;
 pub mod directory_with_port;
 pub mod file;
+pub mod history_head;
+pub mod owned_histories;
+pub mod publish_journal;
+pub mod single_flight;
+pub mod sync_state;
+pub mod version_tags;
+
+/// Counters for the in-memory caches, for reporting
+pub fn cache_stats() -> Vec<single_flight::CacheStats> {
+    vec![
+        directory_with_name::DIRECTORY_VERSIONS_WITH_NAME.stats(),
+        directory_with_name::HISTORY_NAMES.stats(),
+        directory_with_port::DIRECTORY_VERSIONS_WITH_PORT.stats(),
+    ]
+}
@@ -0,0 +1,99 @@
+/*
+ Copyright (c) 2025 Mark Hughes
+
+ This program is free software: you can redistribute it and/or modify
+ it under the terms of the GNU Affero General Public License as published by
+ the Free Software Foundation, either version 3 of the License, or
+ (at your option) any later version.
+
+ This program is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY; without even the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU Affero General Public License for more details.
+
+ You should have received a copy of the GNU Affero General Public License
+ along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! A persisted list of the names of Histories published from this device.
+//!
+//! The secret key of a History is derived from the owner secret key and the NAME
+//! used when it was first published, so the NAME is needed to update it. Nothing
+//! on the network lists the Histories belonging to an owner, so the names are
+//! recorded here when publishing. This allows a dweb server to find the Histories
+//! it can maintain, for example to heal their pointers in the background.
+
+use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+use std::sync::{LazyLock, Mutex};
+
+use color_eyre::eyre::Result;
+
+use crate::helpers::file::get_dweb_data_dir_path;
+
+const OWNED_HISTORIES_FILE: &str = "owned-histories.json";
+
+/// OWNED_HISTORIES holds the NAME of each History published from this device.
+///
+/// Populated from OWNED_HISTORIES_FILE on first use.
+pub static OWNED_HISTORIES: LazyLock<Mutex<BTreeSet<String>>> =
+    LazyLock::new(|| Mutex::new(load_owned_histories().unwrap_or_default()));
+
+/// Record the NAME of a History published from this device
+///
+/// The list is saved to disk when changed. Failure to save is reported but not an error.
+pub fn record_owned_history_name(name: &str) {
+    let mut names = match OWNED_HISTORIES.lock() {
+        Ok(names) => names,
+        Err(e) => {
+            println!("DEBUG record_owned_history_name() failed to lock OWNED_HISTORIES - {e}");
+            return;
+        }
+    };
+
+    if names.insert(name.to_string()) {
+        if let Err(e) = save_owned_histories(&names) {
+            println!("DEBUG failed to save {OWNED_HISTORIES_FILE} - {e}");
+        }
+    }
+}
+
+/// Get the NAME of each History published from this device
+///
+/// Re-reads the list from disk so that names recorded by other processes
+/// (such as the dweb CLI) are included.
+pub fn owned_history_names() -> Vec<String> {
+    let mut names = match OWNED_HISTORIES.lock() {
+        Ok(names) => names,
+        Err(e) => {
+            println!("DEBUG owned_history_names() failed to lock OWNED_HISTORIES - {e}");
+            return Vec::new();
+        }
+    };
+
+    if let Ok(saved_names) = load_owned_histories() {
+        names.extend(saved_names);
+    }
+    names.iter().cloned().collect()
+}
+
+fn owned_histories_file_path() -> Result<PathBuf> {
+    Ok(get_dweb_data_dir_path()?.join(OWNED_HISTORIES_FILE))
+}
+
+fn load_owned_histories() -> Result<BTreeSet<String>> {
+    let path = owned_histories_file_path()?;
+    if !path.exists() {
+        return Ok(BTreeSet::new());
+    }
+    let reader = BufReader::new(File::open(path)?);
+    Ok(serde_json::from_reader(reader)?)
+}
+
+fn save_owned_histories(names: &BTreeSet<String>) -> Result<()> {
+    let writer = BufWriter::new(File::create(owned_histories_file_path()?)?);
+    serde_json::to_writer(writer, names)?;
+    Ok(())
+}
@@ -0,0 +1,368 @@
+/*
+ Copyright (c) 2025 Mark Hughes
+
+ This program is free software: you can redistribute it and/or modify
+ it under the terms of the GNU Affero General Public License as published by
+ the Free Software Foundation, either version 3 of the License, or
+ (at your option) any later version.
+
+ This program is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY; without even the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU Affero General Public License for more details.
+
+ You should have received a copy of the GNU Affero General Public License
+ along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! An on-disk journal of a publish in progress, so that an interrupted publish can resume.
+//!
+//! Every publish or upload of a directory opens the journal for its FILES-ROOT and passes it
+//! to the upload (see storage::directory_upload_private()). When uploading file by file (the
+//! default) the datamap or address of each file is recorded here as soon as it is stored,
+//! along with any payment receipt for an upload which failed after paying. Publishing the
+//! same FILES-ROOT again skips files already stored and re-uses receipts, so only what is
+//! left is paid for. A file which has changed since it was recorded (by size or modification
+//! time) is uploaded again. When uploading in a single batch, a batch which fails after
+//! paying is recorded so that its cached payment is re-used, but every file is uploaded again.
+//!
+//! There is one journal for each FILES-ROOT, saved in the publish-journals directory of the
+//! Autonomi client data directory. It is removed when the publish completes, or by
+//! 'dweb publish-abort'.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use autonomi::AttoTokens;
+use autonomi::client::payment::Receipt;
+use color_eyre::eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::autonomi::access::data_dir::get_client_data_dir_path;
+
+const PUBLISH_JOURNALS_DIRECTORY: &str = "publish-journals";
+
+/// Where journals are saved when not in the client data directory
+static JOURNALS_DIRECTORY_PATH: LazyLock<Mutex<Option<PathBuf>>> =
+    LazyLock::new(|| Mutex::new(None));
+
+/// Save journals in directory instead of the client data directory
+pub fn set_journals_directory(directory: PathBuf) {
+    match JOURNALS_DIRECTORY_PATH.lock() {
+        Ok(mut path) => *path = Some(directory),
+        Err(e) => println!("DEBUG set_journals_directory() failed to lock - {e}"),
+    }
+}
+
+/// The state of an interrupted or in progress publish of a FILES-ROOT
+#[derive(Clone, Serialize, Deserialize)]
+pub struct PublishJournal {
+    pub files_root: PathBuf,
+    /// True if files are being uploaded as public data
+    pub is_public: bool,
+    /// The NAME of a History created for this publish, so a repeated 'publish-new' can use it
+    pub history_name: Option<String>,
+    /// Time the journal was created (seconds since UNIX epoch)
+    pub started: u64,
+    /// Time the journal was last updated (seconds since UNIX epoch)
+    pub updated: u64,
+    /// Keyed by the path of each file recorded
+    pub files: BTreeMap<PathBuf, JournalFile>,
+    /// True if a batched upload failed after paying, so its cached payment will be re-used
+    #[serde(default)]
+    pub batch_paid: bool,
+}
+
+/// The journal record for one file
+#[derive(Clone, Serialize, Deserialize)]
+pub struct JournalFile {
+    /// Size of the file when recorded
+    pub size: u64,
+    /// Modification time of the file when recorded (seconds since UNIX epoch)
+    pub modified: u64,
+    /// Once stored, the datamap chunk (private) or data address (public) as hex
+    pub address: Option<String>,
+    /// Amount paid to store the file
+    pub cost_in_attos: String,
+    /// A payment made for an upload which did not complete, to be re-used
+    pub receipt: Option<Receipt>,
+}
+
+impl PublishJournal {
+    /// Load the journal for files_root, or start a new one
+    ///
+    /// An existing journal for a different type of upload (public or private) is discarded.
+    pub fn open(files_root: &Path, is_public: bool) -> PublishJournal {
+        match PublishJournal::load(files_root) {
+            Ok(Some(journal)) if journal.is_public == is_public => journal,
+            _ => {
+                let now = now();
+                PublishJournal {
+                    files_root: files_root.to_path_buf(),
+                    is_public,
+                    history_name: None,
+                    started: now,
+                    updated: now,
+                    files: BTreeMap::new(),
+                    batch_paid: false,
+                }
+            }
+        }
+    }
+
+    /// Load the journal for files_root if there is one
+    pub fn load(files_root: &Path) -> Result<Option<PublishJournal>> {
+        let path = journal_file_path(files_root)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        let reader = BufReader::new(File::open(&path)?);
+        let journal = serde_json::from_reader(reader)
+            .wrap_err(format!("Failed to read publish journal {path:?}"))?;
+        Ok(Some(journal))
+    }
+
+    /// Load every saved journal
+    pub fn load_all() -> Result<Vec<PublishJournal>> {
+        let mut journals = Vec::new();
+        for entry in std::fs::read_dir(journals_directory()?)?.flatten() {
+            let reader = match File::open(entry.path()) {
+                Ok(file) => BufReader::new(file),
+                Err(_) => continue,
+            };
+            match serde_json::from_reader(reader) {
+                Ok(journal) => journals.push(journal),
+                Err(e) => println!("DEBUG ignoring unreadable journal {:?} - {e}", entry.path()),
+            }
+        }
+        Ok(journals)
+    }
+
+    /// Delete the journal for files_root. Returns true if there was one
+    pub fn remove(files_root: &Path) -> Result<bool> {
+        let path = journal_file_path(files_root)?;
+        if !path.exists() {
+            return Ok(false);
+        }
+        std::fs::remove_file(&path)?;
+        Ok(true)
+    }
+
+    pub fn save(&mut self) -> Result<()> {
+        self.updated = now();
+        let writer = BufWriter::new(File::create(journal_file_path(&self.files_root)?)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    /// The stored address of a file, provided it has not changed since it was recorded
+    pub fn completed_address(&self, path: &Path) -> Option<String> {
+        self.unchanged_file(path)?.address.clone()
+    }
+
+    /// A receipt for an earlier payment to store this file, provided it has not changed
+    pub fn receipt_for(&self, path: &Path) -> Option<Receipt> {
+        self.unchanged_file(path)?.receipt.clone()
+    }
+
+    /// Record that a file has been stored and save the journal
+    pub fn record_completed(&mut self, path: &Path, address: String, cost: AttoTokens) {
+        let (size, modified) = file_fingerprint(path);
+        self.files.insert(
+            path.to_path_buf(),
+            JournalFile {
+                size,
+                modified,
+                address: Some(address),
+                cost_in_attos: format!("{}", cost.as_atto()),
+                receipt: None,
+            },
+        );
+        self.save_or_report();
+    }
+
+    /// Record a payment for a file which has not been stored and save the journal
+    pub fn record_receipt(&mut self, path: &Path, receipt: Receipt) {
+        let (size, modified) = file_fingerprint(path);
+        self.files.insert(
+            path.to_path_buf(),
+            JournalFile {
+                size,
+                modified,
+                address: None,
+                cost_in_attos: "0".to_string(),
+                receipt: Some(receipt),
+            },
+        );
+        self.save_or_report();
+    }
+
+    /// Save the journal so the publish is shown by 'dweb publish-status' even if nothing
+    /// is recorded before it is interrupted
+    pub fn record_started(&mut self) {
+        self.save_or_report();
+    }
+
+    /// Record that a batched upload failed after paying and save the journal
+    pub fn record_batch_paid(&mut self) {
+        self.batch_paid = true;
+        self.save_or_report();
+    }
+
+    /// Record that a History was created for this publish and save the journal
+    pub fn record_history_created(&mut self, name: &str) {
+        self.history_name = Some(name.to_string());
+        self.save_or_report();
+    }
+
+    pub fn files_completed(&self) -> usize {
+        self.files
+            .values()
+            .filter(|file| file.address.is_some())
+            .count()
+    }
+
+    pub fn files_with_receipts(&self) -> usize {
+        self.files
+            .values()
+            .filter(|file| file.receipt.is_some())
+            .count()
+    }
+
+    /// Total paid for the files stored so far
+    pub fn cost_in_attos(&self) -> u128 {
+        self.files
+            .values()
+            .filter_map(|file| file.cost_in_attos.parse::<u128>().ok())
+            .sum()
+    }
+
+    fn unchanged_file(&self, path: &Path) -> Option<&JournalFile> {
+        let file = self.files.get(path)?;
+        if (file.size, file.modified) == file_fingerprint(path) {
+            Some(file)
+        } else {
+            None
+        }
+    }
+
+    // A journal which can't be saved only loses the ability to resume, so is not an error
+    fn save_or_report(&mut self) {
+        if let Err(e) = self.save() {
+            println!("DEBUG failed to save publish journal - {e}");
+        }
+    }
+}
+
+fn journals_directory() -> Result<PathBuf> {
+    let directory = match JOURNALS_DIRECTORY_PATH
+        .lock()
+        .ok()
+        .and_then(|path| path.clone())
+    {
+        Some(directory) => directory,
+        None => get_client_data_dir_path()?.join(PUBLISH_JOURNALS_DIRECTORY),
+    };
+    std::fs::create_dir_all(&directory).wrap_err("Could not create publish journals directory")?;
+    Ok(directory)
+}
+
+fn journal_file_path(files_root: &Path) -> Result<PathBuf> {
+    let files_root = files_root
+        .canonicalize()
+        .unwrap_or(files_root.to_path_buf());
+    let file_name = sha256::digest(files_root.to_string_lossy().as_bytes()) + ".json";
+    Ok(journals_directory()?.join(file_name))
+}
+
+fn file_fingerprint(path: &Path) -> (u64, u64) {
+    match std::fs::metadata(path) {
+        Ok(metadata) => {
+            let modified = metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|duration| duration.as_secs())
+                .unwrap_or(0);
+            (metadata.len(), modified)
+        }
+        Err(_) => (0, 0),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Journals are keyed by FILES-ROOT so each test uses its own
+    fn test_files_root(name: &str) -> PathBuf {
+        set_journals_directory(std::env::temp_dir().join("dweb-test-publish-journals"));
+        let files_root = std::env::temp_dir()
+            .join(format!("dweb-test-journal-{}", std::process::id()))
+            .join(name);
+        std::fs::create_dir_all(&files_root).unwrap();
+        let _ = PublishJournal::remove(&files_root);
+        files_root
+    }
+
+    #[test]
+    fn journal_records_files_and_survives_reload() {
+        let files_root = test_files_root("records");
+        let file = files_root.join("index.html");
+        std::fs::write(&file, "<html></html>").unwrap();
+        assert!(PublishJournal::load(&files_root).unwrap().is_none());
+
+        let mut journal = PublishJournal::open(&files_root, true);
+        journal.record_started();
+        journal.record_history_created("site");
+        journal.record_completed(&file, "abcd".to_string(), AttoTokens::from_u64(5));
+
+        let journal = PublishJournal::load(&files_root).unwrap().unwrap();
+        assert_eq!(journal.history_name, Some("site".to_string()));
+        assert_eq!(journal.files_completed(), 1);
+        assert_eq!(journal.cost_in_attos(), 5);
+        assert_eq!(journal.completed_address(&file), Some("abcd".to_string()));
+
+        // A changed file must be uploaded again
+        std::fs::write(&file, "<html>changed</html>").unwrap();
+        assert_eq!(journal.completed_address(&file), None);
+    }
+
+    #[test]
+    fn journal_of_other_upload_type_is_discarded() {
+        let files_root = test_files_root("type");
+        let mut journal = PublishJournal::open(&files_root, false);
+        journal.record_batch_paid();
+
+        assert!(PublishJournal::open(&files_root, false).batch_paid);
+        assert!(!PublishJournal::open(&files_root, true).batch_paid);
+    }
+
+    #[test]
+    fn started_journal_is_listed_until_removed() {
+        let files_root = test_files_root("abort");
+        PublishJournal::open(&files_root, true).record_started();
+
+        let listed = |files_root: &Path| {
+            PublishJournal::load_all()
+                .unwrap()
+                .iter()
+                .any(|journal| journal.files_root == files_root)
+        };
+        assert!(listed(&files_root));
+        assert!(PublishJournal::remove(&files_root).unwrap());
+        assert!(!listed(&files_root));
+        assert!(!PublishJournal::remove(&files_root).unwrap());
+    }
+}
@@ -0,0 +1,236 @@
+/*
+ Copyright (c) 2025 Mark Hughes
+
+ This program is free software: you can redistribute it and/or modify
+ it under the terms of the GNU Affero General Public License as published by
+ the Free Software Foundation, either version 3 of the License, or
+ (at your option) any later version.
+
+ This program is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY; without even the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU Affero General Public License for more details.
+
+ You should have received a copy of the GNU Affero General Public License
+ along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! An LRU cache which coalesces concurrent fetches of the same key.
+//!
+//! When a browser loads a page it requests many assets at once, and for a site
+//! not yet cached each of those requests needs the same History and archive.
+//! SingleFlightCache::get_or_fetch() ensures that only the first request for a key
+//! accesses the network while the others wait for and share its result.
+//!
+//! The cache lock is only held while reading or updating the map and never across
+//! an .await, and hits, misses and coalesced requests are counted for reporting.
+
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use color_eyre::eyre::{Result, eyre};
+use schnellru::{ByLength, LruMap};
+use serde::{Deserialize, Serialize};
+use tokio::sync::OnceCell;
+
+type InFlight<V> = Arc<OnceCell<std::result::Result<V, String>>>;
+
+pub struct SingleFlightCache<V: Clone> {
+    name: &'static str,
+    entries: Mutex<LruMap<String, V>>,
+    in_flight: Mutex<HashMap<String, InFlight<V>>>,
+
+    hits: AtomicU64,
+    misses: AtomicU64,
+    coalesced: AtomicU64,
+}
+
+/// Counters for a SingleFlightCache
+#[derive(Clone, Serialize, Deserialize, utoipa::ToSchema)]
+pub struct CacheStats {
+    pub name: String,
+    pub entries: usize,
+    /// Lookups satisfied from the cache
+    pub hits: u64,
+    /// Lookups not in the cache (for get_or_fetch() each is one fetch)
+    pub misses: u64,
+    /// Lookups which waited for a fetch already in progress instead of starting another
+    pub coalesced: u64,
+}
+
+impl<V: Clone> SingleFlightCache<V> {
+    pub fn new(name: &'static str, capacity: u32) -> Self {
+        SingleFlightCache {
+            name,
+            entries: Mutex::new(LruMap::new(ByLength::new(capacity))),
+            in_flight: Mutex::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+            coalesced: AtomicU64::new(0),
+        }
+    }
+
+    /// Get a value from the cache, counting the hit or miss
+    pub fn get(&self, key: &str) -> Option<V> {
+        let value = self.peek(key);
+        if value.is_some() {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+        }
+        value
+    }
+
+    /// Get a value from the cache without affecting the counters
+    pub fn peek(&self, key: &str) -> Option<V> {
+        match self.entries.lock() {
+            Ok(mut entries) => entries.get(key).cloned(),
+            Err(e) => {
+                println!("DEBUG {} cache lock failed - {e}", self.name);
+                None
+            }
+        }
+    }
+
+    /// Add or replace a value. Returns false if it could not be added
+    pub fn insert(&self, key: String, value: V) -> bool {
+        match self.entries.lock() {
+            Ok(mut entries) => entries.insert(key, value),
+            Err(e) => {
+                println!("DEBUG {} cache lock failed - {e}", self.name);
+                false
+            }
+        }
+    }
+
+    /// Remove and return the value for key
+    pub fn remove(&self, key: &str) -> Option<V> {
+        match self.entries.lock() {
+            Ok(mut entries) => entries.remove(key),
+            Err(e) => {
+                println!("DEBUG {} cache lock failed - {e}", self.name);
+                None
+            }
+        }
+    }
+
+    /// Add a value unless key is already present. Returns the value cached for key
+    pub fn get_or_insert(&self, key: String, value: V) -> Result<V> {
+        match self.entries.lock() {
+            Ok(mut entries) => match entries.get(&key) {
+                Some(cached) => Ok(cached.clone()),
+                None => {
+                    entries.insert(key, value.clone());
+                    Ok(value)
+                }
+            },
+            Err(e) => Err(eyre!("{} cache lock failed - {e}", self.name)),
+        }
+    }
+
+    /// A copy of every entry, most recently used first
+    pub fn entries(&self) -> Vec<(String, V)> {
+        match self.entries.lock() {
+            Ok(entries) => entries
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Return the cached value for key, or else fetch and cache it
+    ///
+    /// If a fetch for the same key is already in progress this waits for and
+    /// returns its result rather than starting another. A failed fetch is not
+    /// cached, so a later call will try again.
+    pub async fn get_or_fetch<F, Fut>(&self, key: &str, fetch: F) -> Result<V>
+    where
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<V>>,
+    {
+        if let Some(value) = self.peek(key) {
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Ok(value);
+        }
+
+        let cell = self.in_flight_for(key);
+        let mut is_fetcher = false;
+        let result = cell
+            .get_or_init(|| {
+                is_fetcher = true;
+                async move { fetch().await.map_err(|e| e.to_string()) }
+            })
+            .await
+            .clone();
+
+        if is_fetcher {
+            self.misses.fetch_add(1, Ordering::Relaxed);
+            if let Ok(value) = &result {
+                self.insert(key.to_string(), value.clone());
+            }
+            if let Ok(mut in_flight) = self.in_flight.lock() {
+                in_flight.remove(key);
+            }
+        } else {
+            self.coalesced.fetch_add(1, Ordering::Relaxed);
+        }
+
+        result.map_err(|e| eyre!(e))
+    }
+
+    // Get or create the shared fetch for key
+    fn in_flight_for(&self, key: &str) -> InFlight<V> {
+        match self.in_flight.lock() {
+            Ok(mut in_flight) => in_flight
+                .entry(key.to_string())
+                .or_insert_with(|| Arc::new(OnceCell::new()))
+                .clone(),
+            // Without the map each caller fetches independently, which is still correct
+            Err(_) => Arc::new(OnceCell::new()),
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        CacheStats {
+            name: self.name.to_string(),
+            entries: match self.entries.lock() {
+                Ok(entries) => entries.len(),
+                Err(_) => 0,
+            },
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            coalesced: self.coalesced.load(Ordering::Relaxed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[actix_web::test]
+    async fn concurrent_fetches_of_one_key_are_coalesced() {
+        let cache = SingleFlightCache::<u64>::new("test", 10);
+        let fetches = AtomicU64::new(0);
+        let fetch = || async {
+            fetches.fetch_add(1, Ordering::Relaxed);
+            actix_web::rt::time::sleep(std::time::Duration::from_millis(20)).await;
+            Ok(42)
+        };
+
+        let (a, b, c) = tokio::join!(
+            cache.get_or_fetch("key", fetch),
+            cache.get_or_fetch("key", fetch),
+            cache.get_or_fetch("key", fetch),
+        );
+        assert_eq!((a.unwrap(), b.unwrap(), c.unwrap()), (42, 42, 42));
+        assert_eq!(fetches.load(Ordering::Relaxed), 1);
+
+        assert_eq!(cache.get_or_fetch("key", fetch).await.unwrap(), 42);
+        let stats = cache.stats();
+        assert_eq!((stats.misses, stats.coalesced, stats.hits), (1, 2, 1));
+    }
+}
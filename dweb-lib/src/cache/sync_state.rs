@@ -0,0 +1,110 @@
+/*
+ Copyright (c) 2025 Mark Hughes
+
+ This program is free software: you can redistribute it and/or modify
+ it under the terms of the GNU Affero General Public License as published by
+ the Free Software Foundation, either version 3 of the License, or
+ (at your option) any later version.
+
+ This program is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY; without even the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU Affero General Public License for more details.
+
+ You should have received a copy of the GNU Affero General Public License
+ along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! The state of a local directory when it was last synced with a private History.
+//!
+//! 'dweb sync' compares the local directory and the most recent version of the History
+//! with this record to tell which side has changed each file since the last sync, so
+//! that changes can be copied in the right direction and conflicts detected.
+//!
+//! There is one record for each FILES-ROOT, saved in the sync-states directory of the
+//! Autonomi client data directory, in the same way as a PublishJournal.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
+
+use color_eyre::eyre::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::autonomi::access::data_dir::get_client_data_dir_path;
+
+const SYNC_STATES_DIRECTORY: &str = "sync-states";
+
+/// The files of a FILES-ROOT and History version as they were after a sync
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SyncState {
+    pub files_root: PathBuf,
+    /// The History synced with, as hex
+    pub history_address: String,
+    /// The version of the History after the sync
+    pub version: u64,
+    /// Keyed by web path (from the root of FILES-ROOT, beginning with '/')
+    pub files: BTreeMap<String, SyncedFile>,
+}
+
+/// A file present both locally and in the History after a sync
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SyncedFile {
+    /// Size of the local file
+    pub size: u64,
+    /// Modification time of the local file (seconds since UNIX epoch)
+    pub modified: u64,
+    /// The datamap chunk of the file in the History, as hex
+    pub datamap: String,
+}
+
+impl SyncState {
+    pub fn new(files_root: &Path, history_address: String) -> SyncState {
+        SyncState {
+            files_root: files_root.to_path_buf(),
+            history_address,
+            version: 0,
+            files: BTreeMap::new(),
+        }
+    }
+
+    /// Load the state for files_root if it was last synced with history_address
+    pub fn load(files_root: &Path, history_address: &str) -> Result<Option<SyncState>> {
+        let path = state_file_path(files_root)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        let reader = BufReader::new(File::open(&path)?);
+        let state: SyncState = serde_json::from_reader(reader)
+            .wrap_err(format!("Failed to read sync state {path:?}"))?;
+        if state.history_address != history_address {
+            println!(
+                "DEBUG ignoring sync state of {files_root:?} for a different History {}",
+                state.history_address
+            );
+            return Ok(None);
+        }
+        Ok(Some(state))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let writer = BufWriter::new(File::create(state_file_path(&self.files_root)?)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+}
+
+fn states_directory() -> Result<PathBuf> {
+    let directory = get_client_data_dir_path()?.join(SYNC_STATES_DIRECTORY);
+    std::fs::create_dir_all(&directory).wrap_err("Could not create sync states directory")?;
+    Ok(directory)
+}
+
+fn state_file_path(files_root: &Path) -> Result<PathBuf> {
+    let files_root = files_root
+        .canonicalize()
+        .unwrap_or(files_root.to_path_buf());
+    let file_name = sha256::digest(files_root.to_string_lossy().as_bytes()) + ".json";
+    Ok(states_directory()?.join(file_name))
+}
@@ -0,0 +1,95 @@
+/*
+ Copyright (c) 2025 Mark Hughes
+
+ This program is free software: you can redistribute it and/or modify
+ it under the terms of the GNU Affero General Public License as published by
+ the Free Software Foundation, either version 3 of the License, or
+ (at your option) any later version.
+
+ This program is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY; without even the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU Affero General Public License for more details.
+
+ You should have received a copy of the GNU Affero General Public License
+ along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! An index of the tags of each version of a History<Tree>.
+//!
+//! Tags are held in the VersionManifest of each version's archive, so finding the version
+//! for a tag means fetching archives one version at a time. A published version never
+//! changes, so once its tags are known they are kept here and the archive is not fetched
+//! again (see Tree::history_version_for_tag()).
+
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+
+use crate::files::directory::Tree;
+use crate::history::History;
+
+/// VERSION_TAGS holds the tags of each version which has been searched
+///
+/// Key:     see version_tags_key()
+///
+/// Entry:   map of VERSION to the tags of that version (empty if it has none)
+pub static VERSION_TAGS: LazyLock<Mutex<HashMap<String, HashMap<u64, Vec<String>>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// The key used to index the versions of a History
+///
+/// A History read by following a hand-over numbers its versions after those of its
+/// predecessors (see History::from_history_address()) so the offset is part of the key.
+pub fn version_tags_key(history: &History<Tree>) -> String {
+    format!(
+        "{}:{}",
+        history.history_address().to_hex(),
+        history.version_offset()
+    )
+}
+
+/// Get the tags of a version if they are known
+pub fn get_version_tags(key: &str, version: u64) -> Option<Vec<String>> {
+    match VERSION_TAGS.lock() {
+        Ok(index) => index
+            .get(key)
+            .and_then(|versions| versions.get(&version))
+            .cloned(),
+        Err(e) => {
+            println!("DEBUG get_version_tags() failed to lock VERSION_TAGS - {e}");
+            None
+        }
+    }
+}
+
+/// Record the tags of a version
+pub fn update_version_tags(key: &str, version: u64, tags: Vec<String>) {
+    match VERSION_TAGS.lock() {
+        Ok(mut index) => {
+            index
+                .entry(key.to_string())
+                .or_default()
+                .insert(version, tags);
+        }
+        Err(e) => println!("DEBUG update_version_tags() failed to lock VERSION_TAGS - {e}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_are_kept_for_each_history() {
+        update_version_tags("history-a:0", 1, vec!["release".to_string()]);
+        update_version_tags("history-a:0", 2, Vec::new());
+
+        assert_eq!(
+            get_version_tags("history-a:0", 1),
+            Some(vec!["release".to_string()])
+        );
+        assert_eq!(get_version_tags("history-a:0", 2), Some(Vec::new()));
+        assert_eq!(get_version_tags("history-a:0", 3), None);
+        assert_eq!(get_version_tags("history-a:5", 1), None);
+    }
+}
@@ -21,8 +21,13 @@ along with this program. If not, see <https://www.gnu.org/licenses/>.
 //! for interaction with the Autonomi peer-to-peer storage
 //! network.
 //!
-use color_eyre::Result;
+use std::sync::Arc;
 
+use color_eyre::{Result, eyre::eyre};
+
+use crate::backend::DwebBackend;
+use crate::helpers::retry::RetryPolicy;
+use crate::helpers::scratchpad::ForkResolution;
 use crate::token::{Rate, ShowCost};
 use crate::web::{LOCALHOST_STR, SERVER_PORTS_MAIN_PORT};
 use autonomi::client::payment::PaymentOption;
@@ -32,6 +37,8 @@ use autonomi::{Network, Wallet};
 use crate::autonomi::args::max_fee_per_gas::{
     MaxFeePerGasParam, get_max_fee_per_gas_from_opt_param,
 };
+use crate::autonomi::connect::connect_to_network_with_config;
+use crate::config::NetworkProfile;
 
 /// Control how dweb uses and reports on selected Autonomi APIs
 ///
@@ -42,10 +49,14 @@ use crate::autonomi::args::max_fee_per_gas::{
 pub struct ApiControl {
     /// Number of retries on failed chunk upload (0 for none)
     pub file_retries: u64,
-    /// Control number of tries on selected Autonomi calls (0 for unlimited)
-    pub api_tries: u32,
+    /// Control tries, backoff and timeouts of selected Autonomi calls (see helpers::retry)
+    pub retry_policy: RetryPolicy,
     /// Use PublicArchive instead of PrivateArchive when storing directories
     pub use_public_archive: bool,
+    /// Upload directories file by file, paying for each, so that an interrupted upload can
+    /// resume from the files stored. When false a directory is uploaded in a single batch,
+    /// which makes fewer payments but is uploaded again in full if interrupted
+    pub upload_file_by_file: bool,
     /// Control dweb APIs use of pointers.
     ///
     /// For selected APIs, if ignore_pointer is Some(true) the API will find
@@ -65,6 +76,8 @@ pub struct ApiControl {
     pub max_fee_per_gas: Option<MaxFeePerGasParam>,
     /// Revert to 'standard' payment instead of paying just one node
     pub disable_single_node_payment: bool,
+    /// How Scratchpad APIs choose between the versions of a forked Scratchpad (unless given by the request)
+    pub scratchpad_resolve: ForkResolution,
 }
 
 impl Default for ApiControl {
@@ -72,12 +85,14 @@ impl Default for ApiControl {
     fn default() -> Self {
         ApiControl {
             file_retries: 0,
-            api_tries: 1,
+            retry_policy: RetryPolicy::default(),
             use_public_archive: false,
+            upload_file_by_file: true,
             ignore_pointers: false,
             show_dweb_costs: ShowCost::Both,
             max_fee_per_gas: None,
             disable_single_node_payment: false,
+            scratchpad_resolve: ForkResolution::default(),
         }
     }
 }
@@ -86,6 +101,8 @@ impl Default for ApiControl {
 pub struct DwebClientConfig {
     pub local_network: bool,
     pub alpha_network: bool,
+    /// Connect to the network described by this profile instead (see crate::config)
+    pub network_profile: Option<NetworkProfile>,
     pub host: Option<String>,
     pub port: Option<u16>,
     pub client: Option<autonomi::Client>,
@@ -99,6 +116,7 @@ impl Default for DwebClientConfig {
         DwebClientConfig {
             local_network: false,
             alpha_network: false,
+            network_profile: None,
             host: None,
             port: None,
             client: None,
@@ -108,11 +126,33 @@ impl Default for DwebClientConfig {
     }
 }
 
+impl DwebClientConfig {
+    /// The name of the network profile, or "local", "alpha" or "mainnet"
+    pub fn network_name(&self) -> String {
+        match &self.network_profile {
+            Some(profile) => profile.name.clone(),
+            None if self.local_network => "local".to_string(),
+            None if self.alpha_network => "alpha".to_string(),
+            None => "mainnet".to_string(),
+        }
+    }
+
+    pub fn is_local(&self) -> bool {
+        match &self.network_profile {
+            Some(profile) => profile.is_local(),
+            None => self.local_network,
+        }
+    }
+}
+
 /// A wrapper for autonomi::Client which simplifies use of dweb APIs
 /// TODO support separate data creation/owner and wallet keys
 #[derive(Clone)]
 pub struct DwebClient {
-    pub client: autonomi::Client,
+    /// The Autonomi client, or None when using a backend such as MemoryBackend
+    pub client: Option<autonomi::Client>,
+    /// Used to read and write chunks, data, archives, pointers, graph entries and scratchpads
+    pub backend: Arc<dyn DwebBackend>,
     pub client_config: DwebClientConfig, // A copy of the config used to connect
     pub network: Network,
     pub is_local: bool,
@@ -151,7 +191,10 @@ impl DwebClient {
         let mut client = if client_config.client.is_some() {
             client_config.clone().client.unwrap()
         } else {
-            if client_config.local_network {
+            if let Some(profile) = &client_config.network_profile {
+                println!("🔗 Connecting to network '{}'...", profile.name);
+                connect_to_network_with_config(profile).await?
+            } else if client_config.local_network {
                 println!("🏡 Connecting to local testnet...");
                 Client::init_local().await?
             } else if client_config.alpha_network {
@@ -219,8 +262,8 @@ impl DwebClient {
         println!("Max fee per gas set to: {:?}", max_fee_per_gas);
 
         let client = client.clone();
-        let ant_rate = Rate::from_environment("ANT".to_string());
-        let eth_rate = Rate::from_environment("ETH".to_string());
+        let ant_rate = crate::config::config().rate("ANT");
+        let eth_rate = crate::config::config().rate("ETH");
 
         let host = client_config
             .clone()
@@ -228,10 +271,11 @@ impl DwebClient {
             .unwrap_or(LOCALHOST_STR.to_string());
         let port = client_config.port.unwrap_or(SERVER_PORTS_MAIN_PORT);
         Ok(DwebClient {
-            client: client.clone(),
+            client: Some(client.clone()),
+            backend: Arc::new(client.clone()),
             client_config: client_config.clone(),
             network: client.evm_network().clone(),
-            is_local: client_config.local_network,
+            is_local: client_config.is_local(),
             host,
             port,
             wallet,
@@ -242,6 +286,45 @@ impl DwebClient {
         })
     }
 
+    /// Create a client which uses backend instead of connecting to Autonomi, such as
+    /// a MemoryBackend for testing
+    ///
+    /// The wallet is temporary and payments are made according to the backend.
+    pub fn with_backend(
+        backend: Arc<dyn DwebBackend>,
+        client_config: &DwebClientConfig,
+    ) -> DwebClient {
+        let network = Network::ArbitrumSepoliaTest;
+        DwebClient {
+            client: None,
+            backend,
+            client_config: client_config.clone(),
+            network: network.clone(),
+            is_local: true,
+            host: client_config
+                .host
+                .clone()
+                .unwrap_or(LOCALHOST_STR.to_string()),
+            port: client_config.port.unwrap_or(SERVER_PORTS_MAIN_PORT),
+            wallet: Wallet::new_with_random_wallet(network),
+            is_wallet_temporary: true,
+            api_control: client_config.api_control.clone(),
+            ant_rate: None,
+            eth_rate: None,
+        }
+    }
+
+    /// The Autonomi client, for operations which are not provided by a DwebBackend
+    pub fn network_client(&self) -> Result<&autonomi::Client> {
+        match &self.client {
+            Some(client) => Ok(client),
+            None => Err(eyre!(
+                "not supported by the {} backend, requires an Autonomi network",
+                self.backend.name()
+            )),
+        }
+    }
+
     pub fn payment_option(&self) -> PaymentOption {
         PaymentOption::from(&self.wallet)
     }
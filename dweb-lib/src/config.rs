@@ -0,0 +1,694 @@
+/*
+ Copyright (c) 2025 Mark Hughes
+
+ This program is free software: you can redistribute it and/or modify
+ it under the terms of the GNU Affero General Public License as published by
+ the Free Software Foundation, either version 3 of the License, or
+ (at your option) any later version.
+
+ This program is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY; without even the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU Affero General Public License for more details.
+
+ You should have received a copy of the GNU Affero General Public License
+ along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Settings for dweb clients and servers, layered from several sources.
+//!
+//! In order of precedence (highest first):
+//!
+//!     command line flags
+//!     environment variables (DWEB_WORKERS, DWEB_LISTENER_IDLE, DWEB_HEAL_INTERVAL,
+//!         DWEB_HEAL_MAX_PUTS, DWEB_UPLOAD_LIMIT_MB, DWEB_RATE_ANT and DWEB_RATE_ETH)
+//!     a file given by 'dweb --config <FILE>'
+//!     dweb.toml in the Autonomi client data directory
+//!     defaults
+//!
+//! Each layer is a DwebConfig in which a setting is None unless given by that layer,
+//! and layers are combined using DwebConfig::merge(). The result is installed for the
+//! process using set_config() and read using config(), whose accessors supply the
+//! default for any setting not given.
+//!
+//! Secret keys are deliberately not read from a config file. See autonomi::access::keys.
+//!
+//! Networks other than the public Autonomi networks, such as a private testnet, can be
+//! described by a named NetworkProfile in the [networks] section and selected using
+//! 'dweb --network NAME' or the [network] name setting. For example:
+//!
+//!     [networks.mytestnet]
+//!     peers = ["/ip4/10.0.0.5/udp/40000/quic-v1/p2p/12D3KooW..."]
+//!     rpc_url = "http://10.0.0.5:61611"
+//!     payment_token_address = "0x5FbDB2315678afecb367f032d93F642f64180aa3"
+//!     data_payments_address = "0x8464135c8F25Da09e49BC8782676a84730C318bC"
+//!
+//!     [networks.mytestnet.strategy]
+//!     get_quorum = "one"
+//!     retry = "quick"
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use color_eyre::eyre::{Result, eyre};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::autonomi::access::data_dir::get_client_data_dir_path;
+use crate::client::{ApiControl, DwebClientConfig};
+use crate::helpers::retry::RetryPolicy;
+use crate::helpers::scratchpad::ForkResolution;
+use crate::token::{Rate, ShowCost};
+use crate::web::{LOCALHOST_STR, SERVER_PORTS_MAIN_PORT};
+
+pub const CONFIG_FILE_NAME: &str = "dweb.toml";
+
+pub const DEFAULT_WORKERS: usize = 12;
+pub const DEFAULT_CONNECTION_TIMEOUT_SECS: u64 = 75;
+pub const DEFAULT_LISTENER_IDLE_SECS: u64 = 1800;
+pub const DEFAULT_LISTENER_PORT_BASE: u16 = 30_000;
+pub const DEFAULT_LISTENER_PORT_RANGE: u16 = 20_000;
+pub const DEFAULT_HEAL_INTERVAL_SECS: u64 = 900;
+pub const DEFAULT_HEAL_MAX_PUTS: u32 = 3;
+pub const DEFAULT_UPLOAD_LIMIT_MB: u64 = 4096;
+pub const DEFAULT_DWEB_NAMES_CAPACITY: u32 = 1000;
+pub const DEFAULT_VERSIONS_CAPACITY: u32 = 1000;
+pub const DEFAULT_WITH_PORT_CAPACITY: u32 = u16::MAX as u32;
+
+/// All dweb settings, or one layer of them
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DwebConfig {
+    pub network: NetworkConfig,
+    /// Network profiles by name
+    pub networks: BTreeMap<String, NetworkProfile>,
+    pub server: ServerConfig,
+    pub api: ApiConfig,
+    pub cache: CacheConfig,
+    pub rates: RatesConfig,
+}
+
+/// The Autonomi network to connect to. Neither local, alpha nor name means the public network.
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkConfig {
+    /// Connect to a local testnet
+    pub local: Option<bool>,
+    /// Connect to the alpha public network
+    pub alpha: Option<bool>,
+    /// Connect to the network described by this entry in [networks]
+    pub name: Option<String>,
+}
+
+/// An Autonomi network described by its bootstrap peers and EVM payment settings
+///
+/// The EVM network is Arbitrum One unless evm_network is "arbitrum-sepolia-test", or rpc_url
+/// is given, in which case a custom EVM network is used and both payment_token_address
+/// and data_payments_address are required.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct NetworkProfile {
+    /// The name of the profile (the key of its entry in [networks])
+    #[serde(skip)]
+    pub name: String,
+    /// Multiaddrs of peers used to join the network
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub peers: Vec<String>,
+    /// URLs of lists of peers used to join the network
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub network_contacts_url: Vec<String>,
+    /// Look for peers on this machine (for a local testnet)
+    pub local: Option<bool>,
+    /// "arbitrum-one" or "arbitrum-sepolia-test", ignored if rpc_url is given
+    pub evm_network: Option<String>,
+    /// RPC URL of a custom EVM network
+    pub rpc_url: Option<String>,
+    /// Address of the payment token contract of a custom EVM network
+    pub payment_token_address: Option<String>,
+    /// Address of the data payments contract of a custom EVM network
+    pub data_payments_address: Option<String>,
+    pub strategy: StrategyProfile,
+}
+
+/// How the client reads and writes data on the network, applied to every kind of data
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+#[serde(default, deny_unknown_fields)]
+pub struct StrategyProfile {
+    /// Number of peers which must return a record: "one", "majority" or "all"
+    pub get_quorum: Option<String>,
+    /// Number of peers which must store a record: "one", "majority" or "all"
+    pub put_quorum: Option<String>,
+    /// Retries of failed operations: "none", "quick", "balanced" or "persistent"
+    pub retry: Option<String>,
+}
+
+impl NetworkProfile {
+    /// Check the profile is complete and its settings valid
+    pub fn validate(&self) -> Result<()> {
+        if self.peers.is_empty() && self.network_contacts_url.is_empty() && !self.is_local() {
+            return Err(eyre!(
+                "network '{}' needs peers or network_contacts_url unless local = true",
+                self.name
+            ));
+        }
+        for peer in &self.peers {
+            if let Err(e) = peer.parse::<multiaddr::Multiaddr>() {
+                return Err(eyre!(
+                    "network '{}' has invalid peer '{peer}' - {e}",
+                    self.name
+                ));
+            }
+        }
+        if self.rpc_url.is_some()
+            && (self.payment_token_address.is_none() || self.data_payments_address.is_none())
+        {
+            return Err(eyre!(
+                "network '{}' has an rpc_url so needs payment_token_address and data_payments_address",
+                self.name
+            ));
+        }
+        if let Some(evm_network) = &self.evm_network {
+            if !["arbitrum-one", "arbitrum-sepolia-test"].contains(&evm_network.as_str()) {
+                return Err(eyre!(
+                    "network '{}' has unknown evm_network '{evm_network}'",
+                    self.name
+                ));
+            }
+        }
+        for quorum in [&self.strategy.get_quorum, &self.strategy.put_quorum] {
+            if let Some(quorum) = quorum {
+                if !["one", "majority", "all"].contains(&quorum.as_str()) {
+                    return Err(eyre!(
+                        "network '{}' has unknown quorum '{quorum}'",
+                        self.name
+                    ));
+                }
+            }
+        }
+        if let Some(retry) = &self.strategy.retry {
+            if !["none", "quick", "balanced", "persistent"].contains(&retry.as_str()) {
+                return Err(eyre!("network '{}' has unknown retry '{retry}'", self.name));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn is_local(&self) -> bool {
+        self.local.unwrap_or(false)
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    /// Host address of the main dweb server
+    pub host: Option<String>,
+    /// Port of the main dweb server
+    pub port: Option<u16>,
+    /// Maximum number of parallel requests handled by each server (DWEB_WORKERS)
+    pub workers: Option<usize>,
+    /// Seconds an idle HTTP connection is kept alive
+    pub connection_timeout: Option<u64>,
+    /// Seconds without a request before a website listener is stopped, 0 to never stop (DWEB_LISTENER_IDLE)
+    pub listener_idle: Option<u64>,
+    /// Website listeners are given a port from listener_port_base to listener_port_base + listener_port_range
+    pub listener_port_base: Option<u16>,
+    pub listener_port_range: Option<u16>,
+    /// Seconds between checks of owned History pointers, 0 to disable (DWEB_HEAL_INTERVAL)
+    pub heal_interval: Option<u64>,
+    /// Maximum number of paid History pointer heals for each run of the server (DWEB_HEAL_MAX_PUTS)
+    pub heal_max_puts: Option<u32>,
+    /// Maximum total size in megabytes of the files in one multipart upload (DWEB_UPLOAD_LIMIT_MB)
+    pub upload_limit_mb: Option<u64>,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiConfig {
+    /// Number of retries on failed chunk upload (0 for none)
+    pub retry_file_uploads: Option<u64>,
+    /// Number of tries of selected Autonomi calls (0 for unlimited, up to a time limit)
+    pub retry_api: Option<u32>,
+    /// Store directories using PublicArchive instead of PrivateArchive
+    pub use_old_archive: Option<bool>,
+    /// Find the most recent entry of a History by following its graph rather than its pointer
+    pub ignore_pointers: Option<bool>,
+    /// Pay nodes individually instead of paying a single node
+    pub disable_single_node_payment: Option<bool>,
+    /// Show the cost of dweb API calls in tokens, gas, both or none
+    pub show_dweb_costs: Option<ShowCost>,
+    /// How to choose between the versions of a forked Scratchpad (counter, signature, all or merge)
+    pub scratchpad_resolve: Option<ForkResolution>,
+}
+
+/// Capacities of the in-memory caches of the dweb server
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CacheConfig {
+    pub dweb_names: Option<u32>,
+    pub versions: Option<u32>,
+    pub directories_with_port: Option<u32>,
+}
+
+/// Exchange rates used to show costs in a currency, as "RATE,CURRENCY" (e.g. "0.35,GBP")
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RatesConfig {
+    pub ant: Option<String>,
+    pub eth: Option<String>,
+}
+
+static CONFIG: OnceLock<DwebConfig> = OnceLock::new();
+
+/// Install the settings used by this process. Can only be done once, before config() is first used.
+pub fn set_config(config: DwebConfig) -> Result<()> {
+    match CONFIG.set(config) {
+        Ok(()) => Ok(()),
+        Err(_) => Err(eyre!("dweb settings have already been set")),
+    }
+}
+
+/// The settings used by this process
+///
+/// If set_config() has not been used, these are loaded from dweb.toml in the client data
+/// directory and the environment.
+pub fn config() -> &'static DwebConfig {
+    CONFIG.get_or_init(|| match DwebConfig::load(None) {
+        Ok(config) => config,
+        Err(e) => {
+            println!("DEBUG using default settings - {e}");
+            DwebConfig::from_environment()
+        }
+    })
+}
+
+impl DwebConfig {
+    /// Load and merge the config files and environment variables
+    ///
+    /// The result does not include command line flags, which can be merged with it.
+    pub fn load(config_file: Option<&PathBuf>) -> Result<DwebConfig> {
+        let mut config = match default_config_file() {
+            Ok(path) if path.exists() => DwebConfig::from_file(&path)?,
+            _ => DwebConfig::default(),
+        };
+        if let Some(config_file) = config_file {
+            if !config_file.exists() {
+                return Err(eyre!("Config file {config_file:?} not found"));
+            }
+            config = config.merge(DwebConfig::from_file(config_file)?);
+        }
+        Ok(config.merge(DwebConfig::from_environment()))
+    }
+
+    pub fn from_file(path: &Path) -> Result<DwebConfig> {
+        let text = match std::fs::read_to_string(path) {
+            Ok(text) => text,
+            Err(e) => return Err(eyre!("Failed to read {path:?} - {e}")),
+        };
+        DwebConfig::from_toml(&text).map_err(|e| eyre!("Invalid settings in {path:?} - {e}"))
+    }
+
+    pub fn from_toml(text: &str) -> Result<DwebConfig> {
+        match toml::from_str(text) {
+            Ok(config) => Ok(config),
+            Err(e) => Err(eyre!("{e}")),
+        }
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        match toml::to_string_pretty(self) {
+            Ok(text) => Ok(text),
+            Err(e) => Err(eyre!("Failed to encode settings - {e}")),
+        }
+    }
+
+    /// The settings given by environment variables
+    pub fn from_environment() -> DwebConfig {
+        DwebConfig::from_variables(|name| std::env::var(name).ok())
+    }
+
+    fn from_variables(variable: impl Fn(&str) -> Option<String>) -> DwebConfig {
+        let parse_u64 = |name: &str| variable(name).and_then(|s| s.trim().parse::<u64>().ok());
+        let mut config = DwebConfig::default();
+        // Clamp to a minimum of 1
+        config.server.workers = variable("DWEB_WORKERS")
+            .and_then(|s| s.trim().parse::<usize>().ok())
+            .map(|workers| workers.max(1));
+        config.server.listener_idle = parse_u64("DWEB_LISTENER_IDLE");
+        config.server.heal_interval = parse_u64("DWEB_HEAL_INTERVAL");
+        config.server.heal_max_puts = parse_u64("DWEB_HEAL_MAX_PUTS").map(|puts| puts as u32);
+        config.server.upload_limit_mb = parse_u64("DWEB_UPLOAD_LIMIT_MB");
+        config.rates.ant = variable(&Rate::env_var_for(&"ANT".to_string()));
+        config.rates.eth = variable(&Rate::env_var_for(&"ETH".to_string()));
+        config
+    }
+
+    /// Combine with a layer of higher precedence, whose settings replace these
+    pub fn merge(self, higher: DwebConfig) -> DwebConfig {
+        DwebConfig {
+            network: NetworkConfig {
+                local: higher.network.local.or(self.network.local),
+                alpha: higher.network.alpha.or(self.network.alpha),
+                name: higher.network.name.or(self.network.name),
+            },
+            networks: {
+                let mut networks = self.networks;
+                networks.extend(higher.networks);
+                networks
+            },
+            server: ServerConfig {
+                host: higher.server.host.or(self.server.host),
+                port: higher.server.port.or(self.server.port),
+                workers: higher.server.workers.or(self.server.workers),
+                connection_timeout: higher
+                    .server
+                    .connection_timeout
+                    .or(self.server.connection_timeout),
+                listener_idle: higher.server.listener_idle.or(self.server.listener_idle),
+                listener_port_base: higher
+                    .server
+                    .listener_port_base
+                    .or(self.server.listener_port_base),
+                listener_port_range: higher
+                    .server
+                    .listener_port_range
+                    .or(self.server.listener_port_range),
+                heal_interval: higher.server.heal_interval.or(self.server.heal_interval),
+                heal_max_puts: higher.server.heal_max_puts.or(self.server.heal_max_puts),
+                upload_limit_mb: higher
+                    .server
+                    .upload_limit_mb
+                    .or(self.server.upload_limit_mb),
+            },
+            api: ApiConfig {
+                retry_file_uploads: higher
+                    .api
+                    .retry_file_uploads
+                    .or(self.api.retry_file_uploads),
+                retry_api: higher.api.retry_api.or(self.api.retry_api),
+                use_old_archive: higher.api.use_old_archive.or(self.api.use_old_archive),
+                ignore_pointers: higher.api.ignore_pointers.or(self.api.ignore_pointers),
+                disable_single_node_payment: higher
+                    .api
+                    .disable_single_node_payment
+                    .or(self.api.disable_single_node_payment),
+                show_dweb_costs: higher.api.show_dweb_costs.or(self.api.show_dweb_costs),
+                scratchpad_resolve: higher
+                    .api
+                    .scratchpad_resolve
+                    .or(self.api.scratchpad_resolve),
+            },
+            cache: CacheConfig {
+                dweb_names: higher.cache.dweb_names.or(self.cache.dweb_names),
+                versions: higher.cache.versions.or(self.cache.versions),
+                directories_with_port: higher
+                    .cache
+                    .directories_with_port
+                    .or(self.cache.directories_with_port),
+            },
+            rates: RatesConfig {
+                ant: higher.rates.ant.or(self.rates.ant),
+                eth: higher.rates.eth.or(self.rates.eth),
+            },
+        }
+    }
+
+    /// Every setting, with defaults for those not given. Used to show the effective settings.
+    pub fn with_defaults(&self) -> DwebConfig {
+        let defaults = DwebConfig {
+            network: NetworkConfig {
+                local: Some(self.local()),
+                alpha: Some(self.alpha()),
+                name: None,
+            },
+            networks: BTreeMap::new(),
+            server: ServerConfig {
+                host: Some(self.host()),
+                port: Some(self.port()),
+                workers: Some(self.workers()),
+                connection_timeout: Some(self.connection_timeout()),
+                listener_idle: Some(self.listener_idle()),
+                listener_port_base: Some(self.listener_port_base()),
+                listener_port_range: Some(self.listener_port_range()),
+                heal_interval: Some(self.heal_interval()),
+                heal_max_puts: Some(self.heal_max_puts()),
+                upload_limit_mb: Some(self.upload_limit_mb()),
+            },
+            api: ApiConfig {
+                retry_file_uploads: Some(self.api.retry_file_uploads.unwrap_or(0)),
+                retry_api: Some(self.api.retry_api.unwrap_or(0)),
+                use_old_archive: Some(self.api.use_old_archive.unwrap_or(false)),
+                ignore_pointers: Some(self.api.ignore_pointers.unwrap_or(false)),
+                disable_single_node_payment: Some(
+                    self.api.disable_single_node_payment.unwrap_or(false),
+                ),
+                show_dweb_costs: Some(self.show_dweb_costs()),
+                scratchpad_resolve: Some(self.api.scratchpad_resolve.unwrap_or_default()),
+            },
+            cache: CacheConfig {
+                dweb_names: Some(self.dweb_names_capacity()),
+                versions: Some(self.versions_capacity()),
+                directories_with_port: Some(self.with_port_capacity()),
+            },
+            rates: RatesConfig::default(),
+        };
+        defaults.merge(self.clone())
+    }
+
+    pub fn local(&self) -> bool {
+        self.network.local.unwrap_or(false)
+    }
+
+    pub fn alpha(&self) -> bool {
+        self.network.alpha.unwrap_or(false)
+    }
+
+    /// The network profile selected by [network] name, if any
+    pub fn network_profile(&self) -> Option<NetworkProfile> {
+        let name = self.network.name.as_ref()?;
+        let mut profile = self.networks.get(name)?.clone();
+        profile.name = name.clone();
+        Some(profile)
+    }
+
+    /// Check that any network selected by name exists and is valid
+    pub fn check_network_profile(&self) -> Result<()> {
+        match &self.network.name {
+            Some(name) => match self.network_profile() {
+                Some(profile) => profile.validate(),
+                None => Err(eyre!(
+                    "Unknown network '{name}', see 'dweb network list' to list network profiles"
+                )),
+            },
+            None => Ok(()),
+        }
+    }
+
+    pub fn host(&self) -> String {
+        self.server
+            .host
+            .clone()
+            .unwrap_or(LOCALHOST_STR.to_string())
+    }
+
+    pub fn port(&self) -> u16 {
+        self.server.port.unwrap_or(SERVER_PORTS_MAIN_PORT)
+    }
+
+    pub fn workers(&self) -> usize {
+        self.server.workers.unwrap_or(DEFAULT_WORKERS).max(1)
+    }
+
+    pub fn connection_timeout(&self) -> u64 {
+        self.server
+            .connection_timeout
+            .unwrap_or(DEFAULT_CONNECTION_TIMEOUT_SECS)
+    }
+
+    pub fn listener_idle(&self) -> u64 {
+        self.server
+            .listener_idle
+            .unwrap_or(DEFAULT_LISTENER_IDLE_SECS)
+    }
+
+    pub fn listener_port_base(&self) -> u16 {
+        self.server
+            .listener_port_base
+            .unwrap_or(DEFAULT_LISTENER_PORT_BASE)
+    }
+
+    pub fn listener_port_range(&self) -> u16 {
+        self.server
+            .listener_port_range
+            .unwrap_or(DEFAULT_LISTENER_PORT_RANGE)
+            .max(1)
+    }
+
+    pub fn heal_interval(&self) -> u64 {
+        self.server
+            .heal_interval
+            .unwrap_or(DEFAULT_HEAL_INTERVAL_SECS)
+    }
+
+    pub fn heal_max_puts(&self) -> u32 {
+        self.server.heal_max_puts.unwrap_or(DEFAULT_HEAL_MAX_PUTS)
+    }
+
+    pub fn upload_limit_mb(&self) -> u64 {
+        self.server
+            .upload_limit_mb
+            .unwrap_or(DEFAULT_UPLOAD_LIMIT_MB)
+            .max(1)
+    }
+
+    /// The maximum total size of a multipart upload in bytes
+    pub fn upload_limit_bytes(&self) -> usize {
+        usize::try_from(self.upload_limit_mb().saturating_mul(1024 * 1024)).unwrap_or(usize::MAX)
+    }
+
+    pub fn show_dweb_costs(&self) -> ShowCost {
+        self.api.show_dweb_costs.unwrap_or(ShowCost::Both)
+    }
+
+    pub fn dweb_names_capacity(&self) -> u32 {
+        self.cache.dweb_names.unwrap_or(DEFAULT_DWEB_NAMES_CAPACITY)
+    }
+
+    pub fn versions_capacity(&self) -> u32 {
+        self.cache.versions.unwrap_or(DEFAULT_VERSIONS_CAPACITY)
+    }
+
+    pub fn with_port_capacity(&self) -> u32 {
+        self.cache
+            .directories_with_port
+            .unwrap_or(DEFAULT_WITH_PORT_CAPACITY)
+    }
+
+    /// The exchange rate for ticker ("ANT" or "ETH"), if set
+    pub fn rate(&self, ticker: &str) -> Option<Rate> {
+        let value = match ticker {
+            "ANT" => self.rates.ant.as_ref(),
+            "ETH" => self.rates.eth.as_ref(),
+            _ => None,
+        };
+        value.and_then(|value| Rate::parse(ticker.to_string(), value))
+    }
+
+    pub fn api_control(&self) -> ApiControl {
+        ApiControl {
+            file_retries: self.api.retry_file_uploads.unwrap_or(0),
+            retry_policy: RetryPolicy {
+                max_tries: self.api.retry_api.unwrap_or(0),
+                ..Default::default()
+            },
+            use_public_archive: self.api.use_old_archive.unwrap_or(false),
+            ignore_pointers: self.api.ignore_pointers.unwrap_or(false),
+            show_dweb_costs: self.show_dweb_costs(),
+            disable_single_node_payment: self.api.disable_single_node_payment.unwrap_or(false),
+            scratchpad_resolve: self.api.scratchpad_resolve.unwrap_or_default(),
+            ..Default::default()
+        }
+    }
+
+    /// A DwebClientConfig for these settings. Server host and port are only set if given.
+    pub fn client_config(&self) -> DwebClientConfig {
+        DwebClientConfig {
+            local_network: self.local(),
+            alpha_network: self.alpha(),
+            network_profile: self.network_profile(),
+            host: self.server.host.clone(),
+            port: self.server.port,
+            api_control: self.api_control(),
+            ..DwebClientConfig::default()
+        }
+    }
+}
+
+/// The location of dweb.toml in the client data directory
+pub fn default_config_file() -> Result<PathBuf> {
+    Ok(get_client_data_dir_path()?.join(CONFIG_FILE_NAME))
+}
+
+/// Change the settings stored in dweb.toml in the client data directory
+///
+/// Only the settings in the file are changed. Note that comments in the file are not kept.
+pub fn edit_default_config_file(
+    edit: impl FnOnce(&mut DwebConfig) -> Result<()>,
+) -> Result<PathBuf> {
+    let path = default_config_file()?;
+    let mut config = if path.exists() {
+        DwebConfig::from_file(&path)?
+    } else {
+        DwebConfig::default()
+    };
+    edit(&mut config)?;
+    if let Err(e) = std::fs::write(&path, config.to_toml()?) {
+        return Err(eyre!("Failed to write {path:?} - {e}"));
+    }
+    Ok(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn layers_are_merged_by_precedence() {
+        let file = DwebConfig::from_toml(
+            "[server]\nport = 6000\nworkers = 4\n\n[api]\nshow_dweb_costs = \"gas\"\n",
+        )
+        .unwrap();
+        let environment = DwebConfig::from_variables(|name| match name {
+            "DWEB_WORKERS" => Some("24".to_string()),
+            "DWEB_RATE_ANT" => Some("0.35,GBP".to_string()),
+            "DWEB_UPLOAD_LIMIT_MB" => Some("10".to_string()),
+            _ => None,
+        });
+        let mut flags = DwebConfig::default();
+        flags.server.port = Some(7000);
+
+        let config = file.merge(environment).merge(flags);
+        assert_eq!(config.port(), 7000);
+        assert_eq!(config.workers(), 24);
+        assert_eq!(config.connection_timeout(), DEFAULT_CONNECTION_TIMEOUT_SECS);
+        assert_eq!(config.upload_limit_bytes(), 10 * 1024 * 1024);
+        assert!(matches!(config.show_dweb_costs(), ShowCost::Gas));
+        assert_eq!(
+            config.rate("ANT").map(|rate| rate.currency),
+            Some("GBP".to_string())
+        );
+        assert!(config.rate("ETH").is_none());
+    }
+
+    #[test]
+    fn network_profile_is_selected_by_name() {
+        let config = DwebConfig::from_toml(
+            "[network]\nname = \"testnet\"\n\n[networks.testnet]\nlocal = true\nrpc_url = \"http://localhost:61611\"\n",
+        )
+        .unwrap();
+        let profile = config.network_profile().unwrap();
+        assert_eq!(profile.name, "testnet");
+        assert!(profile.is_local());
+        // A custom EVM network needs its contract addresses
+        assert!(config.check_network_profile().is_err());
+
+        let mut flags = DwebConfig::default();
+        flags.network.name = Some("missing".to_string());
+        assert!(config.merge(flags).check_network_profile().is_err());
+    }
+
+    #[test]
+    fn unknown_settings_are_rejected() {
+        assert!(DwebConfig::from_toml("[server]\nprot = 6000\n").is_err());
+    }
+
+    #[test]
+    fn effective_settings_round_trip() {
+        let config = DwebConfig::default().with_defaults();
+        assert_eq!(config.server.workers, Some(DEFAULT_WORKERS));
+        let text = config.to_toml().unwrap();
+        let parsed = DwebConfig::from_toml(&text).unwrap();
+        assert_eq!(parsed.server.port, Some(SERVER_PORTS_MAIN_PORT));
+    }
+}
@@ -19,7 +19,7 @@ use autonomi::client::data::DataAddress;
 use bytes::Bytes;
 use color_eyre::Result;
 
-use autonomi::client::GetError;
+use crate::backend::BackendError;
 
 use crate::client::DwebClient;
 
@@ -27,10 +27,10 @@ use crate::client::DwebClient;
 pub async fn autonomi_get_file_public(
     client: &DwebClient,
     file_address: &DataAddress,
-) -> Result<Bytes, GetError> {
+) -> Result<Bytes, BackendError> {
     println!("DEBUG autonomi_get_file_public()");
     println!("DEBUG calling client.data_get_public()");
-    match client.client.data_get_public(file_address).await {
+    match client.backend.data_get_public(file_address).await {
         Ok(content) => {
             println!("DEBUG Ok() return");
             Ok(content)
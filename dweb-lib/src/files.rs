@@ -17,3 +17,5 @@
 
 pub mod archive;
 pub mod directory;
+pub mod private_history;
+pub mod tree_fs;
@@ -27,6 +27,7 @@ use autonomi::client::files::RenameError;
 use autonomi::data::DataAddress;
 use autonomi::files::{Metadata, PrivateArchive, PublicArchive};
 
+use crate::files::directory::canonicalise_path;
 use crate::storage::DwebType;
 
 /// A single type to handle Autonomi PublicArchive and PrivateArchive
@@ -300,6 +301,190 @@ impl DualArchive {
         }
     }
 
+    /// Serialize according to the dweb_type of the archive
+    pub fn to_bytes(&self) -> Result<Bytes> {
+        let bytes = match self.dweb_type {
+            DwebType::PublicArchive => self.to_bytes_as_public(),
+            _ => self.to_bytes_as_private(),
+        };
+        bytes.map_err(|e| eyre!("Failed to serialize archive: {e:?}"))
+    }
+
+    /// Add a file at web_path (which should start with '/'), replacing any file already there
+    ///
+    /// Requires data_address for a PublicArchive or datamap_chunk for a PrivateArchive.
+    /// Note that this does not upload the archive to the network
+    pub fn put_file(
+        &mut self,
+        web_path: &str,
+        data_address: Option<DataAddress>,
+        datamap_chunk: Option<DataMapChunk>,
+        metadata: Metadata,
+    ) -> Result<()> {
+        let web_path = normalise_web_path(web_path);
+        if web_path.is_empty() {
+            return Err(eyre!("Cannot add a file at the root of the archive"));
+        }
+        match self.dweb_type {
+            DwebType::PublicArchive => match data_address {
+                Some(data_address) => {
+                    self.remove_matching(|path| path == web_path);
+                    self.public_archive
+                        .add_file(PathBuf::from(&web_path), data_address, metadata);
+                    Ok(())
+                }
+                None => Err(eyre!(
+                    "A data address is required to add '{web_path}' to a PublicArchive"
+                )),
+            },
+            DwebType::PrivateArchive => match datamap_chunk {
+                Some(datamap_chunk) => {
+                    self.remove_matching(|path| path == web_path);
+                    add_private_file(
+                        &mut self.private_archive,
+                        PathBuf::from(&web_path),
+                        datamap_chunk,
+                        metadata,
+                    );
+                    Ok(())
+                }
+                None => Err(eyre!("A datamap is required to add '{web_path}' to a PrivateArchive")),
+            },
+            _ => Err(eyre!("Cannot add a file to an archive of type {:?}", self.dweb_type)),
+        }
+    }
+
+    /// Remove the file at web_path, or every file beneath it if web_path is a directory
+    ///
+    /// Returns the number of files removed, which is an error if zero.
+    /// Note that this does not upload the archive to the network
+    pub fn remove_path(&mut self, web_path: &str) -> Result<usize> {
+        let web_path = normalise_web_path(web_path);
+        if web_path.is_empty() {
+            return Err(eyre!("Cannot remove the root of the archive"));
+        }
+        let removed = self.remove_matching(|path| is_at_or_beneath(path, &web_path));
+        if removed == 0 {
+            return Err(eyre!("No file or directory at '{web_path}'"));
+        }
+        Ok(removed)
+    }
+
+    /// Rename or move the file or directory at from_path so that it is at to_path
+    ///
+    /// For a directory, every file beneath it is moved. Fails without changing the archive
+    /// if nothing is at from_path or if any destination is already occupied.
+    ///
+    /// Returns the number of files moved.
+    /// Note that this does not upload the archive to the network
+    pub fn move_path(&mut self, from_path: &str, to_path: &str) -> Result<usize> {
+        let from_path = normalise_web_path(from_path);
+        let to_path = normalise_web_path(to_path);
+        if from_path.is_empty() || to_path.is_empty() {
+            return Err(eyre!("Cannot move to or from the root of the archive"));
+        }
+        if is_at_or_beneath(&to_path, &from_path) {
+            return Err(eyre!("Cannot move '{from_path}' to '{to_path}' which is beneath itself"));
+        }
+
+        let stored_paths: Vec<PathBuf> = self.files().into_iter().map(|(path, _)| path).collect();
+        let mut moves = Vec::<(PathBuf, PathBuf)>::new();
+        for stored_path in &stored_paths {
+            let path = canonicalise_path(&stored_path.to_string_lossy().to_string());
+            if is_at_or_beneath(&path, &from_path) {
+                let new_path = to_path.clone() + &path[from_path.len()..];
+                moves.push((stored_path.clone(), PathBuf::from(new_path)));
+            }
+        }
+        if moves.is_empty() {
+            return Err(eyre!("No file or directory at '{from_path}'"));
+        }
+        for (_, new_path) in &moves {
+            let new_path = new_path.to_string_lossy().to_string();
+            if stored_paths
+                .iter()
+                .any(|path| canonicalise_path(&path.to_string_lossy().to_string()) == new_path)
+            {
+                return Err(eyre!("Cannot move to '{new_path}' which already exists"));
+            }
+        }
+
+        for (old_path, new_path) in &moves {
+            let result = match self.dweb_type {
+                DwebType::PublicArchive => self.public_archive.rename_file(old_path, new_path),
+                _ => self.private_archive.rename_file(old_path, new_path),
+            };
+            if let Err(e) = result {
+                return Err(eyre!("Failed to move {old_path:?} to {new_path:?} - {e:?}"));
+            }
+        }
+        Ok(moves.len())
+    }
+
+    /// A new archive of the files beneath the directory at web_path, with that directory as its root
+    ///
+    /// Returns an error if there are no files beneath web_path.
+    /// Note that this does not upload the archive to the network
+    pub fn subtree(&self, web_path: &str) -> Result<DualArchive> {
+        let web_path = normalise_web_path(web_path);
+        let mut subtree = self.clone();
+        subtree.remove_matching(|path| !path.starts_with(&(web_path.clone() + "/")));
+        if subtree.files().is_empty() {
+            return Err(eyre!("No directory at '{web_path}'"));
+        }
+        if !web_path.is_empty() {
+            for (path, _) in subtree.files() {
+                let stored_path = canonicalise_path(&path.to_string_lossy().to_string());
+                let new_path = PathBuf::from(&stored_path[web_path.len()..]);
+                let result = match subtree.dweb_type {
+                    DwebType::PublicArchive => subtree.public_archive.rename_file(&path, &new_path),
+                    _ => subtree.private_archive.rename_file(&path, &new_path),
+                };
+                if let Err(e) = result {
+                    return Err(eyre!("Failed to move {path:?} to {new_path:?} - {e:?}"));
+                }
+            }
+        }
+        Ok(subtree)
+    }
+
+    // Rebuild the active archive without files whose web path matches. Returns the number removed
+    fn remove_matching(&mut self, matches: impl Fn(&str) -> bool) -> usize {
+        let web_path = |path: &PathBuf| canonicalise_path(&path.to_string_lossy().to_string());
+        let mut removed = 0;
+        match self.dweb_type {
+            DwebType::PublicArchive => {
+                let mut archive = PublicArchive::new();
+                for (path, (data_address, metadata)) in self.public_archive.map() {
+                    if matches(&web_path(path)) {
+                        removed = removed + 1;
+                    } else {
+                        archive.add_file(path.clone(), *data_address, metadata.clone());
+                    }
+                }
+                self.public_archive = archive;
+            }
+            DwebType::PrivateArchive => {
+                let mut archive = PrivateArchive::new();
+                for (path, (datamap_chunk, metadata)) in self.private_archive.map() {
+                    if matches(&web_path(path)) {
+                        removed = removed + 1;
+                    } else {
+                        add_private_file(
+                            &mut archive,
+                            path.clone(),
+                            datamap_chunk.clone(),
+                            metadata.clone(),
+                        );
+                    }
+                }
+                self.private_archive = archive;
+            }
+            _ => {}
+        }
+        removed
+    }
+
     /// Merge as a PublicArchive
     ///
     /// If there are duplicate entries for the same filename, the files from `other` will overwrite those of `self`.
@@ -314,3 +499,93 @@ impl DualArchive {
         self.private_archive.merge(other)
     }
 }
+
+// Add a file to a PrivateArchive keeping any leading slash in path
+//
+// TODO: when autonomi issue (https://github.com/maidsafe/autonomi/issues/3260) is fixed
+//       this can be replaced by PrivateArchive::add_file()
+fn add_private_file(
+    archive: &mut PrivateArchive,
+    path: PathBuf,
+    datamap_chunk: DataMapChunk,
+    metadata: Metadata,
+) {
+    archive.add_file(path.clone(), datamap_chunk, metadata);
+    if !archive.map().contains_key(&path) {
+        if let Ok(added_path) = path.strip_prefix("/") {
+            let _ = archive.rename_file(added_path, path.as_path());
+        }
+    }
+}
+
+// Canonicalise a web path and remove any trailing separator, so the root becomes ""
+fn normalise_web_path(web_path: &str) -> String {
+    canonicalise_path(&web_path.to_string())
+        .trim_end_matches(ARCHIVE_PATH_SEPARATOR)
+        .to_string()
+}
+
+// True if path is the same as, or is beneath, the directory or file at web_path
+fn is_at_or_beneath(path: &str, web_path: &str) -> bool {
+    path == web_path
+        || (path.starts_with(web_path)
+            && path[web_path.len()..].starts_with(ARCHIVE_PATH_SEPARATOR))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn public_archive_of(paths: &[&str]) -> DualArchive {
+        let mut archive = DualArchive::new();
+        archive.dweb_type = DwebType::PublicArchive;
+        let data_address = DataAddress::from_hex(&"a".repeat(64)).unwrap();
+        for path in paths {
+            let metadata = Metadata {
+                created: 0,
+                modified: 0,
+                size: 1,
+                extra: None,
+            };
+            archive
+                .put_file(path, Some(data_address), None, metadata)
+                .unwrap();
+        }
+        archive
+    }
+
+    fn web_paths(archive: &DualArchive) -> Vec<String> {
+        archive
+            .files()
+            .iter()
+            .map(|(path, _)| canonicalise_path(&path.to_string_lossy().to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn move_and_remove_directories() {
+        let mut archive = public_archive_of(&["/a/one.txt", "/a/b/two.txt", "/ab.txt"]);
+
+        assert_eq!(archive.move_path("/a", "/c/").unwrap(), 2);
+        assert_eq!(
+            web_paths(&archive),
+            vec!["/ab.txt", "/c/b/two.txt", "/c/one.txt"]
+        );
+        assert!(archive.move_path("/ab.txt", "/c/one.txt").is_err());
+        assert!(archive.move_path("/c", "/c/d").is_err());
+
+        assert_eq!(archive.remove_path("/c/b").unwrap(), 1);
+        assert!(archive.remove_path("/c/b").is_err());
+        assert_eq!(web_paths(&archive), vec!["/ab.txt", "/c/one.txt"]);
+    }
+
+    #[test]
+    fn subtree_is_rerooted() {
+        let archive = public_archive_of(&["/a/one.txt", "/a/b/two.txt", "/ab.txt"]);
+
+        let subtree = archive.subtree("/a/").unwrap();
+        assert_eq!(web_paths(&subtree), vec!["/b/two.txt", "/one.txt"]);
+        assert_eq!(web_paths(&archive.subtree("/").unwrap()).len(), 3);
+        assert!(archive.subtree("/ab.txt").is_err());
+    }
+}
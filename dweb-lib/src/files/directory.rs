@@ -21,6 +21,8 @@ use bytes::Bytes;
 use color_eyre::eyre::{Result, eyre};
 use http::status::StatusCode;
 use mime_guess;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 use autonomi::chunk::DataMapChunk;
 use autonomi::client::data::DataAddress;
@@ -29,6 +31,7 @@ use autonomi::client::files::archive_private::PrivateArchive;
 use autonomi::client::files::archive_public::PublicArchive;
 use autonomi::files::archive_public::ArchiveAddress;
 
+use crate::cache::version_tags::{get_version_tags, update_version_tags, version_tags_key};
 use crate::client::DwebClient;
 use crate::files::archive::{ARCHIVE_PATH_SEPARATOR, DualArchive};
 use crate::history::{History, Trove};
@@ -116,6 +119,119 @@ pub const DWEB_DIRECTORY_HISTORY_CONTENT: &str =
     "0a2768c3ebbb3651cfb4219222ddef9feafd485e07ed9cf1b27b8f97afa4595d";
 pub const DWEB_DIRECTORY_HISTORY_DATAMAPCHUNK: &str = "81a54669727374939400dc00204f71ccc3ccdb7bcc95ccbaccd80a6eccd6125a2dccab2acc9657ccb5ccd9ccf518ccc4ccc455ccb311cc96ccd3ccf6cca139dc0020ccdbccf2cce1cccdccd03bccfccce4ccbe563220cca74a7fcca6cca13c2f21cc8e0248ccbaccc967cc87ccabcce65ecc99ccdcce000123179401dc0020cc8064cc8112cc9fcca6ccbcccf5151e4a4ccc87ccfc6d455650cc980dcc9a11cc8c7ccc83cced01cc90ccdbccc8cca341dc0020cc891d5677cc8267ccd7ccf6ccaf7acccb75ccde01ccb3cc81cc88cc82cc8c354f135cccd9cc836b48cc85ccf601ccebcce6ce000123179402dc00206accd622cccf50ccf5ccb4ccbccca738027bccd1ccceccc95f222173020921ccb000cc82ccd43a1ecc80cce2cce3ccfedc0020386cccc344360619157d6541ccd2ccbf7dcc866ecca812ccc424cc841c43cc81ccf1cc8563ccab353bccc3ccf4ce00012318";
 
+/// A JSON file holding annotations for the version of a History<Tree> that an archive was published as
+///
+/// See VersionManifest
+pub const DWEB_VERSION_MANIFEST_PATH: &str = ".dweb/version-manifest.json";
+
+/// Maximum length of a version tag
+pub const VERSION_TAG_MAX_LEN: usize = 64;
+
+/// Annotations for a single version of a History<Tree>
+///
+/// These are stored in the archive of each version at DWEB_VERSION_MANIFEST_PATH, next to
+/// the DWEB_HISTORY_DIRECTORY, so that a version can record why, when and by whom it was
+/// published. Tags can be used to select a version instead of a version number (e.g. 'vrelease-2').
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct VersionManifest {
+    /// Optional description of the changes in this version
+    pub message: Option<String>,
+    /// Optional label identifying who published this version
+    pub author: Option<String>,
+    /// Time of publishing in seconds since the UNIX epoch
+    pub published: u64,
+    /// Tags which can be used to select this version
+    pub tags: Vec<String>,
+}
+
+impl VersionManifest {
+    /// Create a manifest timestamped now. Returns an error if any tag is not valid
+    pub fn new(
+        message: Option<String>,
+        author: Option<String>,
+        tags: Vec<String>,
+    ) -> Result<VersionManifest> {
+        for tag in tags.iter() {
+            validate_version_tag(tag)?;
+        }
+
+        let published = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0);
+
+        Ok(VersionManifest {
+            message,
+            author,
+            published,
+            tags,
+        })
+    }
+
+    pub fn from_bytes(bytes: &Bytes) -> Result<VersionManifest> {
+        match serde_json::from_slice::<VersionManifest>(bytes) {
+            Ok(manifest) => Ok(manifest),
+            Err(e) => Err(eyre!("VersionManifest::from_bytes() failed - {e}")),
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<Bytes> {
+        match serde_json::to_vec(self) {
+            Ok(vec) => Ok(Bytes::from(vec)),
+            Err(e) => Err(eyre!("VersionManifest::to_bytes() failed - {e}")),
+        }
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// The publish time formatted as RFC 3339, or an empty string if not known
+    pub fn published_string(&self) -> String {
+        if self.published == 0 {
+            return String::from("");
+        }
+        match chrono::DateTime::<chrono::Utc>::from_timestamp(self.published as i64, 0) {
+            Some(datetime) => datetime.to_rfc3339(),
+            None => String::from(""),
+        }
+    }
+}
+
+/// Check a version tag is valid
+///
+/// A tag must start with an alphabetic character and contain only alphanumeric
+/// characters, '-', '_' or '.' so that 'v<TAG>' can never be mistaken for a version number.
+pub fn validate_version_tag(tag: &str) -> Result<()> {
+    if tag.is_empty() || tag.len() > VERSION_TAG_MAX_LEN {
+        return Err(eyre!(
+            "version tag must be between 1 and {VERSION_TAG_MAX_LEN} characters: '{tag}'"
+        ));
+    }
+
+    if !tag.chars().next().unwrap().is_ascii_alphabetic() {
+        return Err(eyre!(
+            "version tag must begin with an alphabetic character: '{tag}'"
+        ));
+    }
+
+    if !tag
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+    {
+        return Err(eyre!(
+            "version tag may only contain alphanumeric characters, '-', '_' or '.': '{tag}'"
+        ));
+    }
+
+    // Reserved to separate a version from a branch in a URL (see dweb::web::name::BRANCH_SEPARATOR)
+    if tag.contains("--") {
+        return Err(eyre!("version tag cannot contain '--': '{tag}'"));
+    }
+
+    Ok(())
+}
+
 /// A set of default settings for use with a website when dweb_settings is none
 #[derive(Clone)]
 pub struct DwebSettings {
@@ -193,6 +309,10 @@ pub struct Tree {
     // TODO   and that applications only create values under their own key.
     // TODO   In the default provide "awe" as a top level key with no sub-values
     pub dweb_settings: DwebSettings,
+
+    /// Annotations for the History version this Tree was published as, if present in the Archive.
+    /// None until fetched using Tree::load_version_manifest()
+    pub version_manifest: Option<VersionManifest>,
 }
 
 impl Trove<Tree> for Tree {
@@ -219,14 +339,9 @@ impl Trove<Tree> for Tree {
     }
 }
 
-// TODO consider how to use Tree for a virtual file store:
-// TODO - currently it is given a file tree to upload in one operation, based on a local path
-// TODO A virtual file store would:
-// TODO - have a std::fs style interface which return fs style error codes
-// TODO - track what is uploaded and not
-// TODO - have methods to upload / get files, subtrees / the whole tree
 /// Work in progress and subject to breaking changes
-/// TODO consider how to handle use as a virtual file store (see comments above this in the code)
+///
+/// For use as a virtual file store with a std::fs style interface see dweb::files::tree_fs::TreeFs
 impl Tree {
     // pub fn new(website_settings: Option<DwebSettings>) -> Tree {
     //     Tree {
@@ -258,7 +373,7 @@ impl Tree {
     ) -> Result<Tree> {
         println!("DEBUG from_datamap_chunk() at {}", datamap_chunk.to_hex());
 
-        match client.client.data_get(&datamap_chunk).await {
+        match client.backend.data_get(&datamap_chunk).await {
             Ok(data) => match DualArchive::from_bytes(data) {
                 Ok(dual_archive) => {
                     println!(
@@ -293,7 +408,7 @@ impl Tree {
             "DEBUG from_archive_address() at {}",
             archive_address.to_hex()
         );
-        match client.client.data_get_public(&archive_address).await {
+        match client.backend.data_get_public(&archive_address).await {
             Ok(data) => match DualArchive::from_bytes(data) {
                 Ok(dual_archive) => {
                     println!(
@@ -363,11 +478,13 @@ impl Tree {
                 directory_map: TreePathMap::from_private_archive(&archive.private_archive),
                 archive,
                 dweb_settings: DwebSettings::default(),
+                version_manifest: None,
             },
             DwebType::PublicArchive => Tree {
                 directory_map: TreePathMap::from_public_archive(&archive.public_archive),
                 archive,
                 dweb_settings: DwebSettings::default(),
+                version_manifest: None,
             },
             _ => {
                 let message = format!("Tree cannot initialise using unknown DualArchive.dweb_type");
@@ -376,6 +493,7 @@ impl Tree {
                     directory_map: TreePathMap::new(),
                     archive,
                     dweb_settings: DwebSettings::default(),
+                    version_manifest: None,
                 }
             }
         };
@@ -403,6 +521,90 @@ impl Tree {
         false
     }
 
+    /// Get the VersionManifest, fetching it from the network the first time if present in the archive
+    ///
+    /// The manifest is not fetched when the Tree is created, so that loading a Tree costs
+    /// no extra network request unless its annotations are used.
+    pub async fn load_version_manifest(&mut self, client: &DwebClient) -> Option<&VersionManifest> {
+        if self.version_manifest.is_none() {
+            self.update_version_manifest(client).await;
+        }
+        self.version_manifest.as_ref()
+    }
+
+    // Update the VersionManifest if present in the archive
+    // Return true if a manifest was found
+    async fn update_version_manifest(&mut self, client: &DwebClient) -> bool {
+        // Archives may hold the path with or without a leading slash (see storage::publish_or_update_files())
+        for manifest_path in [
+            PathBuf::from(DWEB_VERSION_MANIFEST_PATH),
+            PathBuf::from(format!("/{DWEB_VERSION_MANIFEST_PATH}")),
+        ] {
+            if let Some((datamap_chunk, data_address, _metadata)) =
+                self.archive.lookup_file(&manifest_path)
+            {
+                if let Ok(bytes) = get_content_using_hex(client, datamap_chunk, data_address).await
+                {
+                    match VersionManifest::from_bytes(&bytes) {
+                        Ok(manifest) => {
+                            self.version_manifest = Some(manifest);
+                            return true;
+                        }
+                        Err(e) => println!("DEBUG {e}"),
+                    };
+                }
+            }
+        }
+        false
+    }
+
+    /// Find the most recent version of a History whose VersionManifest includes tag
+    ///
+    /// The tags of each version are kept in crate::cache::version_tags, so a version's
+    /// archive is only fetched the first time it is searched. Searching starts with the
+    /// most recent version, so the first search for a tag applied to an early version of
+    /// a long History will still be slow.
+    pub async fn history_version_for_tag(
+        client: &DwebClient,
+        history: &mut History<Tree>,
+        tag: &str,
+    ) -> Result<u64> {
+        validate_version_tag(tag)?;
+
+        let index_key = version_tags_key(history);
+        let mut version = history.num_versions()?;
+        while version > 0 {
+            let tags = match get_version_tags(&index_key, version) {
+                Some(tags) => tags,
+                None => {
+                    let archive_address = history.get_version_entry_value(version, false).await?;
+                    match Tree::from_archive_address(client, archive_address).await {
+                        Ok(mut tree) => {
+                            let tags = match tree.load_version_manifest(client).await {
+                                Some(manifest) => manifest.tags.clone(),
+                                None => Vec::new(),
+                            };
+                            update_version_tags(&index_key, version, tags.clone());
+                            tags
+                        }
+                        Err(e) => {
+                            println!(
+                                "DEBUG history_version_for_tag() skipping version {version} - {e}"
+                            );
+                            Vec::new()
+                        }
+                    }
+                }
+            };
+            if tags.iter().any(|t| t == tag) {
+                return Ok(version);
+            }
+            version = version - 1;
+        }
+
+        Err(eyre!("no version found with tag '{tag}'"))
+    }
+
     /// Looks up a file or website resource in a version of a History
     ///
     /// First gets a Tree version, using cached data if held by the history
@@ -738,7 +940,7 @@ pub async fn get_content(
                 "DEBUG get_content_using_hex() calling data_get() with datamap_chunk: {}",
                 datamap_chunk.to_hex()
             );
-            client.client.data_get(&datamap_chunk).await
+            client.backend.data_get(&datamap_chunk).await
         }
         None => match data_address {
             Some(data_address) => {
@@ -746,7 +948,7 @@ pub async fn get_content(
                     "DEBUG get_content_using_hex() calling data_get_public() with data_address: {}",
                     data_address.to_hex()
                 );
-                client.client.data_get_public(&data_address).await
+                client.backend.data_get_public(&data_address).await
             }
             None => {
                 return Err(eyre!(
@@ -798,3 +1000,20 @@ pub async fn get_content(
 //         }
 //     };
 // }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_tags_are_validated() {
+        for tag in ["release", "rc-1", "v2_beta.3", "A"] {
+            assert!(validate_version_tag(tag).is_ok(), "{tag}");
+        }
+        for tag in ["", "1release", "-rc", "has space", "rc/1", "rc--staging"] {
+            assert!(validate_version_tag(tag).is_err(), "{tag}");
+        }
+        assert!(validate_version_tag(&"a".repeat(VERSION_TAG_MAX_LEN)).is_ok());
+        assert!(validate_version_tag(&"a".repeat(VERSION_TAG_MAX_LEN + 1)).is_err());
+    }
+}
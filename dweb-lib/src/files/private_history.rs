@@ -0,0 +1,784 @@
+/*
+Copyright (c) 2025- Mark Hughes
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Private versioned storage using a History<Tree>
+//!
+//! Websites are published with archives stored as public data, so anyone with the
+//! History address can read every file. A private History instead stores each version
+//! as a PrivateArchive saved as private data, so the archive datamap gives access to the
+//! archive and every file in it. That datamap is never stored in the clear: the History
+//! entry for a version is the address of the datamap encrypted with a history key
+//! derived from the owner's secret key and the History name.
+//!
+//! Anyone can see the History, but only the owner, or someone given the history key
+//! (see 'dweb share'), can read its versions. Only the owner can add versions.
+//!
+//! sync_private_directory() reconciles a local directory with the most recent version,
+//! using a SyncState recorded after each sync to tell which side changed each file.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use autonomi::chunk::DataMapChunk;
+use autonomi::files::archive_public::ArchiveAddress;
+use autonomi::files::{Metadata, PrivateArchive, PublicArchive};
+use autonomi::{AttoTokens, SecretKey};
+use blsttc::Ciphertext;
+use bytes::Bytes;
+use color_eyre::eyre::{Report, Result, eyre};
+use walkdir::WalkDir;
+
+use crate::cache::publish_journal::PublishJournal;
+use crate::cache::sync_state::{SyncState, SyncedFile};
+use crate::client::DwebClient;
+use crate::files::archive::DualArchive;
+use crate::files::directory::{Tree, canonicalise_path};
+use crate::helpers::retry::retry_with_policy;
+use crate::helpers::upload_progress::{UploadProgressReporter, UploadStage};
+use crate::history::{History, HistoryAddress, HistoryDerivation};
+use crate::storage::{
+    DwebType, directory_upload_private, publish_archive_address_to_history, report_outcome,
+};
+
+/// Mixed with the owner's secret key and History name to derive a history key
+const HISTORY_KEY_DERIVATION: &[u8] = b"dweb-private-history";
+
+/// Access to a private History: its address, the key to read it and, if owned, to update it
+#[derive(Clone)]
+pub struct PrivateHistory {
+    pub history_address: HistoryAddress,
+    /// Decrypts the archive datamap of each version
+    pub history_key: SecretKey,
+    /// The owner's secret key and History name, or None for a History shared by someone else
+    owner: Option<(SecretKey, String)>,
+}
+
+/// A version of a private History
+pub struct PrivateVersion {
+    pub version: u64,
+    /// The datamap of the archive, which gives access to every file in it
+    pub archive_datamap: DataMapChunk,
+    pub archive: DualArchive,
+}
+
+impl PrivateHistory {
+    /// The private History called name, owned by owner_secret_key
+    pub fn owned(owner_secret_key: SecretKey, name: &str) -> Result<PrivateHistory> {
+        if name.is_empty() {
+            return Err(eyre!("A private History cannot have an empty name"));
+        }
+        let history_secret_key = History::<Tree>::history_secret_key(
+            HistoryDerivation::Trove,
+            owner_secret_key.clone(),
+            name,
+        );
+        Ok(PrivateHistory {
+            history_address: HistoryAddress::new(history_secret_key.public_key()),
+            history_key: history_key(&owner_secret_key, name)?,
+            owner: Some((owner_secret_key, name.to_string())),
+        })
+    }
+
+    /// A private History shared by its owner, which can be read but not updated
+    pub fn shared(history_address: HistoryAddress, history_key: SecretKey) -> PrivateHistory {
+        PrivateHistory {
+            history_address,
+            history_key,
+            owner: None,
+        }
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.owner.is_none()
+    }
+
+    /// Get a version of the History, where 0 is the most recent
+    ///
+    /// Returns None if the History has not been created
+    pub async fn get_version(
+        &self,
+        client: &DwebClient,
+        version: u64,
+    ) -> Result<Option<PrivateVersion>> {
+        let mut history = match History::<Tree>::from_history_address(
+            client.clone(),
+            self.history_address,
+            false,
+            0,
+        )
+        .await
+        {
+            Ok(history) => history,
+            Err(e) => {
+                println!(
+                    "DEBUG no History at {} - {e}",
+                    self.history_address.to_hex()
+                );
+                return Ok(None);
+            }
+        };
+        let version = if version == 0 {
+            history.num_versions()?
+        } else {
+            version
+        };
+        let sealed_address = history.get_version_entry_value(version, false).await?;
+        let archive_datamap = unseal_datamap(client, &self.history_key, &sealed_address).await?;
+
+        let archive = match client.backend.data_get(&archive_datamap).await {
+            Ok(bytes) => DualArchive::from_bytes(bytes)?,
+            Err(e) => return Err(eyre!("Failed to get archive of version {version} - {e}")),
+        };
+        if archive.dweb_type != DwebType::PrivateArchive {
+            return Err(eyre!("Version {version} is not a private archive"));
+        }
+        Ok(Some(PrivateVersion {
+            version,
+            archive_datamap,
+            archive,
+        }))
+    }
+
+    /// Store a PrivateArchive and publish it as a new version, creating the History if necessary
+    ///
+    /// Returns the amount paid and the version published
+    pub async fn publish(
+        &self,
+        client: &DwebClient,
+        archive: &DualArchive,
+    ) -> Result<(AttoTokens, u64)> {
+        let (owner_secret_key, name) = match &self.owner {
+            Some(owner) => owner.clone(),
+            None => {
+                return Err(eyre!(
+                    "Cannot update a private History shared by another owner"
+                ));
+            }
+        };
+        let create_history =
+            History::<Tree>::from_history_address(client.clone(), self.history_address, false, 0)
+                .await
+                .is_err();
+
+        let (archive_cost, archive_datamap) = store_private_archive(client, archive).await?;
+        let (seal_cost, sealed_address) =
+            seal_datamap(client, &self.history_key, &archive_datamap).await?;
+        let (history_cost, _, version) = publish_archive_address_to_history(
+            client,
+            owner_secret_key,
+            &name,
+            &sealed_address,
+            create_history,
+        )
+        .await?;
+
+        let total_cost = archive_cost
+            .checked_add(seal_cost)
+            .and_then(|cost| cost.checked_add(history_cost))
+            .unwrap_or(archive_cost);
+        Ok((total_cost, version))
+    }
+}
+
+/// The key which encrypts the archive datamaps of the private History called name
+///
+/// This is derived by hashing so that, unlike a key made with derive_child(), sharing
+/// it reveals nothing about the owner's keys.
+pub fn history_key(owner_secret_key: &SecretKey, name: &str) -> Result<SecretKey> {
+    let mut input = owner_secret_key.to_bytes().to_vec();
+    input.extend_from_slice(HISTORY_KEY_DERIVATION);
+    input.extend_from_slice(name.as_bytes());
+    let digest = ring::digest::digest(&ring::digest::SHA256, &input);
+    let mut key_bytes = [0u8; 32];
+    key_bytes.copy_from_slice(digest.as_ref());
+    // Clear the top bits so the value is always less than the BLS12-381 scalar modulus
+    key_bytes[0] = key_bytes[0] & 0x3f;
+    SecretKey::from_bytes(key_bytes).map_err(|e| eyre!("Failed to derive history key - {e}"))
+}
+
+/// Store an archive as private data. Returns the cost and the archive datamap
+pub async fn store_private_archive(
+    client: &DwebClient,
+    archive: &DualArchive,
+) -> Result<(AttoTokens, DataMapChunk)> {
+    retry_with_policy(
+        &client.api_control.retry_policy,
+        &"Storing private archive with data_put()",
+        (client, archive.to_bytes()?),
+        async move |(client, archive_bytes)| match client
+            .backend
+            .data_put(archive_bytes, client.payment_option())
+            .await
+        {
+            Ok(result) => Ok(result),
+            Err(e) => Err(Report::new(e).wrap_err("Failed to store the archive")),
+        },
+    )
+    .await
+}
+
+/// Upload a directory and publish it as a new version of a private History
+///
+/// The History is created if it does not exist. Afterwards the directory is
+/// recorded as in sync with the new version (see sync_private_directory()).
+///
+/// Returns the amount paid and the version published
+pub async fn upload_private_directory(
+    client: &DwebClient,
+    files_root: &PathBuf,
+    private_history: &PrivateHistory,
+    progress: Option<&UploadProgressReporter>,
+) -> Result<(AttoTokens, u64)> {
+    let result = upload_and_publish(client, files_root, private_history, progress).await;
+    report_outcome(progress, &result);
+    result
+}
+
+async fn upload_and_publish(
+    client: &DwebClient,
+    files_root: &PathBuf,
+    private_history: &PrivateHistory,
+    progress: Option<&UploadProgressReporter>,
+) -> Result<(AttoTokens, u64)> {
+    if private_history.is_read_only() {
+        return Err(eyre!(
+            "Cannot upload to a private History shared by another owner"
+        ));
+    }
+    let mut journal = PublishJournal::open(files_root, false);
+    let (upload_cost, private_archive) =
+        directory_upload_private(client, files_root, &mut journal, progress).await?;
+    let archive = DualArchive {
+        dweb_type: DwebType::PrivateArchive,
+        public_archive: PublicArchive::new(),
+        private_archive,
+    };
+    if let Some(progress) = progress {
+        progress.set_stage(UploadStage::UpdatingHistory);
+    }
+    let (publish_cost, version) = private_history.publish(client, &archive).await?;
+    if let Some(progress) = progress {
+        progress.add_cost(publish_cost);
+    }
+    let _ = PublishJournal::remove(files_root);
+
+    let local_files = scan_local_files(files_root);
+    let mut state = SyncState::new(files_root, private_history.history_address.to_hex());
+    state.version = version;
+    for (path, (datamap, _)) in remote_files(&archive) {
+        if let Some((size, modified)) = local_files.get(&path) {
+            state.files.insert(
+                path,
+                SyncedFile {
+                    size: *size,
+                    modified: *modified,
+                    datamap,
+                },
+            );
+        }
+    }
+    if let Err(e) = state.save() {
+        println!("DEBUG failed to save sync state - {e}");
+    }
+
+    let total_cost = upload_cost.checked_add(publish_cost).unwrap_or(upload_cost);
+    Ok((total_cost, version))
+}
+
+/// How sync_private_directory() handles a file changed both locally and in the History
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ConflictResolution {
+    /// Leave both unchanged and report the conflict
+    Report,
+    /// Replace the file in the History with the local file (or remove it if deleted locally)
+    KeepLocal,
+    /// Replace the local file with the file in the History (or delete it if removed)
+    KeepRemote,
+}
+
+/// A change made (or for a dry run, which would be made) by sync_private_directory()
+#[derive(Clone, Debug, PartialEq)]
+pub enum SyncAction {
+    /// Upload a new or changed local file
+    Upload(String),
+    /// Remove from the History a file deleted locally
+    RemoveRemote(String),
+    /// Download a new or changed file from the History
+    Download(String),
+    /// Delete a local file which was removed from the History
+    RemoveLocal(String),
+    /// The file has changed both locally and in the History since the last sync
+    Conflict(String),
+}
+
+/// The outcome of sync_private_directory()
+pub struct SyncResult {
+    pub actions: Vec<SyncAction>,
+    pub cost: AttoTokens,
+    /// The version of the History now in sync with the directory, if any
+    pub version: Option<u64>,
+    /// True if a new version was published
+    pub published: bool,
+}
+
+/// Reconcile a local directory with the most recent version of a private History
+///
+/// Files changed only locally are uploaded and published as a new version, and files
+/// changed only in the History are downloaded. Files changed on both sides since the
+/// last sync are handled according to resolution. With no record of a previous sync,
+/// a file present on both sides is assumed unchanged if the sizes match.
+///
+/// For a read only History local changes are reported but not uploaded. If dry_run is
+/// true nothing is changed and the actions returned are those which would be made.
+pub async fn sync_private_directory(
+    client: &DwebClient,
+    files_root: &PathBuf,
+    private_history: &PrivateHistory,
+    resolution: ConflictResolution,
+    dry_run: bool,
+) -> Result<SyncResult> {
+    if !files_root.is_dir() {
+        return Err(eyre!("{files_root:?} is not a directory"));
+    }
+    let history_hex = private_history.history_address.to_hex();
+    let base = SyncState::load(files_root, &history_hex)?;
+
+    let head = private_history.get_version(client, 0).await?;
+    if head.is_none() && base.is_some() {
+        // Treating this as an empty History would delete every local file
+        return Err(eyre!(
+            "Unable to get the History at {history_hex} which was synced previously"
+        ));
+    }
+    let mut archive = match &head {
+        Some(head) => head.archive.clone(),
+        None => DualArchive {
+            dweb_type: DwebType::PrivateArchive,
+            public_archive: PublicArchive::new(),
+            private_archive: PrivateArchive::new(),
+        },
+    };
+
+    let local = scan_local_files(files_root);
+    let remote = remote_files(&archive);
+    let base_files = match &base {
+        Some(base) => base.files.clone(),
+        None => BTreeMap::new(),
+    };
+    let actions = plan_sync(&local, &remote, &base_files, resolution);
+    let version = head.as_ref().map(|head| head.version);
+    if dry_run {
+        return Ok(SyncResult {
+            actions,
+            cost: AttoTokens::zero(),
+            version,
+            published: false,
+        });
+    }
+
+    let mut applied = Vec::<SyncAction>::new();
+    let mut total_cost = AttoTokens::zero();
+    let mut conflicts = BTreeSet::<String>::new();
+    for action in actions {
+        match &action {
+            SyncAction::Upload(path) | SyncAction::RemoveRemote(path)
+                if private_history.is_read_only() =>
+            {
+                println!("Not uploading change to read only History: {path}");
+                conflicts.insert(path.clone());
+                continue;
+            }
+            SyncAction::Upload(path) => {
+                let local_path = local_path_for(files_root, path);
+                let (cost, datamap) = upload_file(client, &local_path).await?;
+                total_cost = total_cost.checked_add(cost).unwrap_or(total_cost);
+                archive.put_file(path, None, Some(datamap), file_metadata(&local_path))?;
+            }
+            SyncAction::RemoveRemote(path) => {
+                archive.remove_path(path)?;
+            }
+            SyncAction::Download(path) => {
+                let datamap = match remote.get(path) {
+                    Some((datamap, _)) => DataMapChunk::from_hex(datamap)
+                        .map_err(|e| eyre!("Invalid datamap for {path} - {e}"))?,
+                    None => return Err(eyre!("No file in the History at {path}")),
+                };
+                let content = match client.backend.data_get(&datamap).await {
+                    Ok(content) => content,
+                    Err(e) => return Err(eyre!("Failed to download {path} - {e}")),
+                };
+                let local_path = local_path_for(files_root, path);
+                if let Some(parent) = local_path.parent() {
+                    std::fs::create_dir_all(parent)?;
+                }
+                std::fs::write(&local_path, content)?;
+            }
+            SyncAction::RemoveLocal(path) => {
+                let local_path = local_path_for(files_root, path);
+                if local_path.exists() {
+                    std::fs::remove_file(&local_path)?;
+                }
+            }
+            SyncAction::Conflict(path) => {
+                conflicts.insert(path.clone());
+            }
+        }
+        applied.push(action);
+    }
+
+    let has_remote_changes = applied
+        .iter()
+        .any(|action| matches!(action, SyncAction::Upload(_) | SyncAction::RemoveRemote(_)));
+    let (version, published) = if has_remote_changes {
+        let (cost, version) = private_history.publish(client, &archive).await?;
+        total_cost = total_cost.checked_add(cost).unwrap_or(total_cost);
+        (Some(version), true)
+    } else {
+        (version, false)
+    };
+
+    // Record both sides as in sync, except for conflicts which keep their previous state
+    let local = scan_local_files(files_root);
+    let mut state = SyncState::new(files_root, history_hex);
+    state.version = version.unwrap_or(0);
+    for (path, (datamap, _)) in remote_files(&archive) {
+        let synced_file = if conflicts.contains(&path) {
+            base_files.get(&path).cloned()
+        } else {
+            local.get(&path).map(|(size, modified)| SyncedFile {
+                size: *size,
+                modified: *modified,
+                datamap,
+            })
+        };
+        if let Some(synced_file) = synced_file {
+            state.files.insert(path, synced_file);
+        }
+    }
+    state.save()?;
+
+    Ok(SyncResult {
+        actions: applied,
+        cost: total_cost,
+        version,
+        published,
+    })
+}
+
+/// Decide what sync_private_directory() must do to reconcile local and remote files
+///
+/// local maps the web path of each local file to its size and modification time,
+/// remote maps the web path of each file in the History to its datamap (hex) and size,
+/// and base holds the state of each file after the previous sync.
+pub fn plan_sync(
+    local: &BTreeMap<String, (u64, u64)>,
+    remote: &BTreeMap<String, (String, u64)>,
+    base: &BTreeMap<String, SyncedFile>,
+    resolution: ConflictResolution,
+) -> Vec<SyncAction> {
+    let paths: BTreeSet<&String> = local
+        .keys()
+        .chain(remote.keys())
+        .chain(base.keys())
+        .collect();
+
+    let mut actions = Vec::new();
+    for path in paths {
+        let local_file = local.get(path);
+        let remote_file = remote.get(path);
+        let base_file = base.get(path);
+
+        let local_changed = match (local_file, base_file) {
+            (None, None) => false,
+            (Some((size, modified)), Some(base_file)) => {
+                (*size, *modified) != (base_file.size, base_file.modified)
+            }
+            _ => true,
+        };
+        let remote_changed = match (remote_file, base_file) {
+            (None, None) => false,
+            (Some((datamap, _)), Some(base_file)) => *datamap != base_file.datamap,
+            _ => true,
+        };
+
+        let keep_local = || match local_file {
+            Some(_) => SyncAction::Upload(path.clone()),
+            None => SyncAction::RemoveRemote(path.clone()),
+        };
+        let keep_remote = || match remote_file {
+            Some(_) => SyncAction::Download(path.clone()),
+            None => SyncAction::RemoveLocal(path.clone()),
+        };
+
+        let action = match (local_changed, remote_changed) {
+            (false, false) => None,
+            (true, false) => Some(keep_local()),
+            (false, true) => Some(keep_remote()),
+            (true, true) => match (local_file, remote_file) {
+                (None, None) => None,
+                (Some((size, _)), Some((_, remote_size)))
+                    if base_file.is_none() && size == remote_size =>
+                {
+                    None
+                }
+                _ => match resolution {
+                    ConflictResolution::Report => Some(SyncAction::Conflict(path.clone())),
+                    ConflictResolution::KeepLocal => Some(keep_local()),
+                    ConflictResolution::KeepRemote => Some(keep_remote()),
+                },
+            },
+        };
+        if let Some(action) = action {
+            actions.push(action);
+        }
+    }
+    actions
+}
+
+// The web path, size and modification time of each file beneath files_root
+fn scan_local_files(files_root: &PathBuf) -> BTreeMap<String, (u64, u64)> {
+    WalkDir::new(files_root)
+        .into_iter()
+        .flatten()
+        .filter(|entry| entry.file_type().is_file())
+        .filter_map(|entry| {
+            let relative_path = entry.path().strip_prefix(files_root).ok()?;
+            let web_path = relative_path
+                .components()
+                .map(|component| component.as_os_str().to_string_lossy().to_string())
+                .collect::<Vec<String>>()
+                .join("/");
+            let metadata = file_metadata(entry.path());
+            Some((
+                canonicalise_path(&web_path),
+                (metadata.size, metadata.modified),
+            ))
+        })
+        .collect()
+}
+
+// The web path, datamap (hex) and size of each file in a private archive
+fn remote_files(archive: &DualArchive) -> BTreeMap<String, (String, u64)> {
+    archive
+        .private_archive
+        .map()
+        .iter()
+        .map(|(path, (datamap, metadata))| {
+            (
+                canonicalise_path(&path.to_string_lossy().to_string()),
+                (datamap.to_hex(), metadata.size),
+            )
+        })
+        .collect()
+}
+
+fn local_path_for(files_root: &Path, web_path: &str) -> PathBuf {
+    let mut local_path = files_root.to_path_buf();
+    for component in web_path
+        .split('/')
+        .filter(|component| !component.is_empty())
+    {
+        local_path.push(component);
+    }
+    local_path
+}
+
+fn file_metadata(path: &Path) -> Metadata {
+    let seconds = |time: std::io::Result<std::time::SystemTime>| {
+        time.ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs())
+            .unwrap_or(0)
+    };
+    match std::fs::metadata(path) {
+        Ok(metadata) => Metadata {
+            created: seconds(metadata.created()),
+            modified: seconds(metadata.modified()),
+            size: metadata.len(),
+            extra: None,
+        },
+        Err(_) => Metadata {
+            created: 0,
+            modified: 0,
+            size: 0,
+            extra: None,
+        },
+    }
+}
+
+async fn upload_file(client: &DwebClient, path: &Path) -> Result<(AttoTokens, DataMapChunk)> {
+    println!("Uploading {path:?}");
+    let content = Bytes::from(std::fs::read(path)?);
+    retry_with_policy(
+        &client.api_control.retry_policy,
+        &"Storing file with data_put()",
+        (client, content),
+        async move |(client, content)| match client
+            .backend
+            .data_put(content, client.payment_option())
+            .await
+        {
+            Ok(result) => Ok(result),
+            Err(e) => Err(Report::new(e).wrap_err("Failed to store file")),
+        },
+    )
+    .await
+}
+
+// Encrypt an archive datamap with the history key and store it as public data
+async fn seal_datamap(
+    client: &DwebClient,
+    history_key: &SecretKey,
+    datamap: &DataMapChunk,
+) -> Result<(AttoTokens, ArchiveAddress)> {
+    let sealed = Bytes::from(
+        history_key
+            .public_key()
+            .encrypt(datamap.to_hex().as_bytes())
+            .to_bytes(),
+    );
+    retry_with_policy(
+        &client.api_control.retry_policy,
+        &"Storing encrypted archive datamap with data_put_public()",
+        (client, sealed),
+        async move |(client, sealed)| match client
+            .backend
+            .data_put_public(sealed, client.payment_option())
+            .await
+        {
+            Ok(result) => Ok(result),
+            Err(e) => Err(Report::new(e).wrap_err("Failed to store the encrypted datamap")),
+        },
+    )
+    .await
+}
+
+async fn unseal_datamap(
+    client: &DwebClient,
+    history_key: &SecretKey,
+    sealed_address: &ArchiveAddress,
+) -> Result<DataMapChunk> {
+    let sealed = match client.backend.data_get_public(sealed_address).await {
+        Ok(sealed) => sealed,
+        Err(e) => return Err(eyre!("Failed to get encrypted archive datamap - {e}")),
+    };
+    let ciphertext = match Ciphertext::from_bytes(&sealed) {
+        Ok(ciphertext) => ciphertext,
+        Err(_) => {
+            return Err(eyre!(
+                "Not a private History version (not an encrypted datamap)"
+            ));
+        }
+    };
+    let datamap_hex = match history_key.decrypt(&ciphertext) {
+        Some(datamap_hex) => String::from_utf8_lossy(&datamap_hex).to_string(),
+        None => {
+            return Err(eyre!(
+                "Unable to decrypt the archive datamap - wrong history key?"
+            ));
+        }
+    };
+    DataMapChunk::from_hex(&datamap_hex).map_err(|e| eyre!("Invalid archive datamap - {e}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn synced(size: u64, modified: u64, datamap: &str) -> SyncedFile {
+        SyncedFile {
+            size,
+            modified,
+            datamap: datamap.to_string(),
+        }
+    }
+
+    #[test]
+    fn sync_follows_the_side_which_changed() {
+        let base = BTreeMap::from([
+            ("/same".to_string(), synced(1, 10, "a")),
+            ("/edited-locally".to_string(), synced(1, 10, "b")),
+            ("/edited-remotely".to_string(), synced(1, 10, "c")),
+            ("/deleted-locally".to_string(), synced(1, 10, "d")),
+            ("/deleted-remotely".to_string(), synced(1, 10, "e")),
+            ("/edited-both".to_string(), synced(1, 10, "f")),
+        ]);
+        let local = BTreeMap::from([
+            ("/same".to_string(), (1, 10)),
+            ("/edited-locally".to_string(), (2, 20)),
+            ("/edited-remotely".to_string(), (1, 10)),
+            ("/deleted-remotely".to_string(), (1, 10)),
+            ("/edited-both".to_string(), (2, 20)),
+            ("/new-locally".to_string(), (1, 10)),
+        ]);
+        let remote = BTreeMap::from([
+            ("/same".to_string(), ("a".to_string(), 1)),
+            ("/edited-locally".to_string(), ("b".to_string(), 1)),
+            ("/edited-remotely".to_string(), ("x".to_string(), 3)),
+            ("/deleted-locally".to_string(), ("d".to_string(), 1)),
+            ("/edited-both".to_string(), ("y".to_string(), 3)),
+            ("/new-remotely".to_string(), ("z".to_string(), 1)),
+        ]);
+
+        assert_eq!(
+            plan_sync(&local, &remote, &base, ConflictResolution::Report),
+            vec![
+                SyncAction::RemoveRemote("/deleted-locally".to_string()),
+                SyncAction::RemoveLocal("/deleted-remotely".to_string()),
+                SyncAction::Conflict("/edited-both".to_string()),
+                SyncAction::Upload("/edited-locally".to_string()),
+                SyncAction::Download("/edited-remotely".to_string()),
+                SyncAction::Upload("/new-locally".to_string()),
+                SyncAction::Download("/new-remotely".to_string()),
+            ]
+        );
+        assert!(
+            plan_sync(&local, &remote, &base, ConflictResolution::KeepLocal)
+                .contains(&SyncAction::Upload("/edited-both".to_string()))
+        );
+    }
+
+    #[test]
+    fn first_sync_matches_files_by_size() {
+        let local = BTreeMap::from([
+            ("/same-size".to_string(), (1, 10)),
+            ("/different".to_string(), (1, 10)),
+        ]);
+        let remote = BTreeMap::from([
+            ("/same-size".to_string(), ("a".to_string(), 1)),
+            ("/different".to_string(), ("b".to_string(), 2)),
+        ]);
+        assert_eq!(
+            plan_sync(
+                &local,
+                &remote,
+                &BTreeMap::new(),
+                ConflictResolution::KeepRemote
+            ),
+            vec![SyncAction::Download("/different".to_string())]
+        );
+    }
+
+    #[test]
+    fn history_key_depends_on_name() {
+        let owner_secret_key = SecretKey::random();
+        let key = history_key(&owner_secret_key, "photos").unwrap();
+        assert_eq!(key, history_key(&owner_secret_key, "photos").unwrap());
+        assert_ne!(key, history_key(&owner_secret_key, "notes").unwrap());
+    }
+}
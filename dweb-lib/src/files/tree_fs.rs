@@ -0,0 +1,1027 @@
+/*
+Copyright (c) 2025- Mark Hughes
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! A virtual file store over the versions of a History<Tree>
+//!
+//! TreeFs presents the most recent version of a History as a file system with
+//! methods modelled on std::fs, which return std::io::Error with the ErrorKind
+//! std::fs would use (NotFound, AlreadyExists, NotADirectory etc.).
+//!
+//! Changes are staged locally and nothing is uploaded until commit(), which
+//! stores the content of each file written, stores a new archive of the whole
+//! tree and publishes it as a new version of the History. Until then
+//! pending_changes() lists what has not been uploaded.
+//!
+//! Staged content is held in memory, so the total written between commits is
+//! limited to MAX_STAGED_BYTES. A write() which would exceed this fails with
+//! ErrorKind::FileTooLarge, and commit() must be called before writing more.
+//! Large directories are better uploaded using dweb::storage, which streams
+//! each file from disk.
+//!
+//! Paths are from the root of the tree and use '/' as separator. A leading '/'
+//! is assumed if not present.
+//!
+//! Directories are not stored in Autonomi archives, so a directory exists while
+//! it contains files. An empty directory made with create_dir_all() is kept
+//! only until the TreeFs is dropped.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::io::{Error, ErrorKind};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use autonomi::chunk::DataMapChunk;
+use autonomi::client::data::DataAddress;
+use autonomi::client::files::Metadata as FileMetadata;
+use autonomi::{AttoTokens, SecretKey};
+use bytes::Bytes;
+use color_eyre::eyre::{Report, Result, eyre};
+
+use crate::client::DwebClient;
+use crate::files::archive::{ARCHIVE_PATH_SEPARATOR, DualArchive};
+use crate::files::directory::{
+    DWEB_DIRECTORY_HISTORY_CONTENT, DWEB_DIRECTORY_HISTORY_DATAMAPCHUNK, DWEB_HISTORY_DIRECTORY,
+    DWEB_VERSION_MANIFEST_PATH, Tree, canonicalise_path, get_content,
+};
+use crate::helpers::retry::retry_with_policy;
+use crate::history::History;
+use crate::storage::{DwebType, history_marker_path, history_to_publish, store_archive_bytes};
+
+/// The maximum total size of the content written to a TreeFs between commits
+pub const MAX_STAGED_BYTES: u64 = 512 * 1024 * 1024;
+
+/// The state of a file in a TreeFs
+#[derive(Clone)]
+enum TreeFsFile {
+    /// Content is stored on the network
+    Stored {
+        datamap_chunk: Option<DataMapChunk>,
+        data_address: Option<DataAddress>,
+        metadata: FileMetadata,
+    },
+    /// Content has been written but not uploaded
+    Staged {
+        content: Bytes,
+        metadata: FileMetadata,
+    },
+}
+
+impl TreeFsFile {
+    fn metadata(&self) -> &FileMetadata {
+        match self {
+            TreeFsFile::Stored { metadata, .. } => metadata,
+            TreeFsFile::Staged { metadata, .. } => metadata,
+        }
+    }
+}
+
+/// The content of a file in a TreeFs, returned by TreeFs::content()
+pub enum TreeFsContent {
+    /// Content written since the last commit()
+    Staged(Bytes),
+    /// Content stored on the network
+    Stored {
+        client: DwebClient,
+        datamap_chunk: Option<DataMapChunk>,
+        data_address: Option<DataAddress>,
+    },
+}
+
+impl TreeFsContent {
+    /// Get the content, from the network if it is stored
+    pub async fn read(self) -> std::io::Result<Bytes> {
+        match self {
+            TreeFsContent::Staged(content) => Ok(content),
+            TreeFsContent::Stored {
+                client,
+                datamap_chunk,
+                data_address,
+            } => get_content(&client, datamap_chunk, data_address)
+                .await
+                .map_err(Error::other),
+        }
+    }
+}
+
+/// Metadata for a file or directory in a TreeFs
+#[derive(Clone, Debug)]
+pub struct TreeFsMetadata {
+    pub is_dir: bool,
+    /// Size in bytes (0 for a directory)
+    pub len: u64,
+    /// Seconds since UNIX epoch (0 for a directory)
+    pub created: u64,
+    /// Seconds since UNIX epoch (0 for a directory)
+    pub modified: u64,
+    /// True for a file written since the last commit()
+    pub is_staged: bool,
+    /// Guessed from the file extension
+    pub content_type: Option<String>,
+}
+
+/// An entry returned by TreeFs::read_dir()
+#[derive(Clone, Debug)]
+pub struct TreeFsDirEntry {
+    pub name: String,
+    /// Full path from the root of the tree
+    pub path: String,
+    pub metadata: TreeFsMetadata,
+}
+
+/// A change which has not been committed
+#[derive(Clone, Debug, PartialEq)]
+pub enum TreeFsChange {
+    /// A file was written and its content will be uploaded
+    Write(String),
+    /// A file in the last version is no longer present
+    Remove(String),
+}
+
+/// A std::fs style view of a History<Tree> with changes staged until commit()
+///
+/// Cloning is cheap because staged content is shared, so a clone can be committed
+/// while the original continues to accept changes (see merge_commit()).
+#[derive(Clone)]
+pub struct TreeFs {
+    client: DwebClient,
+    /// None for a read only TreeFs
+    owner_secret_key: Option<SecretKey>,
+    history_name: String,
+    /// True until the History has been created by commit()
+    create_history: bool,
+    /// Archive type used when committing
+    dweb_type: DwebType,
+
+    /// Files of the tree, keyed by path
+    files: BTreeMap<String, TreeFsFile>,
+    /// Paths of files in the version last loaded or committed
+    committed_paths: BTreeSet<String>,
+    /// Directories created explicitly, which may be empty
+    directories: BTreeSet<String>,
+}
+
+impl TreeFs {
+    /// Open the most recent version of the History called name, owned by owner_secret_key
+    ///
+    /// A History with no versions opens as an empty tree.
+    pub async fn open(
+        client: &DwebClient,
+        owner_secret_key: SecretKey,
+        name: &str,
+    ) -> Result<TreeFs> {
+        let mut history = History::<Tree>::from_name(
+            client.clone(),
+            owner_secret_key.clone(),
+            name.to_string(),
+            false,
+            0,
+        )
+        .await?;
+
+        let mut tree_fs = TreeFs::new_empty(client, Some(owner_secret_key), name, false);
+        if history.num_versions().unwrap_or(0) == 0 {
+            return Ok(tree_fs);
+        }
+
+        let archive_address = history.get_version_entry_value(0, false).await?;
+        let tree = Tree::from_archive_address(client, archive_address).await?;
+        tree_fs.load_archive(&tree.archive);
+        Ok(tree_fs)
+    }
+
+    /// Start an empty tree which commit() will publish as the first version of a new History
+    pub fn create(client: &DwebClient, owner_secret_key: SecretKey, name: &str) -> TreeFs {
+        TreeFs::new_empty(client, Some(owner_secret_key), name, true)
+    }
+
+    /// A read only TreeFs of an archive, such as an earlier version of a History
+    pub fn read_only(client: &DwebClient, name: &str, archive: &DualArchive) -> TreeFs {
+        let mut tree_fs = TreeFs::new_empty(client, None, name, false);
+        tree_fs.load_archive(archive);
+        tree_fs
+    }
+
+    fn new_empty(
+        client: &DwebClient,
+        owner_secret_key: Option<SecretKey>,
+        name: &str,
+        create_history: bool,
+    ) -> TreeFs {
+        TreeFs {
+            client: client.clone(),
+            owner_secret_key,
+            history_name: name.to_string(),
+            create_history,
+            dweb_type: DwebType::PrivateArchive,
+            files: BTreeMap::new(),
+            committed_paths: BTreeSet::new(),
+            directories: BTreeSet::new(),
+        }
+    }
+
+    fn load_archive(&mut self, archive: &DualArchive) {
+        self.dweb_type = archive.dweb_type;
+        self.files.clear();
+        match archive.dweb_type {
+            DwebType::PublicArchive => {
+                for (path, (data_address, metadata)) in archive.map_as_public() {
+                    self.files.insert(
+                        fs_path(&path.to_string_lossy()),
+                        TreeFsFile::Stored {
+                            datamap_chunk: None,
+                            data_address: Some(*data_address),
+                            metadata: metadata.clone(),
+                        },
+                    );
+                }
+            }
+            _ => {
+                for (path, (datamap_chunk, metadata)) in archive.map_as_private() {
+                    self.files.insert(
+                        fs_path(&path.to_string_lossy()),
+                        TreeFsFile::Stored {
+                            datamap_chunk: Some(datamap_chunk.clone()),
+                            data_address: None,
+                            metadata: metadata.clone(),
+                        },
+                    );
+                }
+            }
+        }
+        // The manifest and History marker describe the version loaded so are not
+        // carried into the next
+        self.files.remove(&fs_path(DWEB_VERSION_MANIFEST_PATH));
+        let marker_prefix = directory_prefix(&fs_path(DWEB_HISTORY_DIRECTORY));
+        self.files
+            .retain(|path, _| !path.starts_with(&marker_prefix));
+        self.committed_paths = self.files.keys().cloned().collect();
+    }
+
+    pub fn history_name(&self) -> &str {
+        &self.history_name
+    }
+
+    pub fn is_read_only(&self) -> bool {
+        self.owner_secret_key.is_none()
+    }
+
+    /// List the files and directories immediately inside the directory at path
+    pub fn read_dir(&self, path: &str) -> std::io::Result<Vec<TreeFsDirEntry>> {
+        let path = fs_path(path);
+        if !self.is_dir(&path) {
+            return Err(self.not_a_directory_error(&path));
+        }
+
+        let prefix = directory_prefix(&path);
+        let mut entries = BTreeMap::<String, TreeFsDirEntry>::new();
+        let descendants = self
+            .files
+            .keys()
+            .chain(self.directories.iter())
+            .filter(|descendant| descendant.starts_with(&prefix));
+        for descendant in descendants {
+            let name = match descendant[prefix.len()..]
+                .split(ARCHIVE_PATH_SEPARATOR)
+                .next()
+            {
+                Some(name) if !name.is_empty() => name.to_string(),
+                _ => continue,
+            };
+            if entries.contains_key(&name) {
+                continue;
+            }
+            let entry_path = prefix.clone() + &name;
+            if let Ok(metadata) = self.metadata(&entry_path) {
+                entries.insert(
+                    name.clone(),
+                    TreeFsDirEntry {
+                        name,
+                        path: entry_path,
+                        metadata,
+                    },
+                );
+            }
+        }
+        Ok(entries.into_values().collect())
+    }
+
+    /// Get metadata for the file or directory at path
+    pub fn metadata(&self, path: &str) -> std::io::Result<TreeFsMetadata> {
+        let path = fs_path(path);
+        if let Some(file) = self.files.get(&path) {
+            let metadata = file.metadata();
+            return Ok(TreeFsMetadata {
+                is_dir: false,
+                len: metadata.size,
+                created: metadata.created,
+                modified: metadata.modified,
+                is_staged: matches!(file, TreeFsFile::Staged { .. }),
+                content_type: mime_guess::from_path(&path)
+                    .first_raw()
+                    .map(|content_type| content_type.to_string()),
+            });
+        }
+        if self.is_dir(&path) {
+            return Ok(TreeFsMetadata {
+                is_dir: true,
+                len: 0,
+                created: 0,
+                modified: 0,
+                is_staged: false,
+                content_type: None,
+            });
+        }
+        Err(not_found_error(&path))
+    }
+
+    /// True if there is a file or directory at path
+    pub fn exists(&self, path: &str) -> bool {
+        self.metadata(path).is_ok()
+    }
+
+    /// Read the content of a file, from the network unless it has been written since the last commit()
+    pub async fn read(&self, path: &str) -> std::io::Result<Bytes> {
+        self.content(path)?.read().await
+    }
+
+    /// Get what is needed to read the content of a file, without reading it
+    ///
+    /// The TreeFsContent does not borrow the TreeFs, so a TreeFs shared behind a lock
+    /// can be released before fetching content from the network.
+    pub fn content(&self, path: &str) -> std::io::Result<TreeFsContent> {
+        let path = fs_path(path);
+        match self.files.get(&path) {
+            Some(TreeFsFile::Staged { content, .. }) => Ok(TreeFsContent::Staged(content.clone())),
+            Some(TreeFsFile::Stored {
+                datamap_chunk,
+                data_address,
+                ..
+            }) => Ok(TreeFsContent::Stored {
+                client: self.client.clone(),
+                datamap_chunk: datamap_chunk.clone(),
+                data_address: *data_address,
+            }),
+            None if self.is_dir(&path) => Err(Error::new(
+                ErrorKind::IsADirectory,
+                format!("'{path}' is a directory"),
+            )),
+            None => Err(not_found_error(&path)),
+        }
+    }
+
+    /// Create or replace the file at path. The directory containing it must exist
+    pub fn write(&mut self, path: &str, content: impl Into<Bytes>) -> std::io::Result<()> {
+        self.check_writable()?;
+        let path = fs_path(path);
+        if path == ARCHIVE_PATH_SEPARATOR.to_string() || self.is_dir(&path) {
+            return Err(Error::new(
+                ErrorKind::IsADirectory,
+                format!("'{path}' is a directory"),
+            ));
+        }
+        self.check_parent_directory(&path)?;
+
+        let content: Bytes = content.into();
+        let staged_bytes = self.staged_bytes_except(&path) + content.len() as u64;
+        if staged_bytes > MAX_STAGED_BYTES {
+            return Err(Error::new(
+                ErrorKind::FileTooLarge,
+                format!(
+                    "writing '{path}' would stage {staged_bytes} bytes, more than the limit of {MAX_STAGED_BYTES} - commit() before writing more"
+                ),
+            ));
+        }
+        let now = now();
+        let created = match self.files.get(&path) {
+            Some(file) => file.metadata().created,
+            None => now,
+        };
+        let metadata = FileMetadata {
+            created,
+            modified: now,
+            size: content.len() as u64,
+            extra: None,
+        };
+        self.files
+            .insert(path, TreeFsFile::Staged { content, metadata });
+        Ok(())
+    }
+
+    /// Create a directory and any missing parent directories
+    pub fn create_dir_all(&mut self, path: &str) -> std::io::Result<()> {
+        self.check_writable()?;
+        let path = fs_path(path);
+        let mut directory = String::new();
+        for component in path.split(ARCHIVE_PATH_SEPARATOR).filter(|c| !c.is_empty()) {
+            directory = directory + "/" + component;
+            if self.files.contains_key(&directory) {
+                return Err(Error::new(
+                    ErrorKind::NotADirectory,
+                    format!("'{directory}' is a file"),
+                ));
+            }
+            self.directories.insert(directory.clone());
+        }
+        Ok(())
+    }
+
+    /// Remove the file at path, or the directory at path and everything in it
+    pub fn remove(&mut self, path: &str) -> std::io::Result<()> {
+        self.check_writable()?;
+        let path = fs_path(path);
+        if self.files.remove(&path).is_some() {
+            return Ok(());
+        }
+        if path == ARCHIVE_PATH_SEPARATOR.to_string() {
+            return Err(Error::new(
+                ErrorKind::PermissionDenied,
+                "cannot remove the root directory",
+            ));
+        }
+        if !self.is_dir(&path) {
+            return Err(not_found_error(&path));
+        }
+
+        let prefix = directory_prefix(&path);
+        self.files
+            .retain(|file_path, _| !file_path.starts_with(&prefix));
+        self.directories
+            .retain(|directory| directory != &path && !directory.starts_with(&prefix));
+        Ok(())
+    }
+
+    /// Rename a file or directory
+    ///
+    /// A file replaces any file at to. A directory cannot replace anything. The directory
+    /// which is to contain to must exist.
+    pub fn rename(&mut self, from: &str, to: &str) -> std::io::Result<()> {
+        self.transfer(from, to, false)
+    }
+
+    /// Copy a file or directory, with the same rules as rename()
+    ///
+    /// Copies of stored files refer to the same content, so are not uploaded again.
+    pub fn copy(&mut self, from: &str, to: &str) -> std::io::Result<()> {
+        self.transfer(from, to, true)
+    }
+
+    // Rename, or copy if keep_source is true
+    fn transfer(&mut self, from: &str, to: &str, keep_source: bool) -> std::io::Result<()> {
+        self.check_writable()?;
+        let from = fs_path(from);
+        let to = fs_path(to);
+        if from == to {
+            return Ok(());
+        }
+        self.check_parent_directory(&to)?;
+
+        if let Some(file) = self.files.get(&from).cloned() {
+            if self.is_dir(&to) {
+                return Err(Error::new(
+                    ErrorKind::IsADirectory,
+                    format!("'{to}' is a directory"),
+                ));
+            }
+            if !keep_source {
+                self.files.remove(&from);
+            }
+            self.files.insert(to, file);
+            return Ok(());
+        }
+
+        if !self.is_dir(&from) || from == ARCHIVE_PATH_SEPARATOR.to_string() {
+            return Err(not_found_error(&from));
+        }
+        if self.exists(&to) {
+            return Err(Error::new(
+                ErrorKind::AlreadyExists,
+                format!("'{to}' already exists"),
+            ));
+        }
+        let from_prefix = directory_prefix(&from);
+        if to.starts_with(&from_prefix) {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("cannot move or copy '{from}' inside itself"),
+            ));
+        }
+
+        let to_prefix = directory_prefix(&to);
+        let new_path = |path: &String| {
+            if path == &from {
+                Some(to.clone())
+            } else {
+                path.strip_prefix(&from_prefix)
+                    .map(|rest| to_prefix.clone() + rest)
+            }
+        };
+        let files = std::mem::take(&mut self.files);
+        for (path, file) in files {
+            if let Some(new_path) = new_path(&path) {
+                self.files.insert(new_path, file.clone());
+                if !keep_source {
+                    continue;
+                }
+            }
+            self.files.insert(path, file);
+        }
+        let directories = std::mem::take(&mut self.directories);
+        for directory in directories {
+            if let Some(new_directory) = new_path(&directory) {
+                self.directories.insert(new_directory);
+                if !keep_source {
+                    continue;
+                }
+            }
+            self.directories.insert(directory);
+        }
+        Ok(())
+    }
+
+    /// The changes which will be uploaded by commit()
+    pub fn pending_changes(&self) -> Vec<TreeFsChange> {
+        let mut changes = Vec::<TreeFsChange>::new();
+        for (path, file) in &self.files {
+            match file {
+                TreeFsFile::Staged { .. } => changes.push(TreeFsChange::Write(path.clone())),
+                // A stored file may have been renamed
+                TreeFsFile::Stored { .. } if !self.committed_paths.contains(path) => {
+                    changes.push(TreeFsChange::Write(path.clone()))
+                }
+                _ => {}
+            }
+        }
+        for path in &self.committed_paths {
+            if !self.files.contains_key(path) {
+                changes.push(TreeFsChange::Remove(path.clone()));
+            }
+        }
+        changes
+    }
+
+    pub fn has_pending_changes(&self) -> bool {
+        !self.pending_changes().is_empty()
+    }
+
+    /// Upload files written since the last commit and publish the tree as a new version of the History
+    ///
+    /// Returns the total cost and the version published. If an upload fails, files
+    /// already uploaded remain stored so that only the remainder is uploaded when
+    /// commit() is called again.
+    pub async fn commit(&mut self) -> Result<(AttoTokens, u64)> {
+        let owner_secret_key = match &self.owner_secret_key {
+            Some(owner_secret_key) => owner_secret_key.clone(),
+            None => return Err(eyre!("Cannot commit changes to a read only TreeFs")),
+        };
+        if !self.create_history && !self.has_pending_changes() {
+            return Err(eyre!("There are no changes to commit"));
+        }
+
+        let mut total_cost = AttoTokens::zero();
+        let staged: Vec<(String, Bytes)> = self
+            .files
+            .iter()
+            .filter_map(|(path, file)| match file {
+                TreeFsFile::Staged { content, .. } => Some((path.clone(), content.clone())),
+                _ => None,
+            })
+            .collect();
+        for (path, content) in staged {
+            println!("DEBUG TreeFs::commit() uploading {path}");
+            let (cost, datamap_chunk, data_address) = self.upload(content).await?;
+            total_cost = total_cost.checked_add(cost).unwrap_or(total_cost);
+            if let Some(file) = self.files.get_mut(&path) {
+                *file = TreeFsFile::Stored {
+                    datamap_chunk,
+                    data_address,
+                    metadata: file.metadata().clone(),
+                };
+            }
+        }
+
+        let (create_cost, mut history) = history_to_publish(
+            &self.client,
+            owner_secret_key.clone(),
+            &self.history_name,
+            self.create_history,
+        )
+        .await?;
+        self.create_history = false;
+        total_cost = total_cost.checked_add(create_cost).unwrap_or(total_cost);
+
+        // Mark the archive as the next version of the History, as publish_or_update_files() does
+        let mut archive = self.to_archive()?;
+        let next_version = history.num_versions().unwrap_or(0) + 1;
+        let marker_path = history_marker_path(&history.history_address(), next_version);
+        let marker_metadata = FileMetadata {
+            created: 0,
+            modified: 0,
+            size: 1,
+            extra: None,
+        };
+        if self.dweb_type == DwebType::PublicArchive {
+            let data_address = DataAddress::from_hex(DWEB_DIRECTORY_HISTORY_CONTENT)?;
+            archive.put_file(&marker_path, Some(data_address), None, marker_metadata)?;
+        } else {
+            let datamap_chunk = DataMapChunk::from_hex(DWEB_DIRECTORY_HISTORY_DATAMAPCHUNK)?;
+            archive.put_file(&marker_path, None, Some(datamap_chunk), marker_metadata)?;
+        }
+
+        let (archive_cost, archive_address) =
+            store_archive_bytes(&self.client, archive.to_bytes()?).await?;
+        let history_name = &self.history_name;
+        let (update_cost, version) = history
+            .publish_new_version(owner_secret_key, &archive_address)
+            .await
+            .map_err(|e| eyre!("Failed to update History '{history_name}' - {e}"))?;
+        crate::cache::owned_histories::record_owned_history_name(history_name);
+
+        self.committed_paths = self.files.keys().cloned().collect();
+        let total_cost = total_cost
+            .checked_add(archive_cost)
+            .and_then(|cost| cost.checked_add(update_cost))
+            .unwrap_or(total_cost);
+        Ok((total_cost, version))
+    }
+
+    /// Take on the result of committing committed, a clone of this TreeFs taken as snapshot
+    ///
+    /// Files which are unchanged since the snapshot take the stored file from committed,
+    /// while changes made since remain pending. This is also used after a failed commit()
+    /// so that files already uploaded, and any History created, are not uploaded again.
+    pub fn merge_commit(&mut self, snapshot: &TreeFs, committed: &TreeFs) {
+        self.create_history = self.create_history && committed.create_history;
+        self.committed_paths = committed.committed_paths.clone();
+        for (path, file) in self.files.iter_mut() {
+            let content = match file {
+                TreeFsFile::Staged { content, .. } => content,
+                TreeFsFile::Stored { .. } => continue,
+            };
+            let unchanged = match snapshot.files.get(path) {
+                Some(TreeFsFile::Staged {
+                    content: snapshot_content,
+                    ..
+                }) => snapshot_content == content,
+                _ => false,
+            };
+            if let Some(stored @ TreeFsFile::Stored { .. }) = committed.files.get(path) {
+                if unchanged {
+                    *file = stored.clone();
+                }
+            }
+        }
+    }
+
+    // Store content as private or public data according to the archive type.
+    // Returns the cost and either the datamap (private) or address (public)
+    async fn upload(
+        &self,
+        content: Bytes,
+    ) -> Result<(AttoTokens, Option<DataMapChunk>, Option<DataAddress>)> {
+        let client = &self.client;
+        if self.dweb_type == DwebType::PublicArchive {
+            let (cost, data_address) = retry_with_policy(
+                &client.api_control.retry_policy.for_uploads(),
+                &"TreeFs storing file with data_put_public()",
+                (client, content),
+                async move |(client, content)| match client
+                    .backend
+                    .data_put_public(content, client.payment_option())
+                    .await
+                {
+                    Ok(result) => Ok(result),
+                    Err(e) => Err(Report::new(e).wrap_err("Failed to store file")),
+                },
+            )
+            .await?;
+            Ok((cost, None, Some(data_address)))
+        } else {
+            let (cost, datamap_chunk) = retry_with_policy(
+                &client.api_control.retry_policy.for_uploads(),
+                &"TreeFs storing file with data_put()",
+                (client, content),
+                async move |(client, content)| match client
+                    .backend
+                    .data_put(content, client.payment_option())
+                    .await
+                {
+                    Ok(result) => Ok(result),
+                    Err(e) => Err(Report::new(e).wrap_err("Failed to store file")),
+                },
+            )
+            .await?;
+            Ok((cost, Some(datamap_chunk), None))
+        }
+    }
+
+    // Make an archive of the files, all of which must be stored
+    fn to_archive(&self) -> Result<DualArchive> {
+        let mut archive = DualArchive::new();
+        archive.dweb_type = self.dweb_type;
+        for (path, file) in &self.files {
+            match file {
+                TreeFsFile::Stored {
+                    datamap_chunk,
+                    data_address,
+                    metadata,
+                } => archive.put_file(
+                    path,
+                    *data_address,
+                    datamap_chunk.clone(),
+                    metadata.clone(),
+                )?,
+                TreeFsFile::Staged { .. } => {
+                    return Err(eyre!("'{path}' has not been uploaded"));
+                }
+            }
+        }
+        Ok(archive)
+    }
+
+    // The total size of staged content, not counting any file at path
+    fn staged_bytes_except(&self, path: &String) -> u64 {
+        self.files
+            .iter()
+            .filter(|(file_path, _)| *file_path != path)
+            .map(|(_, file)| match file {
+                TreeFsFile::Staged { content, .. } => content.len() as u64,
+                TreeFsFile::Stored { .. } => 0,
+            })
+            .sum()
+    }
+
+    // The root, an explicitly created directory, or any directory containing a file
+    fn is_dir(&self, path: &String) -> bool {
+        if path == &ARCHIVE_PATH_SEPARATOR.to_string() || self.directories.contains(path) {
+            return true;
+        }
+        let prefix = directory_prefix(path);
+        self.files
+            .range(prefix.clone()..)
+            .next()
+            .is_some_and(|(file_path, _)| file_path.starts_with(&prefix))
+    }
+
+    fn check_writable(&self) -> std::io::Result<()> {
+        if self.is_read_only() {
+            return Err(Error::new(
+                ErrorKind::ReadOnlyFilesystem,
+                format!("'{}' is read only", self.history_name),
+            ));
+        }
+        Ok(())
+    }
+
+    fn check_parent_directory(&self, path: &String) -> std::io::Result<()> {
+        let parent = match path.rfind(ARCHIVE_PATH_SEPARATOR) {
+            Some(0) | None => ARCHIVE_PATH_SEPARATOR.to_string(),
+            Some(position) => path[..position].to_string(),
+        };
+        if self.is_dir(&parent) {
+            Ok(())
+        } else if self.files.contains_key(&parent) {
+            Err(self.not_a_directory_error(&parent))
+        } else {
+            Err(not_found_error(&parent))
+        }
+    }
+
+    fn not_a_directory_error(&self, path: &String) -> Error {
+        if self.files.contains_key(path) {
+            Error::new(
+                ErrorKind::NotADirectory,
+                format!("'{path}' is not a directory"),
+            )
+        } else {
+            not_found_error(path)
+        }
+    }
+}
+
+// Canonicalise a path to start with '/' and have no trailing '/' (except for the root)
+fn fs_path(path: &str) -> String {
+    let path = canonicalise_path(&path.to_string());
+    let path = path.trim_end_matches(ARCHIVE_PATH_SEPARATOR);
+    if path.is_empty() {
+        ARCHIVE_PATH_SEPARATOR.to_string()
+    } else {
+        path.to_string()
+    }
+}
+
+// The prefix shared by every path inside a directory
+fn directory_prefix(directory: &String) -> String {
+    if directory.ends_with(ARCHIVE_PATH_SEPARATOR) {
+        directory.clone()
+    } else {
+        directory.clone() + &ARCHIVE_PATH_SEPARATOR.to_string()
+    }
+}
+
+fn not_found_error(path: &String) -> Error {
+    Error::new(ErrorKind::NotFound, format!("'{path}' not found"))
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::backend::MemoryBackend;
+    use crate::client::DwebClientConfig;
+
+    fn test_client() -> DwebClient {
+        DwebClient::with_backend(Arc::new(MemoryBackend::new()), &DwebClientConfig::default())
+    }
+
+    fn names(entries: Vec<TreeFsDirEntry>) -> Vec<String> {
+        entries.into_iter().map(|entry| entry.name).collect()
+    }
+
+    // The History marker paths in the most recent archive, and the expected marker for version
+    async fn history_markers(
+        client: &DwebClient,
+        owner: &SecretKey,
+        name: &str,
+        version: u64,
+    ) -> (Vec<String>, String) {
+        let mut history =
+            History::<Tree>::from_name(client.clone(), owner.clone(), name.to_string(), false, 0)
+                .await
+                .unwrap();
+        let archive_address = history.get_version_entry_value(0, false).await.unwrap();
+        let tree = Tree::from_archive_address(client, archive_address)
+            .await
+            .unwrap();
+        let marker_prefix = directory_prefix(&fs_path(DWEB_HISTORY_DIRECTORY));
+        let markers = tree
+            .archive
+            .files()
+            .into_iter()
+            .map(|(path, _)| fs_path(&path.to_string_lossy()))
+            .filter(|path| path.starts_with(&marker_prefix))
+            .collect();
+        let expected = fs_path(&history_marker_path(&history.history_address(), version));
+        (markers, expected)
+    }
+
+    #[test]
+    fn paths_are_staged_renamed_and_removed() {
+        let mut fs = TreeFs::create(&test_client(), SecretKey::random(), "staging");
+        fs.create_dir_all("/docs/drafts").unwrap();
+        fs.write("docs/a.txt", "a").unwrap();
+        fs.write("/index.html", "<html></html>").unwrap();
+        assert_eq!(
+            fs.write("/missing/b.txt", "b").unwrap_err().kind(),
+            ErrorKind::NotFound
+        );
+        assert_eq!(
+            fs.write("/docs", "x").unwrap_err().kind(),
+            ErrorKind::IsADirectory
+        );
+        assert_eq!(names(fs.read_dir("/").unwrap()), vec!["docs", "index.html"]);
+        assert_eq!(
+            names(fs.read_dir("/docs").unwrap()),
+            vec!["a.txt", "drafts"]
+        );
+        assert!(fs.metadata("/docs/a.txt").unwrap().is_staged);
+
+        fs.rename("/docs", "/notes").unwrap();
+        assert!(!fs.exists("/docs"));
+        assert!(fs.exists("/notes/a.txt"));
+        assert!(fs.exists("/notes/drafts"));
+        assert_eq!(
+            fs.rename("/notes", "/notes/inner").unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+
+        fs.remove("/notes").unwrap();
+        assert!(!fs.exists("/notes/a.txt"));
+        assert_eq!(
+            fs.remove("/").unwrap_err().kind(),
+            ErrorKind::PermissionDenied
+        );
+        assert_eq!(
+            fs.pending_changes(),
+            vec![TreeFsChange::Write("/index.html".to_string())]
+        );
+    }
+
+    #[test]
+    fn staged_content_is_limited() {
+        let mut fs = TreeFs::create(&test_client(), SecretKey::random(), "limit");
+        let half = Bytes::from(vec![0u8; (MAX_STAGED_BYTES / 2) as usize]);
+        fs.write("/a", half.clone()).unwrap();
+        fs.write("/b", half.clone()).unwrap();
+        // Replacing a file doesn't count its old content
+        fs.write("/b", half.clone()).unwrap();
+        assert_eq!(
+            fs.write("/c", "c").unwrap_err().kind(),
+            ErrorKind::FileTooLarge
+        );
+    }
+
+    #[actix_web::test]
+    async fn changes_made_during_a_commit_remain_pending() {
+        let client = test_client();
+        let mut fs = TreeFs::create(&client, SecretKey::random(), "merged");
+        fs.write("/a.txt", "a").unwrap();
+        fs.write("/b.txt", "b").unwrap();
+
+        let snapshot = fs.clone();
+        let mut committed = snapshot.clone();
+        fs.write("/b.txt", "b changed").unwrap();
+        fs.write("/c.txt", "c").unwrap();
+        committed.commit().await.unwrap();
+        fs.merge_commit(&snapshot, &committed);
+
+        assert!(!fs.metadata("/a.txt").unwrap().is_staged);
+        assert!(fs.metadata("/b.txt").unwrap().is_staged);
+        assert_eq!(
+            fs.pending_changes(),
+            vec![
+                TreeFsChange::Write("/b.txt".to_string()),
+                TreeFsChange::Write("/c.txt".to_string()),
+            ]
+        );
+        let (_, version) = fs.commit().await.unwrap();
+        assert_eq!(version, 2);
+    }
+
+    #[test]
+    fn read_only_tree_cannot_be_changed() {
+        let mut fs = TreeFs::read_only(&test_client(), "shared", &DualArchive::new());
+        assert_eq!(
+            fs.write("/a.txt", "a").unwrap_err().kind(),
+            ErrorKind::ReadOnlyFilesystem
+        );
+    }
+
+    #[actix_web::test]
+    async fn commits_are_published_and_reopened() {
+        let client = test_client();
+        let owner = SecretKey::random();
+        let mut fs = TreeFs::create(&client, owner.clone(), "committed");
+        fs.create_dir_all("/docs").unwrap();
+        fs.write("/docs/a.txt", "first").unwrap();
+        fs.write("/index.html", "<html></html>").unwrap();
+        let (_, version) = fs.commit().await.unwrap();
+        assert_eq!(version, 1);
+        assert!(!fs.has_pending_changes());
+        assert!(fs.commit().await.is_err());
+        let (markers, expected) = history_markers(&client, &owner, "committed", 1).await;
+        assert_eq!(markers, vec![expected]);
+
+        let mut fs = TreeFs::open(&client, owner.clone(), "committed")
+            .await
+            .unwrap();
+        assert!(!fs.exists(DWEB_HISTORY_DIRECTORY));
+        assert!(!fs.has_pending_changes());
+        assert_eq!(fs.read("/docs/a.txt").await.unwrap(), Bytes::from("first"));
+        assert!(!fs.metadata("/docs/a.txt").unwrap().is_staged);
+
+        fs.rename("/docs/a.txt", "/docs/b.txt").unwrap();
+        fs.remove("/index.html").unwrap();
+        fs.write("/docs/c.txt", "second").unwrap();
+        assert_eq!(
+            fs.pending_changes(),
+            vec![
+                TreeFsChange::Write("/docs/b.txt".to_string()),
+                TreeFsChange::Write("/docs/c.txt".to_string()),
+                TreeFsChange::Remove("/docs/a.txt".to_string()),
+                TreeFsChange::Remove("/index.html".to_string()),
+            ]
+        );
+        let (_, version) = fs.commit().await.unwrap();
+        assert_eq!(version, 2);
+        let (markers, expected) = history_markers(&client, &owner, "committed", 2).await;
+        assert_eq!(markers, vec![expected]);
+
+        let fs = TreeFs::open(&client, owner, "committed").await.unwrap();
+        assert!(!fs.exists(DWEB_HISTORY_DIRECTORY));
+        assert_eq!(names(fs.read_dir("/docs").unwrap()), vec!["b.txt", "c.txt"]);
+        assert_eq!(fs.read("/docs/b.txt").await.unwrap(), Bytes::from("first"));
+        assert_eq!(fs.read("/docs/c.txt").await.unwrap(), Bytes::from("second"));
+        assert!(!fs.exists("/index.html"));
+    }
+}
@@ -15,7 +15,7 @@ You should have received a copy of the GNU Affero General Public License
 along with this program. If not, see <https://www.gnu.org/licenses/>.
 */
 
-use color_eyre::eyre::{eyre, Result};
+use color_eyre::eyre::{eyre, Report, Result};
 use xor_name::XorName;
 
 use autonomi::chunk::DataMapChunk;
@@ -29,6 +29,12 @@ use crate::cache::directory_with_name::HISTORY_NAMES;
 use crate::history::HistoryAddress;
 
 use super::HISTORY_ADDRESS_LEN;
+use super::retry::PermanentError;
+
+// An address which doesn't parse can't be fixed by trying again (see retry::classify_error())
+fn invalid_address(message: String) -> Report {
+    Report::new(PermanentError(message))
+}
 
 /// Parse a hex DataMapChunk
 pub fn str_to_datamap_chunk(str: &str) -> Result<DataMapChunk> {
@@ -38,14 +44,14 @@ pub fn str_to_datamap_chunk(str: &str) -> Result<DataMapChunk> {
         };
     }
 
-    Err(eyre!("Invalid DataMapChunk hex string: '{str}'"))
+    Err(invalid_address(format!("Invalid DataMapChunk hex string: '{str}'")))
 }
 
 /// Parse a hex HistoryAddress
 pub fn str_to_history_address(str: &str) -> Result<HistoryAddress> {
     match HistoryAddress::from_hex(str) {
         Ok(history_address) => Ok(history_address),
-        Err(e) => Err(eyre!("Invalid History address string '{str}':\n{e:?}")),
+        Err(e) => Err(invalid_address(format!("Invalid History address string '{str}':\n{e:?}"))),
     }
 }
 
@@ -53,7 +59,9 @@ pub fn str_to_history_address(str: &str) -> Result<HistoryAddress> {
 pub fn str_to_graph_entry_address(str: &str) -> Result<GraphEntryAddress> {
     match GraphEntryAddress::from_hex(str) {
         Ok(graphentry_address) => Ok(graphentry_address),
-        Err(e) => Err(eyre!("Invalid GraphEntry address string '{str}':\n{e:?}")),
+        Err(e) => Err(invalid_address(format!(
+            "Invalid GraphEntry address string '{str}':\n{e:?}"
+        ))),
     }
 }
 
@@ -61,7 +69,7 @@ pub fn str_to_graph_entry_address(str: &str) -> Result<GraphEntryAddress> {
 pub fn str_to_pointer_address(str: &str) -> Result<PointerAddress> {
     match PointerAddress::from_hex(str) {
         Ok(pointer_address) => Ok(pointer_address),
-        Err(e) => Err(eyre!("Invalid Pointer address string '{str}':\n{e:?}")),
+        Err(e) => Err(invalid_address(format!("Invalid Pointer address string '{str}':\n{e:?}"))),
     }
 }
 
@@ -69,7 +77,9 @@ pub fn str_to_pointer_address(str: &str) -> Result<PointerAddress> {
 pub fn str_to_scratchpad_address(str: &str) -> Result<ScratchpadAddress> {
     match ScratchpadAddress::from_hex(str) {
         Ok(scratchpad_address) => Ok(scratchpad_address),
-        Err(e) => Err(eyre!("Invalid Scratchpad address string '{str}':\n{e:?}")),
+        Err(e) => Err(invalid_address(format!(
+            "Invalid Scratchpad address string '{str}':\n{e:?}"
+        ))),
     }
 }
 
@@ -98,7 +108,7 @@ pub fn str_to_archive_address(str: &str) -> Result<DataAddress> {
 
     match ArchiveAddress::from_hex(str) {
         Ok(archive_address) => Ok(archive_address),
-        Err(e) => Err(eyre!("ArchiveAddress not valid due to {e:?}")),
+        Err(e) => Err(invalid_address(format!("ArchiveAddress not valid due to {e:?}"))),
     }
 }
 
@@ -111,7 +121,7 @@ pub fn str_to_data_address(str: &str) -> Result<DataAddress> {
 
     match DataAddress::from_hex(str) {
         Ok(data_address) => Ok(data_address),
-        Err(e) => Err(eyre!("DataAddress not valid due to {e:?}")),
+        Err(e) => Err(invalid_address(format!("DataAddress not valid due to {e:?}"))),
     }
 }
 
@@ -152,10 +162,8 @@ pub fn tuple_from_datamap_address_or_name(
         return (None, None, Some(data_address));
     }
 
-    if let Ok(lock) = &mut HISTORY_NAMES.lock() {
-        if let Some(history_address) = lock.get(datamap_address_or_name).copied() {
-            return (None, Some(history_address), None);
-        }
+    if let Some(history_address) = HISTORY_NAMES.get(datamap_address_or_name) {
+        return (None, Some(history_address), None);
     }
 
     return (None, None, None);
@@ -174,10 +182,8 @@ pub fn tuple_from_address_or_name(
         return (None, Some(address));
     }
 
-    if let Ok(lock) = &mut HISTORY_NAMES.lock() {
-        if let Some(history_address) = lock.get(address_or_name).copied() {
-            return (Some(history_address), None);
-        }
+    if let Some(history_address) = HISTORY_NAMES.get(address_or_name) {
+        return (Some(history_address), None);
     }
 
     return (None, None);
@@ -252,7 +258,7 @@ pub fn awe_str_to_pointer_address(str: &str) -> Result<PointerAddress> {
 
     match str_to_pointer_address(str) {
         Ok(pointer_address) => Ok(pointer_address),
-        Err(e) => Err(eyre!("Invalid Pointer address string '{str}':\n{e:?}")),
+        Err(e) => Err(invalid_address(format!("Invalid Pointer address string '{str}':\n{e:?}"))),
     }
 }
 
@@ -272,7 +278,7 @@ pub fn awe_str_to_data_address(str: &str) -> Result<DataAddress> {
 
     match DataAddress::from_hex(str) {
         Ok(data_address) => Ok(data_address),
-        Err(e) => Err(eyre!("DataAddress not valid due to {e:?}")),
+        Err(e) => Err(invalid_address(format!("DataAddress not valid due to {e:?}"))),
     }
 }
 
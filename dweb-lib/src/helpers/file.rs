@@ -15,10 +15,23 @@ You should have received a copy of the GNU Affero General Public License
 along with this program. If not, see <https://www.gnu.org/licenses/>.
 */
 
+use std::path::PathBuf;
 use std::time::{Duration, SystemTime};
 
+use color_eyre::eyre::{Context, Result, eyre};
+
 use autonomi::files::Metadata;
 
+/// Get the directory used by dweb to persist local state, creating it if necessary
+pub fn get_dweb_data_dir_path() -> Result<PathBuf> {
+    let mut data_dir = dirs_next::data_dir()
+        .ok_or_else(|| eyre!("Failed to obtain data dir, your OS might not be supported."))?;
+    data_dir.push("dweb");
+    std::fs::create_dir_all(data_dir.as_path())
+        .wrap_err(format!("Failed to create dweb data dir: {data_dir:?}"))?;
+    Ok(data_dir)
+}
+
 /// Get autonommi::files;:Metadata for a file. The file created and modified times
 /// are stored as the number of seconds since SystemTime::UNIX_EPOCH which is
 /// defined as "1970-01-01 00:00:00 UTC" so these values are effectively in UTC
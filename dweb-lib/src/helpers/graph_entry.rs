@@ -21,9 +21,9 @@ use color_eyre::{eyre::eyre, Result};
 
 use autonomi::client::key_derivation::{DerivationIndex, MainSecretKey};
 use autonomi::client::vault::VaultSecretKey as SecretKey;
-use autonomi::client::Client;
-use autonomi::{graph::GraphError, GraphEntry, GraphEntryAddress};
+use autonomi::{GraphEntry, GraphEntryAddress};
 
+use crate::backend::{BackendError, DwebBackend};
 use crate::history::HistoryValue;
 
 /// Print a summary for a GraphEntry. If main_owner.is_some() the output
@@ -84,13 +84,57 @@ pub fn debug_print_graph_entry(
 }
 
 /// Get a GraphEntry from the network
+///
+/// If the graph is forked at graph_entry_address the entry with the smallest derivation
+/// index is returned. Use graph_entry_get_all() to see every entry of a fork.
 pub async fn graph_entry_get(
-    client: &Client,
+    client: &dyn DwebBackend,
     graph_entry_address: &GraphEntryAddress,
     check_exists: bool,
 ) -> Result<GraphEntry> {
     // println!("DEBUG graph_entry_get() {}", graph_entry_address.to_hex());
 
+    let entries = graph_entry_get_all(client, graph_entry_address, check_exists).await?;
+    if entries.len() == 1 {
+        // debug_print_graph_entry("returning", &entries[0], None);
+        return Ok(entries[0].clone());
+    }
+
+    println!(
+        "Forked history, {} entries found at {}. Using the smallest derivation index for the next entry",
+        entries.len(),
+        graph_entry_address.to_hex()
+    );
+    let (entry_by_smallest_derivation, _) = if let Some(entry) = entries
+        .into_iter()
+        .filter_map(|e| {
+            get_derivation_from_graph_entry(&e)
+                .ok()
+                .map(|derivation| (e, derivation))
+        })
+        .min_by(|a, b| a.1.cmp(&b.1))
+    {
+        entry
+    } else {
+        let msg = format!("No valid descendants found for forked entry at {graph_entry_address:?}");
+        println!("{msg}");
+        return Err(eyre!(msg));
+    };
+    // debug_print_graph_entry(
+    //     "returning smallest by derivation ",
+    //     &entry_by_smallest_derivation,
+    //     None,
+    // );
+    Ok(entry_by_smallest_derivation)
+}
+
+/// Get every GraphEntry stored at an address. There is more than one if the graph is
+/// forked there, which happens when two devices extend the graph at the same time.
+pub async fn graph_entry_get_all(
+    client: &dyn DwebBackend,
+    graph_entry_address: &GraphEntryAddress,
+    check_exists: bool,
+) -> Result<Vec<GraphEntry>> {
     if check_exists {
         match client
             .graph_entry_check_existence(graph_entry_address)
@@ -109,40 +153,11 @@ pub async fn graph_entry_get(
     };
 
     match client.graph_entry_get(graph_entry_address).await {
-        Ok(entry) => {
-            // debug_print_graph_entry("returning", &entry, None);
-            Ok(entry)
-        }
-        Err(GraphError::Fork(entries)) => {
-            println!("Forked history, {entries:?} found. Using the smallest derivation index for the next entry");
-            let (entry_by_smallest_derivation, _) = if let Some(entry) = entries
-                .into_iter()
-                .filter_map(|e| {
-                    get_derivation_from_graph_entry(&e)
-                        .ok()
-                        .map(|derivation| (e, derivation))
-                })
-                .min_by(|a, b| a.1.cmp(&b.1))
-            {
-                // debug_print_graph_entry("returning", &entry.0, None);
-                entry
-            } else {
-                let msg = format!(
-                    "No valid descendants found for forked entry at {graph_entry_address:?}"
-                );
-                println!("{msg}");
-                return Err(eyre!(msg));
-            };
-            // debug_print_graph_entry(
-            //     "returning smallest by derivation ",
-            //     &entry_by_smallest_derivation,
-            //     None,
-            // );
-            Ok(entry_by_smallest_derivation)
-        }
+        Ok(entry) => Ok(vec![entry]),
+        Err(BackendError::GraphEntryFork(entries)) => Ok(entries),
         Err(e) => {
             let msg = format!("failed to get graph entry - {e}");
-            // println!("DEBUG graph_entry_get() {msg}");
+            // println!("DEBUG graph_entry_get_all() {msg}");
             return Err(eyre!(msg));
         }
     }
@@ -154,12 +169,36 @@ pub async fn create_graph_entry(
     parent_entry: Option<&GraphEntry>,
     new_derivation: &DerivationIndex,
     new_value: ArchiveAddress,
+) -> Result<GraphEntry> {
+    create_graph_entry_with_parents(
+        history_secret_key,
+        parent_entry,
+        &[],
+        new_derivation,
+        new_value,
+    )
+    .await
+}
+
+/// Create a new entry with the new value which also has other_parents as parents
+///
+/// The entry follows parent_entry exactly as one made by create_graph_entry(), so a reader
+/// walking back from it follows parent_entry. The owners of other_parents are added after
+/// that of parent_entry, as when a branch of a History is promoted (see History::promote_branch())
+pub async fn create_graph_entry_with_parents(
+    history_secret_key: &SecretKey,
+    parent_entry: Option<&GraphEntry>,
+    other_parents: &[GraphEntry],
+    new_derivation: &DerivationIndex,
+    new_value: ArchiveAddress,
 ) -> Result<GraphEntry> {
     println!("DEBUG create_graph_entry()");
 
     let history_secret_key = MainSecretKey::new(history_secret_key.clone());
     let parents = if let Some(parent_entry) = parent_entry {
-        vec![parent_entry.owner]
+        let mut parents = vec![parent_entry.owner];
+        parents.extend(other_parents.iter().map(|entry| entry.owner));
+        parents
     } else {
         vec![]
     };
@@ -204,18 +243,75 @@ pub async fn create_graph_entry(
     Ok(next_entry)
 }
 
+/// Create an entry which merges the branches of a forked graph
+///
+/// The entry follows canonical_head, so is owned by the key derived from the derivation
+/// index of its descendant. The parents are the owners of canonical_head followed by
+/// those of other_heads, and the value is that of canonical_head, so a reader walking
+/// back from the merge entry follows the canonical branch.
+pub fn create_merge_graph_entry(
+    history_secret_key: &SecretKey,
+    canonical_head: &GraphEntry,
+    other_heads: &[GraphEntry],
+) -> Result<GraphEntry> {
+    println!("DEBUG create_merge_graph_entry()");
+
+    let history_secret_key = MainSecretKey::new(history_secret_key.clone());
+    let derivation = get_derivation_from_graph_entry(canonical_head)?;
+    let entry_secret_key: SecretKey = history_secret_key.clone().derive_key(&derivation).into();
+
+    let mut parents = vec![canonical_head.owner];
+    parents.extend(other_heads.iter().map(|head| head.owner));
+
+    // As create_graph_entry()
+    let next_public_key = history_secret_key.public_key().derive_key(&derivation);
+    let next_derivation = DerivationIndex::random(&mut rand::thread_rng());
+    let descendants: Vec<(PublicKey, [u8; 32])> =
+        vec![(next_public_key.into(), next_derivation.into_bytes())];
+
+    Ok(GraphEntry::new(
+        &entry_secret_key,
+        parents,
+        canonical_head.content,
+        descendants,
+    ))
+}
+
+/// Create the final entry of a graph, which has no descendants
+///
+/// Like an entry made by create_graph_entry() it follows parent_entry, but without a
+/// descendant nothing can be added after it. Used to hand a History over to a successor.
+pub fn create_final_graph_entry(
+    history_secret_key: &SecretKey,
+    parent_entry: &GraphEntry,
+    new_value: HistoryValue,
+) -> Result<GraphEntry> {
+    println!("DEBUG create_final_graph_entry()");
+
+    let history_secret_key = MainSecretKey::new(history_secret_key.clone());
+    let derivation = get_derivation_from_graph_entry(parent_entry)?;
+    let entry_secret_key: SecretKey = history_secret_key.derive_key(&derivation).into();
+
+    Ok(GraphEntry::new(
+        &entry_secret_key,
+        vec![parent_entry.owner],
+        new_value,
+        vec![],
+    ))
+}
+
 /// Get a graph entry and the next derivation index (from its first descendent)
 /// In normal circumstances, there is only one entry with one descendant, yielding ONE entry and ONE derivation index
 /// In the case of a fork or a corrupt History, the smallest derivation index among all the entries descendants is chosen
 /// We chose here to deal with the errors instead of erroring out to allow users to solve Fork and Corrupt issues by
 /// updating the register
 pub async fn get_graph_entry_and_next_derivation_index(
-    client: &Client,
+    client: &dyn DwebBackend,
     graph_entry_addr: &GraphEntryAddress,
 ) -> Result<(GraphEntry, DerivationIndex)> {
     let entry = match client.graph_entry_get(graph_entry_addr).await {
         Ok(e) => e,
-        Err(GraphError::Fork(entries)) => {
+        Err(BackendError::GraphEntryFork(entries)) => {
             println!("DEBUG Forked register, multiple entries found: {entries:?}, choosing the one with the smallest derivation index for the next entry");
             let (entry_by_smallest_derivation, _) = entries
                 .into_iter()
@@ -19,6 +19,8 @@ pub mod convert;
 pub mod file;
 pub mod graph_entry;
 pub mod retry;
+pub mod scratchpad;
+pub mod upload_progress;
 pub mod web;
 
 use blsttc::SecretKey;
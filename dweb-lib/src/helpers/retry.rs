@@ -15,14 +15,268 @@ You should have received a copy of the GNU Affero General Public License
 along with this program. If not, see <https://www.gnu.org/licenses/>.
 */
 
+//! Retrying of Autonomi API calls according to a RetryPolicy
+//!
+//! Each attempt may be limited by a timeout, and failed attempts are followed by an
+//! exponentially increasing delay with random jitter so that many clients retrying
+//! at once don't hit the network in step. Errors are classified by type so that those
+//! which can never succeed (such as insufficient funds, an invalid address or a value
+//! which already exists) are returned immediately rather than retried.
+//!
+//! Dropping the future returned by retry_with_policy() cancels any attempt in progress
+//! and all further attempts.
+
+use std::fmt;
 use std::future::Future;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use color_eyre::eyre::{Report, Result, eyre};
+use rand::Rng;
+
+use crate::backend::BackendError;
+
+/// Receives a RetryEvent for each step of a retry_with_policy() call
+pub type RetryObserver = Arc<dyn Fn(&RetryEvent) + Send + Sync>;
+
+/// Controls how retry_with_policy() retries a failed operation
+#[derive(Clone)]
+pub struct RetryPolicy {
+    /// Maximum number of attempts (0 for unlimited, subject to max_elapsed)
+    pub max_tries: u32,
+    /// Delay after the first failed attempt
+    pub initial_backoff: Duration,
+    /// Upper limit on the delay between attempts
+    pub max_backoff: Duration,
+    /// Factor by which the delay increases after each failed attempt
+    pub backoff_multiplier: f64,
+    /// Fraction of each delay which is randomised (0.0 for none, 1.0 for 'full jitter')
+    pub jitter: f64,
+    /// Give up rather than start an attempt after this time has elapsed (None for no limit)
+    pub max_elapsed: Option<Duration>,
+    /// Abandon an attempt which takes longer than this (None for no limit). Not used
+    /// for uploads of whole files (see for_uploads())
+    pub attempt_timeout: Option<Duration>,
+    /// Called with each RetryEvent. When None events are printed as DEBUG output.
+    pub observer: Option<RetryObserver>,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_tries: 1,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            backoff_multiplier: 2.0,
+            jitter: 0.5,
+            max_elapsed: Some(Duration::from_secs(300)),
+            attempt_timeout: Some(Duration::from_secs(180)),
+            observer: None,
+        }
+    }
+}
 
-use color_eyre::eyre::{eyre, Report, Result};
+impl RetryPolicy {
+    /// A copy of the policy with a different maximum number of attempts (0 for unlimited)
+    ///
+    /// Used where an API call specifies its own number of tries.
+    pub fn with_max_tries(&self, max_tries: u32) -> RetryPolicy {
+        RetryPolicy {
+            max_tries,
+            ..self.clone()
+        }
+    }
+
+    /// A copy of the policy with no timeout on each attempt
+    ///
+    /// Used when uploading whole files, where an attempt may be paying for and storing
+    /// many chunks. The time this takes depends on the size of the file, so abandoning
+    /// a slow attempt would cancel it mid-payment and pay again on the next.
+    pub fn for_uploads(&self) -> RetryPolicy {
+        RetryPolicy {
+            attempt_timeout: None,
+            ..self.clone()
+        }
+    }
+
+    /// The delay to wait after the given number of failed attempts, before jitter
+    pub fn backoff(&self, failed_attempts: u32) -> Duration {
+        let exponent = failed_attempts.saturating_sub(1).min(32) as i32;
+        let delay = self.initial_backoff.as_secs_f64() * self.backoff_multiplier.powi(exponent);
+        if delay.is_finite() && delay < self.max_backoff.as_secs_f64() {
+            Duration::from_secs_f64(delay)
+        } else {
+            self.max_backoff
+        }
+    }
+
+    /// The backoff() with a random part of jitter removed
+    pub fn backoff_with_jitter(&self, failed_attempts: u32) -> Duration {
+        let backoff = self.backoff(failed_attempts);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return backoff;
+        }
+        let factor = 1.0 - jitter * rand::thread_rng().gen_range(0.0..=1.0);
+        backoff.mul_f64(factor)
+    }
+
+    fn tries_string(&self) -> String {
+        if self.max_tries == 0 {
+            "unlimited".to_string()
+        } else {
+            format!("{}", self.max_tries)
+        }
+    }
+
+    fn emit(&self, event: RetryEvent) {
+        match &self.observer {
+            Some(observer) => observer(&event),
+            None => println!("DEBUG {event}"),
+        }
+    }
+}
+
+/// Whether a failed operation might succeed if tried again
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RetryClass {
+    /// The failure may be temporary, such as a network error or timeout
+    Retryable,
+    /// Trying again will fail in the same way
+    Permanent,
+}
+
+/// Wrap an error with this to have retry_with_policy() return it without retrying
+#[derive(Debug)]
+pub struct PermanentError(pub String);
+
+impl fmt::Display for PermanentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl std::error::Error for PermanentError {}
+
+/// Classify an error as Retryable or Permanent
+///
+/// Classification is by the type of error anywhere in the chain of the Report, so
+/// callers should keep the error when adding context (e.g. with wrap_err()) rather than
+/// formatting it into a new one. Errors wrapped in PermanentError, and a BackendError
+/// which can't change by trying again (see BackendError::is_permanent()), such as a
+/// failed payment, are Permanent.
+/// Anything else, including network errors which are only available as text, is
+/// Retryable and limited by RetryPolicy::max_tries and RetryPolicy::max_elapsed.
+pub fn classify_error(error: &Report) -> RetryClass {
+    let permanent = error.chain().any(|cause| {
+        cause.downcast_ref::<PermanentError>().is_some()
+            || cause
+                .downcast_ref::<BackendError>()
+                .is_some_and(|e| e.is_permanent())
+    });
+    if permanent {
+        RetryClass::Permanent
+    } else {
+        RetryClass::Retryable
+    }
+}
 
-/// Retry a closure until Ok(<RETURN>) or tries is reached
-/// If tries is 0, only returns on success
-pub async fn retry_until_ok<F, Fut, Params: Clone, R>(
-    tries: u32,
+/// A step in a retry_with_policy() call
+#[derive(Clone, Debug)]
+pub enum RetryEvent {
+    /// An attempt is starting (attempt counts from 1)
+    Attempt {
+        label: String,
+        attempt: u32,
+        max_tries: u32,
+    },
+    /// An attempt succeeded
+    Succeeded {
+        label: String,
+        attempt: u32,
+        elapsed: Duration,
+    },
+    /// An attempt failed or timed out. If retry_after is None there will be no further attempts
+    Failed {
+        label: String,
+        attempt: u32,
+        error: String,
+        class: RetryClass,
+        timed_out: bool,
+        retry_after: Option<Duration>,
+    },
+    /// No further attempts will be made
+    GaveUp {
+        label: String,
+        attempts: u32,
+        elapsed: Duration,
+        reason: String,
+    },
+}
+
+impl fmt::Display for RetryEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RetryEvent::Attempt {
+                label,
+                attempt,
+                max_tries,
+            } => {
+                let max_tries = if *max_tries == 0 {
+                    "unlimited".to_string()
+                } else {
+                    format!("{max_tries}")
+                };
+                write!(f, "retry {label} attempt {attempt} of {max_tries}")
+            }
+            RetryEvent::Succeeded {
+                label,
+                attempt,
+                elapsed,
+            } => write!(
+                f,
+                "retry {label} succeeded on attempt {attempt} after {elapsed:.1?}"
+            ),
+            RetryEvent::Failed {
+                label,
+                attempt,
+                error,
+                class,
+                timed_out,
+                retry_after,
+            } => {
+                let timed_out = if *timed_out { " (timed out)" } else { "" };
+                match retry_after {
+                    Some(delay) => write!(
+                        f,
+                        "retry {label} attempt {attempt} failed{timed_out} ({class:?}), retrying in {delay:.1?} - {error}"
+                    ),
+                    None => write!(
+                        f,
+                        "retry {label} attempt {attempt} failed{timed_out} ({class:?}) - {error}"
+                    ),
+                }
+            }
+            RetryEvent::GaveUp {
+                label,
+                attempts,
+                elapsed,
+                reason,
+            } => write!(
+                f,
+                "retry {label} gave up after {attempts} attempts in {elapsed:.1?} - {reason}"
+            ),
+        }
+    }
+}
+
+/// Call f(params) until it returns Ok(<RETURN>) or the policy says to stop
+///
+/// Stops immediately on an error classified as Permanent (see classify_error()),
+/// otherwise when policy.max_tries or policy.max_elapsed is reached. Returns the
+/// error from the last attempt.
+pub async fn retry_with_policy<F, Fut, Params: Clone, R>(
+    policy: &RetryPolicy,
     label: &str,
     params: Params,
     f: F,
@@ -31,24 +285,199 @@ where
     F: Fn(Params) -> Fut,
     Fut: Future<Output = Result<R, Report>>,
 {
-    let tries_string = if tries == 0 {
-        "unlimited".to_string()
-    } else {
-        format!("{tries}")
-    };
-
-    let mut try_number = 1;
-    let mut last_error = eyre!("retry_until_ok() - hit a bug!");
-    println!(">>TRYING {label} {tries_string} times: ");
-    while tries == 0 || try_number <= tries {
-        match f(params.clone()).await {
-            Ok(result) => {
-                println!(">>SUCCESS!");
-                return Ok(result);
+    let start = Instant::now();
+    let mut attempt = 0;
+    loop {
+        attempt = attempt + 1;
+        policy.emit(RetryEvent::Attempt {
+            label: label.to_string(),
+            attempt,
+            max_tries: policy.max_tries,
+        });
+
+        let (error, timed_out) = match policy.attempt_timeout {
+            Some(attempt_timeout) => {
+                match tokio::time::timeout(attempt_timeout, f(params.clone())).await {
+                    Ok(Ok(result)) => return Ok(succeeded(policy, label, attempt, start, result)),
+                    Ok(Err(e)) => (e, false),
+                    Err(_) => (eyre!("attempt timed out after {attempt_timeout:?}"), true),
+                }
+            }
+            None => match f(params.clone()).await {
+                Ok(result) => return Ok(succeeded(policy, label, attempt, start, result)),
+                Err(e) => (e, false),
+            },
+        };
+
+        let class = if timed_out {
+            RetryClass::Retryable
+        } else {
+            classify_error(&error)
+        };
+
+        let delay = policy.backoff_with_jitter(attempt);
+        let stop_reason = if class == RetryClass::Permanent {
+            Some("error is not retryable".to_string())
+        } else if policy.max_tries != 0 && attempt >= policy.max_tries {
+            Some(format!("tried {} times", policy.tries_string()))
+        } else if policy
+            .max_elapsed
+            .is_some_and(|max_elapsed| start.elapsed() + delay > max_elapsed)
+        {
+            Some("max elapsed time reached".to_string())
+        } else {
+            None
+        };
+
+        policy.emit(RetryEvent::Failed {
+            label: label.to_string(),
+            attempt,
+            error: format!("{error:#}"),
+            class,
+            timed_out,
+            retry_after: if stop_reason.is_none() {
+                Some(delay)
+            } else {
+                None
+            },
+        });
+
+        if let Some(reason) = stop_reason {
+            policy.emit(RetryEvent::GaveUp {
+                label: label.to_string(),
+                attempts: attempt,
+                elapsed: start.elapsed(),
+                reason,
+            });
+            return Err(error);
+        }
+
+        tokio::time::sleep(delay).await;
+    }
+}
+
+fn succeeded<R>(policy: &RetryPolicy, label: &str, attempt: u32, start: Instant, result: R) -> R {
+    policy.emit(RetryEvent::Succeeded {
+        label: label.to_string(),
+        attempt,
+        elapsed: start.elapsed(),
+    });
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn quick_policy(max_tries: u32, events: Arc<Mutex<Vec<RetryEvent>>>) -> RetryPolicy {
+        RetryPolicy {
+            max_tries,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(4),
+            attempt_timeout: Some(Duration::from_millis(50)),
+            observer: Some(Arc::new(move |event: &RetryEvent| {
+                events.lock().unwrap().push(event.clone())
+            })),
+            ..Default::default()
+        }
+    }
+
+    #[actix_web::test]
+    async fn retries_transient_errors_and_stops_on_permanent() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let policy = quick_policy(5, events.clone());
+        let calls = AtomicU32::new(0);
+        let result = retry_with_policy(&policy, "transient", (), |_| {
+            let call = calls.fetch_add(1, Ordering::Relaxed);
+            async move {
+                match call {
+                    0 => Err(eyre!("connection reset")),
+                    1 => {
+                        tokio::time::sleep(Duration::from_millis(200)).await;
+                        Ok(0)
+                    }
+                    n => Ok(n),
+                }
             }
-            Err(e) => last_error = eyre!(format!(">>{tries_string} complete with error - {e}")),
+        })
+        .await;
+        assert_eq!(result.unwrap(), 2);
+        assert!(matches!(
+            events.lock().unwrap().last(),
+            Some(RetryEvent::Succeeded { attempt: 3, .. })
+        ));
+
+        let calls = AtomicU32::new(0);
+        let result: Result<()> = retry_with_policy(&policy, "permanent", (), |_| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            async {
+                Err(Report::new(BackendError::AlreadyExists("00".to_string()))
+                    .wrap_err("Failed to store graph entry"))
+            }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+        let calls = AtomicU32::new(0);
+        let result: Result<()> = retry_with_policy(&policy, "insufficient funds", (), |_| {
+            calls.fetch_add(1, Ordering::Relaxed);
+            async {
+                Err(Report::new(BackendError::Payment(
+                    "Wallet error: Insufficient funds".to_string(),
+                ))
+                .wrap_err("Failed to store file"))
+            }
+        })
+        .await;
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn errors_are_classified_by_type() {
+        let transient = [
+            eyre!("Not enough node quotes"),
+            eyre!("Invalid response from peer"),
+            Report::new(BackendError::Failed("Not enough peers".to_string())),
+            Report::new(BackendError::NotFound("pointer".to_string())),
+        ];
+        for error in transient {
+            assert_eq!(classify_error(&error), RetryClass::Retryable, "{error}");
+        }
+
+        let permanent = [
+            Report::new(PermanentError("invalid address".to_string())),
+            Report::new(BackendError::AlreadyExists("00".to_string())).wrap_err("put failed"),
+            Report::new(BackendError::GraphEntryFork(Vec::new())),
+            Report::new(BackendError::Payment("Insufficient funds".to_string())),
+            Report::new(BackendError::InvalidInput("invalid datamap".to_string())),
+            crate::helpers::convert::str_to_pointer_address("not hex").unwrap_err(),
+        ];
+        for error in permanent {
+            assert_eq!(classify_error(&error), RetryClass::Permanent, "{error}");
         }
-        try_number = try_number + 1;
     }
-    Err(last_error)
+
+    #[test]
+    fn uploads_have_no_attempt_timeout() {
+        let policy = RetryPolicy::default();
+        assert!(policy.attempt_timeout.is_some());
+        assert_eq!(policy.for_uploads().attempt_timeout, None);
+    }
+
+    #[test]
+    fn backoff_grows_to_the_limit() {
+        let policy = RetryPolicy {
+            initial_backoff: Duration::from_secs(1),
+            max_backoff: Duration::from_secs(5),
+            jitter: 0.0,
+            ..Default::default()
+        };
+        let delays: Vec<u64> = (1..=5).map(|n| policy.backoff(n).as_secs()).collect();
+        assert_eq!(delays, vec![1, 2, 4, 5, 5]);
+        assert_eq!(policy.backoff_with_jitter(2), Duration::from_secs(2));
+    }
 }
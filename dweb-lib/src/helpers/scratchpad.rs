@@ -0,0 +1,172 @@
+/*
+Copyright (c) 2025 Mark Hughes
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Reading Scratchpads which may be forked
+//!
+//! A Scratchpad forks when two versions are written with the same counter, for example
+//! by two devices updating it at the same time. The network returns every version with
+//! the highest counter and the reader must choose between them. ForkResolution selects
+//! how that choice is made, and a fork is repaired by writing the chosen (or merged)
+//! content with a counter higher than any of the versions.
+
+use autonomi::{Scratchpad, ScratchpadAddress};
+use color_eyre::{Result, eyre::eyre};
+use serde::{Deserialize, Serialize};
+
+use crate::backend::{BackendError, BackendResult, DwebBackend};
+
+/// How to choose between the versions of a forked Scratchpad
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ForkResolution {
+    /// The version with the highest counter, or the first returned when several share it
+    #[default]
+    Counter,
+    /// The version with the highest counter, ordered by signature when several share it.
+    /// Every reader makes the same choice from the same versions.
+    Signature,
+    /// As Signature, but also return every version so the app can choose
+    All,
+    /// Choose nothing and return every version so the app can merge them and repair the fork
+    Merge,
+}
+
+impl ForkResolution {
+    pub fn parse(value: &str) -> Result<ForkResolution> {
+        match value.trim().to_lowercase().as_str() {
+            "counter" => Ok(ForkResolution::Counter),
+            "signature" => Ok(ForkResolution::Signature),
+            "all" => Ok(ForkResolution::All),
+            "merge" => Ok(ForkResolution::Merge),
+            other => Err(eyre!(
+                "unknown fork resolution '{other}', expected counter, signature, all or merge"
+            )),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ForkResolution::Counter => "counter",
+            ForkResolution::Signature => "signature",
+            ForkResolution::All => "all",
+            ForkResolution::Merge => "merge",
+        }
+    }
+
+    /// True if every version of a forked Scratchpad should be returned to the app
+    pub fn returns_versions(&self) -> bool {
+        matches!(self, ForkResolution::All | ForkResolution::Merge)
+    }
+}
+
+/// A Scratchpad as read from the network
+pub struct ResolvedScratchpad {
+    /// The version chosen, or None if the Scratchpad is forked and the app is to merge it
+    pub scratchpad: Option<Scratchpad>,
+    /// Every version found if the Scratchpad is forked, otherwise empty
+    pub forks: Vec<Scratchpad>,
+}
+
+impl ResolvedScratchpad {
+    pub fn is_forked(&self) -> bool {
+        !self.forks.is_empty()
+    }
+}
+
+/// Get a Scratchpad, choosing between its versions according to resolution if it is forked
+pub async fn scratchpad_get_resolved(
+    backend: &dyn DwebBackend,
+    address: &ScratchpadAddress,
+    resolution: ForkResolution,
+) -> BackendResult<ResolvedScratchpad> {
+    match backend.scratchpad_get(address).await {
+        Ok(scratchpad) => Ok(ResolvedScratchpad {
+            scratchpad: Some(scratchpad),
+            forks: Vec::new(),
+        }),
+        Err(BackendError::ScratchpadFork(forks)) => {
+            println!(
+                "DEBUG forked Scratchpad at {} has {} versions, resolving by {}",
+                address.to_hex(),
+                forks.len(),
+                resolution.as_str()
+            );
+            Ok(ResolvedScratchpad {
+                scratchpad: resolve_fork(&forks, resolution),
+                forks,
+            })
+        }
+        Err(e) => Err(e),
+    }
+}
+
+/// Choose one of the versions of a forked Scratchpad, or None for ForkResolution::Merge
+pub fn resolve_fork(forks: &[Scratchpad], resolution: ForkResolution) -> Option<Scratchpad> {
+    let highest = highest_counter(forks);
+    let mut candidates = forks.iter().filter(|s| s.counter() == highest);
+    match resolution {
+        ForkResolution::Counter => candidates.next().cloned(),
+        ForkResolution::Signature | ForkResolution::All => {
+            candidates.max_by_key(|s| s.signature().to_bytes()).cloned()
+        }
+        ForkResolution::Merge => None,
+    }
+}
+
+/// The highest counter of the versions of a Scratchpad. A repair must be written with a higher counter.
+pub fn highest_counter(forks: &[Scratchpad]) -> u64 {
+    forks.iter().map(|s| s.counter()).max().unwrap_or(0)
+}
+
+/// The signature of a Scratchpad as hex, used to tell apart the versions of a fork
+pub fn signature_hex(scratchpad: &Scratchpad) -> String {
+    hex::encode(scratchpad.signature().to_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use autonomi::{Bytes, SecretKey};
+
+    #[test]
+    fn fork_resolution_is_deterministic() {
+        let owner = SecretKey::random();
+        let forks: Vec<Scratchpad> = ["a", "b", "c"]
+            .iter()
+            .map(|data| Scratchpad::new(&owner, 0, &Bytes::from(*data), 3))
+            .chain([Scratchpad::new(&owner, 0, &Bytes::from("old"), 2)])
+            .collect();
+
+        let mut reversed = forks.clone();
+        reversed.reverse();
+        let chosen = resolve_fork(&forks, ForkResolution::Signature).unwrap();
+        assert_eq!(chosen.counter(), 3);
+        assert_eq!(
+            resolve_fork(&reversed, ForkResolution::Signature).unwrap(),
+            chosen
+        );
+
+        assert_eq!(
+            resolve_fork(&forks, ForkResolution::Counter).unwrap(),
+            forks[0]
+        );
+        assert!(resolve_fork(&forks, ForkResolution::Merge).is_none());
+        assert_eq!(highest_counter(&forks), 3);
+        assert_eq!(ForkResolution::parse("All").unwrap(), ForkResolution::All);
+        assert!(ForkResolution::parse("newest").is_err());
+    }
+}
@@ -0,0 +1,225 @@
+/*
+Copyright (c) 2025 Mark Hughes
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Progress reporting for uploads and publishing
+//!
+//! An UploadProgressReporter is passed to the upload and publish functions in
+//! dweb::storage which update it as each file is stored. Any number of observers
+//! can subscribe() to receive the latest UploadProgress whenever it changes, for
+//! example to render a progress bar in the CLI or send Server-Sent Events to a
+//! web app. Observers which fall behind only see the most recent state.
+
+use std::sync::{Arc, Mutex};
+
+use autonomi::AttoTokens;
+use serde::{Deserialize, Serialize};
+use tokio::sync::watch;
+use utoipa::ToSchema;
+
+use crate::helpers::retry::{RetryEvent, RetryPolicy};
+
+/// Self-encryption splits files into chunks of at most this size
+const MAX_CHUNK_SIZE: u64 = 4 * 1024 * 1024;
+
+/// The stage reached by an upload or publish operation
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize, ToSchema)]
+pub enum UploadStage {
+    #[default]
+    Starting,
+    UploadingFiles,
+    StoringArchive,
+    UpdatingHistory,
+    Complete,
+    Failed,
+}
+
+/// A snapshot of the progress of an upload or publish operation
+#[derive(Clone, Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct UploadProgress {
+    pub stage: UploadStage,
+    pub files_total: u64,
+    pub files_done: u64,
+    /// Estimated from file sizes, as the actual number is only known once a file is encrypted
+    pub chunks_total: u64,
+    pub chunks_done: u64,
+    pub bytes_total: u64,
+    pub bytes_uploaded: u64,
+    /// Amount paid so far
+    pub cost_in_attos: String,
+    /// Number of failed attempts which have been retried
+    pub retries: u32,
+    /// The file currently being uploaded
+    pub current_file: Option<String>,
+    /// Details of the failure when stage is Failed
+    pub error: Option<String>,
+}
+
+impl UploadProgress {
+    pub fn files_remaining(&self) -> u64 {
+        self.files_total.saturating_sub(self.files_done)
+    }
+
+    pub fn chunks_remaining(&self) -> u64 {
+        self.chunks_total.saturating_sub(self.chunks_done)
+    }
+
+    /// True once the operation has completed or failed
+    pub fn is_finished(&self) -> bool {
+        self.stage == UploadStage::Complete || self.stage == UploadStage::Failed
+    }
+}
+
+/// Updates an UploadProgress and notifies its subscribers
+#[derive(Clone)]
+pub struct UploadProgressReporter {
+    sender: Arc<watch::Sender<UploadProgress>>,
+    cost: Arc<Mutex<AttoTokens>>,
+}
+
+impl Default for UploadProgressReporter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl UploadProgressReporter {
+    pub fn new() -> UploadProgressReporter {
+        let (sender, _) = watch::channel(UploadProgress {
+            cost_in_attos: "0".to_string(),
+            ..Default::default()
+        });
+        UploadProgressReporter {
+            sender: Arc::new(sender),
+            cost: Arc::new(Mutex::new(AttoTokens::zero())),
+        }
+    }
+
+    /// Receive the latest UploadProgress each time it changes
+    pub fn subscribe(&self) -> watch::Receiver<UploadProgress> {
+        self.sender.subscribe()
+    }
+
+    /// A copy of the current UploadProgress
+    pub fn current(&self) -> UploadProgress {
+        self.sender.borrow().clone()
+    }
+
+    /// Modify the progress and notify subscribers
+    pub fn update(&self, modify: impl FnOnce(&mut UploadProgress)) {
+        self.sender.send_modify(modify);
+    }
+
+    pub fn set_stage(&self, stage: UploadStage) {
+        self.update(|progress| progress.stage = stage);
+    }
+
+    /// Add files which are to be uploaded to the totals
+    pub fn add_files_to_upload(&self, files: u64, bytes: u64, chunks: u64) {
+        self.update(|progress| {
+            progress.files_total = progress.files_total + files;
+            progress.bytes_total = progress.bytes_total + bytes;
+            progress.chunks_total = progress.chunks_total + chunks;
+        });
+    }
+
+    pub fn file_started(&self, file: &str) {
+        self.update(|progress| progress.current_file = Some(file.to_string()));
+    }
+
+    pub fn file_done(&self, bytes: u64, chunks: u64) {
+        self.update(|progress| {
+            progress.files_done = progress.files_done + 1;
+            progress.bytes_uploaded = progress.bytes_uploaded + bytes;
+            progress.chunks_done = progress.chunks_done + chunks;
+            progress.current_file = None;
+        });
+    }
+
+    /// Mark every file as done, for uploads which store all the files in one operation
+    pub fn all_files_done(&self) {
+        self.update(|progress| {
+            progress.files_done = progress.files_total;
+            progress.bytes_uploaded = progress.bytes_total;
+            progress.chunks_done = progress.chunks_total;
+            progress.current_file = None;
+        });
+    }
+
+    pub fn add_cost(&self, cost: AttoTokens) {
+        let total = match self.cost.lock() {
+            Ok(mut total) => {
+                *total = total.checked_add(cost).unwrap_or(*total);
+                *total
+            }
+            Err(_) => return,
+        };
+        self.update(|progress| progress.cost_in_attos = format!("{}", total.as_atto()));
+    }
+
+    pub fn complete(&self) {
+        self.update(|progress| {
+            progress.stage = UploadStage::Complete;
+            progress.current_file = None;
+        });
+    }
+
+    pub fn failed(&self, error: &str) {
+        self.update(|progress| {
+            progress.stage = UploadStage::Failed;
+            progress.error = Some(error.to_string());
+        });
+    }
+
+    /// A copy of policy which also counts retries in this progress
+    pub fn retry_policy(&self, policy: &RetryPolicy) -> RetryPolicy {
+        let reporter = self.clone();
+        let observer = policy.observer.clone();
+        RetryPolicy {
+            observer: Some(Arc::new(move |event: &RetryEvent| {
+                if let RetryEvent::Failed {
+                    retry_after: Some(_),
+                    ..
+                } = event
+                {
+                    reporter.update(|progress| progress.retries = progress.retries + 1);
+                }
+                match &observer {
+                    Some(observer) => observer(event),
+                    None => println!("DEBUG {event}"),
+                }
+            })),
+            ..policy.clone()
+        }
+    }
+}
+
+/// Estimate the number of chunks needed to store a file of the given size
+///
+/// Self-encryption produces at least three chunks, except for files too small
+/// to encrypt which are stored in one. Public files also store their datamap.
+pub fn estimated_chunks(size: u64, is_public: bool) -> u64 {
+    let content_chunks = if size < 3 {
+        1
+    } else {
+        size.div_ceil(MAX_CHUNK_SIZE).max(3)
+    };
+    if is_public {
+        content_chunks + 1
+    } else {
+        content_chunks
+    }
+}
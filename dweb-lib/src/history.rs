@@ -27,33 +27,53 @@ along with this program. If not, see <https://www.gnu.org/licenses/>.
 /// clashes and manages the naming of your History objects for you without the
 /// need to manage different owner secrets.
 use std::marker::PhantomData;
+use std::time::Duration;
 
 use autonomi::files::archive_public::ArchiveAddress;
 use blsttc::PublicKey;
-use color_eyre::eyre::{Result, eyre};
+use color_eyre::eyre::{Report, Result, eyre};
 use serde::{Deserialize, Serialize};
 
 use autonomi::SecretKey;
 use autonomi::client::data::DataAddress;
-use autonomi::client::data_types::graph::{GraphContent, GraphError};
+use autonomi::client::data_types::graph::GraphContent;
 use autonomi::client::key_derivation::{DerivationIndex, MainPubkey, MainSecretKey};
+use autonomi::register::RegisterAddress;
 use autonomi::{
     AttoTokens, Bytes, GraphEntry, GraphEntryAddress, Pointer, PointerAddress,
     pointer::PointerTarget,
 };
 
+use crate::backend::BackendError;
+use crate::cache::history_head::{get_history_head_hint, update_history_head_hint};
 use crate::client::DwebClient;
 use crate::data::autonomi_get_file_public;
 use crate::helpers::graph_entry::{
-    create_graph_entry, get_derivation_from_graph_entry, graph_entry_get,
+    create_final_graph_entry, create_graph_entry, create_graph_entry_with_parents,
+    create_merge_graph_entry, get_derivation_from_graph_entry, graph_entry_get,
+    graph_entry_get_all,
 };
-use crate::helpers::retry::retry_until_ok;
+use crate::helpers::retry::{PermanentError, retry_with_policy};
 use crate::token::{Spends, show_spend_return_value};
 
-use crate::types::{HISTORY_POINTER_DERIVATION_INDEX, derive_named_object_secret};
+use crate::types::{
+    HISTORY_BRANCH_DERIVATION_PREFIX, HISTORY_POINTER_DERIVATION_INDEX, derive_named_object_secret,
+};
 
 const LARGEST_VERSION: u64 = u64::MAX;
 
+// The number of the most recent entries checked for a fork before publishing a new version
+const FORK_CHECK_DEPTH: u64 = 3;
+
+// The longest wait for a check that the pointer target has no child (see History::from_name())
+const CHILD_CHECK_TIMEOUT_SECS: u64 = 10;
+
+// The most Histories followed in a chain of successions (see History::hand_over())
+const MAX_SUCCESSIONS: usize = 16;
+
+// Prefixed to the bytes signed by a HistorySuccession
+const HISTORY_SUCCESSION_SIGNATURE_PREFIX: &str = "History succession:";
+
 /// The value of a history: a 32 bytes array (same as [`GraphContent`])
 pub type HistoryValue = GraphContent;
 
@@ -113,6 +133,51 @@ impl HistoryAddress {
         let owner = PublicKey::from_hex(hex)?;
         Ok(Self { owner })
     }
+
+    /// The address of a named branch of this History (see History::create_branch())
+    ///
+    /// This is the address of the first entry of the branch, which is in the graph of this
+    /// History and derived from its key using the branch name, so anyone with this address
+    /// can find its branches. The branch can be read using History::from_history_address().
+    pub fn branch(&self, branch: &str) -> HistoryAddress {
+        let branch_public_key: PublicKey = MainPubkey::new(self.owner)
+            .derive_key(&branch_derivation_index(branch))
+            .into();
+        HistoryAddress::new(branch_public_key)
+    }
+}
+
+/// Check a branch name is suitable for use in a dweb URL
+///
+/// A branch name begins with a lowercase letter followed by lowercase letters, numbers
+/// and single hyphens, up to 32 characters.
+pub fn validate_branch_name(branch: &str) -> Result<()> {
+    if branch.is_empty() || branch.len() > 32 {
+        return Err(eyre!("branch name must be between 1 and 32 characters"));
+    }
+    if !branch.as_bytes()[0].is_ascii_lowercase() {
+        return Err(eyre!("branch name must begin with a lowercase letter"));
+    }
+    if !branch
+        .chars()
+        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+    {
+        return Err(eyre!(
+            "branch name can only contain lowercase letters, numbers and hyphens"
+        ));
+    }
+    if branch.contains("--") || branch.ends_with('-') {
+        return Err(eyre!(
+            "branch name cannot contain '--' or end with a hyphen"
+        ));
+    }
+    Ok(())
+}
+
+// The derivation index of a branch of a History
+fn branch_derivation_index(branch: &str) -> DerivationIndex {
+    let name = format!("{HISTORY_BRANCH_DERIVATION_PREFIX}{branch}");
+    DerivationIndex::from_bytes(xor_name::XorName::from_content(name.as_bytes()).0)
 }
 
 impl std::fmt::Display for HistoryAddress {
@@ -121,6 +186,179 @@ impl std::fmt::Display for HistoryAddress {
     }
 }
 
+/// How the keys and entries of a History are laid out on the network
+///
+/// A dweb History derives its keys using the Trove::trove_type() and stores the type
+/// in entry 0, so version 1 is entry 1. An Autonomi Register uses the same graph and
+/// pointer structure but derives its keys differently and stores a value in every
+/// entry, so version 1 is entry 0. Storing this in the History allows a Register to be
+/// read as a History.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HistoryDerivation {
+    /// A dweb History<T> created with History::create_online()
+    Trove,
+    /// An Autonomi Register (read-only, see History::create_from_register() to migrate it)
+    Register,
+}
+
+/// The state of a History pointer returned by History::check_and_heal_pointer()
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, utoipa::ToSchema)]
+pub enum PointerHealth {
+    /// The pointer counter and target match the head of the graph
+    InSync,
+    /// The pointer was one entry behind and has been healed using pointer_update() (free)
+    HealedByUpdate,
+    /// The pointer has been healed using pointer_put() (which has a cost)
+    HealedByPut,
+    /// The pointer needs healing with pointer_put() but this was not allowed
+    NeedsPut,
+}
+
+impl PointerHealth {
+    /// Decide how check_and_heal_pointer() will treat a pointer
+    ///
+    /// The pointer is InSync only if its counter is the index of the head entry and it
+    /// targets that entry. One entry behind can be healed using pointer_update(), and
+    /// anything else needs pointer_put() which is only used if allow_put is true.
+    pub fn decide(
+        pointer_counter: u64,
+        targets_head: bool,
+        head_index: u64,
+        allow_put: bool,
+    ) -> PointerHealth {
+        if pointer_counter == head_index && targets_head {
+            PointerHealth::InSync
+        } else if pointer_counter + 1 == head_index {
+            PointerHealth::HealedByUpdate
+        } else if allow_put {
+            PointerHealth::HealedByPut
+        } else {
+            PointerHealth::NeedsPut
+        }
+    }
+}
+
+/// The record published when a History is handed over to a successor
+///
+/// The final entry of the predecessor holds the address of this record. It is also
+/// signed with the key of the predecessor so it can be verified without the entry.
+/// See History::hand_over()
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct HistorySuccession {
+    pub predecessor: HistoryAddress,
+    pub successor: HistoryAddress,
+    /// Hex encoded signature of the predecessor and successor addresses
+    pub signature: String,
+}
+
+impl HistorySuccession {
+    /// Create a record handing the History owned by history_secret_key over to successor
+    pub fn new(history_secret_key: &SecretKey, successor: HistoryAddress) -> HistorySuccession {
+        let predecessor = HistoryAddress::new(history_secret_key.public_key());
+        let signature = history_secret_key.sign(Self::bytes_to_sign(&predecessor, &successor));
+        HistorySuccession {
+            predecessor,
+            successor,
+            signature: hex::encode(signature.to_bytes()),
+        }
+    }
+
+    fn bytes_to_sign(predecessor: &HistoryAddress, successor: &HistoryAddress) -> Vec<u8> {
+        let mut bytes = HISTORY_SUCCESSION_SIGNATURE_PREFIX.as_bytes().to_vec();
+        bytes.extend_from_slice(&predecessor.owner.to_bytes());
+        bytes.extend_from_slice(&successor.owner.to_bytes());
+        bytes
+    }
+
+    /// Check the record hands over the History at predecessor and was signed by its owner
+    pub fn verify(&self, predecessor: &HistoryAddress) -> Result<()> {
+        if self.predecessor != *predecessor {
+            return Err(eyre!(
+                "succession record is for History {} not {predecessor}",
+                self.predecessor
+            ));
+        }
+        let signature_bytes: [u8; blsttc::SIG_SIZE] = match hex::decode(&self.signature) {
+            Ok(bytes) => match bytes.try_into() {
+                Ok(bytes) => bytes,
+                Err(_) => return Err(eyre!("succession record signature has the wrong length")),
+            },
+            Err(e) => return Err(eyre!("succession record signature is not valid hex - {e}")),
+        };
+        let signature = blsttc::Signature::from_bytes(signature_bytes)
+            .map_err(|e| eyre!("succession record signature is invalid - {e}"))?;
+        if predecessor.owner.verify(
+            &signature,
+            Self::bytes_to_sign(&self.predecessor, &self.successor),
+        ) {
+            Ok(())
+        } else {
+            Err(eyre!(
+                "succession record for History {predecessor} has a bad signature"
+            ))
+        }
+    }
+}
+
+/// A point where a History has more than one entry at the same address
+///
+/// This happens when two devices publish to a History at the same time. Each of the
+/// entries begins a branch, but only one branch is followed when reading the History
+/// so the versions on the others are hidden. A fork is repaired by adding a merge entry
+/// after the head of the branch chosen to continue (see History::repair_fork()).
+#[derive(Clone, Debug)]
+pub struct HistoryFork {
+    /// The index of the forked entries
+    pub entry_index: u64,
+    /// The address shared by the forked entries
+    pub address: GraphEntryAddress,
+    /// Ordered by the derivation index of their first entry's descendant
+    pub branches: Vec<HistoryBranch>,
+}
+
+impl HistoryFork {
+    /// True if a merge entry has chosen which branch continues
+    pub fn is_repaired(&self) -> bool {
+        self.branches
+            .iter()
+            .any(|branch| branch.merge_entry.is_some())
+    }
+
+    /// The index of the branch followed when reading the History. This is the
+    /// branch chosen by the merge entry, or the first if the fork is not repaired.
+    pub fn canonical_branch(&self) -> usize {
+        canonical_branch_index(&self.branches)
+    }
+}
+
+/// One branch of a forked History
+#[derive(Clone, Debug)]
+pub struct HistoryBranch {
+    /// The entries from the fork to the head of the branch, beginning with one of the forked entries
+    pub entries: Vec<GraphEntry>,
+    /// The entry following the head if this branch was chosen when the fork was repaired
+    pub merge_entry: Option<GraphEntry>,
+}
+
+impl HistoryBranch {
+    /// The most recent entry of the branch
+    pub fn head(&self) -> &GraphEntry {
+        &self.entries[self.entries.len() - 1]
+    }
+
+    /// The number of entries in the branch (not counting any merge entry)
+    pub fn num_entries(&self) -> u64 {
+        self.entries.len() as u64
+    }
+}
+
+fn canonical_branch_index(branches: &[HistoryBranch]) -> usize {
+    branches
+        .iter()
+        .position(|branch| branch.merge_entry.is_some())
+        .unwrap_or(0)
+}
+
 /// gives access to every version of the struct that has ever been stored
 /// on Autonomi.
 ///
@@ -153,15 +391,19 @@ pub trait Trove<T> {
 /// can be used to create and access versions of a file, a collection of
 /// files such as a directory, or all the files and settings that make up a website,
 /// and so on.
-/// TODO provide a way to initialise a history from an Autonomi Register pointer and graph
-//  TODO this will require storing the HISTORY_DERIVATION_INDEX in the History, and
-//  TODO initialising it according to whether or not it is initialised from a Register .
-//  TODO Also changes to History::history_main_secret_key() for compatibility with Registers
+///
+/// A History can also be read from the address of an Autonomi Register (see
+/// History::from_register_address()) in which case it is read-only.
 pub struct History<T: Trove<T> + Clone> {
     client: DwebClient,
 
     history_address: HistoryAddress,
     name: String,
+    // The branch if this is not the main line (see History::create_branch())
+    branch: Option<String>,
+
+    // How keys are derived and entries laid out (dweb History or Autonomi Register)
+    derivation: HistoryDerivation,
 
     // We can't trust a pointer from the network to be up-to-date, so these are updated from the graph
     // Once set, head_graphentry will always be the real head and num_entries always correct
@@ -177,6 +419,13 @@ pub struct History<T: Trove<T> + Clone> {
     // Cached data for the selected version
     pub cached_version: Option<TroveVersion<T>>,
 
+    // Set if this History has been handed over (see History::hand_over())
+    successor: Option<HistoryAddress>,
+    // When read by following a succession, the History this one succeeds and the number
+    // of versions in it and its own predecessors, which come before the versions here
+    predecessor: Option<Box<History<T>>>,
+    version_offset: u64,
+
     // Pretend we hold a Trove so we can restrict some values to type T in the implementation
     phantom: std::marker::PhantomData<T>,
 }
@@ -196,13 +445,10 @@ impl<T: Trove<T> + Clone> History<T> {
                 "History::create_online() failed - cannot use an empty name"
             ));
         }
-        let spends = Spends::new(&client, Some(&"History create online cost: ")).await?;
-
         let history_secret_key =
             Self::history_main_secret_key(owner_secret_key).derive_child(name.as_bytes());
-        let history_address = HistoryAddress::new(history_secret_key.public_key());
 
-        // Put the first entry in the graph
+        // The first entry in the graph
         let root_entry = create_graph_entry(
             &history_secret_key,
             None,
@@ -210,12 +456,25 @@ impl<T: Trove<T> + Clone> History<T> {
             Self::trove_type(),
         )
         .await?;
+        Self::create_online_with_root(client, name, None, history_secret_key, root_entry).await
+    }
+
+    // Put the root entry of a History (or branch) owned by history_secret_key and create its pointer
+    async fn create_online_with_root(
+        client: DwebClient,
+        name: String,
+        branch: Option<String>,
+        history_secret_key: SecretKey,
+        root_entry: GraphEntry,
+    ) -> Result<(AttoTokens, Self)> {
+        let spends = Spends::new(&client, Some(&"History create online cost: ")).await?;
+        let history_address = HistoryAddress::new(history_secret_key.public_key());
         println!(
             "DEBUG graph_entry_put() at {}",
             root_entry.address().to_hex()
         );
         let (graph_cost, root_entry_address) = match client
-            .client
+            .backend
             .graph_entry_put(root_entry.clone(), client.payment_option())
             .await
         {
@@ -231,7 +490,7 @@ impl<T: Trove<T> + Clone> History<T> {
         let pointer_target = PointerTarget::GraphEntryAddress(root_entry_address);
 
         match client
-            .client
+            .backend
             .pointer_create(
                 &pointer_secret_key,
                 pointer_target,
@@ -247,13 +506,18 @@ impl<T: Trove<T> + Clone> History<T> {
                 let history = History {
                     client: client.clone(),
                     name,
+                    branch,
                     history_address,
                     num_entries: 1,
                     head_graphentry: Some(root_entry), // The first and only entry so far
                     pointer_counter: 0,
                     pointer_target: None,
+                    derivation: HistoryDerivation::Trove,
                     default_version: None,
                     cached_version: None,
+                    successor: None,
+                    predecessor: None,
+                    version_offset: 0,
                     phantom: PhantomData,
                 };
 
@@ -281,14 +545,17 @@ impl<T: Trove<T> + Clone> History<T> {
     }
 
     /// Add a trove to the History and return the index of the most recent entry (1 = first trove entry, 0 = root entry)
+    ///
+    /// The new entry also has other_parents as parents (see History::promote_branch())
     async fn update_online(
         &mut self,
         owner_secret_key: SecretKey,
         trove_address: ArchiveAddress,
+        other_parents: &[GraphEntry],
     ) -> Result<(AttoTokens, u64)> {
         println!("DEBUG History::update_online()");
-        let history_secret_key =
-            Self::history_main_secret_key(owner_secret_key).derive_child(self.name.as_bytes());
+        self.check_writable("History::update_online()")?;
+        let history_secret_key = self.secret_key_from_owner(owner_secret_key);
 
         let history_address = HistoryAddress::new(history_secret_key.public_key());
         println!("Updating History at {}", history_address.to_hex());
@@ -301,11 +568,12 @@ impl<T: Trove<T> + Clone> History<T> {
                 let head_address = self.head_graphentry.clone().unwrap().address();
 
                 // Note: if head_address isn't the head, create_next_graph_entry_online() will retry until it reaches it
-                let (graph_cost, next_address) = match self
+                let (graph_cost, next_entry, entries_passed) = match self
                     .create_next_graph_entry_online(
                         history_secret_key.clone(),
                         head_address,
                         &trove_address,
+                        other_parents,
                     )
                     .await
                 {
@@ -319,6 +587,12 @@ impl<T: Trove<T> + Clone> History<T> {
                     }
                 };
 
+                // The new entry follows the head and any entries published elsewhere since it was read
+                let next_address = next_entry.address();
+                self.num_entries = self.num_entries + entries_passed + 1;
+                self.head_graphentry = Some(next_entry);
+                self.record_head_hint();
+
                 println!("Pointer retrieved with counter {}", pointer.counter());
                 let pointer_secret_key = Self::history_pointer_secret_key(history_secret_key);
 
@@ -326,10 +600,10 @@ impl<T: Trove<T> + Clone> History<T> {
                     "Updating pointer with new GraphEntry at: {}",
                     next_address.to_hex()
                 );
-                let client = self.client.client.clone();
+                let client = self.client.backend.clone();
                 let pointer_target = PointerTarget::GraphEntryAddress(next_address);
-                match retry_until_ok(
-                    self.client.api_control.api_tries,
+                match retry_with_policy(
+                    &self.client.api_control.retry_policy,
                     &"pointer_update()",
                     (client, pointer_secret_key, pointer_target),
                     async move |(client, pointer_secret_key, pointer_target)| match client
@@ -341,7 +615,7 @@ impl<T: Trove<T> + Clone> History<T> {
                             Ok(result)
                         }
                         Err(e) => {
-                            return Err(eyre!("Failed to add a trove to history: {e:?}"));
+                            return Err(Report::new(e).wrap_err("Failed to add a trove to history"));
                         }
                     },
                 )
@@ -378,8 +652,10 @@ impl<T: Trove<T> + Clone> History<T> {
     ///   if !ignore_pointer, and minimum_entry_index is non-zero will ignore the pointer only if
     ///   its counter is less than minimum_entry_index.
     ///
-    ///   if !ignore_pointer, and minimum_entry_index is 0, uses the pointer (even though it may be
-    ///   out of date). This should be fast.
+    ///   if !ignore_pointer, and minimum_entry_index is 0, uses the pointer unless the entry it
+    ///   targets has a child, in which case the pointer is stale and the graph is followed from
+    ///   there. Checking for the child is one extra request, limited to CHILD_CHECK_TIMEOUT_SECS
+    ///   because a check for an entry which doesn't exist can be slow.
     pub async fn from_name(
         client: DwebClient,
         owner_secret_key: SecretKey,
@@ -387,15 +663,38 @@ impl<T: Trove<T> + Clone> History<T> {
         ignore_pointer: bool,
         minimum_entry_index: u64,
     ) -> Result<Self> {
-        println!("DEBUG History::from_name({name})");
+        Self::from_name_and_branch(
+            client,
+            owner_secret_key,
+            name,
+            None,
+            ignore_pointer,
+            minimum_entry_index,
+        )
+        .await
+    }
+
+    /// Load a named branch of a History, or the main line if branch is None, for read or
+    /// write access. See History::from_name()
+    pub async fn from_name_and_branch(
+        client: DwebClient,
+        owner_secret_key: SecretKey,
+        name: String,
+        branch: Option<String>,
+        ignore_pointer: bool,
+        minimum_entry_index: u64,
+    ) -> Result<Self> {
+        println!("DEBUG History::from_name_and_branch({name}, {branch:?})");
         if name.is_empty() {
             return Err(eyre!(
                 "History::from_name() failed - cannot use an empty name"
             ));
         }
 
-        let history_secret_key =
-            Self::history_main_secret_key(owner_secret_key).derive_child(name.as_bytes());
+        let history_secret_key = Self::branch_secret_key(
+            Self::history_main_secret_key(owner_secret_key).derive_child(name.as_bytes()),
+            &branch,
+        );
         let history_address = HistoryAddress::new(history_secret_key.public_key());
 
         // Check it exists to avoid accidental creation (and payment)
@@ -429,36 +728,54 @@ impl<T: Trove<T> + Clone> History<T> {
         let mut history = History {
             client: client.clone(),
             name,
+            branch,
             history_address,
             num_entries: 0,
             head_graphentry: None,
             pointer_counter: pointer.counter(),
             pointer_target: Some(pointer_target),
+            derivation: HistoryDerivation::Trove,
             default_version: None,
             cached_version: None,
+            successor: None,
+            predecessor: None,
+            version_offset: 0,
             phantom: PhantomData,
         };
         // Necessary because the pointer may not be up-to-date
-        if ignore_pointer || (!ignore_pointer && minimum_entry_index > pointer.counter()) {
+        // Start from the most recent entry known locally if the pointer is behind it
+        let (head_target, head_counter) =
+            history.best_known_head(&pointer_target, pointer.counter());
+        if ignore_pointer || (!ignore_pointer && minimum_entry_index > head_counter) {
             // Ignore the pointer because that was specified,
             // or the pointer counter() is behind minimum_entry_index
             history
-                .update_from_graph_internal(&pointer_target, pointer.counter())
+                .update_from_graph_internal(&head_target, head_counter)
                 .await?;
         } else {
             // Use the pointer even though it may not be up-to-date
             match history
-                .get_graph_entry_from_network(&pointer_target, false)
+                .get_graph_entry_from_network(&head_target, false)
                 .await
             {
                 Ok(pointer_head) => {
-                    if pointer.counter() == 0 {
-                        println!("WARNING: initialising History with pointer.counter() of 0");
+                    // The pointer may be stale, so only trust it if there is no newer entry
+                    if history.has_child_entry(&pointer_head).await {
+                        println!("DEBUG pointer is behind the head, following the graph");
+                        history
+                            .update_from_graph_internal(&head_target, head_counter)
+                            .await?;
+                    } else {
+                        if head_counter == 0 {
+                            println!("WARNING: initialising History with pointer.counter() of 0");
+                        }
+                        history.num_entries = head_counter + 1;
+                        history.head_graphentry = Some(pointer_head);
+                        history.record_head_hint();
+                        history.pointer_counter = pointer.counter() + 1;
+                        history.pointer_target = Some(pointer_target);
+                        history.successor = history.get_successor().await?;
                     }
-                    history.num_entries = pointer.counter() + 1;
-                    history.head_graphentry = Some(pointer_head);
-                    history.pointer_counter = pointer.counter() + 1;
-                    history.pointer_target = Some(pointer_target);
                 }
                 Err(e) => return Err(eyre!("Failed to get pointer target entry - {e}")),
             };
@@ -467,43 +784,34 @@ impl<T: Trove<T> + Clone> History<T> {
         Ok(history)
     }
 
-    /// Load a read-only History from the network
-    ///
-    /// Note the following behaviour which allows you to control whether to trust the
-    /// History pointer is up-to-date and pointing at the head of the graph. You can
-    /// choose to scan the graph to ensure the History is up-to-date even if the pointer
-    /// is not, but when scanning the graph this function will take several seconds
-    /// in order to detect the end of the graph.
-    ///
-    ///   If  ignore_pointer, updates the head from the graph (which will take several seconds)
+    /// Load a read-only History from the address of an Autonomi Register
     ///
-    ///   if !ignore_pointer, and minimum_entry_index is non-zero will ignore the pointer only if
-    ///   its counter is less than minimum_entry_index.
+    /// Each value in the Register is treated as a version, so the first value is version 1.
+    /// A History read from a Register cannot be updated, but can be migrated to a dweb
+    /// History using History::create_from_register().
     ///
-    ///   if !ignore_pointer, and minimum_entry_index is 0, uses the pointer (even though it may be
-    ///   out of date). This should be fast.
-    pub async fn from_history_address(
+    /// See from_history_address() for the behaviour of ignore_pointer and minimum_entry_index
+    pub async fn from_register_address(
         client: DwebClient,
-        history_address: HistoryAddress,
+        register_address: RegisterAddress,
         ignore_pointer: bool,
         minimum_entry_index: u64,
     ) -> Result<History<T>> {
         println!(
-            "DEBUG History::from_history_address({})",
-            history_address.to_hex()
+            "DEBUG History::from_register_address({})",
+            register_address.to_hex()
         );
-
-        // Check it exists to avoid accidental creation (and payment)
-        let pointer_address = pointer_address_from_history_address(history_address.clone())?;
+        let history_address = HistoryAddress::new(register_address.owner());
+        let pointer_address = register_pointer_address_from_history_address(history_address);
         let pointer = match get_and_verify_pointer(&client, &pointer_address).await {
             Ok(pointer) => pointer,
             Err(e) => {
                 let msg = format!(
-                    "failed to get pointer network address {} - {e}",
+                    "failed to get Register head pointer at network address {} - {e}",
                     pointer_address.to_hex()
                 );
-                println!("DEBUG History::from_history_address() {msg}");
-                return Err(e.into());
+                println!("DEBUG History::from_register_address() {msg}");
+                return Err(eyre!(msg));
             }
         };
 
@@ -511,153 +819,581 @@ impl<T: Trove<T> + Clone> History<T> {
             PointerTarget::GraphEntryAddress(pointer_target) => *pointer_target,
             other => {
                 return Err(eyre!(
-                    "History::from_history_address() pointer target is not a GraphEntry - this is probably a bug. Target: {other:?}"
+                    "History::from_register_address() pointer target is not a GraphEntry. Target: {other:?}"
                 ));
             }
         };
-        println!("DEBUG INSPECT pointer_counter: {}", pointer.counter());
-        println!("DEBUG INSPECT pointer_target : {}", pointer_target.to_hex());
 
         let mut history = History::<T> {
             client,
             name: String::from(""),
+            branch: None,
             history_address,
             num_entries: 0,
             head_graphentry: None,
             pointer_counter: pointer.counter(),
             pointer_target: Some(pointer_target),
+            derivation: HistoryDerivation::Register,
             default_version: None,
             cached_version: None,
+            successor: None,
+            predecessor: None,
+            version_offset: 0,
             phantom: PhantomData,
         };
-        // Necessary because the pointer may not be up-to-date
-        if ignore_pointer || (!ignore_pointer && minimum_entry_index > pointer.counter()) {
-            // Ignore the pointer because that was specified,
-            // or the pointer counter() is behind minimum_entry_index
+
+        let (head_target, head_counter) =
+            history.best_known_head(&pointer_target, pointer.counter());
+        if ignore_pointer || minimum_entry_index > head_counter {
             history
-                .update_from_graph_internal(&pointer_target, pointer.counter())
+                .update_from_graph_internal(&head_target, head_counter)
                 .await?;
         } else {
-            // Use the pointer even though it may not be up-to-date
             match history
-                .get_graph_entry_from_network(&pointer_target, false)
+                .get_graph_entry_from_network(&head_target, false)
                 .await
             {
                 Ok(pointer_head) => {
-                    if pointer.counter() == 0 {
-                        println!("WARNING: initialising History with pointer.counter() of 0");
+                    // The pointer may be stale, so only trust it if there is no newer entry
+                    if history.has_child_entry(&pointer_head).await {
+                        println!("DEBUG pointer is behind the head, following the graph");
+                        history
+                            .update_from_graph_internal(&head_target, head_counter)
+                            .await?;
+                    } else {
+                        history.num_entries = head_counter + 1;
+                        history.head_graphentry = Some(pointer_head);
+                        history.record_head_hint();
                     }
-                    history.num_entries = pointer.counter() + 1;
-                    history.head_graphentry = Some(pointer_head);
-                    history.pointer_counter = pointer.counter() + 1;
-                    history.pointer_target = Some(pointer_target);
                 }
                 Err(e) => return Err(eyre!("Failed to get pointer target entry - {e}")),
             };
         }
 
-        println!(
-            "DEBUG from_history_address() returning History with num_entries: {}",
-            history.num_entries
-        );
         history.update_default_version();
         Ok(history)
     }
 
-    /// Safely get the actual head even if the pointer_target is not the heaad.
+    /// Create a new dweb History by replaying every value of an Autonomi Register
     ///
-    /// If the pointer_target is out of date this function scans the graph starting at pointer_target
-    /// until it reaches the end and can correctly set the head GraphEntry and num_entries.
-    ///
-    /// This will only happen if the target is out of date, so a maximum of once after the history
-    /// is created. When it does, it will take few seconds because it has to wait for the request
-    /// for a graph entry to not be found on the network.
+    /// The values must be addresses of troves of type T (e.g. an archive for a Tree).
+    /// The new History is owned by owner_secret_key and named name, so can then be updated
+    /// using 'dweb publish-update'. The Register is not modified.
     ///
-    /// Returns the head GraphEntry
-    pub async fn update_from_graph(&mut self) -> Result<GraphEntry> {
-        println!("DEBUG History::update_from_graph()");
-        if self.head_graphentry.is_some() {
-            return Ok(self.head_graphentry.clone().unwrap());
+    /// Returns the total cost and the new History
+    pub async fn create_from_register(
+        client: DwebClient,
+        register_address: RegisterAddress,
+        name: String,
+        owner_secret_key: SecretKey,
+    ) -> Result<(AttoTokens, Self)> {
+        println!(
+            "DEBUG History::create_from_register({}, {name})",
+            register_address.to_hex()
+        );
+        let register_history =
+            Self::from_register_address(client.clone(), register_address, true, 0).await?;
+        let values = register_history.get_all_values().await?;
+        if values.is_empty() {
+            return Err(eyre!(
+                "History::create_from_register() - the Register has no values"
+            ));
         }
 
-        // Check the pointer exists to avoid accidental creation (and payment)
-        let pointer_address = pointer_address_from_history_address(self.history_address.clone())?;
-        let pointer = match get_and_verify_pointer(&self.client, &pointer_address).await {
-            Ok(pointer) => pointer,
-            Err(e) => {
-                let msg = format!(
-                    "failed to get pointer network address {} - {e}",
-                    pointer_address.to_hex()
-                );
-                println!("DEBUG History::from_history_address() {msg}");
-                return Err(e.into());
-            }
-        };
+        let (mut total_cost, mut history) =
+            Self::create_online(client.clone(), name, owner_secret_key.clone()).await?;
 
-        let pointer_target = match pointer.target() {
-            PointerTarget::GraphEntryAddress(pointer_target) => *pointer_target,
-            other => {
-                return Err(eyre!(
-                    "History::from_history_address() pointer target is not a GraphEntry - this is probably a bug. Target: {other:?}"
-                ));
-            }
-        };
+        for (index, value) in values.iter().enumerate() {
+            let trove_address = ArchiveAddress::from_hex(&hex::encode(value))?;
+            println!(
+                "Replaying Register value {} of {}: {}",
+                index + 1,
+                values.len(),
+                trove_address.to_hex()
+            );
+            let (cost, _version) = history
+                .publish_new_version(owner_secret_key.clone(), &trove_address)
+                .await?;
+            total_cost = total_cost.checked_add(cost).unwrap_or(total_cost);
+        }
 
-        self.update_from_graph_internal(&pointer_target, pointer.counter())
-            .await
+        Ok((total_cost, history))
     }
 
-    // See update_from_graph() for description
-    async fn update_from_graph_internal(
-        &mut self,
-        pointer_target: &GraphEntryAddress,
-        pointer_counter: u64,
-    ) -> Result<GraphEntry> {
-        println!("DEBUG History::update_from_graph_internal()");
-        if self.head_graphentry.is_some() {
-            return Ok(self.head_graphentry.clone().unwrap());
+    /// Create an Autonomi Register holding the value of every version of this History
+    ///
+    /// The Register key is derived from owner_secret_key and register_name in the same
+    /// way as Client::register_key_from_name() so that Register based apps can update it.
+    ///
+    /// The Register is written using the same graph and head pointer layout as
+    /// Client::register_create() and Client::register_update(), but through the
+    /// DwebBackend so that a single paid pointer put is needed and it can be tested offline.
+    ///
+    /// Returns the total cost and the address of the new Register
+    pub async fn export_to_register(
+        &self,
+        owner_secret_key: SecretKey,
+        register_name: &str,
+    ) -> Result<(AttoTokens, RegisterAddress)> {
+        println!("DEBUG History::export_to_register({register_name})");
+        let values = self.get_all_values().await?;
+        if values.is_empty() {
+            return Err(eyre!(
+                "History::export_to_register() - the History has no versions"
+            ));
         }
 
-        // Get the Pointer target entry and move forwards - because the pointer may not be up to date
-        let pointer_target_entry = match self
-            .get_graph_entry_from_network(pointer_target, false)
+        let register_secret_key =
+            Self::history_secret_key(HistoryDerivation::Register, owner_secret_key, register_name);
+        let register_address = RegisterAddress::new(register_secret_key.public_key());
+        let pointer_secret_key = register_head_pointer_secret_key(&register_secret_key)?;
+
+        // Each value is an entry, the first at the root owned by the Register key
+        let mut total_cost = AttoTokens::zero();
+        let mut parent_entry: Option<GraphEntry> = None;
+        for value in values.iter() {
+            let derivation = match &parent_entry {
+                Some(parent_entry) => get_derivation_from_graph_entry(parent_entry)?,
+                None => DerivationIndex::random(&mut rand::thread_rng()),
+            };
+            let entry = create_graph_entry(
+                &register_secret_key,
+                parent_entry.as_ref(),
+                &derivation,
+                ArchiveAddress::from_hex(&hex::encode(value))?,
+            )
+            .await?;
+            match self
+                .client
+                .backend
+                .graph_entry_put(entry.clone(), self.client.payment_option())
+                .await
+            {
+                Ok((cost, _)) => total_cost = total_cost.checked_add(cost).unwrap_or(total_cost),
+                Err(e) => return Err(eyre!("failed to put Register entry - {e}")),
+            }
+            parent_entry = Some(entry);
+        }
+
+        // The head pointer counter is the index of the last entry, as after each Register update
+        let head_address = match &parent_entry {
+            Some(head) => head.address(),
+            None => return Err(eyre!("History::export_to_register() - no entries created")),
+        };
+        let pointer = Self::create_pointer_for_update(
+            values.len() as u64 - 1,
+            &head_address,
+            &pointer_secret_key,
+        );
+        match self
+            .client
+            .backend
+            .pointer_put(pointer, self.client.payment_option())
             .await
         {
-            Ok(head) => head,
-            Err(e) => return Err(eyre!("Failed to get pointer target entry - {e}")),
-        };
+            Ok((cost, _)) => total_cost = total_cost.checked_add(cost).unwrap_or(total_cost),
+            Err(e) => return Err(eyre!("failed to put Register head pointer - {e}")),
+        }
+        println!("Created Register at {}", register_address.to_hex());
 
-        let mut iter_entry = pointer_target_entry;
-        let mut iter_index = pointer_counter;
+        Ok((total_cost, register_address))
+    }
 
-        let mut final_index = iter_index;
-        let mut final_entry;
+    /// Get the value of every version in order, starting with version 1
+    async fn get_all_values(&self) -> Result<Vec<HistoryValue>> {
+        let mut values = Vec::<HistoryValue>::new();
+        let mut entry = match self.get_root_entry().await? {
+            Some(entry) => entry,
+            None => return Ok(values),
+        };
+
+        let mut index = 0;
         loop {
-            println!("DEBUG get child of GraphEntry index {iter_index} - child...");
-            final_entry = iter_entry.clone();
-            iter_entry = if let Some(entry) = self.get_child_entry_of(&iter_entry, true).await {
-                iter_index = iter_index + 1;
-                final_index = final_index + 1;
-                entry
-            } else {
+            if index >= self.first_version_index() {
+                values.push(entry.content);
+            }
+            index = index + 1;
+            if index >= self.num_entries {
                 break;
             }
+            entry = match self.get_child_entry_of(&entry, false).await {
+                Some(entry) => entry,
+                None => {
+                    return Err(eyre!(
+                        "History::get_all_values() ran out of entries at index {index}"
+                    ));
+                }
+            };
         }
 
-        self.head_graphentry = Some(final_entry.clone());
-        self.num_entries = final_index + 1;
-
-        Ok(final_entry)
+        Ok(values)
     }
 
-    fn create_pointer_for_update(
-        counter: u64,
-        graph_entry_address: &GraphEntryAddress,
-        history_secret_key: &SecretKey,
-    ) -> Pointer {
-        println!("DEBUG create_pointer_for_update()");
-        let pointer_target = PointerTarget::GraphEntryAddress(*graph_entry_address);
-        Pointer::new(history_secret_key, counter, pointer_target)
+    /// Load a read-only History from the network
+    ///
+    /// Note the following behaviour which allows you to control whether to trust the
+    /// History pointer is up-to-date and pointing at the head of the graph. You can
+    /// choose to scan the graph to ensure the History is up-to-date even if the pointer
+    /// is not, but when scanning the graph this function will take several seconds
+    /// in order to detect the end of the graph.
+    ///
+    ///   If  ignore_pointer, updates the head from the graph (which will take several seconds)
+    ///
+    ///   if !ignore_pointer, and minimum_entry_index is non-zero will ignore the pointer only if
+    ///   its counter is less than minimum_entry_index.
+    ///
+    ///   if !ignore_pointer, and minimum_entry_index is 0, uses the pointer unless the entry it
+    ///   targets has a child, in which case the pointer is stale and the graph is followed from
+    ///   there. Checking for the child is one extra request, limited to CHILD_CHECK_TIMEOUT_SECS
+    ///   because a check for an entry which doesn't exist can be slow.
+    ///
+    /// If the History has been handed over (see History::hand_over()) the succession is followed
+    /// and the History returned is the last in the chain. Its versions are numbered after those
+    /// of the Histories it succeeds, and the earlier versions remain available from it.
+    pub async fn from_history_address(
+        client: DwebClient,
+        history_address: HistoryAddress,
+        ignore_pointer: bool,
+        minimum_entry_index: u64,
+    ) -> Result<History<T>> {
+        let mut history = Self::from_history_address_without_succession(
+            client.clone(),
+            history_address,
+            ignore_pointer,
+            minimum_entry_index,
+        )
+        .await?;
+
+        let mut predecessors: Vec<History<T>> = Vec::new();
+        let mut num_versions = 0;
+        while let Some(successor) = history.successor {
+            if predecessors.len() >= MAX_SUCCESSIONS {
+                return Err(eyre!(
+                    "History at {history_address} has more than {MAX_SUCCESSIONS} successions"
+                ));
+            }
+            if successor == history_address
+                || predecessors
+                    .iter()
+                    .any(|predecessor| predecessor.history_address == successor)
+            {
+                return Err(eyre!(
+                    "History at {history_address} has a succession which loops back to {successor}"
+                ));
+            }
+            println!("DEBUG History::from_history_address() following succession to {successor}");
+            num_versions += history.num_versions().unwrap_or(0);
+            let first_version_index = match predecessors.first() {
+                Some(first) => first.first_version_index(),
+                None => history.first_version_index(),
+            };
+            predecessors.push(history);
+            history = Self::from_history_address_without_succession(
+                client.clone(),
+                successor,
+                ignore_pointer,
+                successor_minimum_entry_index(
+                    minimum_entry_index,
+                    first_version_index,
+                    num_versions,
+                ),
+            )
+            .await?;
+        }
+
+        // Most recent first, so each is added beneath those already added
+        for predecessor in predecessors.into_iter().rev() {
+            history.add_predecessor(predecessor);
+        }
+        history.update_default_version();
+        Ok(history)
+    }
+
+    // Load the History at history_address without following any succession
+    async fn from_history_address_without_succession(
+        client: DwebClient,
+        history_address: HistoryAddress,
+        ignore_pointer: bool,
+        minimum_entry_index: u64,
+    ) -> Result<History<T>> {
+        println!(
+            "DEBUG History::from_history_address({})",
+            history_address.to_hex()
+        );
+
+        // Check it exists to avoid accidental creation (and payment)
+        let pointer_address = pointer_address_from_history_address(history_address.clone())?;
+        let (pointer, derivation) = match get_and_verify_pointer(&client, &pointer_address).await {
+            Ok(pointer) => (pointer, HistoryDerivation::Trove),
+            Err(e) => {
+                let msg = format!(
+                    "failed to get pointer network address {} - {e}",
+                    pointer_address.to_hex()
+                );
+                println!("DEBUG History::from_history_address() {msg}");
+
+                // The address may be that of an Autonomi Register, which can be read as a History
+                let register_pointer_address =
+                    register_pointer_address_from_history_address(history_address.clone());
+                match get_and_verify_pointer(&client, &register_pointer_address).await {
+                    Ok(pointer) => {
+                        println!(
+                            "DEBUG History::from_history_address() found Register head pointer at {}",
+                            register_pointer_address.to_hex()
+                        );
+                        (pointer, HistoryDerivation::Register)
+                    }
+                    Err(_) => return Err(e.into()),
+                }
+            }
+        };
+
+        let pointer_target = match pointer.target() {
+            PointerTarget::GraphEntryAddress(pointer_target) => *pointer_target,
+            other => {
+                return Err(eyre!(
+                    "History::from_history_address() pointer target is not a GraphEntry - this is probably a bug. Target: {other:?}"
+                ));
+            }
+        };
+        println!("DEBUG INSPECT pointer_counter: {}", pointer.counter());
+        println!("DEBUG INSPECT pointer_target : {}", pointer_target.to_hex());
+
+        let mut history = History::<T> {
+            client,
+            name: String::from(""),
+            branch: None,
+            history_address,
+            num_entries: 0,
+            head_graphentry: None,
+            pointer_counter: pointer.counter(),
+            pointer_target: Some(pointer_target),
+            derivation,
+            default_version: None,
+            cached_version: None,
+            successor: None,
+            predecessor: None,
+            version_offset: 0,
+            phantom: PhantomData,
+        };
+        // Necessary because the pointer may not be up-to-date
+        // Start from the most recent entry known locally if the pointer is behind it
+        let (head_target, head_counter) =
+            history.best_known_head(&pointer_target, pointer.counter());
+        if ignore_pointer || (!ignore_pointer && minimum_entry_index > head_counter) {
+            // Ignore the pointer because that was specified,
+            // or the pointer counter() is behind minimum_entry_index
+            history
+                .update_from_graph_internal(&head_target, head_counter)
+                .await?;
+        } else {
+            // Use the pointer even though it may not be up-to-date
+            match history
+                .get_graph_entry_from_network(&head_target, false)
+                .await
+            {
+                Ok(pointer_head) => {
+                    // The pointer may be stale, so only trust it if there is no newer entry
+                    if history.has_child_entry(&pointer_head).await {
+                        println!("DEBUG pointer is behind the head, following the graph");
+                        history
+                            .update_from_graph_internal(&head_target, head_counter)
+                            .await?;
+                    } else {
+                        if head_counter == 0 {
+                            println!("WARNING: initialising History with pointer.counter() of 0");
+                        }
+                        history.num_entries = head_counter + 1;
+                        history.head_graphentry = Some(pointer_head);
+                        history.record_head_hint();
+                        history.pointer_counter = pointer.counter() + 1;
+                        history.pointer_target = Some(pointer_target);
+                        history.successor = history.get_successor().await?;
+                    }
+                }
+                Err(e) => return Err(eyre!("Failed to get pointer target entry - {e}")),
+            };
+        }
+
+        println!(
+            "DEBUG from_history_address() returning History with num_entries: {}",
+            history.num_entries
+        );
+        history.update_default_version();
+        Ok(history)
+    }
+
+    // Add the History which this one succeeds, beneath any predecessors already added
+    fn add_predecessor(&mut self, predecessor: History<T>) {
+        self.version_offset += predecessor.num_versions().unwrap_or(0);
+        match &mut self.predecessor {
+            Some(existing) => existing.add_predecessor(predecessor),
+            None => self.predecessor = Some(Box::new(predecessor)),
+        }
+        self.cached_version = None;
+    }
+
+    // Read the successor from the head if it is the final entry added by History::hand_over()
+    async fn get_successor(&self) -> Result<Option<HistoryAddress>> {
+        let head = match &self.head_graphentry {
+            Some(head) => head,
+            None => return Ok(None),
+        };
+        // Every other entry of a dweb History, including the root, has a descendant
+        if self.derivation != HistoryDerivation::Trove || !head.descendants.is_empty() {
+            return Ok(None);
+        }
+
+        let record_address = DataAddress::from_hex(&hex::encode(head.content))
+            .map_err(|e| eyre!("invalid succession record address - {e}"))?;
+        let bytes = match self.client.backend.data_get_public(&record_address).await {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                return Err(eyre!(
+                    "failed to get succession record for History {} - {e}",
+                    self.history_address
+                ));
+            }
+        };
+        let succession: HistorySuccession = serde_json::from_slice(&bytes)
+            .map_err(|e| eyre!("failed to parse succession record - {e}"))?;
+        succession.verify(&self.history_address)?;
+        Ok(Some(succession.successor))
+    }
+
+    /// Safely get the actual head even if the pointer_target is not the heaad.
+    ///
+    /// If the pointer_target is out of date this function scans the graph starting at pointer_target
+    /// until it reaches the end and can correctly set the head GraphEntry and num_entries.
+    ///
+    /// This will only happen if the target is out of date, so a maximum of once after the history
+    /// is created. When it does, it will take few seconds because it has to wait for the request
+    /// for a graph entry to not be found on the network.
+    ///
+    /// Returns the head GraphEntry
+    pub async fn update_from_graph(&mut self) -> Result<GraphEntry> {
+        println!("DEBUG History::update_from_graph()");
+        if self.head_graphentry.is_some() {
+            return Ok(self.head_graphentry.clone().unwrap());
+        }
+
+        // Check the pointer exists to avoid accidental creation (and payment)
+        let pointer_address = self.pointer_address()?;
+        let pointer = match get_and_verify_pointer(&self.client, &pointer_address).await {
+            Ok(pointer) => pointer,
+            Err(e) => {
+                let msg = format!(
+                    "failed to get pointer network address {} - {e}",
+                    pointer_address.to_hex()
+                );
+                println!("DEBUG History::from_history_address() {msg}");
+                return Err(e.into());
+            }
+        };
+
+        let pointer_target = match pointer.target() {
+            PointerTarget::GraphEntryAddress(pointer_target) => *pointer_target,
+            other => {
+                return Err(eyre!(
+                    "History::from_history_address() pointer target is not a GraphEntry - this is probably a bug. Target: {other:?}"
+                ));
+            }
+        };
+
+        let (head_target, head_counter) = self.best_known_head(&pointer_target, pointer.counter());
+        self.update_from_graph_internal(&head_target, head_counter)
+            .await
+    }
+
+    // Returns the more recent of the pointer and the locally recorded head of this History
+    //
+    // Entries are immutable so a recorded head is never wrong, though it may be behind
+    // the real head. See crate::cache::history_head
+    fn best_known_head(
+        &self,
+        pointer_target: &GraphEntryAddress,
+        pointer_counter: u64,
+    ) -> (GraphEntryAddress, u64) {
+        match get_history_head_hint(&self.history_address, self.derivation) {
+            Some(hint) if hint.entry_index > pointer_counter => {
+                println!(
+                    "DEBUG History::best_known_head() pointer counter {pointer_counter} is behind recorded head {}",
+                    hint.entry_index
+                );
+                (hint.entry_address, hint.entry_index)
+            }
+            _ => (*pointer_target, pointer_counter),
+        }
+    }
+
+    // Record the current head so later lookups can start from it
+    fn record_head_hint(&self) {
+        if let Some(head) = &self.head_graphentry {
+            if self.num_entries > 0 {
+                update_history_head_hint(
+                    &self.history_address,
+                    self.derivation,
+                    self.num_entries - 1,
+                    head.address(),
+                );
+            }
+        }
+    }
+
+    // See update_from_graph() for description
+    async fn update_from_graph_internal(
+        &mut self,
+        pointer_target: &GraphEntryAddress,
+        pointer_counter: u64,
+    ) -> Result<GraphEntry> {
+        println!("DEBUG History::update_from_graph_internal()");
+        if self.head_graphentry.is_some() {
+            return Ok(self.head_graphentry.clone().unwrap());
+        }
+
+        // Get the Pointer target entry and move forwards - because the pointer may not be up to date
+        let pointer_target_entry = match self
+            .get_graph_entry_from_network(pointer_target, false)
+            .await
+        {
+            Ok(head) => head,
+            Err(e) => return Err(eyre!("Failed to get pointer target entry - {e}")),
+        };
+
+        let mut iter_entry = pointer_target_entry;
+        let mut iter_index = pointer_counter;
+
+        let mut final_index = iter_index;
+        let mut final_entry;
+        loop {
+            println!("DEBUG get child of GraphEntry index {iter_index} - child...");
+            final_entry = iter_entry.clone();
+            iter_entry = if let Some(entry) = self.get_child_entry_of(&iter_entry, true).await {
+                iter_index = iter_index + 1;
+                final_index = final_index + 1;
+                entry
+            } else {
+                break;
+            }
+        }
+
+        self.head_graphentry = Some(final_entry.clone());
+        self.num_entries = final_index + 1;
+        self.record_head_hint();
+
+        // The head may be the final entry added by hand_over() even if the pointer is not
+        self.successor = self.get_successor().await?;
+        Ok(final_entry)
+    }
+
+    fn create_pointer_for_update(
+        counter: u64,
+        graph_entry_address: &GraphEntryAddress,
+        history_secret_key: &SecretKey,
+    ) -> Pointer {
+        println!("DEBUG create_pointer_for_update()");
+        let pointer_target = PointerTarget::GraphEntryAddress(*graph_entry_address);
+        Pointer::new(history_secret_key, counter, pointer_target)
     }
 
     /// Get the main secret key for all histories belonging to an owner
@@ -670,6 +1406,197 @@ impl<T: Trove<T> + Clone> History<T> {
             .into()
     }
 
+    /// Get the secret key of a named History (or Register) belonging to an owner
+    ///
+    /// For HistoryDerivation::Trove this is derived from history_main_secret_key(), and
+    /// for HistoryDerivation::Register it matches Client::register_key_from_name()
+    pub fn history_secret_key(
+        derivation: HistoryDerivation,
+        owner_secret_key: SecretKey,
+        name: &str,
+    ) -> SecretKey {
+        match derivation {
+            HistoryDerivation::Trove => {
+                Self::history_main_secret_key(owner_secret_key).derive_child(name.as_bytes())
+            }
+            HistoryDerivation::Register => {
+                autonomi::Client::register_key_from_name(&owner_secret_key, name)
+            }
+        }
+    }
+
+    /// How the keys and entries of this History are laid out
+    pub fn derivation(&self) -> HistoryDerivation {
+        self.derivation
+    }
+
+    /// The name of the branch, or None for the main line
+    pub fn branch(&self) -> Option<String> {
+        self.branch.clone()
+    }
+
+    // The secret key of a branch, or history_secret_key itself for the main line
+    fn branch_secret_key(history_secret_key: SecretKey, branch: &Option<String>) -> SecretKey {
+        match branch {
+            Some(branch) => MainSecretKey::new(history_secret_key)
+                .derive_key(&branch_derivation_index(branch))
+                .into(),
+            None => history_secret_key,
+        }
+    }
+
+    // The secret key which owns this History (or branch)
+    fn secret_key_from_owner(&self, owner_secret_key: SecretKey) -> SecretKey {
+        Self::branch_secret_key(
+            Self::history_main_secret_key(owner_secret_key).derive_child(self.name.as_bytes()),
+            &self.branch,
+        )
+    }
+
+    /// Create a named branch of this History which can be published to independently
+    /// and later promoted to the main line (see History::promote_branch())
+    ///
+    /// The branch is a line of entries in the graph of this History, beginning with an
+    /// entry which follows the current head of the main line. That entry holds the
+    /// Trove::trove_type() like the root entry of a History, and its key is derived from
+    /// the key of this History using the branch name (see HistoryAddress::branch()). The
+    /// entries which follow it are derived from that key as for any History, and the branch
+    /// has its own pointer, so it is read as a History at that address whose versions are
+    /// numbered from 1.
+    pub async fn create_branch(
+        &self,
+        owner_secret_key: SecretKey,
+        branch: &str,
+    ) -> Result<(AttoTokens, History<T>)> {
+        println!("DEBUG History::create_branch({branch})");
+        self.check_writable("History::create_branch()")?;
+        if self.branch.is_some() {
+            return Err(eyre!(
+                "History::create_branch() - a branch can only be created from the main line"
+            ));
+        }
+        if let Some(successor) = self.successor {
+            return Err(eyre!(
+                "History has been handed over to {successor} and can no longer be branched"
+            ));
+        }
+        validate_branch_name(branch)?;
+
+        let head = match self.get_head_entry().await? {
+            Some(head) => head,
+            None => return Err(eyre!("History::create_branch() - empty History")),
+        };
+        let history_secret_key =
+            Self::history_main_secret_key(owner_secret_key).derive_child(self.name.as_bytes());
+        let root_entry = create_graph_entry(
+            &history_secret_key,
+            Some(&head),
+            &branch_derivation_index(branch),
+            Self::trove_type(),
+        )
+        .await?;
+
+        let branch = Some(branch.to_string());
+        let branch_secret_key = Self::branch_secret_key(history_secret_key, &branch);
+        Self::create_online_with_root(
+            self.client.clone(),
+            self.name.clone(),
+            branch,
+            branch_secret_key,
+            root_entry,
+        )
+        .await
+    }
+
+    /// Publish a version of a branch as a new version of the main line. If version is
+    /// None the most recent version of the branch is used.
+    ///
+    /// The new entry of the main line has the entry of the branch version as a second
+    /// parent, so the promoted version descends from both the main line and the branch.
+    ///
+    /// Returns the cost and the new version of the main line
+    pub async fn promote_branch(
+        &mut self,
+        owner_secret_key: SecretKey,
+        branch: &mut History<T>,
+        version: Option<u64>,
+    ) -> Result<(AttoTokens, u64)> {
+        if self.branch.is_some() {
+            return Err(eyre!(
+                "History::promote_branch() - a branch can only be promoted to the main line"
+            ));
+        }
+        let branch_name = match &branch.branch {
+            Some(branch_name)
+                if branch.history_address == self.history_address.branch(branch_name) =>
+            {
+                branch_name.clone()
+            }
+            _ => {
+                return Err(eyre!(
+                    "History::promote_branch() - not a branch of History {}",
+                    self.history_address
+                ));
+            }
+        };
+        let version = match version {
+            Some(version) => version,
+            None => branch.num_versions()?,
+        };
+        if version <= branch.version_offset || version > branch.num_versions()? {
+            return Err(eyre!(
+                "branch '{branch_name}' has no version {version} to promote"
+            ));
+        }
+
+        let branch_entry = branch
+            .get_graph_entry(branch.entry_index_of_version(version - branch.version_offset))
+            .await?;
+        let trove_address = ArchiveAddress::from_hex(&hex::encode(branch_entry.content))
+            .map_err(|e| eyre!("invalid ArchiveAddress in branch entry - {e}"))?;
+        println!(
+            "Promoting version {version} of branch '{branch_name}' ({})",
+            trove_address.to_hex()
+        );
+        self.publish_version_with_parents(owner_secret_key, &trove_address, &[branch_entry])
+            .await
+    }
+
+    /// The address of the pointer to the head of this History (or Register)
+    pub fn pointer_address(&self) -> Result<PointerAddress> {
+        match self.derivation {
+            HistoryDerivation::Trove => {
+                pointer_address_from_history_address(self.history_address.clone())
+            }
+            HistoryDerivation::Register => Ok(register_pointer_address_from_history_address(
+                self.history_address.clone(),
+            )),
+        }
+    }
+
+    // The entry index of version 1
+    fn first_version_index(&self) -> u64 {
+        match self.derivation {
+            HistoryDerivation::Trove => 1,
+            HistoryDerivation::Register => 0,
+        }
+    }
+
+    // The entry index of a version (the first version is 1)
+    fn entry_index_of_version(&self, version: u64) -> u64 {
+        (version + self.first_version_index()).saturating_sub(1)
+    }
+
+    // Return an error for operations which would modify a History read from a Register
+    fn check_writable(&self, operation: &str) -> Result<()> {
+        if self.derivation == HistoryDerivation::Register {
+            return Err(eyre!(
+                "{operation} cannot modify a History read from a Register - use 'dweb migrate-register' to create a History from it"
+            ));
+        }
+        Ok(())
+    }
+
     /// Get the history address from a string containing either a HISTORY-ADDRESS or NAME
     pub fn parse_history_address_or_name(
         owner_secret_key: SecretKey,
@@ -737,7 +1664,7 @@ impl<T: Trove<T> + Clone> History<T> {
     }
 
     /// Return the number of entries in the history
-    /// This is one more than the number of versions
+    /// For a dweb History this is one more than the number of versions
     /// because the first entry is reserved for use
     /// as a type (which may point to metadata about
     /// the Trove type). Tree is an example Trove type.
@@ -748,6 +1675,9 @@ impl<T: Trove<T> + Clone> History<T> {
     /// Return the number of available versions
     /// or an error if no versions are available.
     /// The first version is 1 last version is num_versions()
+    ///
+    /// This includes the versions of any Histories this one succeeds, but not the
+    /// final entry of a History which has been handed over.
     pub fn num_versions(&self) -> Result<u64> {
         let num_entries = self.num_entries;
 
@@ -755,10 +1685,22 @@ impl<T: Trove<T> + Clone> History<T> {
             let message = "pointer is empty (0 entries)";
             Err(eyre!(message))
         } else {
-            Ok(num_entries - 1)
+            let final_entries = if self.successor.is_some() { 1 } else { 0 };
+            Ok(num_entries - self.first_version_index() - final_entries + self.version_offset)
         }
     }
 
+    /// The History this one has been handed over to, if any (see History::hand_over())
+    pub fn successor(&self) -> Option<HistoryAddress> {
+        self.successor
+    }
+
+    /// The number of versions in the Histories this one succeeds, which are numbered
+    /// before its own versions (see History::from_history_address())
+    pub fn version_offset(&self) -> u64 {
+        self.version_offset
+    }
+
     /// Download a `Tree` from the network
     async fn trove_download(&self, data_address: ArchiveAddress) -> Result<T> {
         return History::<T>::raw_trove_download(&self.client, data_address).await;
@@ -775,8 +1717,8 @@ impl<T: Trove<T> + Clone> History<T> {
             data_address.to_hex()
         );
 
-        retry_until_ok(
-            client.api_control.api_tries,
+        retry_with_policy(
+            &client.api_control.retry_policy,
             &"autonomi_get_file_public()",
             (client, data_address),
             async move |(client, data_address)| match autonomi_get_file_public(
@@ -824,10 +1766,27 @@ impl<T: Trove<T> + Clone> History<T> {
         ignore_pointer: bool,
     ) -> Result<ArchiveAddress> {
         println!("DEBUG History::get_version_entry_value(version: {version})");
+        // The most recent version may be in a preceding History if none have been published here
+        let version = if version == 0 && self.version_offset > 0 {
+            self.num_versions()?
+        } else {
+            version
+        };
+        if version != 0 && version <= self.version_offset {
+            return match &mut self.predecessor {
+                Some(predecessor) => {
+                    Box::pin(predecessor.get_version_entry_value(version, ignore_pointer)).await
+                }
+                None => Err(eyre!(
+                    "History::get_version_entry_value({version}) is in a preceding History which has not been loaded"
+                )),
+            };
+        }
+
         if ignore_pointer {
             self.update_from_graph().await?;
-        } else {
-            if self.pointer_counter == version {
+        } else if self.derivation == HistoryDerivation::Trove && self.successor.is_none() {
+            if self.pointer_counter == version - self.version_offset {
                 if let Some(head) = &self.head_graphentry {
                     if let Ok(archive_address) =
                         ArchiveAddress::from_hex(&hex::encode(head.content))
@@ -846,15 +1805,9 @@ impl<T: Trove<T> + Clone> History<T> {
             }
         }
 
-        let num_entries = self.num_entries();
-        let version = if version == 0 {
-            num_entries - 1
-        } else {
-            version
-        };
-
-        // The first entry is the Trove<T>::trove_type(), and not used so max version is num_entries - 1
-        let max_version = if num_entries > 0 { num_entries - 1 } else { 0 };
+        // The first entry of a dweb History is the Trove<T>::trove_type(), and not used so max version is num_entries - 1
+        let max_version = self.num_versions().unwrap_or(0);
+        let version = if version == 0 { max_version } else { version };
 
         if version > max_version {
             let message = format!(
@@ -864,7 +1817,8 @@ impl<T: Trove<T> + Clone> History<T> {
             return Err(eyre!(message));
         }
 
-        self.get_entry_value(version).await
+        self.get_entry_value(self.entry_index_of_version(version - self.version_offset))
+            .await
     }
 
     /// Get the value by absolute entry index.
@@ -967,7 +1921,12 @@ impl<T: Trove<T> + Clone> History<T> {
         //     graph_entry_address.to_hex()
         // );
 
-        Ok(graph_entry_get(&self.client.client, graph_entry_address, check_exists).await?)
+        Ok(graph_entry_get(
+            self.client.backend.as_ref(),
+            graph_entry_address,
+            check_exists,
+        )
+        .await?)
     }
 
     // Does not need to update pointer
@@ -992,40 +1951,428 @@ impl<T: Trove<T> + Clone> History<T> {
     }
 
     /// Get the parent of a GraphEntry
+    ///
+    /// For a merge entry this is the head of the branch chosen to continue. If the parent
+    /// is forked, returns the entry on the branch leading to graph_entry.
     pub async fn get_parent_entry_of(
         &self,
         graph_entry: &GraphEntry,
     ) -> Result<Option<GraphEntry>> {
         let parent = GraphEntryAddress::new(graph_entry.parents[0]);
-        Ok(Some(
-            self.get_graph_entry_from_network(&parent, false).await?,
-        ))
+        let entries = self.get_graph_entries_from_network(&parent, false).await?;
+        let address = graph_entry.address();
+        Ok(entries
+            .iter()
+            .find(|entry| self.child_address_of(entry) == Some(address))
+            .or(entries.first())
+            .cloned())
     }
 
-    /// Get the child of a GraphEntry
-    /// Assumes each entry has only one descendent
-    pub async fn get_child_entry_of(
-        &self,
-        graph_entry: &GraphEntry,
-        check_exists: bool,
-    ) -> Option<GraphEntry> {
-        // // TODO I don't understand why this isn't sufficient:
-        // let child = GraphEntryAddress::from_owner(graph_entry.descendants[0].0);
+    /// Get the child of a GraphEntry
+    /// Assumes each entry has only one descendent
+    ///
+    /// If the child is forked, returns the first entry of the canonical branch (see HistoryFork)
+    pub async fn get_child_entry_of(
+        &self,
+        graph_entry: &GraphEntry,
+        check_exists: bool,
+    ) -> Option<GraphEntry> {
+        let child = self.child_address_of(graph_entry)?;
+        let mut entries = match self
+            .get_graph_entries_from_network(&child, check_exists)
+            .await
+        {
+            Ok(entries) => entries,
+            Err(_) => return None,
+        };
+
+        if entries.len() > 1 {
+            println!(
+                "DEBUG History is forked at {}, following the canonical branch",
+                child.to_hex()
+            );
+            let branches = self.get_branches_of_fork(entries).await;
+            let canonical = canonical_branch_index(&branches);
+            return branches
+                .into_iter()
+                .nth(canonical)
+                .map(|branch| branch.entries[0].clone());
+        }
+        entries.pop()
+    }
+
+    // Check whether a GraphEntry has a child, without fetching it
+    //
+    // Gives up after CHILD_CHECK_TIMEOUT_SECS, assuming there is no child
+    async fn has_child_entry(&self, graph_entry: &GraphEntry) -> bool {
+        let child = match self.child_address_of(graph_entry) {
+            Some(child) => child,
+            None => return false,
+        };
+        let check = self.client.backend.graph_entry_check_existence(&child);
+        match tokio::time::timeout(Duration::from_secs(CHILD_CHECK_TIMEOUT_SECS), check).await {
+            Ok(Ok(exists)) => exists,
+            Ok(Err(e)) => {
+                println!("DEBUG has_child_entry() unable to check for child - {e}");
+                false
+            }
+            Err(_) => {
+                println!(
+                    "DEBUG has_child_entry() no answer after {CHILD_CHECK_TIMEOUT_SECS}s, assuming no child"
+                );
+                false
+            }
+        }
+    }
+
+    // The address of the child of a GraphEntry, from the derivation index of its first descendant
+    fn child_address_of(&self, graph_entry: &GraphEntry) -> Option<GraphEntryAddress> {
+        // // TODO I don't understand why this isn't sufficient:
+        // let child = GraphEntryAddress::from_owner(graph_entry.descendants[0].0);
+
+        // TODO this is how Autonomi History does it:
+        let next_derivation = DerivationIndex::from_bytes(graph_entry.descendants.first()?.1);
+        let next_entry_pk: PublicKey = MainPubkey::from(self.history_address().owner)
+            .derive_key(&next_derivation)
+            .into();
+        Some(GraphEntryAddress::new(next_entry_pk))
+    }
+
+    // Get every GraphEntry at an address, of which there is more than one if the History is forked there
+    async fn get_graph_entries_from_network(
+        &self,
+        graph_entry_address: &GraphEntryAddress,
+        check_exists: bool,
+    ) -> Result<Vec<GraphEntry>> {
+        graph_entry_get_all(
+            self.client.backend.as_ref(),
+            graph_entry_address,
+            check_exists,
+        )
+        .await
+    }
+
+    // Follow each of the forked entries at an address to the head of its branch
+    async fn get_branches_of_fork(&self, entries: Vec<GraphEntry>) -> Vec<HistoryBranch> {
+        // Order as graph_entry_get() chooses between them, so an unrepaired History reads as before
+        let mut entries = entries;
+        entries.sort_by_cached_key(|entry| match get_derivation_from_graph_entry(entry) {
+            Ok(derivation) => *derivation.as_bytes(),
+            Err(_) => [u8::MAX; 32],
+        });
+
+        let mut branches = Vec::new();
+        for entry in entries {
+            branches.push(self.follow_branch(entry).await);
+        }
+        branches
+    }
+
+    // Follow a branch of a fork until its head, which is the entry before a merge entry, a
+    // further fork or the end of the graph
+    async fn follow_branch(&self, first_entry: GraphEntry) -> HistoryBranch {
+        let mut branch = HistoryBranch {
+            entries: vec![first_entry],
+            merge_entry: None,
+        };
+
+        loop {
+            let head = branch.head().clone();
+            let child = match self.child_address_of(&head) {
+                Some(child) => child,
+                None => break,
+            };
+            let mut children = match self.get_graph_entries_from_network(&child, true).await {
+                Ok(children) => children,
+                Err(_) => break,
+            };
+            if children.len() != 1 {
+                break;
+            }
+
+            let child_entry = children.remove(0);
+            if child_entry.parents.len() > 1 && child_entry.parents[0] == head.owner {
+                branch.merge_entry = Some(child_entry);
+                break;
+            }
+            branch.entries.push(child_entry);
+        }
+        branch
+    }
+
+    /// Walk the History from the root and return every fork found, including any that
+    /// have been repaired. Each fork is passed by following its canonical branch.
+    ///
+    /// This reads every entry so will take some time for a long History.
+    pub async fn detect_forks(&self) -> Result<Vec<HistoryFork>> {
+        println!("DEBUG History::detect_forks()");
+        let mut forks = Vec::new();
+        let mut iter_entry = match self.get_root_entry().await? {
+            Some(root) => root,
+            None => return Ok(forks),
+        };
+
+        let mut iter_index = 0;
+        loop {
+            let child = match self.child_address_of(&iter_entry) {
+                Some(child) => child,
+                None => break,
+            };
+            let mut entries = match self.get_graph_entries_from_network(&child, true).await {
+                Ok(entries) => entries,
+                Err(_) => break,
+            };
+            iter_index = iter_index + 1;
+
+            if entries.len() > 1 {
+                let fork = HistoryFork {
+                    entry_index: iter_index,
+                    address: child,
+                    branches: self.get_branches_of_fork(entries).await,
+                };
+                println!(
+                    "DEBUG found fork at entry {iter_index} with {} branches",
+                    fork.branches.len()
+                );
+
+                let canonical = fork.branches[fork.canonical_branch()].clone();
+                iter_index = iter_index + canonical.num_entries() - 1;
+                iter_entry = canonical.head().clone();
+                if let Some(merge_entry) = canonical.merge_entry {
+                    iter_index = iter_index + 1;
+                    iter_entry = merge_entry;
+                }
+                forks.push(fork);
+            } else {
+                iter_entry = match entries.pop() {
+                    Some(entry) => entry,
+                    None => break,
+                };
+            }
+        }
+
+        Ok(forks)
+    }
+
+    // Return an error if one of the most recent entries is forked and not repaired
+    //
+    // Devices publishing at the same time fork a History at its head so only the last few
+    // entries are checked, which is quick. Use detect_forks() to check the whole History.
+    async fn check_head_not_forked(&self) -> Result<()> {
+        let mut iter_entry = match self.head_graphentry.clone() {
+            Some(head) => head,
+            None => return Ok(()),
+        };
+        let mut iter_index = self.num_entries.saturating_sub(1);
+
+        for _ in 0..FORK_CHECK_DEPTH {
+            if iter_index == 0 {
+                break;
+            }
+            let entries = self
+                .get_graph_entries_from_network(&iter_entry.address(), false)
+                .await?;
+            if entries.len() > 1 {
+                let branches = self.get_branches_of_fork(entries).await;
+                if !branches.iter().any(|branch| branch.merge_entry.is_some()) {
+                    return Err(eyre!(
+                        "History is forked at entry {iter_index} into {} branches. Use 'dweb repair-history' to choose the branch which continues",
+                        branches.len()
+                    ));
+                }
+            }
+
+            iter_entry = match self.get_parent_entry_of(&iter_entry).await? {
+                Some(entry) => entry,
+                None => break,
+            };
+            iter_index = iter_index - 1;
+        }
+        Ok(())
+    }
+
+    /// Repair a fork by adding a merge entry after the head of the branch chosen to continue
+    ///
+    /// The merge entry has the head of every branch as a parent, with the chosen branch
+    /// first, and repeats the value of the chosen head so that it becomes the most recent
+    /// version. The History then reads along the chosen branch and can be published to again.
+    /// Versions on the other branches remain on the network and are shown by 'dweb inspect-history'.
+    ///
+    /// Only the most recent fork can be repaired because the merge entry must follow the
+    /// head of the chosen branch.
+    ///
+    /// Returns the cost of the merge entry and the number of versions after the repair
+    pub async fn repair_fork(
+        &mut self,
+        owner_secret_key: SecretKey,
+        fork: &HistoryFork,
+        canonical: usize,
+    ) -> Result<(AttoTokens, u64)> {
+        println!(
+            "DEBUG History::repair_fork() at entry {} choosing branch {canonical}",
+            fork.entry_index
+        );
+        self.check_writable("History::repair_fork()")?;
+        if fork.is_repaired() {
+            return Err(eyre!(
+                "the fork at entry {} has already been repaired",
+                fork.entry_index
+            ));
+        }
+        let canonical_branch = match fork.branches.get(canonical) {
+            Some(branch) => branch,
+            None => {
+                return Err(eyre!(
+                    "the fork at entry {} has {} branches, there is no branch {canonical}",
+                    fork.entry_index,
+                    fork.branches.len()
+                ));
+            }
+        };
+        let other_heads: Vec<GraphEntry> = fork
+            .branches
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != canonical)
+            .map(|(_, branch)| branch.head().clone())
+            .collect();
+
+        let history_secret_key = self.secret_key_from_owner(owner_secret_key.clone());
+        let merge_entry =
+            create_merge_graph_entry(&history_secret_key, canonical_branch.head(), &other_heads)?;
+        println!(
+            "DEBUG graph_entry_put() merge entry at {}",
+            merge_entry.address().to_hex()
+        );
+        let (merge_cost, merge_address) = match self
+            .client
+            .backend
+            .graph_entry_put(merge_entry, self.client.payment_option())
+            .await
+        {
+            Ok(result) => result,
+            Err(e) => return Err(eyre!("failed to put merge entry - {e}")),
+        };
+
+        // The merge entry is the new head
+        let merge_index = fork.entry_index + canonical_branch.num_entries();
+        self.head_graphentry = None;
+        self.update_from_graph_internal(&merge_address, merge_index)
+            .await?;
+        self.check_and_heal_pointer(owner_secret_key, true).await?;
+
+        let version = self.num_versions()?;
+        self.default_version = Some(version);
+        self.cached_version = None;
+        Ok((merge_cost, version))
+    }
+
+    /// Hand this History over to a successor History, usually owned by a different key
+    ///
+    /// Publishes a HistorySuccession record signed with the key of this History and appends a
+    /// final entry holding its address, after which this History can no longer be updated.
+    /// Readers using History::from_history_address() follow the succession, so the versions
+    /// of the successor are numbered after those of this History.
+    ///
+    /// The successor must already exist (e.g. created by its owner with History::create_online())
+    ///
+    /// Returns the cost of the record and final entry
+    pub async fn hand_over(
+        &mut self,
+        owner_secret_key: SecretKey,
+        successor: HistoryAddress,
+    ) -> Result<AttoTokens> {
+        println!("DEBUG History::hand_over() to {successor}");
+        self.check_writable("History::hand_over()")?;
+        if successor == self.history_address {
+            return Err(eyre!("a History cannot be handed over to itself"));
+        }
+
+        // Make sure the final entry follows the real head
+        self.head_graphentry = None;
+        let head = self.update_from_graph().await?;
+        if let Some(existing) = self.get_successor().await? {
+            return Err(eyre!("History has already been handed over to {existing}"));
+        }
+        self.check_head_not_forked().await?;
 
-        // TODO this is how Autonomi History does it:
-        let next_derivation = DerivationIndex::from_bytes(graph_entry.descendants[0].1);
-        let next_entry_pk: PublicKey = MainPubkey::from(self.history_address().owner)
-            .derive_key(&next_derivation)
-            .into();
-        let child = GraphEntryAddress::new(next_entry_pk);
+        let successor_pointer = pointer_address_from_history_address(successor)?;
+        if let Err(e) = get_and_verify_pointer(&self.client, &successor_pointer).await {
+            return Err(eyre!("successor History {successor} not found - {e}"));
+        }
 
-        match self
-            .get_graph_entry_from_network(&child, check_exists)
+        let spends = Spends::new(&self.client, Some(&"History hand over cost: ")).await?;
+        let history_secret_key = self.secret_key_from_owner(owner_secret_key.clone());
+        let succession = HistorySuccession::new(&history_secret_key, successor);
+        let bytes = match serde_json::to_vec(&succession) {
+            Ok(bytes) => Bytes::from(bytes),
+            Err(e) => return Err(eyre!("failed to serialise succession record - {e}")),
+        };
+        let (record_cost, record_address) = match self
+            .client
+            .backend
+            .data_put_public(bytes, self.client.payment_option())
             .await
         {
-            Ok(graph_entry) => Some(graph_entry),
-            Err(_) => None,
-        }
+            Ok(result) => result,
+            Err(e) => {
+                return show_spend_return_value::<Result<AttoTokens>>(
+                    &spends,
+                    Err(eyre!("failed to put succession record - {e}")),
+                )
+                .await;
+            }
+        };
+
+        let final_entry =
+            create_final_graph_entry(&history_secret_key, &head, record_address.xorname().0)?;
+        println!(
+            "DEBUG graph_entry_put() final entry at {}",
+            final_entry.address().to_hex()
+        );
+        let (entry_cost, entry_address) = match self
+            .client
+            .backend
+            .graph_entry_put(final_entry.clone(), self.client.payment_option())
+            .await
+        {
+            Ok(result) => result,
+            Err(BackendError::AlreadyExists(_)) => {
+                return show_spend_return_value::<Result<AttoTokens>>(
+                    &spends,
+                    Err(eyre!(
+                        "a new version was published during the hand over, please try again"
+                    )),
+                )
+                .await;
+            }
+            Err(e) => {
+                return show_spend_return_value::<Result<AttoTokens>>(
+                    &spends,
+                    Err(eyre!("failed to put final entry - {e}")),
+                )
+                .await;
+            }
+        };
+
+        self.head_graphentry = Some(final_entry);
+        self.num_entries += 1;
+        self.record_head_hint();
+        self.successor = Some(successor);
+        self.heal_pointer_using_update(owner_secret_key, entry_address)
+            .await?;
+
+        let total_cost = match record_cost.checked_add(entry_cost) {
+            Some(total_cost) => total_cost,
+            None => {
+                return show_spend_return_value::<Result<AttoTokens>>(
+                    &spends,
+                    Err(eyre!("Invalid cost")),
+                )
+                .await;
+            }
+        };
+        show_spend_return_value::<Result<AttoTokens>>(&spends, Ok(total_cost)).await
     }
 
     // Returns the version of the cached entry if present
@@ -1077,8 +2424,8 @@ impl<T: Trove<T> + Clone> History<T> {
         graphentry_address: GraphEntryAddress,
     ) -> Result<()> {
         println!("DEBUG History::heal_pointer_using_put()");
-        let history_secret_key =
-            Self::history_main_secret_key(owner_secret_key).derive_child(self.name.as_bytes());
+        self.check_writable("History::heal_pointer_using_put()")?;
+        let history_secret_key = self.secret_key_from_owner(owner_secret_key);
         let history_address = HistoryAddress::new(history_secret_key.public_key());
         println!("Updating History at {}", history_address.to_hex());
 
@@ -1096,10 +2443,10 @@ impl<T: Trove<T> + Clone> History<T> {
             "Updating pointer counter to {counter}, target to {}",
             graphentry_address.to_hex()
         );
-        let client = self.client.client.clone();
+        let client = self.client.backend.clone();
         let payment_option = self.client.payment_option();
-        match retry_until_ok(
-            1, //self.client.api_control.api_tries,
+        match retry_with_policy(
+            &self.client.api_control.retry_policy.with_max_tries(1),
             &"pointer_put()",
             (client, pointer, payment_option),
             async move |(client, pointer, payment_option)| match client
@@ -1111,7 +2458,7 @@ impl<T: Trove<T> + Clone> History<T> {
                     Ok(result)
                 }
                 Err(e) => {
-                    return Err(eyre!("Failed to put pointer: {e:?}"));
+                    return Err(Report::new(e).wrap_err("Failed to put pointer"));
                 }
             },
         )
@@ -1126,6 +2473,48 @@ impl<T: Trove<T> + Clone> History<T> {
         }
     }
 
+    /// Compare the pointer with the head of the graph and heal it if they differ
+    ///
+    /// The History should be loaded with ignore_pointer so that the head is up-to-date,
+    /// which walks the graph from the pointer or the recorded head, whichever is further.
+    /// A pointer one entry behind the head is healed using pointer_update() which is
+    /// free. Otherwise healing requires pointer_put(), which has a cost, so is only
+    /// attempted if allow_put is true.
+    pub async fn check_and_heal_pointer(
+        &mut self,
+        owner_secret_key: SecretKey,
+        allow_put: bool,
+    ) -> Result<PointerHealth> {
+        println!("DEBUG History::check_and_heal_pointer()");
+        let head_address = self.head_entry_address()?;
+        let head_index = self.num_entries.saturating_sub(1);
+        let pointer_counter = self.pointer_counter;
+
+        let targets_head = self.pointer_target == Some(head_address);
+        let health = PointerHealth::decide(pointer_counter, targets_head, head_index, allow_put);
+        if health == PointerHealth::InSync {
+            return Ok(health);
+        }
+
+        println!(
+            "DEBUG pointer counter {pointer_counter} target {:?} differs from head entry {head_index} at {}",
+            self.pointer_target.map(|target| target.to_hex()),
+            head_address.to_hex()
+        );
+        match health {
+            PointerHealth::HealedByUpdate => {
+                self.heal_pointer_using_update(owner_secret_key, head_address)
+                    .await?;
+            }
+            PointerHealth::HealedByPut => {
+                self.heal_pointer_using_put(owner_secret_key, head_index, head_address)
+                    .await?;
+            }
+            _ => {}
+        }
+        Ok(health)
+    }
+
     /// Use pointer_update() to bump the pointer counter and set the target
     pub async fn heal_pointer_using_update(
         &mut self,
@@ -1133,8 +2522,8 @@ impl<T: Trove<T> + Clone> History<T> {
         graphentry_address: GraphEntryAddress,
     ) -> Result<u64> {
         println!("DEBUG History::heal_pointer_using_update()");
-        let history_secret_key =
-            Self::history_main_secret_key(owner_secret_key).derive_child(self.name.as_bytes());
+        self.check_writable("History::heal_pointer_using_update()")?;
+        let history_secret_key = self.secret_key_from_owner(owner_secret_key);
         let history_address = HistoryAddress::new(history_secret_key.public_key());
         println!("Updating History at {}", history_address.to_hex());
 
@@ -1148,9 +2537,9 @@ impl<T: Trove<T> + Clone> History<T> {
                 let pointer_secret_key = Self::history_pointer_secret_key(history_secret_key);
                 let pointer_target = PointerTarget::GraphEntryAddress(graphentry_address);
                 println!("Updating pointer target to: {}", pointer_target.to_hex());
-                let client = self.client.client.clone();
-                match retry_until_ok(
-                    1, //self.client.api_control.api_tries,
+                let client = self.client.backend.clone();
+                match retry_with_policy(
+                    &self.client.api_control.retry_policy.with_max_tries(1),
                     &"pointer_update()",
                     (client, pointer_secret_key, pointer_target),
                     async move |(client, pointer_secret_key, pointer_target)| match client
@@ -1162,7 +2551,7 @@ impl<T: Trove<T> + Clone> History<T> {
                             Ok(result)
                         }
                         Err(e) => {
-                            return Err(eyre!("Failed to update pointer: {e:?}"));
+                            return Err(Report::new(e).wrap_err("Failed to update pointer"));
                         }
                     },
                 )
@@ -1183,12 +2572,15 @@ impl<T: Trove<T> + Clone> History<T> {
     /// Create the next graph entry.
     /// Begins at the provided head_address but handles the case where this is
     /// not the head by moving along the graph until it finds the real head.
+    ///
+    /// Returns the cost, the new entry and the number of existing entries moved past
     async fn create_next_graph_entry_online(
         &self,
         history_secret_key: SecretKey,
         head_address: GraphEntryAddress,
         content: &ArchiveAddress,
-    ) -> Result<(AttoTokens, GraphEntryAddress)> {
+        other_parents: &[GraphEntry],
+    ) -> Result<(AttoTokens, GraphEntry, u64)> {
         println!(
             "DEBUG create_next_graph_entry_online() with content {}",
             content.to_hex()
@@ -1196,15 +2588,17 @@ impl<T: Trove<T> + Clone> History<T> {
 
         println!("DEBUG head_address: {}", head_address.to_hex());
         let mut head_address = head_address;
+        let mut entries_passed = 0;
         loop {
             // Get the next derivation index from the current most recent entry
             let (parent_entry, new_derivation) = self
                 .history_get_graph_entry_and_next_derivation_index(&head_address)
                 .await?;
 
-            let new_entry = create_graph_entry(
+            let new_entry = create_graph_entry_with_parents(
                 &history_secret_key,
                 Some(&parent_entry),
+                other_parents,
                 &new_derivation,
                 *content,
             )
@@ -1214,17 +2608,21 @@ impl<T: Trove<T> + Clone> History<T> {
             println!("DEBUG new_entry address: {}", new_entry.address().to_hex());
             match self
                 .client
-                .client
-                .graph_entry_put(new_entry, self.client.payment_option())
+                .backend
+                .graph_entry_put(new_entry.clone(), self.client.payment_option())
                 .await
             {
-                Ok(result) => return Ok(result),
+                Ok((cost, _)) => return Ok((cost, new_entry, entries_passed)),
                 Err(e) => match e {
-                    GraphError::AlreadyExists(existing_address) => {
+                    BackendError::AlreadyExists(existing_address) => {
                         println!(
                             "DEBUG new_entry already exists, trying again with that as 'head'"
                         );
-                        head_address = existing_address
+                        entries_passed = entries_passed + 1;
+                        head_address = match GraphEntryAddress::from_hex(&existing_address) {
+                            Ok(address) => address,
+                            Err(e) => return Err(eyre!("Invalid GraphEntryAddress - {e}")),
+                        };
                     }
                     _ => {
                         let msg = format!("Failed graph_entry_put() - {e}");
@@ -1239,12 +2637,35 @@ impl<T: Trove<T> + Clone> History<T> {
     /// Publishes a new version pointing to the trove provided
     /// which becomes the newly selected version
     /// Returns the selected version as a number
+    ///
+    /// Fails without publishing if the most recent entries are forked and the fork
+    /// has not been repaired (see History::repair_fork())
     pub async fn publish_new_version(
         &mut self,
         owner_secret_key: SecretKey,
         trove_address: &ArchiveAddress,
     ) -> Result<(AttoTokens, u64)> {
-        let (update_cost, _) = self.update_online(owner_secret_key, *trove_address).await?;
+        self.publish_version_with_parents(owner_secret_key, trove_address, &[])
+            .await
+    }
+
+    // Publish a new version whose entry also has other_parents as parents
+    async fn publish_version_with_parents(
+        &mut self,
+        owner_secret_key: SecretKey,
+        trove_address: &ArchiveAddress,
+        other_parents: &[GraphEntry],
+    ) -> Result<(AttoTokens, u64)> {
+        if let Some(successor) = self.successor {
+            return Err(eyre!(
+                "History has been handed over to {successor} and can no longer be updated"
+            ));
+        }
+        // Publishing on one branch of a fork would leave the versions on the others hidden
+        self.check_head_not_forked().await?;
+        let (update_cost, _) = self
+            .update_online(owner_secret_key, *trove_address, other_parents)
+            .await?;
         println!("trove_address added to history: {}", trove_address.to_hex());
         let version = self.num_versions()?;
         self.default_version = Some(version);
@@ -1340,6 +2761,25 @@ impl<T: Trove<T> + Clone> History<T> {
     }
 }
 
+// Convert minimum_entry_index, an entry index of the first History in a succession whose
+// version 1 is at first_version_index, to an entry index of a successor which comes after
+// version_offset versions. Returns 0 (no minimum) if the minimum is before the successor.
+fn successor_minimum_entry_index(
+    minimum_entry_index: u64,
+    first_version_index: u64,
+    version_offset: u64,
+) -> u64 {
+    if minimum_entry_index < first_version_index {
+        return 0;
+    }
+    let minimum_version = minimum_entry_index - first_version_index + 1;
+    if minimum_version <= version_offset {
+        return 0;
+    }
+    // Version 1 of a successor (a dweb History) is at entry index 1
+    minimum_version - version_offset
+}
+
 /// The state of a Trove struct at a given version  with optional cache of its data
 #[derive(Clone)]
 pub struct TroveVersion<ST: Trove<ST> + Clone> {
@@ -1384,17 +2824,47 @@ pub fn pointer_address_from_history_address(
     Ok(PointerAddress::new(pointer_pk.into()))
 }
 
+/// The address of the head pointer of an Autonomi Register with the same owner as history_address
+pub fn register_pointer_address_from_history_address(
+    history_address: HistoryAddress,
+) -> PointerAddress {
+    autonomi::Client::register_head_pointer_address(&RegisterAddress::new(history_address.owner()))
+}
+
+// The derivation of a Register head pointer from the Register key, as used by autonomi::Client
+const REGISTER_HEAD_DERIVATION_INDEX: [u8; 32] = [0; 32];
+
+/// Get the secret key of the head pointer of the Register owned by register_secret_key
+///
+/// Fails if the key does not match Client::register_head_pointer_address(), so that a
+/// Register is never written which Register based apps could not read.
+pub fn register_head_pointer_secret_key(register_secret_key: &SecretKey) -> Result<SecretKey> {
+    let pointer_secret_key: SecretKey = MainSecretKey::new(register_secret_key.clone())
+        .derive_key(&DerivationIndex::from_bytes(REGISTER_HEAD_DERIVATION_INDEX))
+        .into();
+    let expected = register_pointer_address_from_history_address(HistoryAddress::new(
+        register_secret_key.public_key(),
+    ));
+    if PointerAddress::new(pointer_secret_key.public_key()) != expected {
+        return Err(eyre!(
+            "Register head pointer key does not match {} - probably a bug",
+            expected.to_hex()
+        ));
+    }
+    Ok(pointer_secret_key)
+}
+
 pub async fn get_and_verify_pointer(
     client: &DwebClient,
     pointer_address: &PointerAddress,
 ) -> Result<Pointer> {
     let operation_label = format!("pointer_get({pointer_address})");
-    retry_until_ok(
-        client.api_control.api_tries,
+    retry_with_policy(
+        &client.api_control.retry_policy,
         &operation_label,
         (client, pointer_address),
         async move |(client, pointer_address)| match client
-            .client
+            .backend
             .pointer_get(pointer_address)
             .await
         {
@@ -1403,12 +2873,15 @@ pub async fn get_and_verify_pointer(
                     let message =
                         format!("Error - pointer retrieved from network has INVALID SIGNATURE");
                     println!("{message}");
-                    return Err(eyre!(message));
+                    return Err(Report::new(PermanentError(message)));
                 }
 
                 let head_address = match pointer.target() {
                     PointerTarget::GraphEntryAddress(address) => address,
-                    other => return Err(eyre!("Invalid head address {:?}", other.clone())),
+                    other => {
+                        let message = format!("Invalid head address {:?}", other.clone());
+                        return Err(Report::new(PermanentError(message)));
+                    }
                 };
                 println!(
                     "DEBUG pointer counter: {}, head address: {}",
@@ -1418,12 +2891,560 @@ pub async fn get_and_verify_pointer(
                 Ok(pointer)
             }
             Err(e) => {
-                let message =
-                    format!("failed to get pointer network address {pointer_address} - {e}",);
-                println!("{message}");
-                return Err(eyre!(message));
+                let message = format!("failed to get pointer network address {pointer_address}");
+                println!("{message} - {e}");
+                return Err(Report::new(e).wrap_err(message));
             }
         },
     )
     .await
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use xor_name::XorName;
+
+    use super::*;
+    use crate::backend::{DwebBackend, MemoryBackend};
+    use crate::client::DwebClientConfig;
+    use crate::files::directory::Tree;
+
+    #[actix_web::test]
+    async fn publish_versions_offline() {
+        let backend = Arc::new(MemoryBackend::new());
+        let client = DwebClient::with_backend(backend.clone(), &DwebClientConfig::default());
+        let owner = SecretKey::random();
+        let name = "offline".to_string();
+
+        let (_, mut history) =
+            History::<Tree>::create_online(client.clone(), name.clone(), owner.clone())
+                .await
+                .unwrap();
+        for expected in 1..=2 {
+            let trove_address = ArchiveAddress::new(XorName::random(&mut rand::thread_rng()));
+            let (_, version) = history
+                .publish_new_version(owner.clone(), &trove_address)
+                .await
+                .unwrap();
+            assert_eq!(version, expected);
+        }
+
+        let history = History::<Tree>::from_name(client, owner, name, false, 0)
+            .await
+            .unwrap();
+        assert_eq!(history.num_versions().unwrap(), 2);
+        assert!(backend.paid_puts() >= 4);
+    }
+
+    #[test]
+    fn pointer_heal_is_decided() {
+        use PointerHealth::*;
+        assert_eq!(PointerHealth::decide(3, true, 3, false), InSync);
+        assert_eq!(PointerHealth::decide(2, false, 3, false), HealedByUpdate);
+        assert_eq!(PointerHealth::decide(1, false, 3, false), NeedsPut);
+        assert_eq!(PointerHealth::decide(1, false, 3, true), HealedByPut);
+        // Right counter but wrong target can only be healed by a put
+        assert_eq!(PointerHealth::decide(3, false, 3, false), NeedsPut);
+    }
+
+    #[actix_web::test]
+    async fn stale_pointer_is_walked_forward() {
+        let backend = Arc::new(MemoryBackend::new());
+        let client = DwebClient::with_backend(backend.clone(), &DwebClientConfig::default());
+        let owner = SecretKey::random();
+        let name = "stale".to_string();
+
+        let (_, mut history) =
+            History::<Tree>::create_online(client.clone(), name.clone(), owner.clone())
+                .await
+                .unwrap();
+        let trove_address = ArchiveAddress::new(XorName::random(&mut rand::thread_rng()));
+        history
+            .publish_new_version(owner.clone(), &trove_address)
+            .await
+            .unwrap();
+
+        // Add an entry without updating the pointer, as when a publish is interrupted
+        let history_secret_key = history.secret_key_from_owner(owner.clone());
+        let head = history.head_graphentry.clone().unwrap();
+        let derivation = get_derivation_from_graph_entry(&head).unwrap();
+        let trove_address = ArchiveAddress::new(XorName::random(&mut rand::thread_rng()));
+        let entry =
+            create_graph_entry(&history_secret_key, Some(&head), &derivation, trove_address)
+                .await
+                .unwrap();
+        backend
+            .graph_entry_put(entry, client.payment_option())
+            .await
+            .unwrap();
+
+        let stale =
+            History::<Tree>::from_history_address(client, history.history_address(), false, 0)
+                .await
+                .unwrap();
+        assert_eq!(stale.num_versions().unwrap(), 2);
+    }
+
+    #[actix_web::test]
+    async fn pointer_one_behind_is_healed_by_update() {
+        crate::cache::history_head::set_history_heads_file_path(None);
+        let backend = Arc::new(MemoryBackend::new());
+        let client = DwebClient::with_backend(backend.clone(), &DwebClientConfig::default());
+        let owner = SecretKey::random();
+        let name = "healed".to_string();
+
+        let (_, mut history) =
+            History::<Tree>::create_online(client.clone(), name.clone(), owner.clone())
+                .await
+                .unwrap();
+        let trove_address = ArchiveAddress::new(XorName::random(&mut rand::thread_rng()));
+        history
+            .publish_new_version(owner.clone(), &trove_address)
+            .await
+            .unwrap();
+        let pointer_address =
+            pointer_address_from_history_address(history.history_address()).unwrap();
+        let counter = backend
+            .pointer_get(&pointer_address)
+            .await
+            .unwrap()
+            .counter();
+
+        // Add an entry without updating the pointer, leaving it one behind the head
+        let history_secret_key = history.secret_key_from_owner(owner.clone());
+        let head = history.head_graphentry.clone().unwrap();
+        let derivation = get_derivation_from_graph_entry(&head).unwrap();
+        let trove_address = ArchiveAddress::new(XorName::random(&mut rand::thread_rng()));
+        let entry =
+            create_graph_entry(&history_secret_key, Some(&head), &derivation, trove_address)
+                .await
+                .unwrap();
+        let (_, head_address) = backend
+            .graph_entry_put(entry, client.payment_option())
+            .await
+            .unwrap();
+
+        let mut lagging =
+            History::<Tree>::from_name(client.clone(), owner.clone(), name.clone(), true, 0)
+                .await
+                .unwrap();
+        assert_eq!(
+            lagging
+                .check_and_heal_pointer(owner.clone(), false)
+                .await
+                .unwrap(),
+            PointerHealth::HealedByUpdate
+        );
+        let pointer = backend.pointer_get(&pointer_address).await.unwrap();
+        assert_eq!(pointer.counter(), counter + 1);
+        assert_eq!(
+            pointer.target(),
+            &PointerTarget::GraphEntryAddress(head_address)
+        );
+
+        let mut healed = History::<Tree>::from_name(client, owner.clone(), name, true, 0)
+            .await
+            .unwrap();
+        assert_eq!(
+            healed.check_and_heal_pointer(owner, false).await.unwrap(),
+            PointerHealth::InSync
+        );
+    }
+
+    #[actix_web::test]
+    async fn history_is_exported_to_a_register_and_migrated_back() {
+        let backend = Arc::new(MemoryBackend::new());
+        let client = DwebClient::with_backend(backend.clone(), &DwebClientConfig::default());
+        let owner = SecretKey::random();
+        let troves = [
+            ArchiveAddress::new(XorName::random(&mut rand::thread_rng())),
+            ArchiveAddress::new(XorName::random(&mut rand::thread_rng())),
+        ];
+
+        let (_, mut history) =
+            History::<Tree>::create_online(client.clone(), "exported".to_string(), owner.clone())
+                .await
+                .unwrap();
+        for trove in troves.iter() {
+            history
+                .publish_new_version(owner.clone(), trove)
+                .await
+                .unwrap();
+        }
+        let (_, register_address) = history
+            .export_to_register(owner.clone(), "exported-register")
+            .await
+            .unwrap();
+
+        // Each Register value is read as a version, starting with version 1
+        let mut register_history =
+            History::<Tree>::from_register_address(client.clone(), register_address, false, 0)
+                .await
+                .unwrap();
+        assert_eq!(register_history.derivation(), HistoryDerivation::Register);
+        assert_eq!(register_history.num_versions().unwrap(), 2);
+        for (index, trove) in troves.iter().enumerate() {
+            assert_eq!(
+                register_history
+                    .get_trove_address_from_history(index as u64 + 1)
+                    .await
+                    .unwrap(),
+                *trove
+            );
+        }
+        assert!(
+            register_history
+                .publish_new_version(owner.clone(), &troves[0])
+                .await
+                .is_err()
+        );
+
+        // The Register address can also be given where a HistoryAddress is expected
+        let fallback = History::<Tree>::from_history_address(
+            client.clone(),
+            HistoryAddress::new(register_address.owner()),
+            true,
+            0,
+        )
+        .await
+        .unwrap();
+        assert_eq!(fallback.derivation(), HistoryDerivation::Register);
+        assert_eq!(fallback.num_versions().unwrap(), 2);
+
+        // Migrating replays every value as a version of a new History
+        History::<Tree>::create_from_register(
+            client.clone(),
+            register_address,
+            "migrated".to_string(),
+            owner.clone(),
+        )
+        .await
+        .unwrap();
+        let mut migrated =
+            History::<Tree>::from_name(client, owner, "migrated".to_string(), true, 0)
+                .await
+                .unwrap();
+        assert_eq!(migrated.derivation(), HistoryDerivation::Trove);
+        assert_eq!(migrated.num_versions().unwrap(), 2);
+        assert_eq!(
+            migrated.get_trove_address_from_history(2).await.unwrap(),
+            troves[1]
+        );
+    }
+
+    #[actix_web::test]
+    async fn branch_is_published_and_promoted() {
+        let backend = Arc::new(MemoryBackend::new());
+        let client = DwebClient::with_backend(backend.clone(), &DwebClientConfig::default());
+        let owner = SecretKey::random();
+        let name = "branched".to_string();
+        let random_trove = || ArchiveAddress::new(XorName::random(&mut rand::thread_rng()));
+
+        let (_, mut history) =
+            History::<Tree>::create_online(client.clone(), name.clone(), owner.clone())
+                .await
+                .unwrap();
+        history
+            .publish_new_version(owner.clone(), &random_trove())
+            .await
+            .unwrap();
+
+        let (_, mut staging) = history
+            .create_branch(owner.clone(), "staging")
+            .await
+            .unwrap();
+        assert_eq!(
+            staging.history_address(),
+            history.history_address().branch("staging")
+        );
+        let staged_trove = random_trove();
+        let (_, version) = staging
+            .publish_new_version(owner.clone(), &staged_trove)
+            .await
+            .unwrap();
+        assert_eq!(version, 1);
+        assert_eq!(history.num_versions().unwrap(), 1);
+
+        // The branch follows the head of the main line when it was created
+        let trunk_head = history.get_head_entry().await.unwrap().unwrap();
+        let branch_root = staging.get_root_entry().await.unwrap().unwrap();
+        assert_eq!(branch_root.parents, vec![trunk_head.owner]);
+        assert!(
+            history
+                .create_branch(owner.clone(), "staging")
+                .await
+                .is_err()
+        );
+
+        let (_, version) = history
+            .promote_branch(owner.clone(), &mut staging, None)
+            .await
+            .unwrap();
+        assert_eq!(version, 2);
+
+        // The promoted version follows both the main line and the branch
+        let branch_head = staging.get_head_entry().await.unwrap().unwrap();
+        let promoted = history.get_head_entry().await.unwrap().unwrap();
+        assert_eq!(promoted.parents, vec![trunk_head.owner, branch_head.owner]);
+        assert!(
+            history
+                .promote_branch(owner.clone(), &mut staging, Some(2))
+                .await
+                .is_err()
+        );
+
+        let mut history =
+            History::<Tree>::from_name(client.clone(), owner.clone(), name.clone(), true, 0)
+                .await
+                .unwrap();
+        assert_eq!(
+            history.get_trove_address_from_history(2).await.unwrap(),
+            staged_trove
+        );
+        let staging = History::<Tree>::from_name_and_branch(
+            client.clone(),
+            owner,
+            name,
+            Some("staging".to_string()),
+            true,
+            0,
+        )
+        .await
+        .unwrap();
+        assert_eq!(staging.num_versions().unwrap(), 1);
+
+        // Readers find the branch from the address of the History
+        let mut reader = History::<Tree>::from_history_address(
+            client,
+            history.history_address().branch("staging"),
+            false,
+            0,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            reader.get_trove_address_from_history(1).await.unwrap(),
+            staged_trove
+        );
+        assert!(validate_branch_name("Staging").is_err());
+    }
+
+    #[actix_web::test]
+    async fn handed_over_history_is_followed_by_readers() {
+        let backend = Arc::new(MemoryBackend::new());
+        let client = DwebClient::with_backend(backend.clone(), &DwebClientConfig::default());
+        let old_owner = SecretKey::random();
+        let new_owner = SecretKey::random();
+        let name = "handed-over".to_string();
+        let random_trove = || ArchiveAddress::new(XorName::random(&mut rand::thread_rng()));
+
+        let (_, mut history) =
+            History::<Tree>::create_online(client.clone(), name.clone(), old_owner.clone())
+                .await
+                .unwrap();
+        let first_trove = random_trove();
+        for trove in [first_trove, random_trove()] {
+            history
+                .publish_new_version(old_owner.clone(), &trove)
+                .await
+                .unwrap();
+        }
+        let (_, mut successor) =
+            History::<Tree>::create_online(client.clone(), name.clone(), new_owner.clone())
+                .await
+                .unwrap();
+
+        history
+            .hand_over(old_owner.clone(), successor.history_address())
+            .await
+            .unwrap();
+        assert!(
+            history
+                .publish_new_version(old_owner.clone(), &random_trove())
+                .await
+                .is_err()
+        );
+
+        let successor_trove = random_trove();
+        successor
+            .publish_new_version(new_owner, &successor_trove)
+            .await
+            .unwrap();
+
+        let mut reader =
+            History::<Tree>::from_history_address(client, history.history_address(), true, 0)
+                .await
+                .unwrap();
+        assert_eq!(reader.history_address(), successor.history_address());
+        assert_eq!(reader.num_versions().unwrap(), 3);
+        assert_eq!(
+            reader.get_trove_address_from_history(1).await.unwrap(),
+            first_trove
+        );
+        assert_eq!(
+            reader.get_trove_address_from_history(3).await.unwrap(),
+            successor_trove
+        );
+    }
+
+    #[test]
+    fn minimum_entry_index_is_converted_for_successors() {
+        // No minimum stays no minimum
+        assert_eq!(successor_minimum_entry_index(0, 1, 3), 0);
+        // Within the versions of the predecessors
+        assert_eq!(successor_minimum_entry_index(3, 1, 3), 0);
+        // Version 5 of a dweb History is version 2 of a successor after 3 versions
+        assert_eq!(successor_minimum_entry_index(5, 1, 3), 2);
+        // Version 5 of a History read from a Register is at entry index 4
+        assert_eq!(successor_minimum_entry_index(4, 0, 3), 2);
+    }
+
+    #[actix_web::test]
+    async fn succession_is_found_behind_a_stale_pointer() {
+        crate::cache::history_head::set_history_heads_file_path(None);
+        let backend = Arc::new(MemoryBackend::new());
+        let client = DwebClient::with_backend(backend.clone(), &DwebClientConfig::default());
+        let old_owner = SecretKey::random();
+        let new_owner = SecretKey::random();
+        let name = "interrupted".to_string();
+        let random_trove = || ArchiveAddress::new(XorName::random(&mut rand::thread_rng()));
+
+        let (_, mut history) =
+            History::<Tree>::create_online(client.clone(), name.clone(), old_owner.clone())
+                .await
+                .unwrap();
+        for _ in 0..2 {
+            history
+                .publish_new_version(old_owner.clone(), &random_trove())
+                .await
+                .unwrap();
+        }
+        let (_, mut successor) =
+            History::<Tree>::create_online(client.clone(), name.clone(), new_owner.clone())
+                .await
+                .unwrap();
+        let successor_trove = random_trove();
+        successor
+            .publish_new_version(new_owner, &successor_trove)
+            .await
+            .unwrap();
+
+        // Hand over without updating the pointer, as when a hand over is interrupted
+        let history_secret_key = history.secret_key_from_owner(old_owner);
+        let succession = HistorySuccession::new(&history_secret_key, successor.history_address());
+        let (_, record_address) = backend
+            .data_put_public(
+                Bytes::from(serde_json::to_vec(&succession).unwrap()),
+                client.payment_option(),
+            )
+            .await
+            .unwrap();
+        let head = history.head_graphentry.clone().unwrap();
+        let final_entry =
+            create_final_graph_entry(&history_secret_key, &head, record_address.xorname().0)
+                .unwrap();
+        backend
+            .graph_entry_put(final_entry, client.payment_option())
+            .await
+            .unwrap();
+
+        let mut reader = History::<Tree>::from_history_address(
+            client.clone(),
+            history.history_address(),
+            false,
+            0,
+        )
+        .await
+        .unwrap();
+        assert_eq!(reader.history_address(), successor.history_address());
+        assert_eq!(reader.num_versions().unwrap(), 3);
+        assert_eq!(
+            reader.get_trove_address_from_history(3).await.unwrap(),
+            successor_trove
+        );
+
+        // A minimum entry index in the first History is converted to one in the successor
+        let reader =
+            History::<Tree>::from_history_address(client, history.history_address(), false, 3)
+                .await
+                .unwrap();
+        assert_eq!(reader.num_versions().unwrap(), 3);
+    }
+
+    #[actix_web::test]
+    async fn forked_history_is_repaired_before_publishing() {
+        let backend = Arc::new(MemoryBackend::new());
+        let client = DwebClient::with_backend(backend.clone(), &DwebClientConfig::default());
+        let owner = SecretKey::random();
+        let name = "forked".to_string();
+        let random_trove = || ArchiveAddress::new(XorName::random(&mut rand::thread_rng()));
+
+        let (_, mut history) =
+            History::<Tree>::create_online(client.clone(), name.clone(), owner.clone())
+                .await
+                .unwrap();
+        history
+            .publish_new_version(owner.clone(), &random_trove())
+            .await
+            .unwrap();
+
+        // Another device publishes version 1 at the same time
+        let history_secret_key =
+            History::<Tree>::history_main_secret_key(owner.clone()).derive_child(name.as_bytes());
+        let root = history.get_root_entry().await.unwrap().unwrap();
+        let other_trove = random_trove();
+        let other_entry = create_graph_entry(
+            &history_secret_key,
+            Some(&root),
+            &get_derivation_from_graph_entry(&root).unwrap(),
+            other_trove,
+        )
+        .await
+        .unwrap();
+        backend.insert_graph_entry_fork(other_entry.clone());
+
+        assert!(
+            history
+                .publish_new_version(owner.clone(), &random_trove())
+                .await
+                .is_err()
+        );
+        let forks = history.detect_forks().await.unwrap();
+        assert_eq!(forks.len(), 1);
+        assert_eq!(forks[0].entry_index, 1);
+        assert!(!forks[0].is_repaired());
+
+        let other_branch = forks[0]
+            .branches
+            .iter()
+            .position(|branch| branch.head().content == other_entry.content)
+            .unwrap();
+        let (_, version) = history
+            .repair_fork(owner.clone(), &forks[0], other_branch)
+            .await
+            .unwrap();
+        assert_eq!(version, 2);
+        let (_, version) = history
+            .publish_new_version(owner.clone(), &random_trove())
+            .await
+            .unwrap();
+        assert_eq!(version, 3);
+
+        let mut history = History::<Tree>::from_name(client, owner, name, true, 0)
+            .await
+            .unwrap();
+        assert_eq!(history.num_versions().unwrap(), 3);
+        assert_eq!(
+            history.get_trove_address_from_history(1).await.unwrap(),
+            other_trove
+        );
+        assert_eq!(
+            history.get_trove_address_from_history(2).await.unwrap(),
+            other_trove
+        );
+        let forks = history.detect_forks().await.unwrap();
+        assert_eq!(forks[0].canonical_branch(), other_branch);
+    }
+}
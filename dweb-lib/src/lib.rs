@@ -16,8 +16,10 @@
 */
 pub mod api;
 pub mod autonomi;
+pub mod backend;
 pub mod cache;
 pub mod client;
+pub mod config;
 pub mod data;
 pub mod files;
 pub mod generated_rs;
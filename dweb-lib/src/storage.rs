@@ -15,34 +15,40 @@ You should have received a copy of the GNU Affero General Public License
 along with this program. If not, see <https://www.gnu.org/licenses/>.
 */
 use std::path::PathBuf;
+use std::sync::Mutex;
 
 use autonomi::chunk::DataMapChunk;
 use blsttc::SecretKey;
-use color_eyre::eyre::{Result, eyre};
+use color_eyre::eyre::{Report, Result, eyre};
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 use walkdir::WalkDir;
 
 use autonomi::AttoTokens;
+use autonomi::client::PutError;
 use autonomi::client::files::archive_public::PublicArchive;
-use autonomi::client::payment::PaymentOption;
+use autonomi::client::payment::{PaymentOption, Receipt};
 use autonomi::data::DataAddress;
 use autonomi::files::archive_public::ArchiveAddress;
-use autonomi::files::{Metadata as FileMetadata, PrivateArchive};
+use autonomi::files::{Metadata as FileMetadata, PrivateArchive, UploadError};
 
 use crate::autonomi::access::cached_payments;
+use crate::backend::network::upload_error;
+use crate::cache::publish_journal::PublishJournal;
 
 use crate::client::DwebClient;
 use crate::files::archive::ARCHIVE_PATH_SEPARATOR;
 use crate::files::directory::{
     DWEB_DIRECTORY_HISTORY_CONTENT, DWEB_HISTORY_DIRECTORY, DWEB_SETTINGS_PATH,
+    DWEB_VERSION_MANIFEST_PATH, VersionManifest,
 };
 use crate::files::directory::{DWEB_DIRECTORY_HISTORY_DATAMAPCHUNK, Tree, osstr_to_string};
-use crate::helpers::retry::retry_until_ok;
+use crate::helpers::retry::retry_with_policy;
+use crate::helpers::upload_progress::{UploadProgressReporter, UploadStage, estimated_chunks};
 use crate::history::{History, HistoryAddress};
 
 /// Network data types for dweb APIs
-#[derive(Clone, Copy, Debug, Serialize, Deserialize, ToSchema)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize, ToSchema)]
 pub enum DwebType {
     Chunk,
     PublicFile,
@@ -62,7 +68,10 @@ pub enum DwebType {
 ///
 /// files_root is the path to a the directory tree to upload
 /// name is required for update but not publishing the first version
+/// branch is an optional named branch to update instead of the main line, created if needed
 /// dweb_settings is an optional configuration if publishing a website (TODO)
+/// version_manifest is optional annotation for the new version (message, author, tags)
+/// progress is optional, and if provided is updated as files are uploaded
 ///
 /// Returns the amount paid (cost), history name for updates, and the history address
 pub async fn publish_or_update_files(
@@ -70,8 +79,41 @@ pub async fn publish_or_update_files(
     files_root: &PathBuf,
     app_secret_key: SecretKey,
     name: Option<String>,
+    branch: Option<String>,
     dweb_settings: Option<PathBuf>,
+    version_manifest: Option<VersionManifest>,
     is_publish: bool,
+    progress: Option<&UploadProgressReporter>,
+) -> Result<(AttoTokens, String, HistoryAddress, u64)> {
+    let result = publish_or_update(
+        client,
+        files_root,
+        app_secret_key,
+        name,
+        branch,
+        dweb_settings,
+        version_manifest,
+        is_publish,
+        progress,
+    )
+    .await;
+    report_outcome(progress, &result);
+    if result.is_ok() {
+        let _ = PublishJournal::remove(files_root);
+    }
+    result
+}
+
+async fn publish_or_update(
+    client: &DwebClient,
+    files_root: &PathBuf,
+    app_secret_key: SecretKey,
+    name: Option<String>,
+    branch: Option<String>,
+    dweb_settings: Option<PathBuf>,
+    version_manifest: Option<VersionManifest>,
+    is_publish: bool,
+    progress: Option<&UploadProgressReporter>,
 ) -> Result<(AttoTokens, String, HistoryAddress, u64)> {
     println!("DEBUG publish_or_update_files()...");
     check_path_for_upload(&files_root)?;
@@ -104,13 +146,32 @@ pub async fn publish_or_update_files(
         ));
     };
 
+    if is_publish && branch.is_some() {
+        return Err(eyre!(
+            "A branch can only be published to once the History exists, using an update"
+        ));
+    }
+
+    // A publish which was interrupted may already have created the History
+    let mut journal = PublishJournal::open(files_root, client.api_control.use_public_archive);
+    let is_resumed_publish = is_publish && journal.history_name.as_ref() == Some(&name);
+    if is_resumed_publish {
+        println!("Resuming publish using the History created previously");
+    }
+
     // check the history does not exist
-    let (history_cost, mut files_history) = if is_publish {
+    let (history_cost, mut files_history) = if is_publish && !is_resumed_publish {
         println!("Creating History on network...");
         match History::<Tree>::create_online(client.clone(), name.clone(), app_secret_key.clone())
             .await
         {
-            Ok((cost, history)) => (cost, history),
+            Ok((cost, history)) => {
+                journal.record_history_created(&name);
+                if let Some(progress) = progress {
+                    progress.add_cost(cost);
+                }
+                (cost, history)
+            }
             Err(e) => {
                 let message = format!("Failed to publish new content - {e}");
                 println!("{message}");
@@ -119,16 +180,34 @@ pub async fn publish_or_update_files(
         }
     } else {
         println!("Getting History from network...");
-        match History::<Tree>::from_name(
+        match History::<Tree>::from_name_and_branch(
             client.clone(),
             app_secret_key.clone(),
             name.clone(),
+            branch.clone(),
             false,
             0,
         )
         .await
         {
             Ok(history) => (Into::into(0), history),
+            Err(_) if branch.is_some() => {
+                let branch = branch.clone().unwrap_or_default();
+                println!("Creating branch '{branch}' of History on network...");
+                match create_history_branch(client, app_secret_key.clone(), &name, &branch).await {
+                    Ok((cost, history)) => {
+                        if let Some(progress) = progress {
+                            progress.add_cost(cost);
+                        }
+                        (cost, history)
+                    }
+                    Err(e) => {
+                        let message = format!("Failed to create branch '{branch}' - {e}");
+                        println!("{message}");
+                        return Err(eyre!(message));
+                    }
+                }
+            }
             Err(e) => {
                 let message = format!("Failed to publish update to content - {e}");
                 println!("{message}");
@@ -162,21 +241,34 @@ pub async fn publish_or_update_files(
     println!("Uploading files to network...");
 
     let (files_cost, archive_bytes, archive_type) = if client.api_control.use_public_archive {
-        let (cost, mut archive) = publish_files_public(&client, &files_root, dweb_settings)
-            .await
-            .inspect_err(|e| println!("{}", e))?;
+        let (mut cost, mut archive) =
+            publish_files_public(&client, &files_root, dweb_settings, &mut journal, progress)
+                .await
+                .inspect_err(|e| println!("{}", e))?;
 
         let data_address = DataAddress::from_hex(DWEB_DIRECTORY_HISTORY_CONTENT).unwrap();
         archive.add_file(history_file_path, data_address, autonomi_metadata);
 
+        if let Some(version_manifest) = &version_manifest {
+            let (manifest_cost, manifest_address, manifest_metadata) =
+                upload_version_manifest_public(client, version_manifest).await?;
+            archive.add_file(
+                PathBuf::from(DWEB_VERSION_MANIFEST_PATH),
+                manifest_address,
+                manifest_metadata,
+            );
+            cost = cost.checked_add(manifest_cost).unwrap_or(cost);
+        }
+
         let bytes = archive
             .to_bytes()
             .map_err(|e| eyre!(format!("Failed to serialize archive: {e:?}")))?;
         (cost, bytes, "PublicArchive")
     } else {
-        let (cost, mut archive) = publish_files_private(&client, &files_root, dweb_settings)
-            .await
-            .inspect_err(|e| println!("{}", e))?;
+        let (mut cost, mut archive) =
+            publish_files_private(&client, &files_root, dweb_settings, &mut journal, progress)
+                .await
+                .inspect_err(|e| println!("{}", e))?;
 
         let datamap_chunk = DataMapChunk::from_hex(DWEB_DIRECTORY_HISTORY_DATAMAPCHUNK).unwrap();
         archive.add_file(history_file_path.clone(), datamap_chunk, autonomi_metadata);
@@ -189,6 +281,18 @@ pub async fn publish_or_update_files(
             slash_path.join(history_file_path).as_path(),
         );
 
+        if let Some(version_manifest) = &version_manifest {
+            let (manifest_cost, manifest_datamap, manifest_metadata) =
+                upload_version_manifest_private(client, version_manifest).await?;
+            let manifest_path = PathBuf::from(DWEB_VERSION_MANIFEST_PATH);
+            archive.add_file(manifest_path.clone(), manifest_datamap, manifest_metadata);
+            let _ = archive.rename_file(
+                manifest_path.as_path(),
+                slash_path.join(manifest_path.clone()).as_path(),
+            );
+            cost = cost.checked_add(manifest_cost).unwrap_or(cost);
+        }
+
         let bytes = archive
             .to_bytes()
             .map_err(|e| eyre!(format!("Failed to serialize archive: {e:?}")))?;
@@ -196,12 +300,15 @@ pub async fn publish_or_update_files(
     };
 
     println!("DEBUG storing {archive_type}...");
-    let (archive_cost, archive_address) = match retry_until_ok(
-        client.api_control.api_tries,
+    if let Some(progress) = progress {
+        progress.set_stage(UploadStage::StoringArchive);
+    }
+    let (archive_cost, archive_address) = match retry_with_policy(
+        &client.api_control.retry_policy,
         &"Storing archive as bytes with data_put_public()",
         (client, archive_bytes),
         async move |(client, archive_bytes)| match client
-            .client
+            .backend
             .data_put_public(archive_bytes, client.payment_option())
             .await
         {
@@ -212,7 +319,7 @@ pub async fn publish_or_update_files(
                 // );
                 Ok((cost, archive_address))
             }
-            Err(e) => Err(eyre!("Failed to store the archive of uploaded files: {e}")),
+            Err(e) => Err(Report::new(e).wrap_err("Failed to store the archive of uploaded files")),
         },
     )
     .await
@@ -225,17 +332,28 @@ pub async fn publish_or_update_files(
         }
     };
     println!("{archive_type}: {archive_address}");
+    if let Some(progress) = progress {
+        progress.add_cost(archive_cost);
+    }
 
     let mut total_cost = files_cost.checked_add(history_cost).or(Some(files_cost));
     total_cost = total_cost.unwrap().checked_add(archive_cost).or(total_cost);
 
     println!("Updating History...");
+    if let Some(progress) = progress {
+        progress.set_stage(UploadStage::UpdatingHistory);
+    }
     match files_history
         .publish_new_version(app_secret_key, &archive_address)
         .await
     {
         Ok((update_cost, version)) => {
+            if let Some(progress) = progress {
+                progress.add_cost(update_cost);
+            }
             total_cost = total_cost.unwrap().checked_add(update_cost).or(total_cost);
+            // So that a dweb server can find and maintain this History
+            crate::cache::owned_histories::record_owned_history_name(&name);
             Ok((
                 total_cost.unwrap(),
                 name,
@@ -251,6 +369,195 @@ pub async fn publish_or_update_files(
     }
 }
 
+/// Store a VersionManifest as public data
+///
+/// Returns the cost, the address of the data and metadata for adding it to an archive
+async fn upload_version_manifest_public(
+    client: &DwebClient,
+    version_manifest: &VersionManifest,
+) -> Result<(AttoTokens, DataAddress, FileMetadata)> {
+    let bytes = version_manifest.to_bytes()?;
+    let metadata = version_manifest_metadata(version_manifest, bytes.len());
+    let (cost, data_address) = retry_with_policy(
+        &client.api_control.retry_policy,
+        &"Storing version manifest with data_put_public()",
+        (client, bytes),
+        async move |(client, bytes)| match client
+            .backend
+            .data_put_public(bytes, client.payment_option())
+            .await
+        {
+            Ok(result) => Ok(result),
+            Err(e) => Err(Report::new(e).wrap_err("Failed to store version manifest")),
+        },
+    )
+    .await?;
+
+    Ok((cost, data_address, metadata))
+}
+
+/// Store a VersionManifest as private data
+///
+/// Returns the cost, the datamap chunk of the data and metadata for adding it to an archive
+async fn upload_version_manifest_private(
+    client: &DwebClient,
+    version_manifest: &VersionManifest,
+) -> Result<(AttoTokens, DataMapChunk, FileMetadata)> {
+    let bytes = version_manifest.to_bytes()?;
+    let metadata = version_manifest_metadata(version_manifest, bytes.len());
+    let (cost, datamap_chunk) = retry_with_policy(
+        &client.api_control.retry_policy,
+        &"Storing version manifest with data_put()",
+        (client, bytes),
+        async move |(client, bytes)| match client
+            .backend
+            .data_put(bytes, client.payment_option())
+            .await
+        {
+            Ok(result) => Ok(result),
+            Err(e) => Err(Report::new(e).wrap_err("Failed to store version manifest")),
+        },
+    )
+    .await?;
+
+    Ok((cost, datamap_chunk, metadata))
+}
+
+fn version_manifest_metadata(version_manifest: &VersionManifest, size: usize) -> FileMetadata {
+    FileMetadata {
+        created: version_manifest.published,
+        modified: version_manifest.published,
+        size: size as u64,
+        extra: None,
+    }
+}
+
+/// Store a serialised archive and publish it as a new version of the History with NAME
+///
+/// If create_history is true a new History is created, otherwise the History must exist.
+///
+/// Returns the amount paid (cost), the history address and the new version number
+// Create a branch of the History with the given name
+async fn create_history_branch(
+    client: &DwebClient,
+    app_secret_key: SecretKey,
+    name: &String,
+    branch: &str,
+) -> Result<(AttoTokens, History<Tree>)> {
+    let history = History::<Tree>::from_name(
+        client.clone(),
+        app_secret_key.clone(),
+        name.clone(),
+        false,
+        0,
+    )
+    .await?;
+    history.create_branch(app_secret_key, branch).await
+}
+
+pub async fn publish_archive_to_history(
+    client: &DwebClient,
+    app_secret_key: SecretKey,
+    name: &String,
+    archive_bytes: bytes::Bytes,
+    create_history: bool,
+) -> Result<(AttoTokens, HistoryAddress, u64)> {
+    let (archive_cost, archive_address) = store_archive_bytes(client, archive_bytes).await?;
+    let (history_cost, history_address, version) = publish_archive_address_to_history(
+        client,
+        app_secret_key,
+        name,
+        &archive_address,
+        create_history,
+    )
+    .await?;
+
+    let total_cost = archive_cost
+        .checked_add(history_cost)
+        .unwrap_or(archive_cost);
+    Ok((total_cost, history_address, version))
+}
+
+/// Store a serialised archive as public data. Returns the cost and address of the archive
+pub async fn store_archive_bytes(
+    client: &DwebClient,
+    archive_bytes: bytes::Bytes,
+) -> Result<(AttoTokens, ArchiveAddress)> {
+    retry_with_policy(
+        &client.api_control.retry_policy,
+        &"Storing archive as bytes with data_put_public()",
+        (client, archive_bytes),
+        async move |(client, archive_bytes)| match client
+            .backend
+            .data_put_public(archive_bytes, client.payment_option())
+            .await
+        {
+            Ok(result) => Ok(result),
+            Err(e) => Err(Report::new(e).wrap_err("Failed to store the archive")),
+        },
+    )
+    .await
+}
+
+/// Publish an archive already stored as a new version of the History with NAME
+///
+/// If create_history is true a new History is created, otherwise the History must exist.
+///
+/// Returns the amount paid (cost), the history address and the new version number
+pub async fn publish_archive_address_to_history(
+    client: &DwebClient,
+    app_secret_key: SecretKey,
+    name: &String,
+    archive_address: &ArchiveAddress,
+    create_history: bool,
+) -> Result<(AttoTokens, HistoryAddress, u64)> {
+    let (history_cost, mut history) =
+        history_to_publish(client, app_secret_key.clone(), name, create_history).await?;
+
+    let (update_cost, version) = history
+        .publish_new_version(app_secret_key, archive_address)
+        .await
+        .map_err(|e| eyre!("Failed to update History '{name}' - {e}"))?;
+    crate::cache::owned_histories::record_owned_history_name(name);
+
+    let total_cost = history_cost
+        .checked_add(update_cost)
+        .unwrap_or(history_cost);
+    Ok((total_cost, history.history_address(), version))
+}
+
+/// Get the History called name, or create it if create_history is true
+///
+/// Returns the cost of creating the History and the History
+pub async fn history_to_publish(
+    client: &DwebClient,
+    app_secret_key: SecretKey,
+    name: &String,
+    create_history: bool,
+) -> Result<(AttoTokens, History<Tree>)> {
+    if create_history {
+        History::<Tree>::create_online(client.clone(), name.clone(), app_secret_key)
+            .await
+            .map_err(|e| eyre!("Failed to create History '{name}' - {e}"))
+    } else {
+        let history =
+            History::<Tree>::from_name(client.clone(), app_secret_key, name.clone(), false, 0)
+                .await
+                .map_err(|e| eyre!("Failed to get History '{name}' - {e}"))?;
+        Ok((AttoTokens::zero(), history))
+    }
+}
+
+/// The path of the file which marks an archive as a version of the History at history_address
+///
+/// See DWEB_HISTORY_DIRECTORY
+pub fn history_marker_path(history_address: &HistoryAddress, version: u64) -> String {
+    format!(
+        "{DWEB_HISTORY_DIRECTORY}{ARCHIVE_PATH_SEPARATOR}{}:{version}",
+        history_address.to_hex()
+    )
+}
+
 pub fn report_content_published_or_updated(
     history_address: &HistoryAddress,
     name: &String,
@@ -311,22 +618,48 @@ pub async fn publish_directory(
     files_root: &PathBuf,
     use_public_archive: bool,
     dweb_settings: Option<PathBuf>,
+    progress: Option<&UploadProgressReporter>,
+) -> Result<(AttoTokens, ArchiveAddress)> {
+    let result = store_directory(
+        client,
+        files_root,
+        use_public_archive,
+        dweb_settings,
+        progress,
+    )
+    .await;
+    report_outcome(progress, &result);
+    if result.is_ok() {
+        let _ = PublishJournal::remove(files_root);
+    }
+    result
+}
+
+async fn store_directory(
+    client: &DwebClient,
+    files_root: &PathBuf,
+    use_public_archive: bool,
+    dweb_settings: Option<PathBuf>,
+    progress: Option<&UploadProgressReporter>,
 ) -> Result<(AttoTokens, ArchiveAddress)> {
     println!("DEBUG publish_directory() files_root '{files_root:?}'");
+    let mut journal = PublishJournal::open(files_root, use_public_archive);
 
     let (files_cost, archive_bytes, archive_type) = if use_public_archive {
-        let (cost, archive) = publish_files_public(&client, &files_root, dweb_settings)
-            .await
-            .inspect_err(|e| println!("{}", e))?;
+        let (cost, archive) =
+            publish_files_public(&client, &files_root, dweb_settings, &mut journal, progress)
+                .await
+                .inspect_err(|e| println!("{}", e))?;
 
         let bytes = archive
             .to_bytes()
             .map_err(|e| eyre!(format!("Failed to serialize archive: {e:?}")))?;
         (cost, bytes, "PublicArchive")
     } else {
-        let (cost, archive) = publish_files_private(&client, &files_root, dweb_settings)
-            .await
-            .inspect_err(|e| println!("{}", e))?;
+        let (cost, archive) =
+            publish_files_private(&client, &files_root, dweb_settings, &mut journal, progress)
+                .await
+                .inspect_err(|e| println!("{}", e))?;
 
         let bytes = archive
             .to_bytes()
@@ -335,12 +668,15 @@ pub async fn publish_directory(
     };
 
     println!("DEBUG storing {archive_type}...");
-    let (archive_cost, archive_address) = match retry_until_ok(
-        client.api_control.api_tries,
+    if let Some(progress) = progress {
+        progress.set_stage(UploadStage::StoringArchive);
+    }
+    let (archive_cost, archive_address) = match retry_with_policy(
+        &client.api_control.retry_policy,
         &"Storing archive as bytes with data_put_public()",
         (client, archive_bytes),
         async move |(client, archive_bytes)| match client
-            .client
+            .backend
             .data_put_public(archive_bytes, client.payment_option())
             .await
         {
@@ -351,7 +687,7 @@ pub async fn publish_directory(
                 // );
                 Ok((cost, archive_address))
             }
-            Err(e) => Err(eyre!("Failed to store the archive of uploaded files: {e}")),
+            Err(e) => Err(Report::new(e).wrap_err("Failed to store the archive of uploaded files")),
         },
     )
     .await
@@ -364,6 +700,9 @@ pub async fn publish_directory(
         }
     };
     println!("{archive_type}: {archive_address}");
+    if let Some(progress) = progress {
+        progress.add_cost(archive_cost);
+    }
 
     let total_cost = files_cost.checked_add(archive_cost).unwrap_or(files_cost);
     Ok((total_cost, archive_address))
@@ -386,6 +725,8 @@ pub async fn publish_files_private(
     client: &DwebClient,
     files_root: &PathBuf,
     dweb_settings: Option<PathBuf>,
+    journal: &mut PublishJournal,
+    progress: Option<&UploadProgressReporter>,
 ) -> Result<(AttoTokens, PrivateArchive)> {
     if !files_root.is_dir() {
         return Err(eyre!("Path to files must be a directory: {files_root:?}"));
@@ -399,35 +740,41 @@ pub async fn publish_files_private(
         return Err(eyre!("Path to files is empty: {files_root:?}"));
     }
 
-    let (files_cost, mut archive) = match directory_upload_private(client, files_root).await {
-        Ok(result) => result,
-        Err(e) => return Err(eyre!("Error max tries reached - {e}")),
-    };
+    let (files_cost, mut archive) =
+        match directory_upload_private(client, files_root, journal, progress).await {
+            Ok(result) => result,
+            Err(e) => return Err(eyre!("Error max tries reached - {e}")),
+        };
 
     let settings_cost = if let Some(dweb_path) = dweb_settings {
         let dweb_settings_file = dweb_path.to_string_lossy();
         let dweb_settings_path = PathBuf::from(DWEB_SETTINGS_PATH);
         println!("Uploading {dweb_settings_file}");
 
-        match retry_until_ok(
-            client.api_control.api_tries,
+        match retry_with_policy(
+            &client.api_control.retry_policy.for_uploads(),
             &"file_content_upload_public()",
             (client, dweb_path.clone(), client.payment_option()),
             async move |(client, dweb_path, payment_option)| match client
-                .client
+                .network_client()?
                 .file_content_upload(dweb_path, payment_option)
                 .await
             {
                 Ok(result) => Ok(result),
                 Err(e) => {
                     println!("Failed to upload dweb settings - {e}");
-                    return Err(e.into());
+                    return Err(
+                        Report::new(upload_error(&e)).wrap_err("Failed to upload dweb settings")
+                    );
                 }
             },
         )
         .await
         {
             Ok((cost, datamap_chunk)) => {
+                if let Some(progress) = progress {
+                    progress.add_cost(cost);
+                }
                 let autonomi_metadata =
                     crate::helpers::file::metadata_for_file(&dweb_settings_file);
                 archive.add_file(dweb_settings_path.clone(), datamap_chunk, autonomi_metadata);
@@ -482,6 +829,8 @@ pub async fn publish_files_public(
     client: &DwebClient,
     files_root: &PathBuf,
     dweb_settings: Option<PathBuf>,
+    journal: &mut PublishJournal,
+    progress: Option<&UploadProgressReporter>,
 ) -> Result<(AttoTokens, PublicArchive)> {
     if !files_root.is_dir() {
         return Err(eyre!("Path to files must be a directory: {files_root:?}"));
@@ -495,35 +844,41 @@ pub async fn publish_files_public(
         return Err(eyre!("Path to files is empty: {files_root:?}"));
     }
 
-    let (files_cost, mut archive) = match directory_upload_public(client, files_root).await {
-        Ok(result) => result,
-        Err(e) => return Err(eyre!("Error max tries reached - {e}")),
-    };
+    let (files_cost, mut archive) =
+        match directory_upload_public(client, files_root, journal, progress).await {
+            Ok(result) => result,
+            Err(e) => return Err(eyre!("Error max tries reached - {e}")),
+        };
 
     let settings_cost = if let Some(dweb_path) = dweb_settings {
         let dweb_settings_file = dweb_path.to_string_lossy();
         let dweb_settings_path = PathBuf::from(DWEB_SETTINGS_PATH);
         println!("Uploading {dweb_settings_file}");
 
-        match retry_until_ok(
-            client.api_control.api_tries,
+        match retry_with_policy(
+            &client.api_control.retry_policy.for_uploads(),
             &"file_content_upload_public()",
             (client, dweb_path.clone(), client.payment_option()),
             async move |(client, dweb_path, payment_option)| match client
-                .client
+                .network_client()?
                 .file_content_upload_public(dweb_path, payment_option)
                 .await
             {
                 Ok(result) => Ok(result),
                 Err(e) => {
                     println!("Failed to upload dweb settings - {e}");
-                    return Err(e.into());
+                    return Err(
+                        Report::new(upload_error(&e)).wrap_err("Failed to upload dweb settings")
+                    );
                 }
             },
         )
         .await
         {
             Ok((cost, upload_address)) => {
+                if let Some(progress) = progress {
+                    progress.add_cost(cost);
+                }
                 let autonomi_metadata =
                     crate::helpers::file::metadata_for_file(&dweb_settings_file);
                 archive.add_file(
@@ -568,66 +923,366 @@ pub async fn publish_files_public(
 }
 
 /// Upload a directory and retun the PrivateArchive and total cost
+///
+/// Each file is uploaded and paid for separately, and recorded in the journal so that an
+/// interrupted upload can be resumed, unless ApiControl::upload_file_by_file is false. The
+/// directory is then uploaded in a single batched operation, and if this fails after
+/// paying the payment is cached and re-used by the next attempt.
+/// progress is optional, and if provided is updated as files are uploaded
 pub async fn directory_upload_private(
     client: &DwebClient,
     files_root: &PathBuf,
+    journal: &mut PublishJournal,
+    progress: Option<&UploadProgressReporter>,
 ) -> Result<(AttoTokens, PrivateArchive)> {
     println!("Uploading files from directory: {files_root:?}");
     // The following file-by-file upload code is modelled on autonomi ant-cli commands::file::upload()
 
+    // Journal every upload so that an interrupted one is shown by 'dweb publish-status'
+    journal.record_started();
+
     let files_root_str = files_root.to_str().unwrap_or("");
-    let payment_option =
-        if let Ok(Some(receipt)) = cached_payments::load_payment_for_file(files_root_str) {
-            println!("Using cached payment: no need to re-pay");
-            PaymentOption::Receipt(receipt)
-        } else {
-            PaymentOption::Wallet(client.wallet.clone())
+    let payment_option = if let Ok(Some(receipt)) =
+        cached_payments::load_payment_for_file(files_root_str)
+    {
+        println!("Using cached payment: no need to re-pay");
+        PaymentOption::Receipt(receipt)
+    } else if client.api_control.upload_file_by_file {
+        println!("Uploading data to network...");
+        let (cost, mut archive) = match progress {
+            Some(progress) => upload_files_private(client, files_root, journal, progress).await?,
+            None => {
+                let progress = UploadProgressReporter::new();
+                upload_files_private(client, files_root, journal, &progress).await?
+            }
         };
+        webify_private_archive(&mut archive);
+        return Ok((cost, archive));
+    } else {
+        PaymentOption::Wallet(client.wallet.clone())
+    };
 
     println!("Uploading data to network...");
+    if let Some(progress) = progress {
+        files_to_upload(files_root, false, progress);
+    }
 
     match client
-        .client
+        .network_client()?
         .dir_content_upload(files_root.clone(), payment_option)
         .await
     {
         Ok((cost, mut archive)) => {
+            if let Some(progress) = progress {
+                progress.all_files_done();
+                progress.add_cost(cost);
+            }
             webify_private_archive(&mut archive);
             Ok((cost, archive))
         }
-        Err(e) => return Err(eyre!("Failed to upload directory tree: {e}")),
+        Err(e) => {
+            if let UploadError::PutError(PutError::Batch(upload_state)) = &e {
+                if cached_payments::save_payment(files_root_str, upload_state).is_ok() {
+                    journal.record_batch_paid();
+                }
+            }
+            return Err(eyre!("Failed to upload directory tree: {e}"));
+        }
     }
 }
 
 /// Upload a directory and retun the PublicArchive and total cost
+///
+/// Each file is uploaded and paid for separately, and recorded in the journal so that an
+/// interrupted upload can be resumed, unless ApiControl::upload_file_by_file is false. The
+/// directory is then uploaded in a single batched operation, and if this fails after
+/// paying the payment is cached and re-used by the next attempt.
+/// progress is optional, and if provided is updated as files are uploaded
 pub async fn directory_upload_public(
     client: &DwebClient,
     files_root: &PathBuf,
+    journal: &mut PublishJournal,
+    progress: Option<&UploadProgressReporter>,
 ) -> Result<(AttoTokens, PublicArchive)> {
     println!("Uploading files from directory: {files_root:?}");
     // The following file-by-file upload code is modelled on autonomi ant-cli commands::file::upload()
 
+    // Journal every upload so that an interrupted one is shown by 'dweb publish-status'
+    journal.record_started();
+
     let files_root_str = files_root.to_str().unwrap_or("");
-    let payment_option =
-        if let Ok(Some(receipt)) = cached_payments::load_payment_for_file(files_root_str) {
-            println!("Using cached payment: no need to re-pay");
-            PaymentOption::Receipt(receipt)
-        } else {
-            PaymentOption::Wallet(client.wallet.clone())
+    let payment_option = if let Ok(Some(receipt)) =
+        cached_payments::load_payment_for_file(files_root_str)
+    {
+        println!("Using cached payment: no need to re-pay");
+        PaymentOption::Receipt(receipt)
+    } else if client.api_control.upload_file_by_file {
+        println!("Uploading data to network...");
+        let (cost, mut archive) = match progress {
+            Some(progress) => upload_files_public(client, files_root, journal, progress).await?,
+            None => {
+                let progress = UploadProgressReporter::new();
+                upload_files_public(client, files_root, journal, &progress).await?
+            }
         };
+        webify_public_archive(&mut archive);
+        return Ok((cost, archive));
+    } else {
+        PaymentOption::Wallet(client.wallet.clone())
+    };
 
     println!("Uploading data to network...");
+    if let Some(progress) = progress {
+        files_to_upload(files_root, true, progress);
+    }
 
     match client
-        .client
+        .network_client()?
         .dir_content_upload_public(files_root.clone(), payment_option)
         .await
     {
         Ok((cost, mut archive)) => {
+            if let Some(progress) = progress {
+                progress.all_files_done();
+                progress.add_cost(cost);
+            }
             webify_public_archive(&mut archive);
             Ok((cost, archive))
         }
-        Err(e) => return Err(eyre!("Failed to upload directory tree: {e}")),
+        Err(e) => {
+            if let UploadError::PutError(PutError::Batch(upload_state)) = &e {
+                if cached_payments::save_payment(files_root_str, upload_state).is_ok() {
+                    journal.record_batch_paid();
+                }
+            }
+            return Err(eyre!("Failed to upload directory tree: {e}"));
+        }
+    }
+}
+
+/// Upload each file of a directory tree, reporting progress, and return a PrivateArchive of the files
+///
+/// Each file is paid for separately, so this makes more payments than dir_content_upload().
+/// Progress is recorded in a PublishJournal so that if interrupted, files already stored
+/// are skipped when uploading the same files_root again.
+async fn upload_files_private(
+    client: &DwebClient,
+    files_root: &PathBuf,
+    journal: &mut PublishJournal,
+    progress: &UploadProgressReporter,
+) -> Result<(AttoTokens, PrivateArchive)> {
+    let files_root = &files_root.canonicalize().unwrap_or(files_root.clone());
+    let files = files_to_upload(files_root, false, progress);
+    let retry_policy = progress.retry_policy(&client.api_control.retry_policy.for_uploads());
+    report_resume(journal);
+
+    let mut total_cost = AttoTokens::zero();
+    let mut archive = PrivateArchive::new();
+    for (path, size) in files {
+        let path_string = path.to_string_lossy().to_string();
+        let metadata = crate::helpers::file::metadata_for_file(&path_string);
+        let completed = journal
+            .completed_address(&path)
+            .and_then(|address| DataMapChunk::from_hex(&address).ok());
+        if let Some(datamap_chunk) = completed {
+            archive.add_file(archive_path_for(files_root, &path), datamap_chunk, metadata);
+            progress.file_done(size, estimated_chunks(size, false));
+            continue;
+        }
+
+        progress.file_started(&path_string);
+        // A payment made by a failed attempt is re-used by the next
+        let receipt = Mutex::new(journal.receipt_for(&path));
+        let receipt_ref = &receipt;
+        let result = retry_with_policy(
+            &retry_policy,
+            &"file_content_upload()",
+            (client, path.clone()),
+            async move |(client, path)| {
+                let payment_option =
+                    match receipt_ref.lock().ok().and_then(|receipt| receipt.clone()) {
+                        Some(receipt) => PaymentOption::Receipt(receipt),
+                        None => client.payment_option(),
+                    };
+                match client
+                    .network_client()?
+                    .file_content_upload(path, payment_option)
+                    .await
+                {
+                    Ok(result) => Ok(result),
+                    Err(e) => {
+                        if let Some(payment) = payment_from_upload_error(&e) {
+                            if let Ok(mut receipt) = receipt_ref.lock() {
+                                *receipt = Some(payment);
+                            }
+                        }
+                        Err(Report::new(upload_error(&e)).wrap_err("Failed to upload file"))
+                    }
+                }
+            },
+        )
+        .await;
+
+        let (cost, datamap_chunk) = match result {
+            Ok(result) => result,
+            Err(e) => {
+                if let Ok(Some(receipt)) = receipt.into_inner() {
+                    journal.record_receipt(&path, receipt);
+                }
+                return Err(eyre!("Failed to upload {path_string} - {e}"));
+            }
+        };
+
+        journal.record_completed(&path, datamap_chunk.to_hex(), cost);
+        archive.add_file(archive_path_for(files_root, &path), datamap_chunk, metadata);
+        progress.add_cost(cost);
+        progress.file_done(size, estimated_chunks(size, false));
+        total_cost = total_cost.checked_add(cost).unwrap_or(total_cost);
+    }
+    Ok((total_cost, archive))
+}
+
+/// Upload each file of a directory tree, reporting progress, and return a PublicArchive of the files
+///
+/// Each file is paid for separately, so this makes more payments than dir_content_upload_public().
+/// Progress is recorded in a PublishJournal so that if interrupted, files already stored
+/// are skipped when uploading the same files_root again.
+async fn upload_files_public(
+    client: &DwebClient,
+    files_root: &PathBuf,
+    journal: &mut PublishJournal,
+    progress: &UploadProgressReporter,
+) -> Result<(AttoTokens, PublicArchive)> {
+    let files_root = &files_root.canonicalize().unwrap_or(files_root.clone());
+    let files = files_to_upload(files_root, true, progress);
+    let retry_policy = progress.retry_policy(&client.api_control.retry_policy.for_uploads());
+    report_resume(journal);
+
+    let mut total_cost = AttoTokens::zero();
+    let mut archive = PublicArchive::new();
+    for (path, size) in files {
+        let path_string = path.to_string_lossy().to_string();
+        let metadata = crate::helpers::file::metadata_for_file(&path_string);
+        let completed = journal
+            .completed_address(&path)
+            .and_then(|address| DataAddress::from_hex(&address).ok());
+        if let Some(data_address) = completed {
+            archive.add_file(archive_path_for(files_root, &path), data_address, metadata);
+            progress.file_done(size, estimated_chunks(size, true));
+            continue;
+        }
+
+        progress.file_started(&path_string);
+        // A payment made by a failed attempt is re-used by the next
+        let receipt = Mutex::new(journal.receipt_for(&path));
+        let receipt_ref = &receipt;
+        let result = retry_with_policy(
+            &retry_policy,
+            &"file_content_upload_public()",
+            (client, path.clone()),
+            async move |(client, path)| {
+                let payment_option =
+                    match receipt_ref.lock().ok().and_then(|receipt| receipt.clone()) {
+                        Some(receipt) => PaymentOption::Receipt(receipt),
+                        None => client.payment_option(),
+                    };
+                match client
+                    .network_client()?
+                    .file_content_upload_public(path, payment_option)
+                    .await
+                {
+                    Ok(result) => Ok(result),
+                    Err(e) => {
+                        if let Some(payment) = payment_from_upload_error(&e) {
+                            if let Ok(mut receipt) = receipt_ref.lock() {
+                                *receipt = Some(payment);
+                            }
+                        }
+                        Err(Report::new(upload_error(&e)).wrap_err("Failed to upload file"))
+                    }
+                }
+            },
+        )
+        .await;
+
+        let (cost, data_address) = match result {
+            Ok(result) => result,
+            Err(e) => {
+                if let Ok(Some(receipt)) = receipt.into_inner() {
+                    journal.record_receipt(&path, receipt);
+                }
+                return Err(eyre!("Failed to upload {path_string} - {e}"));
+            }
+        };
+
+        journal.record_completed(&path, data_address.to_hex(), cost);
+        archive.add_file(archive_path_for(files_root, &path), data_address, metadata);
+        progress.add_cost(cost);
+        progress.file_done(size, estimated_chunks(size, true));
+        total_cost = total_cost.checked_add(cost).unwrap_or(total_cost);
+    }
+    Ok((total_cost, archive))
+}
+
+/// List the files in a directory tree with their sizes and add them to the progress totals
+fn files_to_upload(
+    files_root: &PathBuf,
+    is_public: bool,
+    progress: &UploadProgressReporter,
+) -> Vec<(PathBuf, u64)> {
+    let files: Vec<(PathBuf, u64)> = WalkDir::new(files_root)
+        .into_iter()
+        .flatten()
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| {
+            let size = entry.metadata().map(|metadata| metadata.len()).unwrap_or(0);
+            (entry.into_path(), size)
+        })
+        .collect();
+
+    let bytes = files.iter().map(|(_, size)| size).sum();
+    let chunks = files
+        .iter()
+        .map(|(_, size)| estimated_chunks(*size, is_public))
+        .sum();
+    progress.add_files_to_upload(files.len() as u64, bytes, chunks);
+    progress.set_stage(UploadStage::UploadingFiles);
+    files
+}
+
+/// The path of an uploaded file as given by dir_content_upload(), which is
+/// relative to the parent of files_root. See webify_private_archive()
+fn archive_path_for(files_root: &PathBuf, path: &PathBuf) -> PathBuf {
+    let relative_path = path.strip_prefix(files_root).unwrap_or(path);
+    match files_root.file_name() {
+        Some(directory_name) => PathBuf::from(directory_name).join(relative_path),
+        None => relative_path.to_path_buf(),
+    }
+}
+
+fn report_resume(journal: &PublishJournal) {
+    if !journal.files.is_empty() {
+        println!(
+            "Resuming upload with {} files already stored (see 'dweb publish-status')",
+            journal.files_completed()
+        );
+    }
+}
+
+/// The payment made by an upload which failed, if any
+fn payment_from_upload_error(error: &UploadError) -> Option<Receipt> {
+    match error {
+        UploadError::PutError(PutError::Batch(upload_state)) => upload_state.payment.clone(),
+        _ => None,
+    }
+}
+
+pub(crate) fn report_outcome<T>(progress: Option<&UploadProgressReporter>, result: &Result<T>) {
+    if let Some(progress) = progress {
+        match result {
+            Ok(_) => progress.complete(),
+            Err(e) => progress.failed(&format!("{e}")),
+        }
     }
 }
 
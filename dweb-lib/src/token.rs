@@ -16,6 +16,7 @@ along with this program. If not, see <https://www.gnu.org/licenses/>.
 */
 
 use color_eyre::{eyre::eyre, Result};
+use serde::{Deserialize, Serialize};
 
 use autonomi::Amount;
 use autonomi::AttoTokens;
@@ -24,7 +25,8 @@ use evmlib::common::U256;
 use crate::client::DwebClient;
 
 /// Control 'show cost' operations
-#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum ShowCost {
     Token,
     Gas,
@@ -52,8 +54,8 @@ impl Spends {
         let label = label.unwrap_or("Cost total: ").to_string();
         let client = client.clone();
         let show_cost = client.api_control.show_dweb_costs.clone();
-        let token = client.wallet.balance_of_tokens().await?;
-        let gas = client.wallet.balance_of_gas_tokens().await?;
+        let token = client.backend.balance_of_tokens(&client.wallet).await?;
+        let gas = client.backend.balance_of_gas_tokens(&client.wallet).await?;
         Ok(Spends {
             token: token,
             gas: gas,
@@ -64,8 +66,8 @@ impl Spends {
     }
 
     pub async fn update(&mut self) -> Result<()> {
-        self.token = self.client.wallet.balance_of_tokens().await?;
-        self.gas = self.client.wallet.balance_of_gas_tokens().await?;
+        self.token = self.client.backend.balance_of_tokens(&self.client.wallet).await?;
+        self.gas = self.client.backend.balance_of_gas_tokens(&self.client.wallet).await?;
         Ok(())
     }
 
@@ -129,7 +131,7 @@ impl Spends {
     }
 
     pub async fn spent_tokens(&self) -> Result<Amount> {
-        let balance = self.client.wallet.balance_of_tokens().await?;
+        let balance = self.client.backend.balance_of_tokens(&self.client.wallet).await?;
         match self.token.checked_sub(balance) {
             Some(spent) => Ok(spent),
             None => Err(eyre!("Error calculating spent tokens")),
@@ -137,7 +139,7 @@ impl Spends {
     }
 
     pub async fn spent_gas(&self) -> Result<Amount> {
-        let balance = self.client.wallet.balance_of_gas_tokens().await?;
+        let balance = self.client.backend.balance_of_gas_tokens(&self.client.wallet).await?;
         match self.gas.checked_sub(balance) {
             Some(spent) => Ok(spent),
             None => {
@@ -188,8 +190,12 @@ impl Rate {
             Ok(value) => value,
             Err(_) => return None,
         };
+        Self::parse(ticker, &env_value)
+    }
 
-        let mut iter = env_value.split(',');
+    /// Parse a rate given as "RATE,CURRENCY[,DATE]" (e.g. "0.35,GBP")
+    pub fn parse(ticker: String, value: &str) -> Option<Rate> {
+        let mut iter = value.split(',');
         let rate = match iter.next().unwrap_or("0.0").parse::<f32>() {
             Ok(rate) => rate,
             Err(_) => return None,
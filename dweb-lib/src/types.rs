@@ -47,6 +47,9 @@ pub const PUBLIC_SCRATCHPAD_DERIVATION_INDEX: &str = "PrivateScratchpad derivn.
 /// type has a separate derivation index.
 pub const HISTORY_POINTER_DERIVATION_INDEX: &str = "History Pointer derivatatn. indx";
 
+/// Prefix for the name of a History branch, hashed to give the branch derivation index
+pub const HISTORY_BRANCH_DERIVATION_PREFIX: &str = "History branch:";
+
 // /// Get the main secret key for all Pointers belonging to an owner
 // pub fn pointer_secret_key_from_owner(owner_secret: SecretKey) -> SecretKey {
 //     derive_type_owner_secret(owner_secret, POINTER_DERIVATION_INDEX)
@@ -29,7 +29,7 @@ use crate::client::DwebClient;
 use crate::files::directory::{Tree, get_content_using_hex};
 use crate::history::{History, HistoryAddress};
 use crate::web::name::DwebHost;
-use crate::web::name::decode_dweb_host;
+use crate::web::name::{BRANCH_SEPARATOR, DOMAIN_PART, TLD_PART, decode_dweb_host};
 use crate::{
     cache::directory_with_name::{
         DIRECTORY_VERSIONS_WITH_NAME, DirectoryVersionWithName, HISTORY_NAMES,
@@ -126,11 +126,8 @@ pub async fn fetch(client: &DwebClient, url: Url, as_website: bool) -> HttpRespo
 //
 // Notes:
 //   1) ensures that cache locks are released ASAP, and not held during network access.
-//   2) may return an error, but still update the cache with an incomplete DirectoryVersionWithName
-//      if it obtains the DirectoryVersionWithName.archive_address but not the directory_tree. A subsequent call
-//      using the same DwebHost can then skip getting the archive_address and will just retry getting
-//      the directory_tree.
-// TODO refactor directory_version_get() to reduce complexity
+//   2) concurrent requests for the same DwebHost (such as the assets of a page) share a single
+//      fetch from the network (see crate::cache::single_flight)
 pub async fn directory_version_get(
     client: &DwebClient,
     dweb_host: &DwebHost,
@@ -140,151 +137,136 @@ pub async fn directory_version_get(
         dweb_host.dweb_host_string, dweb_host.dweb_name, dweb_host.version
     );
 
-    // If the cache has all the info we return, or if it has an entry but no Tree we can use the addresses
-    let (history_address, archive_address) =
-        if let Ok(lock) = &mut DIRECTORY_VERSIONS_WITH_NAME.lock() {
-            if let Some(cached_directory_version) = lock.get(&dweb_host.dweb_host_string) {
-                if cached_directory_version.directory_tree.is_some() {
-                    // Version 0 is ok here because if we have the tree we will already have cached the version
-                    return Ok((0, cached_directory_version.clone()));
-                } else {
-                    (
-                        Some(cached_directory_version.history_address),
-                        Some(cached_directory_version.archive_address),
-                    )
-                }
-            } else {
-                (None, None)
-            }
-        } else {
-            (None, None)
-        };
+    let mut version_fetched: Option<u64> = None;
+    let version_slot = &mut version_fetched;
+    let directory_version = DIRECTORY_VERSIONS_WITH_NAME
+        .get_or_fetch(&dweb_host.dweb_host_string, || async move {
+            let (version, directory_version) = directory_version_fetch(client, dweb_host).await?;
+            *version_slot = Some(version);
+            Ok(directory_version)
+        })
+        .await?;
+
+    // Version 0 is ok when cached because if we have the tree we will already have cached the version
+    Ok((version_fetched.unwrap_or(0), directory_version))
+}
 
-    let history_address = if history_address.is_none() {
-        // We need the history to get either the ArchiveAddress and/or the Tree
-        if let Ok(lock) = &mut HISTORY_NAMES.lock() {
-            if let Some(history_address) = lock.get(&dweb_host.dweb_name).copied() {
-                history_address
-            } else {
-                return Err(eyre!(format!(
-                    "unknown DWEB-NAME '{}'",
-                    dweb_host.dweb_name
-                )));
-            }
-        } else {
-            return Err(eyre!(format!("failed to access DWEB-NAME cache",)));
+// Access the network to create a DirectoryVersionWithName for directory_version_get()
+// TODO refactor directory_version_fetch() to reduce complexity
+async fn directory_version_fetch(
+    client: &DwebClient,
+    dweb_host: &DwebHost,
+) -> Result<(u64, DirectoryVersionWithName)> {
+    // We need the history to get the ArchiveAddress and the Tree
+    let history_address = match HISTORY_NAMES.get(&dweb_host.dweb_name) {
+        Some(history_address) => history_address,
+        None => {
+            return Err(eyre!(format!(
+                "unknown DWEB-NAME '{}'",
+                dweb_host.dweb_name
+            )));
         }
-    } else {
-        history_address.unwrap()
+    };
+    let history_address = match &dweb_host.branch {
+        Some(branch) => history_address.branch(branch),
+        None => history_address,
     };
 
-    // At this point we have at least a history address
-    if archive_address.is_some() {
-        let archive_address = archive_address.unwrap();
-        let directory_tree =
-            match History::<Tree>::raw_trove_download(client, archive_address).await {
-                Ok(directory_tree) => directory_tree,
-                Err(e) => return Err(eyre!("failed to download directory from network: {e}")),
-            };
-        return update_cached_directory_version_with_name(
-            &dweb_host,
-            history_address,
-            archive_address,
-            Some(directory_tree),
-        );
+    // Note: from_history_address() starts from the most recent entry recorded locally when the
+    // pointer is behind it (see crate::cache::history_head) so a stale pointer no longer hides
+    // versions seen before. Walking the graph from there still needs one round trip per newer
+    // entry plus a failed lookup to detect the end, so is only done when a version requires it.
+    // TODO If apps cache the pointer counter, provide a way they can pass that for minimum_entry_index
+
+    // A requested version is at entry index VERSION, so only walk if neither the pointer
+    // nor the recorded head has reached it
+    let (ignore_pointer, minimum_entry_index) = if let Some(version) = dweb_host.version {
+        (false, version)
     } else {
-        // TODO using dweb_host.version.is_none() for ignore pointer would ensures all versions
-        // TODO are available even if the pointer is out of date, but this takes more than 20s.
-        // TODO If apps cache the pointer counter, provide a way they can pass that for minimum_entry_index
-        // TODO so that from_history_address() never has to wait while walking the graph, and
-        // TODO can know the pointer is up-to-date from the minimum_entry_index
-
-        // TODO this avoids issue where pointer is not up-to-date but makes the first load take a while
-        let (ignore_pointer, minimum_entry_index) = if dweb_host.version.is_some() {
-            (false, dweb_host.version.unwrap() + 1)
-        } else {
-            (true, 0)
-        };
+        (false, 0)
+    };
 
-        // TODO this will load fast but may be missing later updates if the pointer
-        // TODO isn't up-to-date on the network
-        let (ignore_pointer, minimum_entry_index) = (false, 0);
+    let mut history = match History::<Tree>::from_history_address(
+        client.clone(),
+        history_address,
+        ignore_pointer,
+        minimum_entry_index,
+    )
+    .await
+    {
+        Ok(history) => history,
+        Err(e) => {
+            return Err(eyre!(
+                "failed to get History for DWEB-NAME '{}': {e}",
+                dweb_host.dweb_name,
+            ));
+        }
+    };
 
-        let mut history = match History::<Tree>::from_history_address(
-            client.clone(),
-            history_address,
-            ignore_pointer,
-            minimum_entry_index,
-        )
-        .await
-        {
-            Ok(history) => history,
-            Err(e) => {
+    if let Some(version) = dweb_host.version {
+        if let Ok(history_versions) = history.num_versions() {
+            if history_versions == 0 {
+                return Err(eyre!("History is empty - no website to display"));
+            } else if version > history_versions {
                 return Err(eyre!(
-                    "failed to get History for DWEB-NAME '{}': {e}",
-                    dweb_host.dweb_name,
+                    "Invalid version {version}, highest version is {history_versions}"
                 ));
-            }
+            } else if version < 1 {
+                return Err(eyre!("Invalid version {version}, lowest version is 1"));
+            };
+        }
+    }
+
+    let (archive_address, directory_tree, version) =
+        match history.fetch_version_trove(dweb_host.version).await {
+            Some(directory_tree) => match history.get_cached_version() {
+                Some(cached_version) => (
+                    cached_version.trove_address(),
+                    directory_tree,
+                    cached_version.version,
+                ),
+                None => return Err(eyre!("History failed to get_cached_version()")),
+            },
+            None => return Err(eyre!("History failed to fetch_version_metadata()")),
         };
 
-        if let Some(version) = dweb_host.version {
-            if let Ok(history_versions) = history.num_versions() {
-                if history_versions == 0 {
-                    return Err(eyre!("History is empty - no website to display"));
-                } else if version > history_versions {
-                    return Err(eyre!(
-                        "Invalid version {version}, highest version is {history_versions}"
-                    ));
-                } else if version < 1 {
-                    return Err(eyre!("Invalid version {version}, lowest version is 1"));
-                };
-            }
-        }
+    // When retrieving the most recent version, ensure that the corresponding versioned DwebHost is cached
+    let default_result = update_cached_directory_version_with_name(
+        &dweb_host,
+        history_address,
+        archive_address,
+        Some(directory_tree.clone()),
+    );
 
-        let (archive_address, directory_tree, version) =
-            match history.fetch_version_trove(dweb_host.version).await {
-                Some(directory_tree) => match history.get_cached_version() {
-                    Some(cached_version) => (
-                        cached_version.trove_address(),
-                        directory_tree,
-                        cached_version.version,
-                    ),
-                    None => return Err(eyre!("History failed to get_cached_version()")),
-                },
-                None => return Err(eyre!("History failed to fetch_version_metadata()")),
-            };
+    // When retrieving the most recent version, ensure that the corresponding versioned DwebHost is cached
+    if dweb_host.version.is_none() {
+        let versioned_host = match &dweb_host.branch {
+            Some(branch) => format!(
+                "v{version}{BRANCH_SEPARATOR}{branch}.{}.{DOMAIN_PART}.{TLD_PART}",
+                dweb_host.dweb_name
+            ),
+            None => format!("v{version}.{}", dweb_host.dweb_host_string),
+        };
+        let versioned_dweb_host = DwebHost {
+            dweb_host_string: versioned_host,
+            dweb_name: dweb_host.dweb_name.clone(),
+            version: Some(version),
+            branch: dweb_host.branch.clone(),
+
+            #[cfg(feature = "fixed-dweb-hosts")]
+            // Development build feature for non-versioned Tree references
+            is_fixed_dweb_host: false,
+        };
 
-        // When retrieving the most recent version, ensure that the corresponding versioned DwebHost is cached
-        let default_result = update_cached_directory_version_with_name(
-            &dweb_host,
+        return update_cached_directory_version_with_name(
+            &versioned_dweb_host,
             history_address,
             archive_address,
-            Some(directory_tree.clone()),
+            Some(directory_tree),
         );
+    }
 
-        // When retrieving the most recent version, ensure that the corresponding versioned DwebHost is cached
-        if dweb_host.version.is_none() {
-            let versioned_host = format!("v{version}.{}", dweb_host.dweb_host_string);
-            let versioned_dweb_host = DwebHost {
-                dweb_host_string: versioned_host,
-                dweb_name: dweb_host.dweb_name.clone(),
-                version: Some(version),
-
-                #[cfg(feature = "fixed-dweb-hosts")]
-                // Development build feature for non-versioned Tree references
-                is_fixed_dweb_host: false,
-            };
-
-            return update_cached_directory_version_with_name(
-                &versioned_dweb_host,
-                history_address,
-                archive_address,
-                Some(directory_tree),
-            );
-        }
-
-        return default_result;
-    };
+    default_result
 }
 
 /// Get a Tree from the network using the address and if a history, the optional version
@@ -356,25 +338,20 @@ pub fn update_cached_directory_version_with_name(
     let new_directory_version =
         DirectoryVersionWithName::new(&dweb_host, history_address, archive_address, directory_tree);
 
-    match &mut DIRECTORY_VERSIONS_WITH_NAME.lock() {
-        Ok(lock) => {
-            #[cfg(feature = "development")]
-            println!(
-                "DEBUG directory version (v {:?}) added to cache for host: {}",
-                dweb_host.version, dweb_host.dweb_host_string
-            );
-
-            lock.insert(
-                dweb_host.dweb_host_string.clone(),
-                new_directory_version.clone(),
-            );
-        }
-        Err(e) => {
-            return Err(eyre!(
-                "Failed to store DirectoryVersionWithName in cache for DWEB-NAME '{}': {e}",
-                dweb_host.dweb_name
-            ));
-        }
+    #[cfg(feature = "development")]
+    println!(
+        "DEBUG directory version (v {:?}) added to cache for host: {}",
+        dweb_host.version, dweb_host.dweb_host_string
+    );
+
+    if !DIRECTORY_VERSIONS_WITH_NAME.insert(
+        dweb_host.dweb_host_string.clone(),
+        new_directory_version.clone(),
+    ) {
+        return Err(eyre!(
+            "Failed to store DirectoryVersionWithName in cache for DWEB-NAME '{}'",
+            dweb_host.dweb_name
+        ));
     }
 
     Ok((dweb_host.version.unwrap_or(0), new_directory_version))
@@ -396,21 +373,14 @@ pub fn update_cached_directory_version_with_port(
         directory_tree,
     );
 
-    match &mut DIRECTORY_VERSIONS_WITH_PORT.lock() {
-        Ok(lock) => {
-            #[cfg(feature = "development")]
-            println!(
-                "DEBUG directory version with port (v {version:?}) added to cache for port: {port}",
-            );
+    #[cfg(feature = "development")]
+    println!("DEBUG directory version with port (v {version:?}) added to cache for port: {port}",);
 
-            let key = key_for_directory_versions_with_port(archive_address);
-            lock.insert(key, new_directory_version.clone());
-        }
-        Err(e) => {
-            return Err(eyre!(
-                "Failed to store DirectoryVersionWithPort in cache for PORT '{port}': {e}",
-            ));
-        }
+    let key = key_for_directory_versions_with_port(archive_address);
+    if !DIRECTORY_VERSIONS_WITH_PORT.insert(key, new_directory_version.clone()) {
+        return Err(eyre!(
+            "Failed to store DirectoryVersionWithPort in cache for PORT '{port}'",
+        ));
     }
 
     Ok((version.unwrap_or(0), new_directory_version))
@@ -18,6 +18,7 @@
 pub mod fetch;
 pub mod name;
 pub mod request;
+pub mod server;
 
 // Default ports for HTTP / HTTPS
 pub const DEFAULT_HTTP_PORT: u16 = 5537;
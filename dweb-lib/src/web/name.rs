@@ -20,13 +20,16 @@
 //! The 'host' part of a dweb web URL uses the domain 'www-dweb.au' plus either
 //! one or two subdomains:
 //!
-//!    `[v<VERSION>.]<DWEB-NAME>.www-dweb.au`
+//!    `[v[<VERSION>][--<BRANCH>].]<DWEB-NAME>.www-dweb.au`
 //!
 //! The first part is an optional followed by a short name (which correponds to a History
 //! stored on Autonomi).
 //!
 //!    VERSION 1 is the first version, 2 the second etc, and if omitted implies 'most recent'.
 //!
+//!    BRANCH selects a named branch of the History (see History::create_branch()) in which
+//!    case VERSION is a version of the branch.
+//!
 //!    A DWEB-NAME corresponds to a particular website history. It begins with a memorable part,
 //!    a mnemonic for the website, followed by a hyphen and ends with the first few characters from
 //!    the xor encoded HistoryAddress. The memorable part is a lowercase alphabetic string which
@@ -37,6 +40,7 @@
 //!    'awesome-f8b3.www-dweb.au'          - the most recent version a website
 //!    'v23.awesome-f8b3.www-dweb.au'      - the 23rd version the same website
 //!    'v23.awesome-f2e4.www-dweb.au'      - the 23rd version of a different website
+//!    'v--staging.awesome-f8b3.www-dweb.au' - the most recent version of the 'staging' branch
 //!
 //! DwebHosts allow the correct website version to be retrieved from a History<Tree>
 //! on Autonomi and the corresponding content to be returned to a standard web browser. They act
@@ -62,7 +66,7 @@
 use color_eyre::eyre::{Result, eyre};
 
 use crate::cache::directory_with_name::HISTORY_NAMES;
-use crate::history::HistoryAddress;
+use crate::history::{HistoryAddress, validate_branch_name};
 
 // Domain name and subdomain constraints based on IETF RFC1035 with links to relevant sections:
 pub const MAX_SUBDOMAIN_LEN: usize = 63; //  S2.3.4 Size limits (https://datatracker.ietf.org/doc/html/rfc1035#section-2.3.4)
@@ -76,6 +80,10 @@ pub const DOMAIN_PART: &str = "www-dweb";
 pub const TLD_PART: &str = "au";
 
 const VERSION_CHAR: u8 = b'v';
+
+/// Separates a branch from the version in a version selector, as in 'v3--staging'.
+/// Neither a DWEB-NAME, version tag nor branch name can contain it.
+pub const BRANCH_SEPARATOR: &str = "--";
 const FIXED_WEBNAME_SEPARATOR: &str = "-f";
 
 /// DwebHost corresponds to the HOST part of a dweb URL and encapsulates the component
@@ -88,6 +96,8 @@ pub struct DwebHost {
     pub dweb_name: String,
     /// None implies most recent version (highest number)
     pub version: Option<u64>,
+    /// A named branch of the History, or None for the main line
+    pub branch: Option<String>,
 
     #[cfg(feature = "fixed-dweb-hosts")]
     // Development build feature for non-versioned Tree references
@@ -146,6 +156,19 @@ pub fn make_dweb_name(memorable_part: &String, history_address: HistoryAddress)
     )
 }
 
+/// Split a version selector such as "v3--staging" into its version part ("v3") and branch
+///
+/// Returns the selector unchanged with no branch if it does not contain BRANCH_SEPARATOR
+pub fn split_branch_selector(selector: &str) -> Result<(&str, Option<String>)> {
+    match selector.split_once(BRANCH_SEPARATOR) {
+        Some((version_part, branch)) => {
+            validate_branch_name(branch)?;
+            Ok((version_part, Some(branch.to_string())))
+        }
+        None => Ok((selector, None)),
+    }
+}
+
 /// Create a version part ("v[VERSION]") for a www-dweb URL
 pub fn make_version_part(version: u64) -> String {
     if version > 0 {
@@ -259,7 +282,8 @@ pub fn decode_dweb_host(dweb_host: &str) -> Result<DwebHost> {
     }
 
     let mut found_version_segment = false;
-    // If four segments are present, process the first as 'v<VERSION>'
+    let mut branch = None;
+    // If four segments are present, process the first as 'v[<VERSION>][--<BRANCH>]'
     let version = if segments.clone().count() == 4 && dweb_host.as_bytes()[0] == VERSION_CHAR {
         match segments.next() {
             Some(str) => {
@@ -268,20 +292,30 @@ pub fn decode_dweb_host(dweb_host: &str) -> Result<DwebHost> {
                         "Dweb host contains four segments (separated by '.') so first must start with 'v'"
                     ));
                 }
-                match str[1..].parse::<u64>() {
-                    Ok(version) => {
-                        if version > 0 {
-                            found_version_segment = true;
-                            Some(version)
-                        } else {
-                            return Err(eyre!("Invalid version {version}, lowest version is 1"));
+                let (str, branch_part) = split_branch_selector(str)?;
+                branch = branch_part;
+                if str.len() == 1 && branch.is_some() {
+                    // The most recent version of the branch
+                    found_version_segment = true;
+                    None
+                } else {
+                    match str[1..].parse::<u64>() {
+                        Ok(version) => {
+                            if version > 0 {
+                                found_version_segment = true;
+                                Some(version)
+                            } else {
+                                return Err(eyre!(
+                                    "Invalid version {version}, lowest version is 1"
+                                ));
+                            }
                         }
+                        Err(_) => {
+                            return Err(eyre!(
+                                "VERSION must be an integer in web name: '{dweb_host}"
+                            ));
+                        } // }
                     }
-                    Err(_) => {
-                        return Err(eyre!(
-                            "VERSION must be an integer in web name: '{dweb_host}"
-                        ));
-                    } // }
                 }
             }
             None => {
@@ -334,12 +368,15 @@ pub fn decode_dweb_host(dweb_host: &str) -> Result<DwebHost> {
     #[cfg(feature = "fixed-dweb-hosts")]
     let is_fixed_dweb_host = !found_version_segment && dweb_name.contains(&fixed_dweb_host_tag);
 
-    println!("DEBUG returning DwebHost: version: {version:?}, dweb_name: '{dweb_name}'");
+    println!(
+        "DEBUG returning DwebHost: version: {version:?}, branch: {branch:?}, dweb_name: '{dweb_name}'"
+    );
 
     Ok(DwebHost {
         dweb_host_string: dweb_host.to_ascii_lowercase(),
         dweb_name: dweb_name.to_string().to_ascii_lowercase(),
         version,
+        branch,
 
         #[cfg(feature = "fixed-dweb-hosts")]
         is_fixed_dweb_host,
@@ -419,25 +456,15 @@ pub fn register_name(dweb_name: &str, history_address: HistoryAddress) -> Result
         }
     };
 
-    match &mut HISTORY_NAMES.lock() {
-        Ok(lock) => {
-            let cached_history_address = lock.get(dweb_name);
-            if cached_history_address.is_some() {
-                let cached_history_address = cached_history_address.unwrap();
-                if history_address != *cached_history_address {
-                    let msg = format!(
-                        "DWEB-NAME '{dweb_name}' already in use for HISTORY-ADDRESS '{}'",
-                        cached_history_address.to_hex()
-                    );
-                    println!("{msg}");
-                    return Err(eyre!(msg));
-                }
-                // println!("DWEB-NAME '{dweb_name}' already registered for {history_address_string}");
-            } else {
-                lock.insert(String::from(dweb_name), history_address);
-                // println!(
-                //     "DWEB-NAME '{dweb_name}' successfully registered for {history_address_string}"
-                // );
+    match HISTORY_NAMES.get_or_insert(String::from(dweb_name), history_address) {
+        Ok(cached_history_address) => {
+            if history_address != cached_history_address {
+                let msg = format!(
+                    "DWEB-NAME '{dweb_name}' already in use for HISTORY-ADDRESS '{}'",
+                    cached_history_address.to_hex()
+                );
+                println!("{msg}");
+                return Err(eyre!(msg));
             }
         }
         Err(e) => {
@@ -494,18 +521,11 @@ pub struct RecognisedName {
 pub fn recognised_dwebnames() -> Result<Vec<RecognisedName>> {
     let mut names_vec = Vec::<RecognisedName>::new();
 
-    match &mut HISTORY_NAMES.lock() {
-        Ok(lock) => {
-            for cached_item in lock.iter() {
-                names_vec.push(RecognisedName {
-                    key: cached_item.0.to_string(),
-                    history_address: cached_item.1.to_hex(),
-                });
-            }
-        }
-        Err(e) => {
-            return Err(eyre!("Failed to access dweb name cache - {e}"));
-        }
+    for (key, history_address) in HISTORY_NAMES.entries() {
+        names_vec.push(RecognisedName {
+            key,
+            history_address: history_address.to_hex(),
+        });
     }
 
     Ok(names_vec)
@@ -525,4 +545,21 @@ fn check_malformed_web_name() {
     assert!(name::decode_dweb_host("v2.9awesome-f834.www-dweb.au").is_err());
     assert!(name::decode_dweb_host("v0.awesome-f834.www-dweb.au").is_err());
     assert!(name::decode_dweb_host("v2nd.awesome-f834.www-dweb.au").is_err());
+    assert!(name::decode_dweb_host("v2--Staging.awesome-f834.www-dweb.au").is_err());
+    assert!(name::decode_dweb_host("v2--staging-.awesome-f834.www-dweb.au").is_err());
+}
+
+#[test]
+fn decode_web_name_with_branch() {
+    use crate::web::name;
+    let dweb_host = name::decode_dweb_host("v2--staging.awesome-f834.www-dweb.au").unwrap();
+    assert_eq!(dweb_host.version, Some(2));
+    assert_eq!(dweb_host.branch.as_deref(), Some("staging"));
+
+    let dweb_host = name::decode_dweb_host("v--staging.awesome-f834.www-dweb.au").unwrap();
+    assert_eq!(dweb_host.version, None);
+    assert_eq!(dweb_host.branch.as_deref(), Some("staging"));
+
+    let dweb_host = name::decode_dweb_host("v2.awesome-f834.www-dweb.au").unwrap();
+    assert_eq!(dweb_host.branch, None);
 }
@@ -0,0 +1,190 @@
+/*
+ Copyright (c) 2025 Mark Hughes
+
+ This program is free software: you can redistribute it and/or modify
+ it under the terms of the GNU Affero General Public License as published by
+ the Free Software Foundation, either version 3 of the License, or
+ (at your option) any later version.
+
+ This program is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY; without even the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU Affero General Public License for more details.
+
+ You should have received a copy of the GNU Affero General Public License
+ along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! Management of running dweb servers.
+//!
+//! Each dweb server answers /dweb-0/server-info and /dweb-0/server-stop. A main server
+//! started by 'dweb server start' also records a ServerLock in the servers directory of
+//! the Autonomi client data directory, so that 'dweb server info|stop all' can find it and
+//! a second server is not started on the same port.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use color_eyre::eyre::{Context, Result, eyre};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use crate::autonomi::access::data_dir::get_client_data_dir_path;
+use crate::web::request::make_main_server_url;
+
+const SERVER_LOCKS_DIRECTORY: &str = "servers";
+
+/// Set by 'dweb server start' for a server it starts with output redirected to a logfile
+pub const DWEB_SERVER_LOGFILE: &str = "DWEB_SERVER_LOGFILE";
+
+/// How long to wait for a dweb server to respond to a management request
+const SERVER_REQUEST_TIMEOUT_SECS: u64 = 5;
+
+/// Information about a dweb server returned by /dweb-0/server-info
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct ServerInfo {
+    pub host: String,
+    pub port: u16,
+    /// Process id of the server
+    pub pid: u32,
+    /// Version of the dweb server
+    pub dweb_version: String,
+    /// True for a main server, false for a listener serving a single directory/website
+    pub is_main_server: bool,
+    /// The network the server is connected to
+    pub network: String,
+    /// For a main server, the listeners it has started. For a listener, only itself.
+    pub listeners: Vec<ListenerInfo>,
+}
+
+/// A listener serving one version of a directory/website on its own port
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct ListenerInfo {
+    pub port: u16,
+    /// Address of the History being served, as hex (None when opened by ARCHIVE-ADDRESS)
+    pub history_address: Option<String>,
+    /// The version of the History, where None or 0 means the most recent
+    pub version: Option<u64>,
+    /// Address of the archive being served, as hex
+    pub archive_address: String,
+    /// Seconds since the listener last handled a request, when known
+    pub idle_secs: Option<u64>,
+}
+
+/// Record of a main server started by 'dweb server start'
+#[derive(Clone, Serialize, Deserialize)]
+pub struct ServerLock {
+    pub pid: u32,
+    pub host: String,
+    pub port: u16,
+    /// File receiving the server output, if not a terminal
+    pub logfile: Option<PathBuf>,
+}
+
+impl ServerLock {
+    pub fn new(host: &str, port: u16, logfile: Option<PathBuf>) -> ServerLock {
+        ServerLock {
+            pid: std::process::id(),
+            host: host.to_string(),
+            port,
+            logfile,
+        }
+    }
+
+    /// Load the lock for a server on port, if present
+    pub fn load(port: u16) -> Result<Option<ServerLock>> {
+        let path = lock_file_path(port)?;
+        if !path.exists() {
+            return Ok(None);
+        }
+        let reader = BufReader::new(File::open(&path)?);
+        let lock: ServerLock = serde_json::from_reader(reader)
+            .wrap_err(format!("Failed to read server lock {path:?}"))?;
+        Ok(Some(lock))
+    }
+
+    /// Every server lock present, ordered by port
+    pub fn list() -> Result<Vec<ServerLock>> {
+        let mut locks = Vec::new();
+        for entry in std::fs::read_dir(locks_directory()?)? {
+            let path = entry?.path();
+            let reader = match File::open(&path) {
+                Ok(file) => BufReader::new(file),
+                Err(_) => continue,
+            };
+            match serde_json::from_reader::<_, ServerLock>(reader) {
+                Ok(lock) => locks.push(lock),
+                Err(e) => println!("DEBUG ignoring invalid server lock {path:?} - {e}"),
+            }
+        }
+        locks.sort_by_key(|lock| lock.port);
+        Ok(locks)
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let writer = BufWriter::new(File::create(lock_file_path(self.port)?)?);
+        serde_json::to_writer(writer, self)?;
+        Ok(())
+    }
+
+    /// Remove the lock for port, if present
+    pub fn remove(port: u16) -> Result<()> {
+        let path = lock_file_path(port)?;
+        if path.exists() {
+            std::fs::remove_file(&path).wrap_err(format!("Failed to remove {path:?}"))?;
+        }
+        Ok(())
+    }
+}
+
+fn locks_directory() -> Result<PathBuf> {
+    let directory = get_client_data_dir_path()?.join(SERVER_LOCKS_DIRECTORY);
+    std::fs::create_dir_all(&directory).wrap_err("Could not create servers directory")?;
+    Ok(directory)
+}
+
+fn lock_file_path(port: u16) -> Result<PathBuf> {
+    Ok(locks_directory()?.join(format!("dweb-server-{port}.json")))
+}
+
+/// Get information about the dweb server on host:port
+pub async fn request_server_info(host: Option<&String>, port: Option<u16>) -> Result<ServerInfo> {
+    let url_path = format!("{}/server-info", crate::api::DWEB_API_ROUTE);
+    let url = make_main_server_url(host, port, &url_path);
+    let response = management_client()?
+        .get(&url)
+        .header("Accept", "application/json")
+        .send()
+        .await
+        .wrap_err(format!("No dweb server responded at {url}"))?;
+    if !response.status().is_success() {
+        return Err(eyre!("{url} returned {}", response.status()));
+    }
+    let body = response.text().await?;
+    let info: ServerInfo =
+        serde_json::from_str(&body).wrap_err(format!("Invalid response from {url}"))?;
+    Ok(info)
+}
+
+/// Ask the dweb server on host:port to stop once it has finished handling current requests
+pub async fn request_server_stop(host: Option<&String>, port: Option<u16>) -> Result<()> {
+    let url_path = format!("{}/server-stop", crate::api::DWEB_API_ROUTE);
+    let url = make_main_server_url(host, port, &url_path);
+    let response = management_client()?
+        .post(&url)
+        .send()
+        .await
+        .wrap_err(format!("No dweb server responded at {url}"))?;
+    if !response.status().is_success() {
+        return Err(eyre!("{url} returned {}", response.status()));
+    }
+    Ok(())
+}
+
+fn management_client() -> Result<reqwest::Client> {
+    Ok(reqwest::Client::builder()
+        .timeout(Duration::from_secs(SERVER_REQUEST_TIMEOUT_SECS))
+        .build()?)
+}
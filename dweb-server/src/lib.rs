@@ -28,7 +28,7 @@ use dweb::client::{DwebClient, DwebClientConfig};
 
 // Note: some of this code was modelled on the OAuth2PkceService implementation
 // in https://github.com/apicize/app/blob/64ca56852aea48032f8125a674f6af47eb56f9a4/app/src-tauri/src/pkce.rs#L240
-// The StopHandle feature been left in place in case they are wanted later, but is not yet used by dweb.
+// Each server registers its StopHandle so that it can be stopped using /dweb-0/server-stop.
 pub struct DwebService {
     client_config: DwebClientConfig,
     is_started: bool,
@@ -19,7 +19,13 @@ mod app;
 pub(crate) mod api_dweb;
 pub(crate) mod api_dweb_ant;
 pub(crate) mod helpers;
+pub(crate) mod history_healer;
+pub(crate) mod listeners;
 pub mod openapi;
+pub(crate) mod rclone;
+pub(crate) mod solid;
+pub(crate) mod tree_fs_mounts;
+pub(crate) mod webdav;
 pub(crate) mod www;
 
 use std::io;
@@ -27,6 +33,7 @@ use std::io::Error;
 use std::io::ErrorKind::NotConnected;
 use std::time::Duration;
 
+use actix_multipart::form::MultipartFormConfig;
 use actix_web::{dev::Service, middleware::Logger, web, web::Data, App, HttpServer};
 use utoipa::OpenApi;
 use utoipa_actix_web::scope::scope;
@@ -36,13 +43,16 @@ use utoipa_swagger_ui::SwaggerUi;
 use crate::StopHandle;
 use dweb::cache::directory_with_port::DirectoryVersionWithPort;
 use dweb::client::{DwebClient, DwebClientConfig};
+use dweb::config::config;
 use dweb::web::SERVER_PORTS_MAIN_PORT;
 
-pub const CONNECTION_TIMEOUT: u64 = 75;
-
 #[cfg(feature = "development")]
 pub const DWEB_SERVICE_DEBUG: &str = "debug-dweb.au";
 
+// Request bodies for WebDAV, rclone and Solid are staged in memory by a TreeFs, which
+// can't hold more than this between commits
+const TREE_FS_PAYLOAD_LIMIT: usize = dweb::files::tree_fs::MAX_STAGED_BYTES as usize;
+
 /// init_dweb_service_blocking() and init_dweb_service_non_blocking()
 ///
 /// Two init functions are provided below to allow a server to be started as blocking or non-blocking.
@@ -53,8 +63,8 @@ pub const DWEB_SERVICE_DEBUG: &str = "debug-dweb.au";
 ///     directory_version_with_port as None this server stays alive until killed on the command line. Its job is to:
 ///       1) respond to /dweb-open URLs (e.g. when opened by 'dweb open') by looking up the directory
 ///     version and if no server is running, call init_dweb_server_non_blocking() to start one before redirecting the link;
-///       2) manage DirectoryVersionsWithPort servers by killing them when it shuts down and supporting a web API
-///     for listing and killing other DirectoryVersionsWithPort servers.
+///       2) manage DirectoryVersionsWithPort servers by killing them when it shuts down or they are idle, and
+///     supporting a web API for listing and killing them (see services::listeners).
 ///
 /// Via dweb open: when it uses the server API to open an Autonomi link on the main server port and no DirectoryVersionWithPort
 ///     has been found.
@@ -122,8 +132,21 @@ pub async fn init_dweb_server_blocking(
     let port = client_config.port.unwrap_or(SERVER_PORTS_MAIN_PORT);
     let client = client.clone();
 
-    // Determine number of Actix workers from env var DWEB_WORKERS (default 12)
-    let workers = get_worker_count_from_env();
+    // Only the main server maintains owned Histories and the listeners it starts
+    if is_main_server {
+        actix_web::rt::spawn(history_healer::run_history_healer(client.clone()));
+        actix_web::rt::spawn(listeners::run_listener_reaper());
+    }
+    let listener_port = directory_version_with_port
+        .as_ref()
+        .map(|directory_version| directory_version.port);
+
+    // Every server can be stopped using /dweb-0/server-stop
+    let stop_handle = stop_handle.unwrap_or(Data::new(StopHandle::default()));
+    let stop_handle_for_app = stop_handle.clone();
+
+    // Number of Actix workers from DWEB_WORKERS or dweb.toml (default 12)
+    let workers = config().workers();
 
     let http_server = HttpServer::new(move || {
         App::new()
@@ -138,7 +161,10 @@ pub async fn init_dweb_server_blocking(
             // Macro logging using env_logger for both actix and libs such as Autonomi
             .wrap(Logger::default())
             // Log Requests and Responses to terminal
-            .wrap_fn(|req, srv| {
+            .wrap_fn(move |req, srv| {
+                if let Some(port) = listener_port {
+                    listeners::record_request(port);
+                }
                 println!(
                     "DEBUG serve with ports HttpRequest : {} {}",
                     req.head().method,
@@ -189,6 +215,7 @@ pub async fn init_dweb_server_blocking(
                     .service(api_dweb_ant::v0::archive::archive_post_private)
                     .service(api_dweb_ant::v0::archive::archive_get)
                     .service(api_dweb_ant::v0::archive::archive_get_version)
+                    .service(api_dweb_ant::v0::archive::archive_patch)
                     .service(api_dweb_ant::v0::chunk::chunk_post)
                     .service(api_dweb_ant::v0::chunk::chunk_get)
                     .service(api_dweb_ant::v0::data::data_get)
@@ -198,10 +225,12 @@ pub async fn init_dweb_server_blocking(
                     .service(api_dweb_ant::v0::pointer::pointer_get_owned)
                     .service(api_dweb_ant::v0::scratchpad::scratchpad_public_post)
                     .service(api_dweb_ant::v0::scratchpad::scratchpad_public_put)
+                    .service(api_dweb_ant::v0::scratchpad::scratchpad_public_repair)
                     .service(api_dweb_ant::v0::scratchpad::scratchpad_public_get)
                     .service(api_dweb_ant::v0::scratchpad::scratchpad_public_get_owned)
                     .service(api_dweb_ant::v0::scratchpad::scratchpad_private_post)
                     .service(api_dweb_ant::v0::scratchpad::scratchpad_private_put)
+                    .service(api_dweb_ant::v0::scratchpad::scratchpad_private_repair)
                     .service(api_dweb_ant::v0::scratchpad::scratchpad_private_get)
                     .service(api_dweb_ant::v0::scratchpad::scratchpad_private_get_owned)
                     // dweb APIs
@@ -211,8 +240,37 @@ pub async fn init_dweb_server_blocking(
                     .service(api_dweb::v0::file::file_get)
                     .service(api_dweb::v0::form::data_put)
                     .service(api_dweb::v0::form::data_put_list)
-                    .service(api_dweb::v0::wallet::wallet_balance_get),
+                    .service(api_dweb::v0::wallet::wallet_balance_get)
+                    .service(api_dweb::v0::history_healer::history_healer_get)
+                    .service(api_dweb::v0::cache_stats::cache_stats_get)
+                    .service(api_dweb::v0::upload_progress::upload_progress_get)
+                    .service(api_dweb::v0::server::server_info_get)
+                    .service(api_dweb::v0::server::server_stop_post)
+                    .service(api_dweb::v0::server::listeners_get)
+                    .service(api_dweb::v0::server::listener_delete)
+                    .service(api_dweb::v0::server::listeners_delete)
+                    .service(webdav::webdav_commit_post)
+                    .service(rclone::rclone_commit_post)
+                    .service(solid::solid_commit_post),
             )
+            // WebDAV methods such as PROPFIND are not supported by utoipa so use plain actix resources
+            .map(|app| {
+                app.service(
+                    web::resource(webdav::webdav_resource_path())
+                        .app_data(web::PayloadConfig::new(TREE_FS_PAYLOAD_LIMIT))
+                        .route(web::route().to(webdav::webdav_handler)),
+                )
+                .service(
+                    web::resource(rclone::rclone_resource_path())
+                        .app_data(web::PayloadConfig::new(TREE_FS_PAYLOAD_LIMIT))
+                        .route(web::route().to(rclone::rclone_handler)),
+                )
+                .service(
+                    web::resource(solid::solid_resource_path())
+                        .app_data(web::PayloadConfig::new(TREE_FS_PAYLOAD_LIMIT))
+                        .route(web::route().to(solid::solid_handler)),
+                )
+            })
             .default_service(web::get().to(www::www_handler))
             .openapi_service(|api| {
                 SwaggerUi::new("/swagger-ui/{_:.*}").url("/api/openapi.json", api)
@@ -222,9 +280,12 @@ pub async fn init_dweb_server_blocking(
             .app_data(Data::new(archive_address.clone()))
             .app_data(Data::new(directory_version_with_port.clone()))
             .app_data(Data::new(is_main_server))
+            .app_data(stop_handle_for_app.clone())
+            // Uploads are streamed to temporary files, so are limited only by the configured size
+            .app_data(MultipartFormConfig::default().total_limit(config().upload_limit_bytes()))
             .into_app()
     })
-    .keep_alive(Duration::from_secs(crate::services::CONNECTION_TIMEOUT))
+    .keep_alive(Duration::from_secs(config().connection_timeout()))
     .workers(workers)
     .bind((host.clone(), port));
     match http_server {
@@ -244,10 +305,12 @@ pub async fn init_dweb_server_blocking(
                 }
             };
             let running_server = server.run();
-            if let Some(stop_handle) = stop_handle {
-                stop_handle.register(running_server.handle());
+            stop_handle.register(running_server.handle());
+            let result = running_server.await;
+            if is_main_server {
+                listeners::stop_all_listeners();
             }
-            running_server.await
+            result
         }
         Err(err) => {
             eprintln!("Unable to start server at http://127.0.0.1:{port}, {err}");
@@ -255,17 +318,3 @@ pub async fn init_dweb_server_blocking(
         }
     }
 }
-
-const DEFAULT_WORKERS: usize = 12;
-
-fn parse_workers(value: Option<String>) -> usize {
-    match value.and_then(|s| s.trim().parse::<usize>().ok()) {
-        Some(n) if n >= 1 => n,
-        Some(_) => 1, // clamp to minimum of 1
-        None => DEFAULT_WORKERS,
-    }
-}
-
-fn get_worker_count_from_env() -> usize {
-    parse_workers(std::env::var("DWEB_WORKERS").ok())
-}
\ No newline at end of file
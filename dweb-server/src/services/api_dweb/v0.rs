@@ -16,9 +16,13 @@
 */
 
 pub mod app_settings;
+pub mod cache_stats;
 pub mod file;
 pub mod form;
+pub mod history_healer;
 pub mod name;
+pub mod server;
+pub mod upload_progress;
 pub mod wallet;
 
 use actix_web::{
@@ -33,6 +37,7 @@ use utoipa::ToSchema;
 use autonomi::SecretKey;
 
 use dweb::client::DwebClient;
+use dweb::helpers::scratchpad::ForkResolution;
 use dweb::storage::DwebType;
 
 use crate::services::helpers::*;
@@ -75,6 +80,8 @@ pub struct MutateQueryParams {
     owner_secret: Option<String>,
     /// ("Ant-Derivation-Index" = Option<String>, Header, description = "optional 32 character string to use instead of the dweb default when deriving keys for objects of this type"),
     type_derivation_key: Option<[u8; 32]>,
+    /// How to choose between the versions of a forked Scratchpad: counter, signature, all or merge
+    resolve: Option<String>,
 }
 
 /// Validated parameters based on request query and headers
@@ -90,6 +97,8 @@ pub struct ParsedRequestParams {
     pub type_derivation_index: Option<[u8; 32]>,
     /// optional unique string identifier for this app (as suggested by Autonomi and used to derive the VaultContentType used by an app)
     pub app_id: Option<String>,
+    /// How to choose between the versions of a forked Scratchpad
+    pub resolve: ForkResolution,
 }
 
 impl Default for ParsedRequestParams {
@@ -100,6 +109,7 @@ impl Default for ParsedRequestParams {
             owner_secret: None,
             type_derivation_index: None,
             app_id: None,
+            resolve: ForkResolution::default(),
         }
     }
 }
@@ -126,7 +136,7 @@ impl ParsedRequestParams {
                 };
             };
         }
-        let tries = tries.unwrap_or(client.api_control.api_tries);
+        let tries = tries.unwrap_or(client.api_control.retry_policy.max_tries);
 
         // TODO return error if appropriate - I'm not sure it is worth reporting header_valud.to_str() errors
         let mut object_name = query_params.object_name.clone();
@@ -177,11 +187,14 @@ impl ParsedRequestParams {
             };
         };
 
+        let resolve = parse_fork_resolution(client, &query_params.resolve)?;
+
         let mut parsed_params = ParsedRequestParams {
             tries,
             object_name,
             owner_secret,
             type_derivation_index,
+            resolve,
             ..Default::default()
         };
 
@@ -242,6 +255,18 @@ impl ParsedRequestParams {
         ))
     }
 }
+
+/// The fork resolution given by a request, or the server default if none was given
+pub(crate) fn parse_fork_resolution(
+    client: &DwebClient,
+    resolve: &Option<String>,
+) -> Result<ForkResolution> {
+    match resolve {
+        Some(resolve) => ForkResolution::parse(resolve),
+        None => Ok(client.api_control.scratchpad_resolve),
+    }
+}
+
 /// MutateResult is used to return the result of POST or PUT operations for several network data types
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct MutateResult {
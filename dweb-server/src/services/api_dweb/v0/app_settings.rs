@@ -59,9 +59,12 @@ pub async fn app_settings(
         println!("WARNING {rest_operation} not made on an app server so some information will not be available in the response");
     };
 
+    let network_profile = client.client_config.network_profile.clone();
     let settings = DwebNetworkSettings {
-        network_id: client.client.evm_network().to_string(),
+        network_id: client.network.to_string(),
         is_local: client.is_local,
+        network_name: client.client_config.network_name(),
+        network_profile,
 
         app_port: app_port,
     };
@@ -86,6 +89,7 @@ pub async fn app_settings(
 }
 
 // TODO move to v0
+use dweb::config::NetworkProfile;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 /// Information about the Autonomi network connection and the app's server
@@ -94,6 +98,10 @@ pub struct DwebNetworkSettings {
     // Network
     network_id: String,
     is_local: bool,
+    /// The name of the network profile, or "local", "alpha" or "mainnet"
+    network_name: String,
+    /// The peers, EVM and strategy settings when connected using a network profile
+    network_profile: Option<NetworkProfile>,
 
     // The app server port when serving the app from network. Otherwise -1, such as when using a local development server)
     app_port: i32,
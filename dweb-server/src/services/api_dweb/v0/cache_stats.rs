@@ -0,0 +1,62 @@
+/*
+Copyright (c) 2025- Mark Hughes
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use actix_web::{
+    get,
+    http::{header::ContentType, StatusCode},
+    HttpRequest, HttpResponse,
+};
+
+use dweb::cache::single_flight::CacheStats;
+
+use crate::services::helpers::*;
+
+/// Get the hit, miss and coalesced request counts for the server caches
+///
+/// A coalesced request is one which waited for a fetch already in progress for
+/// the same key rather than accessing the network itself.
+///
+/// url: <code>/dweb-0/cache-stats</code>
+#[utoipa::path(
+    responses(
+        (status = StatusCode::OK, description = "Success", body = [CacheStats]),
+        ),
+    tags = ["Server"],
+)]
+#[get("/cache-stats")]
+pub async fn cache_stats_get(request: HttpRequest) -> HttpResponse {
+    println!("DEBUG {}", request.path());
+    let rest_operation = "/cache-stats GET".to_string();
+    let rest_handler = "cache_stats_get()";
+
+    let stats: Vec<CacheStats> = dweb::cache::cache_stats();
+    let json = match serde_json::to_string(&stats) {
+        Ok(json) => json,
+        Err(e) => {
+            return make_error_response_page(
+                Some(StatusCode::INTERNAL_SERVER_ERROR),
+                &mut HttpResponse::NotFound(),
+                rest_operation,
+                &format!("{rest_handler} failed to encode JSON result - {e}"),
+            )
+        }
+    };
+
+    HttpResponse::Ok()
+        .insert_header(ContentType(mime::APPLICATION_JSON))
+        .body(json)
+}
@@ -15,7 +15,7 @@
  along with this program. If not, see <https://www.gnu.org/licenses/>.
 */
 
-use std::io::Read;
+use std::path::PathBuf;
 
 use actix_multipart::form::{tempfile::TempFile, MultipartForm};
 use actix_web::{
@@ -24,27 +24,42 @@ use actix_web::{
     web::Data,
     HttpRequest, HttpResponse,
 };
-use color_eyre::eyre::eyre;
+use autonomi::files::{Metadata as FileMetadata, PrivateArchive, PublicArchive};
+use color_eyre::eyre::{eyre, Report, Result};
+use futures_util::StreamExt;
 use serde::{Deserialize, Serialize};
 use utoipa::{schema, ToSchema};
 
-use dweb::helpers::retry::retry_until_ok;
+use dweb::backend::network::upload_error;
+use dweb::helpers::retry::retry_with_policy;
+use dweb::helpers::upload_progress::{estimated_chunks, UploadProgressReporter, UploadStage};
+use dweb::storage::publish_archive_to_history;
 use dweb::{client::DwebClient, token::format_tokens_as_attos};
 
+use super::upload_progress::{upload_progress_finished, upload_progress_reporter};
 use super::{DwebType, MutateResult};
 use crate::services::helpers::*;
 
 #[derive(Deserialize, ToSchema)]
 struct QueryParams {
     tries: Option<u32>,
+    progress_id: Option<String>,
+    parallel: Option<usize>,
+    history_name: Option<String>,
+    create_history: Option<bool>,
 }
+
+/// Default number of files of a /form-upload-file-list request uploaded at once
+const DEFAULT_PARALLEL_UPLOADS: usize = 4;
+/// The most files of a /form-upload-file-list request uploaded at once
+const MAX_PARALLEL_UPLOADS: usize = 16;
+
 // NOTES:
 //  To derive ToSchema can try:
 //      Building the schema or faking the struct: https://github.com/juhaku/utoipa/discussions/742
 //      Using #[schema(...)] (see https://docs.rs/utoipa/latest/utoipa/derive.ToSchema.html#mixed-enum-unit-field-variant-optional-configuration-options-for-serdeschema)
 #[derive(Debug, MultipartForm, ToSchema)]
 struct UploadForm {
-    #[multipart(limit = "100MB")] // TODO remove limit when streaming supported in Autonomi APIs
     #[schema(value_type = String, format = Binary)]
     file: TempFile,
     // #[schema(value_type = String)]
@@ -79,7 +94,8 @@ struct UploadForm {
     put,
     params(
         ("make_public" = bool, description = "true to upload data as public"),
-        ("tries" = Option<u32>, Query, description = "number of times to try calling the Autonomi upload API for each file upload, 0 means unlimited. This overrides the API control setting in the server.")),
+        ("tries" = Option<u32>, Query, description = "number of times to try calling the Autonomi upload API for each file upload, 0 means unlimited. This overrides the API control setting in the server."),
+        ("progress_id" = Option<String>, Query, description = "a unique id with which to follow progress of the upload using /upload-progress/{progress_id}")),
     request_body(content = UploadForm, content_type = "multipart/form-data"),
     responses(
         (status = StatusCode::CREATED, description = "A MutateResult featuring either status 201 with cost and data address on the network, or in case of error an error status code and message about the error.<br/>\
@@ -92,21 +108,33 @@ struct UploadForm {
 )]
 #[put("/form-upload-file/{make_public}")]
 pub async fn data_put(
-    MultipartForm(mut form): MultipartForm<UploadForm>,
+    MultipartForm(form): MultipartForm<UploadForm>,
     request: HttpRequest,
     path_params: web::Path<bool>,
     query_params: web::Query<QueryParams>,
     client: Data<dweb::client::DwebClient>,
 ) -> HttpResponse {
     let make_public = path_params.into_inner();
-    let tries = query_params.tries.unwrap_or(client.api_control.api_tries);
+    let tries = query_params
+        .tries
+        .unwrap_or(client.api_control.retry_policy.max_tries);
 
     println!("DEBUG {}", request.path());
+    let progress = start_progress(
+        query_params.progress_id.as_deref(),
+        std::slice::from_ref(&form.file),
+        make_public,
+    );
     let mutate_result = if make_public {
-        put_file_public(&client, &mut form.file, tries).await
+        put_file_public(&client, &form.file, tries, progress.as_ref()).await
     } else {
-        put_file_private(&client, &mut form.file, tries).await
+        put_file_private(&client, &form.file, tries, progress.as_ref()).await
     };
+    finish_progress(
+        query_params.progress_id.as_deref(),
+        progress,
+        std::slice::from_ref(&mutate_result),
+    );
 
     mutate_result.make_response("/form-upload-file PUT error", "data_put()")
 }
@@ -114,7 +142,6 @@ pub async fn data_put(
 #[derive(Debug, MultipartForm, ToSchema)]
 struct UploadFormList {
     // #[multipart(rename = "file")]
-    #[multipart(limit = "100MB")] // TODO remove limit when streaming supported in Autonomi APIs
     #[schema(value_type = Vec<String>, format = Binary)]
     files: Vec<TempFile>,
 }
@@ -122,6 +149,8 @@ struct UploadFormList {
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct MutateResultList {
     mutate_results: Vec<MutateResult>,
+    /// When history_name is given, the result of publishing the uploaded files as a new version
+    history_result: Option<MutateResult>,
 }
 
 /// Multipart form upload of one or more files (as public or private)
@@ -130,6 +159,14 @@ pub struct MutateResultList {
 /// instead of a file by using JavaScript. Either with a FormData object,
 /// or by setting properties on an input element.
 ///
+/// Files are uploaded several at a time, and their total size is limited by the
+/// server's upload_limit_mb setting (DWEB_UPLOAD_LIMIT_MB).
+///
+/// If history_name is given, the files stored are also published as a new version
+/// of that History, owned by the server's app secret key. The version holds only the
+/// uploaded files, each at the root of the directory tree and named by the last component
+/// of its file name. The request is rejected if a file name is empty, '.' or '..'.
+///
 /// Example form:
 /// ```
 /// <form target="/form-upload-file-list/true" method="put" enctype="multipart/form-data">
@@ -143,7 +180,11 @@ pub struct MutateResultList {
     put,
     params(
         ("make_public" = bool, description = "true to upload data as public"),
-        ("tries" = Option<u32>, Query, description = "number of times to try calling the Autonomi upload API for each file upload, 0 means unlimited. This overrides the API control setting in the server.")),
+        ("tries" = Option<u32>, Query, description = "number of times to try calling the Autonomi upload API for each file upload, 0 means unlimited. This overrides the API control setting in the server."),
+        ("progress_id" = Option<String>, Query, description = "a unique id with which to follow progress of the upload using /upload-progress/{progress_id}"),
+        ("parallel" = Option<usize>, Query, description = "maximum number of files to upload at once (default 4, at most 16)"),
+        ("history_name" = Option<String>, Query, description = "NAME of a History to which the uploaded files are added as a new version"),
+        ("create_history" = Option<bool>, Query, description = "true to create the History called history_name (default false)")),
     request_body(content = UploadFormList, content_type = "multipart/form-data"),
     responses(
         (status = StatusCode::CREATED, description = "Returned if any successful storage occurs. A MutateResultList is returned featuring a MutateResult for each upload either status 201 with cost and data address on the network, or in case of error an error status code and message about the error. Inspect the individual MutateResult.status_code values to see which have been successful.<br/>\
@@ -151,6 +192,7 @@ pub struct MutateResultList {
         &nbsp;&nbsp;&nbsp;INTERNAL_SERVER_ERROR: Error reading file or storing in memory<br/>\
         &nbsp;&nbsp;&nbsp;BAD_GATEWAY: Autonomi network error", body = [MutateResultList],
             example = json!("{\"mutate_results\": [{\"file_name\": \"somefile.txt\", \"status\": \"201\", \"cost_in_ant\": \"12\", \"data_address\": \"a9cd8dd0c9f2b9dc71ad548d1f37fcba6597d5eb1be0b8c63793802cc6c7de27\", \"data_map\": \"\", \"message\": \"\" }]}")),
+        (status = StatusCode::BAD_REQUEST, description = "history_name is given and a file name is empty, '.' or '..'"),
     ),
     tags = ["Dweb"],
 )]
@@ -167,24 +209,84 @@ pub async fn data_put_list(
     let rest_handler = "data_put_list()";
 
     let make_public = path_params.into_inner();
-    let tries = query_params.tries.unwrap_or(client.api_control.api_tries);
+    let tries = query_params
+        .tries
+        .unwrap_or(client.api_control.retry_policy.max_tries);
 
-    let mut put_list = MutateResultList {
-        mutate_results: Vec::<MutateResult>::new(),
-    };
-    for mut file in form.files {
-        println!(
-            "DEBUG data_put_list() file: {:?}, size: {}",
-            file.file_name, file.size
-        );
-        let mutate_result = if make_public {
-            put_file_public(&client, &mut file, tries).await
-        } else {
-            put_file_private(&client, &mut file, tries).await
-        };
-        put_list.mutate_results.push(mutate_result);
+    // Check names before paying for any uploads, as they become paths in the History
+    if query_params.history_name.is_some() {
+        for file in form.files.iter() {
+            let file_name = file.file_name.as_deref().unwrap_or("unknown");
+            if archive_file_name(file_name).is_none() {
+                return make_error_response_page(
+                    Some(StatusCode::BAD_REQUEST),
+                    &mut HttpResponse::BadRequest(),
+                    rest_operation.to_string(),
+                    &format!("{rest_handler} invalid file name '{file_name}'"),
+                );
+            }
+        }
     }
 
+    let progress = start_progress(
+        query_params.progress_id.as_deref(),
+        &form.files,
+        make_public,
+    );
+    let parallel = query_params
+        .parallel
+        .unwrap_or(DEFAULT_PARALLEL_UPLOADS)
+        .clamp(1, MAX_PARALLEL_UPLOADS);
+    let mutate_results: Vec<MutateResult> = futures_util::stream::iter(form.files.iter())
+        .map(|file| {
+            println!(
+                "DEBUG data_put_list() file: {:?}, size: {}",
+                file.file_name, file.size
+            );
+            let client = &client;
+            let progress = progress.as_ref();
+            async move {
+                if make_public {
+                    put_file_public(client, file, tries, progress).await
+                } else {
+                    put_file_private(client, file, tries, progress).await
+                }
+            }
+        })
+        .buffered(parallel)
+        .collect()
+        .await;
+
+    let history_result = match &query_params.history_name {
+        Some(history_name) => {
+            if let Some(progress) = &progress {
+                progress.set_stage(UploadStage::UpdatingHistory);
+            }
+            Some(
+                publish_uploads_to_history(
+                    &client,
+                    &form.files,
+                    &mutate_results,
+                    make_public,
+                    history_name,
+                    query_params.create_history.unwrap_or(false),
+                )
+                .await,
+            )
+        }
+        None => None,
+    };
+
+    let put_list = MutateResultList {
+        mutate_results,
+        history_result,
+    };
+    finish_progress(
+        query_params.progress_id.as_deref(),
+        progress,
+        &put_list.mutate_results,
+    );
+
     let json = match serde_json::to_string(&put_list) {
         Ok(json) => json,
         Err(e) => {
@@ -204,41 +306,44 @@ pub async fn data_put_list(
         .body(json)
 }
 
-async fn put_file_public(client: &DwebClient, file: &mut TempFile, tries: u32) -> MutateResult {
+async fn put_file_public(
+    client: &DwebClient,
+    file: &TempFile,
+    tries: u32,
+    progress: Option<&UploadProgressReporter>,
+) -> MutateResult {
     let dweb_type = DwebType::PublicFile;
 
-    // TODO update if Autonomi supports streamed uploads
-    let mut content = Vec::<u8>::new();
+    // Upload from the temporary file so that it is never held in memory
     let file_name = file.file_name.clone().unwrap_or("unknown".to_string());
-    let content_len = match file.file.read_to_end(&mut content) {
-        Ok(content_len) => content_len,
-        Err(e) => {
-            let status_message =
-                format!("put_file_public() failed to read file '{file_name}' into buffer - {e}");
-            println!("DEBUG {status_message}");
-            return MutateResult {
-                dweb_type,
-                status_code: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
-                status_message,
-                file_name,
-                ..Default::default()
-            };
-        }
+    let file_path = file.file.path().to_path_buf();
+    let content_len = file.size;
+
+    if let Some(progress) = progress {
+        progress.file_started(&file_name);
+    }
+    let retry_policy = client
+        .api_control
+        .retry_policy
+        .with_max_tries(tries)
+        .for_uploads();
+    let retry_policy = match progress {
+        Some(progress) => progress.retry_policy(&retry_policy),
+        None => retry_policy,
     };
 
-    let data = content.into();
     let payment_option = client.payment_option().clone();
-    let result = retry_until_ok(
-        tries,
-        &"data_put_public()",
-        (data, payment_option),
-        async move |(data, payment_option)| match client
-            .client
-            .data_put_public(data, payment_option.clone())
+    let result = retry_with_policy(
+        &retry_policy,
+        &"file_content_upload_public()",
+        (file_path, payment_option),
+        async move |(file_path, payment_option)| match client
+            .network_client()?
+            .file_content_upload_public(file_path, payment_option.clone())
             .await
         {
             Ok(result) => Ok(result),
-            Err(e) => Err(eyre!(e)),
+            Err(e) => Err(Report::new(upload_error(&e))),
         },
     )
     .await;
@@ -246,6 +351,13 @@ async fn put_file_public(client: &DwebClient, file: &mut TempFile, tries: u32) -
     match result {
         Ok(result) => {
             println!("DEBUG put_file_public() stored '{file_name}' {content_len} bytes on the network at address");
+            if let Some(progress) = progress {
+                progress.add_cost(result.0);
+                progress.file_done(
+                    content_len as u64,
+                    estimated_chunks(content_len as u64, true),
+                );
+            }
             MutateResult {
                 dweb_type,
                 status_code: StatusCode::CREATED.as_u16(),
@@ -271,41 +383,44 @@ async fn put_file_public(client: &DwebClient, file: &mut TempFile, tries: u32) -
     }
 }
 
-async fn put_file_private(client: &DwebClient, file: &mut TempFile, tries: u32) -> MutateResult {
+async fn put_file_private(
+    client: &DwebClient,
+    file: &TempFile,
+    tries: u32,
+    progress: Option<&UploadProgressReporter>,
+) -> MutateResult {
     let dweb_type = DwebType::PrivateFile;
 
-    // TODO update if Autonomi supports streamed data_put() (or file_put_public())
-    let mut content = Vec::<u8>::new();
+    // Upload from the temporary file so that it is never held in memory
     let file_name = file.file_name.clone().unwrap_or("unknown".to_string());
-    let content_len = match file.file.read_to_end(&mut content) {
-        Ok(content_len) => content_len,
-        Err(e) => {
-            let status_message =
-                format!("put_file_private() failed to read file '{file_name}' into buffer - {e}");
-            println!("DEBUG {status_message}");
-            return MutateResult {
-                dweb_type,
-                status_code: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
-                status_message,
-                file_name,
-                ..Default::default()
-            };
-        }
+    let file_path = file.file.path().to_path_buf();
+    let content_len = file.size;
+
+    if let Some(progress) = progress {
+        progress.file_started(&file_name);
+    }
+    let retry_policy = client
+        .api_control
+        .retry_policy
+        .with_max_tries(tries)
+        .for_uploads();
+    let retry_policy = match progress {
+        Some(progress) => progress.retry_policy(&retry_policy),
+        None => retry_policy,
     };
 
-    let data = content.into();
     let payment_option = client.payment_option().clone();
-    let result = retry_until_ok(
-        tries,
-        &"data_put()",
-        (data, payment_option),
-        async move |(data, payment_option)| match client
-            .client
-            .data_put(data, payment_option.clone())
+    let result = retry_with_policy(
+        &retry_policy,
+        &"file_content_upload()",
+        (file_path, payment_option),
+        async move |(file_path, payment_option)| match client
+            .network_client()?
+            .file_content_upload(file_path, payment_option.clone())
             .await
         {
             Ok(result) => Ok(result),
-            Err(e) => Err(eyre!(e)),
+            Err(e) => Err(Report::new(upload_error(&e))),
         },
     )
     .await;
@@ -313,6 +428,13 @@ async fn put_file_private(client: &DwebClient, file: &mut TempFile, tries: u32)
     match result {
         Ok(result) => {
             println!("DEBUG put_file_private() stored '{file_name}' {content_len} bytes on the network at address");
+            if let Some(progress) = progress {
+                progress.add_cost(result.0);
+                progress.file_done(
+                    content_len as u64,
+                    estimated_chunks(content_len as u64, false),
+                );
+            }
             MutateResult {
                 dweb_type,
                 status_code: StatusCode::CREATED.as_u16(),
@@ -337,3 +459,179 @@ async fn put_file_private(client: &DwebClient, file: &mut TempFile, tries: u32)
         }
     }
 }
+
+/// If the client gave a progress_id, get its reporter and add the files to be uploaded
+fn start_progress(
+    progress_id: Option<&str>,
+    files: &[TempFile],
+    make_public: bool,
+) -> Option<UploadProgressReporter> {
+    let progress = upload_progress_reporter(progress_id?);
+    let bytes = files.iter().map(|file| file.size as u64).sum();
+    let chunks = files
+        .iter()
+        .map(|file| estimated_chunks(file.size as u64, make_public))
+        .sum();
+    progress.add_files_to_upload(files.len() as u64, bytes, chunks);
+    progress.set_stage(UploadStage::UploadingFiles);
+    Some(progress)
+}
+
+fn finish_progress(
+    progress_id: Option<&str>,
+    progress: Option<UploadProgressReporter>,
+    mutate_results: &[MutateResult],
+) {
+    if let (Some(progress_id), Some(progress)) = (progress_id, progress) {
+        let failures = mutate_results
+            .iter()
+            .filter(|result| result.status_code != StatusCode::CREATED.as_u16())
+            .count();
+        if failures == 0 {
+            progress.complete();
+        } else {
+            progress.failed(&format!(
+                "{failures} of {} uploads failed",
+                mutate_results.len()
+            ));
+        }
+        upload_progress_finished(progress_id);
+    }
+}
+
+/// Publish the files stored by a /form-upload-file-list request as a new version of a History
+async fn publish_uploads_to_history(
+    client: &DwebClient,
+    files: &[TempFile],
+    mutate_results: &[MutateResult],
+    make_public: bool,
+    history_name: &String,
+    create_history: bool,
+) -> MutateResult {
+    let result = match uploads_archive_bytes(files, mutate_results, make_public) {
+        Ok(archive_bytes) => match dweb::helpers::get_app_secret_key() {
+            Ok(app_secret_key) => {
+                publish_archive_to_history(
+                    client,
+                    app_secret_key,
+                    history_name,
+                    archive_bytes,
+                    create_history,
+                )
+                .await
+            }
+            Err(e) => Err(e),
+        },
+        Err(e) => Err(e),
+    };
+
+    match result {
+        Ok((cost, history_address, version)) => {
+            println!(
+                "DEBUG publish_uploads_to_history() published version {version} of History '{history_name}'"
+            );
+            MutateResult {
+                dweb_type: DwebType::History,
+                status_code: StatusCode::CREATED.as_u16(),
+                status_message: format!("success - version {version}"),
+                cost_in_ant: format_tokens_as_attos(cost.as_atto()),
+                object_name: history_name.clone(),
+                network_address: history_address.to_hex(),
+                ..Default::default()
+            }
+        }
+        Err(e) => {
+            let status_message = format!(
+                "publish_uploads_to_history() failed to publish to History '{history_name}' - {e}"
+            );
+            println!("DEBUG {status_message}");
+            MutateResult {
+                dweb_type: DwebType::History,
+                status_code: StatusCode::BAD_GATEWAY.as_u16(),
+                status_message,
+                object_name: history_name.clone(),
+                ..Default::default()
+            }
+        }
+    }
+}
+
+/// Make an archive of the files which were stored, each at the root of the directory tree
+fn uploads_archive_bytes(
+    files: &[TempFile],
+    mutate_results: &[MutateResult],
+    make_public: bool,
+) -> Result<bytes::Bytes> {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0);
+
+    let mut public_archive = PublicArchive::new();
+    let mut private_archive = PrivateArchive::new();
+    let mut count = 0;
+    for (file, result) in files.iter().zip(mutate_results) {
+        if result.status_code != StatusCode::CREATED.as_u16() {
+            continue;
+        }
+        let metadata = FileMetadata {
+            created: now,
+            modified: now,
+            size: file.size as u64,
+            extra: None,
+        };
+        let file_name = match archive_file_name(&result.file_name) {
+            Some(file_name) => file_name,
+            None => return Err(eyre!("invalid file name '{}'", result.file_name)),
+        };
+        let path = PathBuf::from("/").join(file_name);
+        if make_public {
+            let data_address = autonomi::data::DataAddress::from_hex(&result.network_address)?;
+            public_archive.add_file(path, data_address, metadata);
+        } else {
+            let datamap_chunk = autonomi::chunk::DataMapChunk::from_hex(&result.data_map)?;
+            private_archive.add_file(path.clone(), datamap_chunk, metadata);
+            // PrivateArchive::add_file() drops the leading slash (see storage::publish_or_update_files())
+            let _ = private_archive.rename_file(PathBuf::from(file_name).as_path(), path.as_path());
+        }
+        count = count + 1;
+    }
+
+    if count == 0 {
+        return Err(eyre!("no files were stored"));
+    }
+    let archive_bytes = if make_public {
+        public_archive.to_bytes()
+    } else {
+        private_archive.to_bytes()
+    };
+    archive_bytes.map_err(|e| eyre!("Failed to serialize archive: {e:?}"))
+}
+
+/// The name of an uploaded file within the archive of a History
+///
+/// Only the last component of the name given by the client is used, so that a file can't
+/// be placed elsewhere in the directory tree. Returns None if this is empty, '.' or '..'.
+fn archive_file_name(file_name: &str) -> Option<&str> {
+    let name = file_name.rsplit(['/', '\\']).next().unwrap_or("");
+    match name {
+        "" | "." | ".." => None,
+        name => Some(name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn archive_file_names_are_the_last_component() {
+        assert_eq!(archive_file_name("index.html"), Some("index.html"));
+        assert_eq!(archive_file_name("../../etc/passwd"), Some("passwd"));
+        assert_eq!(archive_file_name("/a/b/c.txt"), Some("c.txt"));
+        assert_eq!(archive_file_name("dir\\file.txt"), Some("file.txt"));
+        for invalid in ["", ".", "..", "dir/", "dir/..", "a\\."] {
+            assert_eq!(archive_file_name(invalid), None, "{invalid}");
+        }
+    }
+}
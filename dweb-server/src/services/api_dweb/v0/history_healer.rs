@@ -0,0 +1,61 @@
+/*
+Copyright (c) 2025- Mark Hughes
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use actix_web::{
+    get,
+    http::{header::ContentType, StatusCode},
+    HttpRequest, HttpResponse,
+};
+
+use crate::services::helpers::*;
+use crate::services::history_healer::{history_healer_status, HistoryHealerStatus};
+
+/// Get the status of the background task which heals the pointers of owned Histories
+///
+/// The healer only runs on the main dweb server and checks each History published
+/// from this device. See DWEB_HEAL_INTERVAL and DWEB_HEAL_MAX_PUTS for configuration.
+///
+/// url: <code>/dweb-0/history-healer</code>
+#[utoipa::path(
+    responses(
+        (status = StatusCode::OK, description = "Success", body = HistoryHealerStatus),
+        ),
+    tags = ["Server"],
+)]
+#[get("/history-healer")]
+pub async fn history_healer_get(request: HttpRequest) -> HttpResponse {
+    println!("DEBUG {}", request.path());
+    let rest_operation = "/history-healer GET".to_string();
+    let rest_handler = "history_healer_get()";
+
+    let status: HistoryHealerStatus = history_healer_status();
+    let json = match serde_json::to_string(&status) {
+        Ok(json) => json,
+        Err(e) => {
+            return make_error_response_page(
+                Some(StatusCode::INTERNAL_SERVER_ERROR),
+                &mut HttpResponse::NotFound(),
+                rest_operation,
+                &format!("{rest_handler} failed to encode JSON result - {e}"),
+            )
+        }
+    };
+
+    HttpResponse::Ok()
+        .insert_header(ContentType(mime::APPLICATION_JSON))
+        .body(json)
+}
@@ -0,0 +1,240 @@
+/*
+ Copyright (c) 2025- Mark Hughes
+
+ This program is free software: you can redistribute it and/or modify
+ it under the terms of the GNU Affero General Public License as published by
+ the Free Software Foundation, either version 3 of the License, or
+ (at your option) any later version.
+
+ This program is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY; without even the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU Affero General Public License for more details.
+
+ You should have received a copy of the GNU Affero General Public License
+ along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::time::Duration;
+
+use actix_web::{
+    delete, get,
+    http::{header::ContentType, StatusCode},
+    post,
+    web::{self, Data},
+    HttpRequest, HttpResponse,
+};
+
+use dweb::cache::directory_with_port::{
+    forget_directory_version_with_port, DirectoryVersionWithPort,
+};
+use dweb::client::DwebClient;
+use dweb::web::server::{ListenerInfo, ServerInfo};
+use dweb::web::SERVER_PORTS_MAIN_PORT;
+
+use crate::services::helpers::*;
+use crate::services::listeners::{listener_infos, stop_all_listeners, stop_listener};
+use crate::StopHandle;
+
+// Allows the response to a stop request to be sent before the server stops
+const STOP_DELAY_MILLIS: u64 = 200;
+
+/// Get information about this dweb server
+///
+/// A main server lists every listener it has started to serve a directory/website on
+/// its own port. A listener lists only itself.
+///
+/// url: <code>/dweb-0/server-info</code>
+#[utoipa::path(
+    responses(
+        (status = StatusCode::OK, description = "Success", body = ServerInfo),
+        ),
+    tags = ["Server"],
+)]
+#[get("/server-info")]
+pub async fn server_info_get(
+    request: HttpRequest,
+    client: Data<DwebClient>,
+    is_main_server: Data<bool>,
+    our_directory_version: Data<Option<DirectoryVersionWithPort>>,
+) -> HttpResponse {
+    println!("DEBUG {}", request.path());
+    let rest_operation = "/server-info GET".to_string();
+    let rest_handler = "server_info_get()";
+
+    let (port, listeners) = match our_directory_version.as_ref() {
+        Some(directory_version) => (
+            directory_version.port,
+            vec![directory_version.listener_info()],
+        ),
+        None => (
+            client.client_config.port.unwrap_or(SERVER_PORTS_MAIN_PORT),
+            listener_infos(),
+        ),
+    };
+
+    let info = ServerInfo {
+        host: client.host.clone(),
+        port,
+        pid: std::process::id(),
+        dweb_version: env!("CARGO_PKG_VERSION").to_string(),
+        is_main_server: *is_main_server.get_ref(),
+        network: client.network.to_string(),
+        listeners,
+    };
+
+    let json = match serde_json::to_string(&info) {
+        Ok(json) => json,
+        Err(e) => {
+            return make_error_response_page(
+                Some(StatusCode::INTERNAL_SERVER_ERROR),
+                &mut HttpResponse::NotFound(),
+                rest_operation,
+                &format!("{rest_handler} failed to encode JSON result - {e}"),
+            )
+        }
+    };
+
+    HttpResponse::Ok()
+        .insert_header(ContentType(mime::APPLICATION_JSON))
+        .body(json)
+}
+
+/// Stop this dweb server
+///
+/// The server stops gracefully once it has finished handling current requests. Stopping
+/// a listener means the next request to open its directory/website will start a new one.
+/// Stopping a main server also stops the listeners it started.
+///
+/// url: <code>/dweb-0/server-stop</code>
+#[utoipa::path(
+    responses(
+        (status = StatusCode::OK, description = "The server is stopping"),
+        ),
+    tags = ["Server"],
+)]
+#[post("/server-stop")]
+pub async fn server_stop_post(
+    request: HttpRequest,
+    stop_handle: Data<StopHandle>,
+    our_directory_version: Data<Option<DirectoryVersionWithPort>>,
+) -> HttpResponse {
+    println!("DEBUG {}", request.path());
+
+    if let Some(directory_version) = our_directory_version.as_ref() {
+        forget_directory_version_with_port(directory_version.archive_address);
+    }
+
+    actix_web::rt::spawn(async move {
+        actix_web::rt::time::sleep(Duration::from_millis(STOP_DELAY_MILLIS)).await;
+        stop_handle.stop(true);
+    });
+
+    HttpResponse::Ok()
+        .insert_header(ContentType(mime::TEXT_PLAIN))
+        .body("stopping")
+}
+
+/// List the listeners started by this server, each serving a directory/website on its own port
+///
+/// A listener is stopped when it has not handled a request for DWEB_LISTENER_IDLE seconds
+/// (default 1800, 0 to never stop idle listeners).
+///
+/// url: <code>/dweb-0/listeners</code>
+#[utoipa::path(
+    responses(
+        (status = StatusCode::OK, description = "Success", body = [ListenerInfo]),
+        ),
+    tags = ["Server"],
+)]
+#[get("/listeners")]
+pub async fn listeners_get(request: HttpRequest) -> HttpResponse {
+    println!("DEBUG {}", request.path());
+    let rest_operation = "/listeners GET".to_string();
+    let rest_handler = "listeners_get()";
+
+    let json = match serde_json::to_string(&listener_infos()) {
+        Ok(json) => json,
+        Err(e) => {
+            return make_error_response_page(
+                Some(StatusCode::INTERNAL_SERVER_ERROR),
+                &mut HttpResponse::NotFound(),
+                rest_operation,
+                &format!("{rest_handler} failed to encode JSON result - {e}"),
+            )
+        }
+    };
+
+    HttpResponse::Ok()
+        .insert_header(ContentType(mime::APPLICATION_JSON))
+        .body(json)
+}
+
+/// Stop the listener on a port
+///
+/// The listener stops gracefully once it has finished handling current requests. Opening
+/// its directory/website again will start a new listener.
+///
+/// url: <code>/dweb-0/listeners/{port}</code>
+#[utoipa::path(
+    params(
+        ("port" = u16, Path, description = "the port of the listener to stop"),
+    ),
+    responses(
+        (status = StatusCode::OK, description = "The listener is stopping"),
+        (status = StatusCode::NOT_FOUND, description = "No listener was started on the port"),
+        ),
+    tags = ["Server"],
+)]
+#[delete("/listeners/{port}")]
+pub async fn listener_delete(request: HttpRequest, port: web::Path<u16>) -> HttpResponse {
+    println!("DEBUG {}", request.path());
+    let port = port.into_inner();
+
+    if !stop_listener(port) {
+        return make_error_response_page(
+            Some(StatusCode::NOT_FOUND),
+            &mut HttpResponse::NotFound(),
+            "/listeners DELETE".to_string(),
+            &format!("No listener was started on port {port}"),
+        );
+    }
+
+    HttpResponse::Ok()
+        .insert_header(ContentType(mime::TEXT_PLAIN))
+        .body("stopping")
+}
+
+/// Stop every listener started by this server
+///
+/// Returns the ports of the listeners being stopped.
+///
+/// url: <code>/dweb-0/listeners</code>
+#[utoipa::path(
+    responses(
+        (status = StatusCode::OK, description = "The ports of the listeners being stopped", body = [u16]),
+        ),
+    tags = ["Server"],
+)]
+#[delete("/listeners")]
+pub async fn listeners_delete(request: HttpRequest) -> HttpResponse {
+    println!("DEBUG {}", request.path());
+    let rest_operation = "/listeners DELETE".to_string();
+    let rest_handler = "listeners_delete()";
+
+    let json = match serde_json::to_string(&stop_all_listeners()) {
+        Ok(json) => json,
+        Err(e) => {
+            return make_error_response_page(
+                Some(StatusCode::INTERNAL_SERVER_ERROR),
+                &mut HttpResponse::NotFound(),
+                rest_operation,
+                &format!("{rest_handler} failed to encode JSON result - {e}"),
+            )
+        }
+    };
+
+    HttpResponse::Ok()
+        .insert_header(ContentType(mime::APPLICATION_JSON))
+        .body(json)
+}
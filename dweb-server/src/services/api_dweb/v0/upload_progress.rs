@@ -0,0 +1,121 @@
+/*
+ Copyright (c) 2025- Mark Hughes
+
+ This program is free software: you can redistribute it and/or modify
+ it under the terms of the GNU Affero General Public License as published by
+ the Free Software Foundation, either version 3 of the License, or
+ (at your option) any later version.
+
+ This program is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY; without even the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU Affero General Public License for more details.
+
+ You should have received a copy of the GNU Affero General Public License
+ along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::Duration;
+
+use actix_web::{
+    get,
+    http::{header, header::ContentType, StatusCode},
+    web, HttpRequest, HttpResponse,
+};
+
+use dweb::helpers::upload_progress::{UploadProgress, UploadProgressReporter};
+
+/// How long the final state of an upload remains available after it finishes
+const FINISHED_UPLOAD_RETENTION_SECS: u64 = 60;
+
+/// UPLOADS holds the progress of uploads which specify a progress_id
+///
+/// Key:     the progress_id chosen by the client
+///
+/// Entry:   UploadProgressReporter
+///
+/// An entry is created by whichever comes first, the upload or a request for its
+/// progress, so a client can subscribe before starting the upload.
+static UPLOADS: LazyLock<Mutex<HashMap<String, UploadProgressReporter>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Get the reporter for progress_id, creating it if necessary
+pub fn upload_progress_reporter(progress_id: &str) -> UploadProgressReporter {
+    match UPLOADS.lock() {
+        Ok(mut uploads) => uploads
+            .entry(progress_id.to_string())
+            .or_insert_with(UploadProgressReporter::new)
+            .clone(),
+        // Progress will not be visible to subscribers but the upload is unaffected
+        Err(_) => UploadProgressReporter::new(),
+    }
+}
+
+/// Forget progress_id once subscribers have had time to see the final state
+pub fn upload_progress_finished(progress_id: &str) {
+    let progress_id = progress_id.to_string();
+    actix_web::rt::spawn(async move {
+        actix_web::rt::time::sleep(Duration::from_secs(FINISHED_UPLOAD_RETENTION_SECS)).await;
+        if let Ok(mut uploads) = UPLOADS.lock() {
+            uploads.remove(&progress_id);
+        }
+    });
+}
+
+/// Follow the progress of an upload as Server-Sent Events
+///
+/// To follow an upload, choose a unique PROGRESS-ID and open this URL using an
+/// EventSource before starting the upload with the query parameter <code>progress_id=PROGRESS-ID</code>.
+/// The endpoints which support this are:
+/// <code>/form-upload-file</code> and <code>/form-upload-file-list</code>.
+///
+/// Each event holds an UploadProgress as JSON. The stream ends after the event
+/// whose stage is Complete or Failed.
+///
+/// url: <code>/dweb-0/upload-progress/{progress_id}</code>
+#[utoipa::path(
+    params(
+        ("progress_id" = String, Path, description = "the progress_id given when starting the upload"),
+    ),
+    responses(
+        (status = StatusCode::OK, description = "A stream of Server-Sent Events, each holding an UploadProgress as JSON", body = UploadProgress, content_type = "text/event-stream"),
+        ),
+    tags = ["Dweb"],
+)]
+#[get("/upload-progress/{progress_id}")]
+pub async fn upload_progress_get(
+    request: HttpRequest,
+    progress_id: web::Path<String>,
+) -> HttpResponse {
+    println!("DEBUG {}", request.path());
+    let receiver = upload_progress_reporter(&progress_id.into_inner()).subscribe();
+
+    // The first event is sent immediately, then one for each change until the upload finishes
+    let events = futures_util::stream::unfold(Some((receiver, true)), |state| async move {
+        let (mut receiver, is_first) = state?;
+        if !is_first && receiver.changed().await.is_err() {
+            return None;
+        }
+        let progress = receiver.borrow_and_update().clone();
+        let event = match serde_json::to_string(&progress) {
+            Ok(json) => format!("data: {json}\n\n"),
+            Err(e) => format!("event: error\ndata: {e}\n\n"),
+        };
+        let next_state = if progress.is_finished() {
+            None
+        } else {
+            Some((receiver, false))
+        };
+        Some((
+            Ok::<_, actix_web::Error>(web::Bytes::from(event)),
+            next_state,
+        ))
+    });
+
+    HttpResponse::Ok()
+        .insert_header(ContentType(mime::TEXT_EVENT_STREAM))
+        .insert_header((header::CACHE_CONTROL, "no-cache"))
+        .streaming(events)
+}
@@ -21,7 +21,7 @@ use std::path::PathBuf;
 use actix_web::{
     get,
     http::{header::ContentType, StatusCode},
-    post,
+    patch, post,
     web::{self, Data},
     HttpRequest, HttpResponse,
 };
@@ -36,10 +36,11 @@ use autonomi::{files::PrivateArchive, files::PublicArchive};
 
 use dweb::client::DwebClient;
 use dweb::files::archive::DualArchive;
-use dweb::files::directory::Tree;
-use dweb::helpers::{convert::*, retry::retry_until_ok, web::*};
+use dweb::files::directory::{Tree, VersionManifest};
+use dweb::helpers::{convert::*, retry::retry_with_policy, web::*};
 use dweb::history::History;
-use dweb::storage::DwebType;
+use dweb::storage::{publish_archive_address_to_history, store_archive_bytes, DwebType};
+use dweb::token::format_tokens_as_attos;
 
 use crate::services::api_dweb::v0::MutateResult;
 use crate::services::helpers::*;
@@ -128,9 +129,9 @@ pub async fn archive_get(
 ///
 /// Path parameters refer to the required version and dweb History:
 ///
-///     [v{VERSION-NUMBER}/]{ADDRESS-OR-NAME}
+///     [v{VERSION-NUMBER-OR-TAG}/]{ADDRESS-OR-NAME}
 ///
-/// VERSION-NUMBER      Optional version when ADDRESS-OR-NAME refers to a <code>History<Tree></code>
+/// VERSION-NUMBER-OR-TAG   Optional version, or a tag given when the version was published, when ADDRESS-OR-NAME refers to a <code>History<Tree></code>
 ///
 /// ADDRESS-OR-NAME     A hexadecimal address or a short name referring to a History or an Autonomi archive
 ///
@@ -170,8 +171,8 @@ pub async fn archive_get_version(
         }
     };
 
-    let (version, _as_name, address_or_name, _remote_path) = decoded_params;
-    let version = version.clone();
+    let (version, version_tag, _as_name, address_or_name, _remote_path) = decoded_params;
+    let mut version = version.clone();
     let mut history_metadata = None;
     let mut most_recent = false;
     let mut is_versioned = false;
@@ -207,7 +208,8 @@ pub async fn archive_get_version(
         let history_address = history_address.unwrap();
         history_address_string = history_address.to_hex();
         is_versioned = true;
-        most_recent = version.is_none();
+        // A tag may be moved to a later version so does not resolve to immutable data
+        most_recent = version.is_none() || version_tag.is_some();
 
         // If the URL includes a specific version the URL resolves to immutable data
         // so ETag None is OK for this check
@@ -237,6 +239,21 @@ pub async fn archive_get_version(
             }
         };
 
+        if let Some(version_tag) = &version_tag {
+            version = match Tree::history_version_for_tag(&client, &mut history, version_tag).await
+            {
+                Ok(version) => Some(version),
+                Err(e) => {
+                    return make_error_response_page(
+                        None,
+                        &mut HttpResponse::NotFound(),
+                        "{rest_operation} error".to_string(),
+                        &format!("{rest_operation} {e}"),
+                    );
+                }
+            };
+        }
+
         println!("DEBUG history.num_entries() is {}", history.num_entries());
         actual_version = history.num_entries() - 1;
         let version = version.unwrap_or(0);
@@ -257,6 +274,7 @@ pub async fn archive_get_version(
             version,
             history_address: history_address.to_hex(),
             history_size: history.num_entries() - 1,
+            version_manifest: None,
         });
 
         let ignore_pointer = false;
@@ -277,7 +295,7 @@ pub async fn archive_get_version(
         }
     };
 
-    let tree = match Tree::from_datamap_or_address(&client, None, archive_address).await {
+    let mut tree = match Tree::from_datamap_or_address(&client, None, archive_address).await {
         Ok(archive) => archive,
         Err(e) => {
             let message = format!("{rest_operation} archive_get() failed - {e}");
@@ -291,6 +309,10 @@ pub async fn archive_get_version(
         }
     };
 
+    if let Some(history_metadata) = history_metadata.as_mut() {
+        history_metadata.version_manifest = tree.load_version_manifest(&client).await.cloned();
+    }
+
     let mut dweb_archive = DwebArchive::from_tree(&tree);
     dweb_archive.history_metadata = history_metadata;
     let json = match serde_json::to_string(&dweb_archive) {
@@ -369,7 +391,7 @@ pub async fn api_directory_load(
         }
     };
 
-    let (version, _as_name, address_or_name, _remote_path) = decoded_params;
+    let (version, version_tag, _as_name, address_or_name, _remote_path) = decoded_params;
     let version = version.clone();
 
     let (history_address, archive_address) = tuple_from_address_or_name(&address_or_name);
@@ -408,8 +430,23 @@ pub async fn api_directory_load(
             }
         };
 
+        let version = if let Some(version_tag) = version_tag {
+            match Tree::history_version_for_tag(&client, &mut history, &version_tag).await {
+                Ok(version) => version,
+                Err(e) => {
+                    return make_error_response_page(
+                        None,
+                        &mut HttpResponse::NotFound(),
+                        rest_operation.to_string(),
+                        &format!("{rest_operation} {e}"),
+                    );
+                }
+            }
+        } else {
+            version.unwrap_or(0)
+        };
+
         let ignore_pointer = false;
-        let version = version.unwrap_or(0);
         match history
             .get_version_entry_value(version, ignore_pointer)
             .await
@@ -491,7 +528,9 @@ pub async fn archive_post_private(
     let rest_operation = "/archive-private POST";
     let rest_handler = "archive_post_private()";
 
-    let tries = query_params.tries.unwrap_or(client.api_control.api_tries);
+    let tries = query_params
+        .tries
+        .unwrap_or(client.api_control.retry_policy.max_tries);
 
     let private_archive = match dweb_archive.into_inner().to_private_archive() {
         Ok(archive) => archive,
@@ -537,7 +576,9 @@ pub async fn archive_post_public(
     client: Data<dweb::client::DwebClient>,
 ) -> HttpResponse {
     println!("DEBUG {}", request.path());
-    let tries = query_params.tries.unwrap_or(client.api_control.api_tries);
+    let tries = query_params
+        .tries
+        .unwrap_or(client.api_control.retry_policy.max_tries);
     let rest_operation = "/archive-public POST";
     let rest_handler = "archive_post_public()";
 
@@ -568,12 +609,12 @@ async fn put_archive_private(
     let dweb_type = DwebType::PrivateArchive;
 
     let payment_option = client.payment_option().clone();
-    let result = retry_until_ok(
-        tries,
+    let result = retry_with_policy(
+        &client.api_control.retry_policy.with_max_tries(tries),
         &"archive_put_private()",
         (archive, payment_option),
         async move |(archive, payment_option)| match client
-            .client
+            .backend
             .archive_put(archive, payment_option.clone())
             .await
         {
@@ -616,12 +657,12 @@ async fn put_archive_public(
     let dweb_type = DwebType::PublicArchive;
 
     let payment_option = client.payment_option().clone();
-    let result = retry_until_ok(
-        tries,
+    let result = retry_with_policy(
+        &client.api_control.retry_policy.with_max_tries(tries),
         &"archive_put_public()",
         (archive, payment_option),
         async move |(archive, payment_option)| match client
-            .client
+            .backend
             .archive_put_public(archive, payment_option.clone())
             .await
         {
@@ -656,6 +697,276 @@ async fn put_archive_public(
     }
 }
 
+#[derive(Deserialize, ToSchema)]
+struct ModifyQueryParams {
+    history_name: Option<String>,
+    create_history: Option<bool>,
+}
+
+/// Modify an archive by adding, removing, renaming or moving files and directories
+///
+/// Applies the operations in the request body in order to a copy of the archive, stores
+/// the result and returns its address. The original archive is unchanged. If any operation
+/// fails nothing is stored.
+///
+/// Files to be added must already be stored, for example using /form-upload-file, and
+/// each entry must have a data_address when modifying a PublicArchive or a datamap when
+/// modifying a PrivateArchive.
+///
+/// If history_name is given, the modified archive is also published as a new version of
+/// that History, owned by the server's app secret key.
+///
+/// url: <code>/archive/{DATAMAP-ADDRESS-OR-NAME}</code>
+#[utoipa::path(
+    patch,
+    params(
+        ("datamap_address_or_name" = String, Path, description = "the hex encoded datamap chunk or address of an archive, or the address or name of a History whose most recent version is modified"),
+        ("history_name" = Option<String>, Query, description = "NAME of a History to which the modified archive is added as a new version"),
+        ("create_history" = Option<bool>, Query, description = "true to create the History called history_name (default false)")),
+    request_body(content = DwebArchiveChanges, content_type = "application/json"),
+    responses(
+        (status = StatusCode::CREATED, description = "A DwebArchiveChangesResult with the cost and address of the modified archive and, if requested, the result of publishing it to a History.<br/>\
+        <b>Error StatusCodes</b><br/>\
+        &nbsp;&nbsp;&nbsp;BAD_REQUEST: Invalid archive or an operation failed<br/>\
+        &nbsp;&nbsp;&nbsp;BAD_GATEWAY: Autonomi network error", body = DwebArchiveChangesResult),
+    ),
+    tags = ["Dweb Autonomi"],
+)]
+#[patch("/archive/{datamap_address_or_name}")]
+pub async fn archive_patch(
+    request: HttpRequest,
+    datamap_address_or_name: web::Path<String>,
+    changes: web::Json<DwebArchiveChanges>,
+    query_params: web::Query<ModifyQueryParams>,
+    client: Data<dweb::client::DwebClient>,
+) -> HttpResponse {
+    println!("DEBUG {}", request.path());
+    let rest_operation = "/archive PATCH";
+    let rest_handler = "archive_patch()";
+
+    let mut archive = match get_archive_to_modify(&client, &datamap_address_or_name).await {
+        Ok(archive) => archive,
+        Err(e) => {
+            let message = format!("{rest_operation} {rest_handler} failed - {e}");
+            println!("DEBUG {message}");
+            return make_error_response_page(
+                None,
+                &mut HttpResponse::BadRequest(),
+                rest_operation.to_string(),
+                &message,
+            );
+        }
+    };
+
+    for (index, operation) in changes.operations.iter().enumerate() {
+        if let Err(e) = operation.apply(&mut archive) {
+            let message = format!("{rest_operation} operation {index} failed - {e}");
+            println!("DEBUG {message}");
+            return make_error_response_page(
+                Some(StatusCode::BAD_REQUEST),
+                &mut HttpResponse::BadRequest(),
+                rest_operation.to_string(),
+                &message,
+            );
+        }
+    }
+
+    let stored = match archive.to_bytes() {
+        Ok(archive_bytes) => store_archive_bytes(&client, archive_bytes).await,
+        Err(e) => Err(e),
+    };
+    let (archive_cost, archive_address) = match stored {
+        Ok(stored) => stored,
+        Err(e) => {
+            let message = format!("{rest_operation} failed to store modified archive - {e}");
+            println!("DEBUG {message}");
+            return make_error_response_page(
+                Some(StatusCode::BAD_GATEWAY),
+                &mut HttpResponse::BadGateway(),
+                rest_operation.to_string(),
+                &message,
+            );
+        }
+    };
+    println!(
+        "DEBUG {rest_handler} stored modified archive at {}",
+        archive_address.to_hex()
+    );
+
+    let history_result = match &query_params.history_name {
+        Some(history_name) => {
+            let published = match dweb::helpers::get_app_secret_key() {
+                Ok(app_secret_key) => {
+                    publish_archive_address_to_history(
+                        &client,
+                        app_secret_key,
+                        history_name,
+                        &archive_address,
+                        query_params.create_history.unwrap_or(false),
+                    )
+                    .await
+                }
+                Err(e) => Err(e),
+            };
+            Some(match published {
+                Ok((cost, history_address, version)) => MutateResult {
+                    dweb_type: DwebType::History,
+                    rest_operation: rest_operation.to_string(),
+                    status_code: StatusCode::CREATED.as_u16(),
+                    status_message: format!("success - version {version}"),
+                    cost_in_ant: format_tokens_as_attos(cost.as_atto()),
+                    object_name: history_name.clone(),
+                    network_address: history_address.to_hex(),
+                    ..Default::default()
+                },
+                Err(e) => MutateResult {
+                    dweb_type: DwebType::History,
+                    rest_operation: rest_operation.to_string(),
+                    status_code: StatusCode::BAD_GATEWAY.as_u16(),
+                    status_message: format!("failed to publish to History '{history_name}' - {e}"),
+                    object_name: history_name.clone(),
+                    ..Default::default()
+                },
+            })
+        }
+        None => None,
+    };
+
+    let changes_result = DwebArchiveChangesResult {
+        archive_result: MutateResult {
+            dweb_type: archive.dweb_type,
+            rest_operation: rest_operation.to_string(),
+            status_code: StatusCode::CREATED.as_u16(),
+            status_message: "success".to_string(),
+            cost_in_ant: format_tokens_as_attos(archive_cost.as_atto()),
+            network_address: archive_address.to_hex(),
+            ..Default::default()
+        },
+        history_result,
+    };
+
+    let json = match serde_json::to_string(&changes_result) {
+        Ok(json) => json,
+        Err(e) => {
+            return make_error_response_page(
+                Some(StatusCode::INTERNAL_SERVER_ERROR),
+                &mut HttpResponse::InternalServerError(),
+                rest_operation.to_string(),
+                &format!("{rest_handler} failed to encode JSON result - {e}"),
+            )
+        }
+    };
+
+    HttpResponse::Created()
+        .insert_header(ContentType(mime::APPLICATION_JSON))
+        .body(json)
+}
+
+// Get the archive at a datamap or address, or the most recent version of a History
+async fn get_archive_to_modify(
+    client: &DwebClient,
+    datamap_address_or_name: &str,
+) -> Result<DualArchive> {
+    let (datamap_chunk, history_address, archive_address) =
+        tuple_from_datamap_address_or_name(datamap_address_or_name);
+
+    let archive_address = match history_address {
+        Some(history_address) => {
+            let mut history = History::<Tree>::from_history_address(
+                client.clone(),
+                history_address,
+                client.api_control.ignore_pointers,
+                0,
+            )
+            .await?;
+            Some(history.get_version_entry_value(0, false).await?)
+        }
+        None => archive_address,
+    };
+
+    if datamap_chunk.is_none() && archive_address.is_none() {
+        return Err(eyre!(
+            "unrecognised DWEB-NAME or invalid datamap or address: '{datamap_address_or_name}'"
+        ));
+    }
+
+    let tree = Tree::from_datamap_or_address(client, datamap_chunk, archive_address).await?;
+    Ok(tree.archive)
+}
+
+/// A list of changes to make to an archive using /archive PATCH
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct DwebArchiveChanges {
+    /// Operations applied in order
+    pub operations: Vec<DwebArchiveOperation>,
+}
+
+/// A single change to an archive. Paths are from the root of the archive and must start with '/'
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(tag = "operation")]
+pub enum DwebArchiveOperation {
+    /// Add a file which is already stored, replacing any file at the same path
+    Add { entry: DwebArchiveEntry },
+    /// Remove a file, or a directory and everything beneath it
+    Remove { path: String },
+    /// Give a file or directory a new full path
+    Rename { from: String, to: String },
+    /// Move a file or directory into another directory, keeping its name
+    Move { from: String, to_directory: String },
+}
+
+impl DwebArchiveOperation {
+    /// Apply the operation to archive
+    pub fn apply(&self, archive: &mut DualArchive) -> Result<()> {
+        match self {
+            DwebArchiveOperation::Add { entry } => {
+                let data_address = if entry.data_address.is_empty() {
+                    None
+                } else {
+                    Some(DataAddress::from_hex(&entry.data_address)?)
+                };
+                let datamap_chunk = if entry.datamap.is_empty() {
+                    None
+                } else {
+                    Some(DataMapChunk::from_hex(&entry.datamap)?)
+                };
+                archive.put_file(
+                    &entry.full_path,
+                    data_address,
+                    datamap_chunk,
+                    entry.file_metadata(),
+                )
+            }
+            DwebArchiveOperation::Remove { path } => archive.remove_path(path).map(|_| ()),
+            DwebArchiveOperation::Rename { from, to } => archive.move_path(from, to).map(|_| ()),
+            DwebArchiveOperation::Move { from, to_directory } => {
+                let name = match from
+                    .trim_end_matches(dweb::files::archive::ARCHIVE_PATH_SEPARATOR)
+                    .rsplit(dweb::files::archive::ARCHIVE_PATH_SEPARATOR)
+                    .next()
+                {
+                    Some(name) if !name.is_empty() => name,
+                    _ => return Err(eyre!("Cannot move '{from}'")),
+                };
+                let to = format!(
+                    "{}/{name}",
+                    to_directory.trim_end_matches(dweb::files::archive::ARCHIVE_PATH_SEPARATOR)
+                );
+                archive.move_path(from, &to).map(|_| ())
+            }
+        }
+    }
+}
+
+/// The result of /archive PATCH
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct DwebArchiveChangesResult {
+    /// The cost and network_address of the modified archive
+    pub archive_result: MutateResult,
+    /// When history_name is given, the result of publishing the modified archive as a new version
+    pub history_result: Option<MutateResult>,
+}
+
 /// Metadata about the History from which a DwebArchive was obtained
 #[derive(Serialize, Deserialize, ToSchema)]
 pub struct DwebHistoryReference {
@@ -665,6 +976,8 @@ pub struct DwebHistoryReference {
     version: u64,
     /// The total number of versions when the History was accessed
     history_size: u64,
+    /// Annotations (message, author, publish time and tags) recorded when this version was published, if any
+    version_manifest: Option<VersionManifest>,
 }
 
 /// A representation of the Autonomi PublicArchive for web clients
@@ -816,20 +1129,7 @@ impl DwebArchive {
                             return Err(eyre!(message));
                         }
                     };
-                    let created = json_date_to_metadata_date(&entry.created).unwrap_or(0);
-                    let modified = json_date_to_metadata_date(&entry.modified).unwrap_or(0);
-                    let extra = if entry.extra.is_empty() {
-                        None
-                    } else {
-                        Some(entry.extra.clone())
-                    };
-
-                    let metadata = FileMetadata {
-                        created,
-                        modified,
-                        size: entry.size,
-                        extra,
-                    };
+                    let metadata = entry.file_metadata();
 
                     archive.add_file(file_path, data_address, metadata)
                 }
@@ -857,20 +1157,7 @@ impl DwebArchive {
                             return Err(eyre!(message));
                         }
                     };
-                    let created = json_date_to_metadata_date(&entry.created).unwrap_or(0);
-                    let modified = json_date_to_metadata_date(&entry.modified).unwrap_or(0);
-                    let extra = if entry.extra.is_empty() {
-                        None
-                    } else {
-                        Some(entry.extra.clone())
-                    };
-
-                    let metadata = FileMetadata {
-                        created,
-                        modified,
-                        size: entry.size,
-                        extra,
-                    };
+                    let metadata = entry.file_metadata();
 
                     archive.add_file(file_path, datamap_chunk, metadata)
                 }
@@ -919,6 +1206,24 @@ pub struct DwebArchiveEntry {
 }
 
 impl DwebArchiveEntry {
+    /// The Autonomi file metadata for this entry
+    pub fn file_metadata(&self) -> FileMetadata {
+        let created = json_date_to_metadata_date(&self.created).unwrap_or(0);
+        let modified = json_date_to_metadata_date(&self.modified).unwrap_or(0);
+        let extra = if self.extra.is_empty() {
+            None
+        } else {
+            Some(self.extra.clone())
+        };
+
+        FileMetadata {
+            created,
+            modified,
+            size: self.size,
+            extra,
+        }
+    }
+
     pub fn new_directory(full_path: String) -> DwebArchiveEntry {
         DwebArchiveEntry {
             dweb_type: DwebArchiveEntryType::Directory,
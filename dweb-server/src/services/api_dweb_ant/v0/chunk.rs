@@ -28,7 +28,7 @@ use utoipa::ToSchema;
 
 use autonomi::{Chunk, ChunkAddress};
 
-use dweb::helpers::retry::retry_until_ok;
+use dweb::helpers::retry::retry_with_policy;
 use dweb::storage::DwebType;
 
 use crate::services::api_dweb::v0::MutateResult;
@@ -79,7 +79,7 @@ pub async fn chunk_get(
         }
     };
 
-    let content = match client.client.chunk_get(&chunk_address).await {
+    let content = match client.backend.chunk_get(&chunk_address).await {
         Ok(bytes) => bytes,
         Err(e) => {
             return make_error_response_page(
@@ -131,7 +131,9 @@ pub async fn chunk_post(
     let rest_handler = "chunk_post()";
     let dweb_type = DwebType::Chunk;
 
-    let tries = query_params.tries.unwrap_or(client.api_control.api_tries);
+    let tries = query_params
+        .tries
+        .unwrap_or(client.api_control.retry_policy.max_tries);
     let chunk = Chunk::new(body);
 
     if chunk.is_too_big() {
@@ -152,12 +154,12 @@ pub async fn chunk_post(
     let client = &client;
     let payment_option = client.payment_option().clone();
 
-    let result = retry_until_ok(
-        tries,
+    let result = retry_with_policy(
+        &client.api_control.retry_policy.with_max_tries(tries),
         &"chunk_put()",
         (&chunk.clone(), payment_option),
         async move |(chunk, payment_option)| match client
-            .client
+            .backend
             .chunk_put(chunk, payment_option)
             .await
         {
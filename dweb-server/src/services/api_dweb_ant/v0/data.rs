@@ -59,7 +59,7 @@ pub async fn data_get(
 
     let content = if datamap_chunk.is_some() {
         match client
-            .client
+            .backend
             .data_get(&datamap_chunk.clone().unwrap())
             .await
         {
@@ -74,7 +74,7 @@ pub async fn data_get(
             }
         }
     } else if data_address.is_some() {
-        match client.client.data_get_public(&data_address.unwrap()).await {
+        match client.backend.data_get_public(&data_address.unwrap()).await {
             Ok(bytes) => bytes,
             Err(e) => {
                 return make_error_response_page(
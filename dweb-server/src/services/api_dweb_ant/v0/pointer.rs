@@ -32,7 +32,8 @@ use autonomi::{
     ScratchpadAddress,
 };
 
-use dweb::helpers::retry::retry_until_ok;
+use dweb::backend::BackendError;
+use dweb::helpers::retry::retry_with_policy;
 use dweb::storage::DwebType;
 use dweb::token::Spends;
 use dweb::types::POINTER_DERIVATION_INDEX;
@@ -54,7 +55,8 @@ const REST_TYPE: &str = "Pointer";
     responses(
         (status = StatusCode::OK, description = "Success", body = [DwebPointer]),
         (status = StatusCode::BAD_REQUEST, description = "The pointer address is not valid"),
-        (status = StatusCode::NOT_FOUND, description = "The pointer was not found or a network error occured"),
+        (status = StatusCode::NOT_FOUND, description = "The pointer was not found"),
+        (status = StatusCode::BAD_GATEWAY, description = "A network error occurred"),
         ),
     tags = ["Dweb Autonomi"],
 )]
@@ -76,16 +78,9 @@ pub async fn pointer_get(
                 "DEBUG {rest_operation} calling client.pointer_get({})",
                 pointer_address.to_hex()
             );
-            match client.client.pointer_get(&pointer_address).await {
+            match client.backend.pointer_get(&pointer_address).await {
                 Ok(pointer) => pointer,
-                Err(e) => {
-                    return make_error_response_page(
-                        None,
-                        &mut HttpResponse::NotFound(),
-                        rest_operation.to_string(),
-                        &format!("{rest_handler} failed to get {REST_TYPE} from network - {e}"),
-                    );
-                }
+                Err(e) => return pointer_get_error_response(rest_operation, rest_handler, e),
             }
         }
         Err(e) => {
@@ -124,6 +119,29 @@ pub async fn pointer_get(
         .body(json)
 }
 
+// NOT_FOUND only when the pointer doesn't exist, so that a network failure isn't taken to mean it was never created
+fn pointer_get_error_response(
+    rest_operation: &str,
+    rest_handler: &str,
+    e: BackendError,
+) -> HttpResponse {
+    let message = format!("{rest_handler} failed to get {REST_TYPE} from network - {e}");
+    match e {
+        BackendError::NotFound(_) => make_error_response_page(
+            Some(StatusCode::NOT_FOUND),
+            &mut HttpResponse::NotFound(),
+            rest_operation.to_string(),
+            &message,
+        ),
+        _ => make_error_response_page(
+            Some(StatusCode::BAD_GATEWAY),
+            &mut HttpResponse::BadGateway(),
+            rest_operation.to_string(),
+            &message,
+        ),
+    }
+}
+
 /// Get a Pointer you own with optional name
 /// TODO example JSON
 #[utoipa::path(
@@ -138,7 +156,8 @@ pub async fn pointer_get(
     responses(
         (status = StatusCode::OK, description = "Success", body = [DwebPointer]),
         (status = StatusCode::BAD_REQUEST, description = "The pointer address is not valid"),
-        (status = StatusCode::NOT_FOUND, description = "The pointer was not found or a network error occured"),
+        (status = StatusCode::NOT_FOUND, description = "The pointer was not found"),
+        (status = StatusCode::BAD_GATEWAY, description = "A network error occurred"),
         ),
     tags = ["Dweb Autonomi"],
 )]
@@ -185,16 +204,9 @@ pub async fn pointer_get_owned(
 
     let pointer_address = PointerAddress::new(pointer_secret.public_key());
 
-    let pointer = match client.client.pointer_get(&pointer_address).await {
+    let pointer = match client.backend.pointer_get(&pointer_address).await {
         Ok(pointer) => pointer,
-        Err(e) => {
-            return make_error_response_page(
-                None,
-                &mut HttpResponse::NotFound(),
-                rest_operation.to_string(),
-                &format!("{rest_handler} failed to get {REST_TYPE} from network - {e}"),
-            );
-        }
+        Err(e) => return pointer_get_error_response(rest_operation, rest_handler, e),
     };
 
     let dweb_pointer = DwebPointer {
@@ -312,12 +324,15 @@ pub async fn pointer_post(
     let payment_option = client.payment_option().clone();
 
     let spends = Spends::new(&client, None).await;
-    let result = retry_until_ok(
-        request_params.tries,
+    let result = retry_with_policy(
+        &client
+            .api_control
+            .retry_policy
+            .with_max_tries(request_params.tries),
         &rest_operation,
         (pointer_secret, target, payment_option),
         async move |(pointer_secret, target, payment_option)| match client
-            .client
+            .backend
             .pointer_create(&pointer_secret, target, payment_option)
             .await
         {
@@ -455,12 +470,15 @@ pub async fn pointer_put(
         }
     };
 
-    let result = retry_until_ok(
-        request_params.tries,
+    let result = retry_with_policy(
+        &client
+            .api_control
+            .retry_policy
+            .with_max_tries(request_params.tries),
         &rest_handler,
         (pointer_secret, target),
         async move |(pointer_secret, target)| match client
-            .client
+            .backend
             .pointer_update(&pointer_secret, target)
             .await
         {
@@ -571,3 +589,68 @@ impl DwebPointer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use actix_web::{test, App};
+    use autonomi::{SecretKey, XorName};
+
+    use dweb::backend::{DwebBackend, MemoryBackend};
+    use dweb::client::{DwebClient, DwebClientConfig};
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn pointers_are_got_by_address() {
+        let backend = Arc::new(MemoryBackend::new());
+        let client = DwebClient::with_backend(backend.clone(), &DwebClientConfig::default());
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(client.clone()))
+                .service(web::scope(dweb::api::DWEB_API_ROUTE).service(pointer_get)),
+        )
+        .await;
+
+        let owner = SecretKey::random();
+        let target = ChunkAddress::new(XorName::from_content(b"target"));
+        let (_, pointer_address) = backend
+            .pointer_create(
+                &owner,
+                PointerTarget::ChunkAddress(target),
+                client.payment_option(),
+            )
+            .await
+            .unwrap();
+        let uri = format!("/dweb-0/pointer/{}", pointer_address.to_hex());
+
+        let response =
+            test::call_service(&app, test::TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let dweb_pointer: DwebPointer = test::read_body_json(response).await;
+        assert_eq!(dweb_pointer.counter, 0);
+        assert_eq!(dweb_pointer.chunk_target_address, target.to_hex());
+
+        // A network failure is not reported as a missing pointer
+        backend.fail_next(1);
+        let response =
+            test::call_service(&app, test::TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+
+        let missing = PointerAddress::new(SecretKey::random().public_key());
+        let uri = format!("/dweb-0/pointer/{}", missing.to_hex());
+        let response =
+            test::call_service(&app, test::TestRequest::get().uri(&uri).to_request()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let response = test::call_service(
+            &app,
+            test::TestRequest::get()
+                .uri("/dweb-0/pointer/not-an-address")
+                .to_request(),
+        )
+        .await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+}
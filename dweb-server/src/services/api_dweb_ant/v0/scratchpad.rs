@@ -28,9 +28,14 @@ use color_eyre::eyre::eyre;
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
-use autonomi::{scratchpad::ScratchpadError, Bytes, Scratchpad, ScratchpadAddress, SecretKey};
+use autonomi::{Bytes, Scratchpad, ScratchpadAddress, SecretKey};
 
-use dweb::helpers::retry::retry_until_ok;
+use dweb::backend::BackendError;
+use dweb::helpers::retry::retry_with_policy;
+use dweb::helpers::scratchpad::{
+    highest_counter, resolve_fork, scratchpad_get_resolved, signature_hex, ForkResolution,
+    ResolvedScratchpad,
+};
 use dweb::storage::DwebType;
 use dweb::token::Spends;
 use dweb::types::{
@@ -38,7 +43,9 @@ use dweb::types::{
     PUBLIC_SCRATCHPAD_DERIVATION_INDEX,
 };
 
-use crate::services::api_dweb::v0::{MutateQueryParams, MutateResult, ParsedRequestParams};
+use crate::services::api_dweb::v0::{
+    parse_fork_resolution, MutateQueryParams, MutateResult, ParsedRequestParams,
+};
 use crate::services::helpers::*;
 
 /// Get a private Scratchpad from the network using a hex encoded ScratchpadAddress
@@ -48,9 +55,13 @@ use crate::services::helpers::*;
 /// if you did not create the scratchpad with a name. To get a named scratchpad
 /// and decrypt its content, use the scratchpad GET without an address parameter.
 #[utoipa::path(
-    params(("scratchpad_address" = String, Path, description = "the hex encoded address of a Scratchpad on the network"),),
+    params(
+        ("scratchpad_address" = String, Path, description = "the hex encoded address of a Scratchpad on the network"),
+        ("resolve" = Option<String>, Query, description = "how to choose between the versions of a forked Scratchpad: counter, signature, all or merge (default set by the server)"),
+    ),
     responses(
         (status = StatusCode::OK, description = "Success", body = [DwebScratchpad]),
+        (status = StatusCode::CONFLICT, description = "The Scratchpad is forked and resolve=merge was given. Every version is returned in forks", body = [DwebScratchpad]),
         (status = StatusCode::BAD_REQUEST, description = "The scratchpad address is not valid"),
         (status = StatusCode::NOT_FOUND, description = "The scratchpad was not found or a network error occured"),
         ),
@@ -60,6 +71,7 @@ use crate::services::helpers::*;
 pub async fn scratchpad_private_get(
     request: HttpRequest,
     scratchpad_address: web::Path<String>,
+    query_params: web::Query<ResolveQueryParams>,
     client: Data<dweb::client::DwebClient>,
 ) -> HttpResponse {
     println!("DEBUG {}", request.path());
@@ -67,29 +79,20 @@ pub async fn scratchpad_private_get(
     let rest_operation = "/scratchpad-private GET";
     let rest_handler = "scratchpad_private_get()";
 
-    let scratchpad_address = ScratchpadAddress::from_hex(&scratchpad_address.into_inner());
-
-    let scratchpad = match scratchpad_address {
-        Ok(scratchpad_address) => {
-            println!(
-                "DEBUG {rest_operation} calling client.scratchpad_get({})",
-                scratchpad_address.to_hex()
+    let resolution = match parse_fork_resolution(&client, &query_params.resolve) {
+        Ok(resolution) => resolution,
+        Err(e) => {
+            return make_error_response_page(
+                Some(StatusCode::BAD_REQUEST),
+                &mut HttpResponse::BadRequest(),
+                rest_operation.to_string(),
+                &format!("{rest_operation} request error - {e}"),
             );
-            match client.client.scratchpad_get(&scratchpad_address).await {
-                Ok(scratchpad) => scratchpad,
-                Err(e) => match e {
-                    ScratchpadError::Fork(scratchpads) => scratchpads[0].clone(),
-                    e => {
-                        return make_error_response_page(
-                            None,
-                            &mut HttpResponse::NotFound(),
-                            rest_operation.to_string(),
-                            &format!("{rest_handler} failed to get {REST_TYPE} from network - {e}"),
-                        );
-                    }
-                },
-            }
         }
+    };
+
+    let scratchpad_address = match ScratchpadAddress::from_hex(&scratchpad_address.into_inner()) {
+        Ok(scratchpad_address) => scratchpad_address,
         Err(e) => {
             return make_error_response_page(
                 Some(StatusCode::BAD_REQUEST),
@@ -100,58 +103,66 @@ pub async fn scratchpad_private_get(
         }
     };
 
-    let mut dweb_scratchpad = DwebScratchpad {
-        dweb_type: DwebType::PrivateScratchpad,
-        scratchpad_address: scratchpad.address().to_hex(),
-        data_encoding: scratchpad.data_encoding(),
-        encrypted_data: scratchpad.encrypted_data().to_vec(),
-        counter: scratchpad.counter(),
-        ..Default::default()
-    };
+    println!(
+        "DEBUG {rest_operation} calling client.scratchpad_get({})",
+        scratchpad_address.to_hex()
+    );
+    let resolved =
+        match scratchpad_get_resolved(client.backend.as_ref(), &scratchpad_address, resolution)
+            .await
+        {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                return make_error_response_page(
+                    None,
+                    &mut HttpResponse::NotFound(),
+                    rest_operation.to_string(),
+                    &format!("{rest_handler} failed to get {REST_TYPE} from network - {e}"),
+                );
+            }
+        };
 
     // Attempt decryption. This will only work if the scratchpad was created
     // using this owner_secret and without an object_name.
 
     // TODO use separate owner_secret from DwebClient when available
-    match dweb::helpers::get_app_secret_key() {
-        Ok(owner_secret) => match scratchpad.decrypt_data(&derive_named_object_secret(owner_secret, PRIVATE_SCRATCHPAD_DERIVATION_INDEX, &None, None, None)) {
-            Ok(bytes) => {
-                dweb_scratchpad.unencrypted_data = bytes.to_vec();
-                println!("DEBUG {rest_handler} decrypted data: {bytes:?}");
-            },
-            Err(_e) => println!("DEBUG {rest_handler} scratchpad decryption failed. This will fail if scratchpad was created with an object_name. In that case use the route which takes an object_name not scratchpad_address.")
-        },
-            Err(_e) => println!("DEBUG {rest_handler} unable to decrypt content - failed to get owner_secret")
-    };
-
-    let json = match serde_json::to_string(&dweb_scratchpad) {
-        Ok(json) => json,
-        Err(e) => {
-            return make_error_response_page(
-                Some(StatusCode::INTERNAL_SERVER_ERROR),
-                &mut HttpResponse::NotFound(),
-                rest_operation.to_string(),
-                &format!("{rest_handler} failed to encode JSON result - {e}"),
-            )
+    let scratchpad_secret = match dweb::helpers::get_app_secret_key() {
+        Ok(owner_secret) => Some(derive_named_object_secret(
+            owner_secret,
+            PRIVATE_SCRATCHPAD_DERIVATION_INDEX,
+            &None,
+            None,
+            None,
+        )),
+        Err(_e) => {
+            println!("DEBUG {rest_handler} unable to decrypt content - failed to get owner_secret");
+            None
         }
     };
 
-    // println!("DEBUG DwebScratchpad as JSON: {json:?}");
+    let dweb_scratchpad = DwebScratchpad::from_resolved(
+        DwebType::PrivateScratchpad,
+        &scratchpad_address,
+        &resolved,
+        resolution,
+        |scratchpad| {
+            let unencrypted_data = match &scratchpad_secret {
+                Some(scratchpad_secret) => match scratchpad.decrypt_data(scratchpad_secret) {
+                    Ok(bytes) => bytes.to_vec(),
+                    Err(_e) => {
+                        println!("DEBUG {rest_handler} scratchpad decryption failed. This will fail if scratchpad was created with an object_name. In that case use the route which takes an object_name not scratchpad_address.");
+                        Vec::new()
+                    }
+                },
+                None => Vec::new(),
+            };
+            (scratchpad.encrypted_data().to_vec(), unencrypted_data)
+        },
+    );
 
-    HttpResponse::Ok()
-        .insert_header(ContentType(mime::APPLICATION_JSON))
-        .body(json)
+    dweb_scratchpad.response(&resolved, rest_operation, rest_handler)
 }
 
-// TODO consider selecting Scratchpad based on modifying the following to:
-// 1. filter by highest counter
-// 2. choose the first scratchpad ordered by hash of content
-//
-// The above is simple and always chooses the same fork from a given set.
-// TODO provide an API which returns an array, which is either the
-// only Scratchpad returned, or all the forks returned. The client can
-// then select manually.
-
 /// @zettawatt's code from: https://github.com/zettawatt/colonylib/blob/b0d0ef8767cb0061d8965a4e9c0621b2986e0bf4/src/pod.rs#L1084
 /// Selects the newest scratchpad from a vector of scratchpads based on timestamp comments.
 ///
@@ -213,6 +224,7 @@ pub async fn scratchpad_private_get(
 #[utoipa::path(
     params(
         ("object_name" = Option<String>, Query, description = "optional name, used to allow more than one scratchpad per owner secret/app id combination"),
+        ("resolve" = Option<String>, Query, description = "how to choose between the versions of a forked Scratchpad: counter, signature, all or merge (default set by the server)"),
         ("Ant-App-ID" = Option<String>, Header, description = "a unique string identifier for this app (as suggested by Autonomi and used to derive the VaultContentType used by an app)")),
         // Support Query params using headers but don't document in the SwaggerUI to keep it simple
         // ("Ant-API-Tries" = Option<u32>, Header, description = "optional number of time to try a mutation operation before returning failure (0 = unlimited)"),
@@ -221,6 +233,7 @@ pub async fn scratchpad_private_get(
         // ("Ant-Derivation-Index" = Option<String>, Header, description = "optional 32 character string to use instead of the dweb default when deriving keys for objects of this type"),
     responses(
         (status = StatusCode::OK, description = "Success", body = [DwebScratchpad]),
+        (status = StatusCode::CONFLICT, description = "The Scratchpad is forked and resolve=merge was given. Every version is returned in forks", body = [DwebScratchpad]),
         (status = StatusCode::BAD_REQUEST, description = "The scratchpad address is not valid"),
         (status = StatusCode::NOT_FOUND, description = "The scratchpad was not found or a network error occured"),
         ),
@@ -271,57 +284,47 @@ pub async fn scratchpad_private_get_owned(
 
     let scratchpad_address = ScratchpadAddress::new(scratchpad_secret.public_key());
 
-    let scratchpad = match client.client.scratchpad_get(&scratchpad_address).await {
-        Ok(scratchpad) => scratchpad,
-        Err(e) => match e {
-            ScratchpadError::Fork(scratchpads) => scratchpads[0].clone(),
-            e => {
-                return make_error_response_page(
-                    None,
-                    &mut HttpResponse::NotFound(),
-                    rest_operation.to_string(),
-                    &format!("{rest_handler} failed to get {REST_TYPE} from network - {e}"),
-                );
-            }
-        },
-    };
-
-    let mut dweb_scratchpad = DwebScratchpad {
-        dweb_type: DwebType::PrivateScratchpad,
-        scratchpad_address: scratchpad.address().to_hex(),
-        data_encoding: scratchpad.data_encoding(),
-        encrypted_data: scratchpad.encrypted_data().to_vec(),
-        counter: scratchpad.counter(),
-        ..Default::default()
-    };
-
-    match scratchpad.decrypt_data(&scratchpad_secret) {
-        Ok(bytes) => {
-            dweb_scratchpad.unencrypted_data = bytes.to_vec();
-            println!("DEBUG {rest_operation} successfully decrypted scratchpad data");
-        }
-        Err(e) => {
-            println!("DEBUG {rest_operation} failed to decrypt scratchpad data failed - {e}")
-        }
-    };
-
-    let json = match serde_json::to_string(&dweb_scratchpad) {
-        Ok(json) => json,
+    let resolved = match scratchpad_get_resolved(
+        client.backend.as_ref(),
+        &scratchpad_address,
+        request_params.resolve,
+    )
+    .await
+    {
+        Ok(resolved) => resolved,
         Err(e) => {
             return make_error_response_page(
-                Some(StatusCode::INTERNAL_SERVER_ERROR),
+                None,
                 &mut HttpResponse::NotFound(),
                 rest_operation.to_string(),
-                &format!("{rest_handler} failed to encode JSON result - {e}"),
-            )
+                &format!("{rest_handler} failed to get {REST_TYPE} from network - {e}"),
+            );
         }
     };
 
-    // println!("DEBUG DwebScratchpad as JSON: {json:?}");
+    let dweb_scratchpad = DwebScratchpad::from_resolved(
+        DwebType::PrivateScratchpad,
+        &scratchpad_address,
+        &resolved,
+        request_params.resolve,
+        |scratchpad| {
+            let unencrypted_data = match scratchpad.decrypt_data(&scratchpad_secret) {
+                Ok(bytes) => {
+                    println!("DEBUG {rest_operation} successfully decrypted scratchpad data");
+                    bytes.to_vec()
+                }
+                Err(e) => {
+                    println!(
+                        "DEBUG {rest_operation} failed to decrypt scratchpad data failed - {e}"
+                    );
+                    Vec::new()
+                }
+            };
+            (scratchpad.encrypted_data().to_vec(), unencrypted_data)
+        },
+    );
 
-    HttpResponse::Ok()
-        .insert_header(ContentType(mime::APPLICATION_JSON))
-        .body(json)
+    dweb_scratchpad.response(&resolved, rest_operation, rest_handler)
 }
 
 /// Create a new private Scratchpad on the network
@@ -433,8 +436,11 @@ pub async fn scratchpad_private_post(
     };
 
     let spends = Spends::new(&client, None).await;
-    let result = retry_until_ok(
-        request_params.tries,
+    let result = retry_with_policy(
+        &client
+            .api_control
+            .retry_policy
+            .with_max_tries(request_params.tries),
         &rest_operation,
         (
             scratchpad_secret,
@@ -443,7 +449,7 @@ pub async fn scratchpad_private_post(
             payment_option,
         ),
         async move |(scratchpad_secret, content_type, initial_data, payment_option)| match client
-            .client
+            .backend
             .scratchpad_create(
                 &scratchpad_secret,
                 content_type,
@@ -596,15 +602,18 @@ pub async fn scratchpad_private_put(
 
     let payment_option = client.payment_option().clone();
 
-    let result = retry_until_ok(
-        request_params.tries,
+    let result = retry_with_policy(
+        &client
+            .api_control
+            .retry_policy
+            .with_max_tries(request_params.tries),
         &rest_handler,
         (
             scratchpad_secret.clone(),
             content_type,
             new_data.clone(),
             payment_option.clone(),
-            client.client.clone(),
+            client.backend.clone(),
         ),
         async move |(scratchpad_secret, content_type, new_data, payment_option, client)| {
             match client
@@ -613,8 +622,8 @@ pub async fn scratchpad_private_put(
             {
                 Ok(result) => Ok(result),
                 Err(e) => match e {
-                    ScratchpadError::Fork(scratchpads) => {
-                        let counter = scratchpads[0].counter() + 1;
+                    BackendError::ScratchpadFork(scratchpads) => {
+                        let counter = highest_counter(&scratchpads) + 1;
                         let new_scratchpad =
                             Scratchpad::new(&scratchpad_secret, content_type, &new_data, counter);
                         client
@@ -665,9 +674,13 @@ pub async fn scratchpad_private_put(
 ///
 /// Scratchpad data is assumed to be unencrypted
 #[utoipa::path(
-    params(("scratchpad_address" = String, Path, description = "the hex encoded address of a Scratchpad on the network"),),
+    params(
+        ("scratchpad_address" = String, Path, description = "the hex encoded address of a Scratchpad on the network"),
+        ("resolve" = Option<String>, Query, description = "how to choose between the versions of a forked Scratchpad: counter, signature, all or merge (default set by the server)"),
+    ),
     responses(
         (status = StatusCode::OK, description = "Success", body = [DwebScratchpad]),
+        (status = StatusCode::CONFLICT, description = "The Scratchpad is forked and resolve=merge was given. Every version is returned in forks", body = [DwebScratchpad]),
         (status = StatusCode::BAD_REQUEST, description = "The scratchpad address is not valid"),
         (status = StatusCode::NOT_FOUND, description = "The scratchpad was not found or a network error occured"),
         ),
@@ -677,6 +690,7 @@ pub async fn scratchpad_private_put(
 pub async fn scratchpad_public_get(
     request: HttpRequest,
     scratchpad_address: web::Path<String>,
+    query_params: web::Query<ResolveQueryParams>,
     client: Data<dweb::client::DwebClient>,
 ) -> HttpResponse {
     println!("DEBUG {}", request.path());
@@ -684,65 +698,58 @@ pub async fn scratchpad_public_get(
     let rest_operation = "/scratchpad-public GET";
     let rest_handler = "scratchpad_public_get()";
 
-    let scratchpad_address = ScratchpadAddress::from_hex(&scratchpad_address.into_inner());
-
-    let scratchpad = match scratchpad_address {
-        Ok(scratchpad_address) => {
-            println!(
-                "DEBUG {rest_operation} calling client.scratchpad_get({})",
-                scratchpad_address.to_hex()
-            );
-            match client.client.scratchpad_get(&scratchpad_address).await {
-                Ok(scratchpad) => scratchpad,
-                Err(e) => match e {
-                    ScratchpadError::Fork(scratchpads) => scratchpads[0].clone(),
-                    e => {
-                        return make_error_response_page(
-                            None,
-                            &mut HttpResponse::NotFound(),
-                            rest_operation.to_string(),
-                            &format!("{rest_handler} failed to get {REST_TYPE} from network - {e}"),
-                        );
-                    }
-                },
-            }
-        }
+    let resolution = match parse_fork_resolution(&client, &query_params.resolve) {
+        Ok(resolution) => resolution,
         Err(e) => {
             return make_error_response_page(
                 Some(StatusCode::BAD_REQUEST),
                 &mut HttpResponse::BadRequest(),
                 rest_operation.to_string(),
-                &format!("/scratchpad GET failed due to invalid {REST_TYPE} address - {e}"),
-            )
+                &format!("{rest_operation} request error - {e}"),
+            );
         }
     };
 
-    let dweb_scratchpad = DwebScratchpad {
-        dweb_type: DwebType::PublicScratchpad,
-        scratchpad_address: scratchpad.address().to_hex(),
-        data_encoding: scratchpad.data_encoding(),
-        unencrypted_data: scratchpad.encrypted_data().to_vec(),
-        counter: scratchpad.counter(),
-        ..Default::default()
-    };
-
-    let json = match serde_json::to_string(&dweb_scratchpad) {
-        Ok(json) => json,
+    let scratchpad_address = match ScratchpadAddress::from_hex(&scratchpad_address.into_inner()) {
+        Ok(scratchpad_address) => scratchpad_address,
         Err(e) => {
             return make_error_response_page(
-                Some(StatusCode::INTERNAL_SERVER_ERROR),
-                &mut HttpResponse::NotFound(),
+                Some(StatusCode::BAD_REQUEST),
+                &mut HttpResponse::BadRequest(),
                 rest_operation.to_string(),
-                &format!("{rest_handler} failed to encode JSON result - {e}"),
+                &format!("/scratchpad GET failed due to invalid {REST_TYPE} address - {e}"),
             )
         }
     };
 
-    // println!("DEBUG DwebScratchpad as JSON: {json:?}");
+    println!(
+        "DEBUG {rest_operation} calling client.scratchpad_get({})",
+        scratchpad_address.to_hex()
+    );
+    let resolved =
+        match scratchpad_get_resolved(client.backend.as_ref(), &scratchpad_address, resolution)
+            .await
+        {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                return make_error_response_page(
+                    None,
+                    &mut HttpResponse::NotFound(),
+                    rest_operation.to_string(),
+                    &format!("{rest_handler} failed to get {REST_TYPE} from network - {e}"),
+                );
+            }
+        };
 
-    HttpResponse::Ok()
-        .insert_header(ContentType(mime::APPLICATION_JSON))
-        .body(json)
+    let dweb_scratchpad = DwebScratchpad::from_resolved(
+        DwebType::PublicScratchpad,
+        &scratchpad_address,
+        &resolved,
+        resolution,
+        |scratchpad| (Vec::new(), scratchpad.encrypted_data().to_vec()),
+    );
+
+    dweb_scratchpad.response(&resolved, rest_operation, rest_handler)
 }
 
 /// Get a public Scratchpad you own, with optional name
@@ -752,6 +759,7 @@ pub async fn scratchpad_public_get(
 #[utoipa::path(
     params(
         ("object_name" = Option<String>, Query, description = "optional name, used to allow more than one scratchpad per owner secret/app id combination"),
+        ("resolve" = Option<String>, Query, description = "how to choose between the versions of a forked Scratchpad: counter, signature, all or merge (default set by the server)"),
         ("Ant-App-ID" = Option<String>, Header, description = "a unique string identifier for this app (as suggested by Autonomi and used to derive the VaultContentType used by an app)")),
         // Support Query params using headers but don't document in the SwaggerUI to keep it simple
         // ("Ant-API-Tries" = Option<u32>, Header, description = "optional number of time to try a mutation operation before returning failure (0 = unlimited)"),
@@ -760,6 +768,7 @@ pub async fn scratchpad_public_get(
         // ("Ant-Derivation-Index" = Option<String>, Header, description = "optional 32 character string to use instead of the dweb default when deriving keys for objects of this type"),
     responses(
         (status = StatusCode::OK, description = "Success", body = [DwebScratchpad]),
+        (status = StatusCode::CONFLICT, description = "The Scratchpad is forked and resolve=merge was given. Every version is returned in forks", body = [DwebScratchpad]),
         (status = StatusCode::BAD_REQUEST, description = "The scratchpad address is not valid"),
         (status = StatusCode::NOT_FOUND, description = "The scratchpad was not found or a network error occured"),
         ),
@@ -810,57 +819,42 @@ pub async fn scratchpad_public_get_owned(
 
     let scratchpad_address = ScratchpadAddress::new(scratchpad_secret.public_key());
 
-    let scratchpad = match client.client.scratchpad_get(&scratchpad_address).await {
-        Ok(scratchpad) => scratchpad,
-        Err(e) => match e {
-            ScratchpadError::Fork(scratchpads) => scratchpads[0].clone(),
-            e => {
-                return make_error_response_page(
-                    None,
-                    &mut HttpResponse::NotFound(),
-                    rest_operation.to_string(),
-                    &format!("{rest_handler} failed to get {REST_TYPE} from network - {e}"),
-                );
-            }
-        },
-    };
-
-    let mut dweb_scratchpad = DwebScratchpad {
-        dweb_type: DwebType::PublicScratchpad,
-        scratchpad_address: scratchpad.address().to_hex(),
-        data_encoding: scratchpad.data_encoding(),
-        unencrypted_data: scratchpad.encrypted_data().to_vec(),
-        counter: scratchpad.counter(),
-        ..Default::default()
-    };
-
-    match scratchpad.decrypt_data(&scratchpad_secret) {
-        Ok(bytes) => {
-            dweb_scratchpad.unencrypted_data = bytes.to_vec();
-            println!("DEBUG {rest_operation} successfully decrypted scratchpad data");
-        }
-        Err(e) => {
-            println!("DEBUG {rest_operation} failed to decrypt scratchpad data failed - {e}")
-        }
-    };
-
-    let json = match serde_json::to_string(&dweb_scratchpad) {
-        Ok(json) => json,
+    let resolved = match scratchpad_get_resolved(
+        client.backend.as_ref(),
+        &scratchpad_address,
+        request_params.resolve,
+    )
+    .await
+    {
+        Ok(resolved) => resolved,
         Err(e) => {
             return make_error_response_page(
-                Some(StatusCode::INTERNAL_SERVER_ERROR),
+                None,
                 &mut HttpResponse::NotFound(),
                 rest_operation.to_string(),
-                &format!("{rest_handler} failed to encode JSON result - {e}"),
-            )
+                &format!("{rest_handler} failed to get {REST_TYPE} from network - {e}"),
+            );
         }
     };
 
-    // println!("DEBUG DwebScratchpad as JSON: {json:?}");
+    let dweb_scratchpad = DwebScratchpad::from_resolved(
+        DwebType::PublicScratchpad,
+        &scratchpad_address,
+        &resolved,
+        request_params.resolve,
+        |scratchpad| match scratchpad.decrypt_data(&scratchpad_secret) {
+            Ok(bytes) => {
+                println!("DEBUG {rest_operation} successfully decrypted scratchpad data");
+                (Vec::new(), bytes.to_vec())
+            }
+            Err(e) => {
+                println!("DEBUG {rest_operation} failed to decrypt scratchpad data failed - {e}");
+                (Vec::new(), scratchpad.encrypted_data().to_vec())
+            }
+        },
+    );
 
-    HttpResponse::Ok()
-        .insert_header(ContentType(mime::APPLICATION_JSON))
-        .body(json)
+    dweb_scratchpad.response(&resolved, rest_operation, rest_handler)
 }
 
 /// Create a new public Scratchpad on the network
@@ -960,12 +954,15 @@ pub async fn scratchpad_public_post(
         create_public_scratchpad(&scratchpad_secret, content_type, &initial_data, 0);
 
     let spends = Spends::new(&client, None).await;
-    let result = retry_until_ok(
-        request_params.tries,
+    let result = retry_with_policy(
+        &client
+            .api_control
+            .retry_policy
+            .with_max_tries(request_params.tries),
         &rest_operation,
         (public_scratchpad, payment_option),
         async move |(public_scratchpad, payment_option)| match client
-            .client
+            .backend
             .scratchpad_put(public_scratchpad, payment_option)
             .await
         {
@@ -1120,12 +1117,15 @@ pub async fn scratchpad_public_put(
 
     // Updates to a public Scratchpad are charged because an Autonomi API limitation
     let spends = Spends::new(&client, None).await;
-    let result = retry_until_ok(
-        request_params.tries,
+    let result = retry_with_policy(
+        &client
+            .api_control
+            .retry_policy
+            .with_max_tries(request_params.tries),
         &rest_handler,
         (public_scratchpad, payment_option),
         async move |(public_scratchpad, payment_option)| match client
-            .client
+            .backend
             .scratchpad_put(public_scratchpad, payment_option)
             .await
         {
@@ -1177,6 +1177,273 @@ pub async fn scratchpad_public_put(
     }
 }
 
+///////////////////////////// Forked Scratchpads ///////////////////////////////
+
+/// Repair a forked private Scratchpad you own, with optional name
+///
+/// Writes a new version with a counter higher than any version of the fork, which replaces
+/// them all. If unencrypted_data is given (for example the app's merge of the versions
+/// returned using resolve=merge) that is written, otherwise the version chosen using
+/// resolve. Nothing is written if the Scratchpad is not forked.
+#[utoipa::path(
+    put,
+    params(
+        ("tries" = Option<u32>, Query, description = "number of times to try calling the Autonomi upload API for each put, 0 means unlimited. This overrides the API control setting in the server."),
+        ("object_name" = Option<String>, Query, description = "optional name, used to allow more than one scratchpad per owner secret/app id combination"),
+        ("resolve" = Option<String>, Query, description = "the version to write if no unencrypted_data is given: counter or signature (default set by the server)"),
+        ("Ant-App-ID" = Option<String>, Header, description = "a unique string identifier for this app (as suggested by Autonomi and used to derive the VaultContentType used by an app)")),
+    request_body(content = DwebScratchpad, content_type = "application/json"),
+    responses(
+        (status = StatusCode::OK, description = "A MutateResult featuring either status 200 with cost and the network address of the repaired Scratchpad, or in case of error an error status code and message about the error.<br/>\
+        <b>Error StatusCodes</b><br/>\
+        &nbsp;&nbsp;&nbsp;400 BAD_REQUEST: resolve=merge was given without unencrypted_data<br/>\
+        &nbsp;&nbsp;&nbsp;502 BAD_GATEWAY: Autonomi network error<br/>", body = MutateResult,)
+    ),
+    tags = ["Dweb Autonomi"],
+)]
+#[put("/scratchpad-private/repair")]
+pub async fn scratchpad_private_repair(
+    request: HttpRequest,
+    scratchpad: web::Json<DwebScratchpad>,
+    query_params: web::Query<MutateQueryParams>,
+    client: Data<dweb::client::DwebClient>,
+) -> HttpResponse {
+    println!("DEBUG {}", request.path());
+    repair_scratchpad(
+        &request,
+        scratchpad.into_inner(),
+        query_params.into_inner(),
+        &client,
+        DwebType::PrivateScratchpad,
+    )
+    .await
+}
+
+/// Repair a forked public Scratchpad you own, with optional name
+///
+/// Writes a new version with a counter higher than any version of the fork, which replaces
+/// them all. If unencrypted_data is given (for example the app's merge of the versions
+/// returned using resolve=merge) that is written, otherwise the version chosen using
+/// resolve. Nothing is written if the Scratchpad is not forked.
+#[utoipa::path(
+    put,
+    params(
+        ("tries" = Option<u32>, Query, description = "number of times to try calling the Autonomi upload API for each put, 0 means unlimited. This overrides the API control setting in the server."),
+        ("object_name" = Option<String>, Query, description = "optional name, used to allow more than one scratchpad per owner secret/app id combination"),
+        ("resolve" = Option<String>, Query, description = "the version to write if no unencrypted_data is given: counter or signature (default set by the server)"),
+        ("Ant-App-ID" = Option<String>, Header, description = "a unique string identifier for this app (as suggested by Autonomi and used to derive the VaultContentType used by an app)")),
+    request_body(content = DwebScratchpad, content_type = "application/json"),
+    responses(
+        (status = StatusCode::OK, description = "A MutateResult featuring either status 200 with cost and the network address of the repaired Scratchpad, or in case of error an error status code and message about the error.<br/>\
+        <b>Error StatusCodes</b><br/>\
+        &nbsp;&nbsp;&nbsp;400 BAD_REQUEST: resolve=merge was given without unencrypted_data<br/>\
+        &nbsp;&nbsp;&nbsp;502 BAD_GATEWAY: Autonomi network error<br/>", body = MutateResult,)
+    ),
+    tags = ["Dweb Autonomi"],
+)]
+#[put("/scratchpad-public/repair")]
+pub async fn scratchpad_public_repair(
+    request: HttpRequest,
+    scratchpad: web::Json<DwebScratchpad>,
+    query_params: web::Query<MutateQueryParams>,
+    client: Data<dweb::client::DwebClient>,
+) -> HttpResponse {
+    println!("DEBUG {}", request.path());
+    repair_scratchpad(
+        &request,
+        scratchpad.into_inner(),
+        query_params.into_inner(),
+        &client,
+        DwebType::PublicScratchpad,
+    )
+    .await
+}
+
+async fn repair_scratchpad(
+    request: &HttpRequest,
+    dweb_scratchpad: DwebScratchpad,
+    query_params: MutateQueryParams,
+    client: &dweb::client::DwebClient,
+    dweb_type: DwebType,
+) -> HttpResponse {
+    let is_public = matches!(dweb_type, DwebType::PublicScratchpad);
+    let (rest_operation, rest_handler, type_derivation_index) = if is_public {
+        (
+            "/scratchpad-public/repair PUT".to_string(),
+            "scratchpad_public_repair()",
+            PUBLIC_SCRATCHPAD_DERIVATION_INDEX,
+        )
+    } else {
+        (
+            "/scratchpad-private/repair PUT".to_string(),
+            "scratchpad_private_repair()",
+            PRIVATE_SCRATCHPAD_DERIVATION_INDEX,
+        )
+    };
+
+    let request_params = match ParsedRequestParams::process_mutable_type_header_and_query_params(
+        client,
+        request.headers(),
+        &query_params,
+    ) {
+        Ok(params) => params,
+        Err(e) => {
+            return make_error_response_page(
+                Some(StatusCode::BAD_REQUEST),
+                &mut HttpResponse::BadRequest(),
+                rest_operation.to_string(),
+                &format!("{rest_operation} request error - {e}"),
+            );
+        }
+    };
+
+    let scratchpad_secret = match request_params.derive_object_owner_secret(type_derivation_index) {
+        Ok(derived_secret) => derived_secret,
+        Err(e) => {
+            return make_error_response_page(
+                Some(StatusCode::BAD_REQUEST),
+                &mut HttpResponse::BadRequest(),
+                rest_operation.to_string(),
+                &format!("{rest_handler} failed to derive owner secret for Scratchpad - {e}"),
+            );
+        }
+    };
+    let scratchpad_address = ScratchpadAddress::new(scratchpad_secret.public_key());
+
+    let forks = match client.backend.scratchpad_get(&scratchpad_address).await {
+        Ok(_) => {
+            return MutateResult {
+                rest_operation,
+                dweb_type,
+                status_code: StatusCode::OK.as_u16(),
+                status_message: "success - the Scratchpad is not forked".to_string(),
+                network_address: scratchpad_address.to_hex(),
+                ..Default::default()
+            }
+            .response(rest_handler);
+        }
+        Err(BackendError::ScratchpadFork(forks)) => forks,
+        Err(e) => {
+            return MutateResult {
+                rest_operation,
+                dweb_type,
+                status_code: StatusCode::BAD_GATEWAY.as_u16(),
+                status_message: format!("failed to get Scratchpad from network - {e}"),
+                ..Default::default()
+            }
+            .response(rest_handler);
+        }
+    };
+
+    let (content_type, data) = if !dweb_scratchpad.unencrypted_data.is_empty() {
+        (
+            dweb_scratchpad.data_encoding,
+            Bytes::from(dweb_scratchpad.unencrypted_data),
+        )
+    } else {
+        let chosen = match resolve_fork(&forks, request_params.resolve) {
+            Some(chosen) => chosen,
+            None => {
+                return MutateResult {
+                    rest_operation,
+                    dweb_type,
+                    status_code: StatusCode::BAD_REQUEST.as_u16(),
+                    status_message: format!(
+                        "{rest_handler} needs the merged content in unencrypted_data when resolve=merge"
+                    ),
+                    ..Default::default()
+                }
+                .response(rest_handler);
+            }
+        };
+        let data = if is_public {
+            Bytes::from(chosen.encrypted_data().to_vec())
+        } else {
+            match chosen.decrypt_data(&scratchpad_secret) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    return MutateResult {
+                        rest_operation,
+                        dweb_type,
+                        status_code: StatusCode::INTERNAL_SERVER_ERROR.as_u16(),
+                        status_message: format!("failed to decrypt Scratchpad - {e}"),
+                        ..Default::default()
+                    }
+                    .response(rest_handler);
+                }
+            }
+        };
+        (chosen.data_encoding(), data)
+    };
+
+    let counter = highest_counter(&forks) + 1;
+    println!(
+        "DEBUG {rest_handler} repairing fork of {} versions with counter {counter}",
+        forks.len()
+    );
+    let repaired = if is_public {
+        create_public_scratchpad(&scratchpad_secret, content_type, &data, counter)
+    } else {
+        Scratchpad::new(&scratchpad_secret, content_type, &data, counter)
+    };
+
+    let payment_option = client.payment_option().clone();
+    let spends = Spends::new(client, None).await;
+    let result = retry_with_policy(
+        &client
+            .api_control
+            .retry_policy
+            .with_max_tries(request_params.tries),
+        &rest_handler,
+        (repaired, payment_option),
+        async move |(repaired, payment_option)| match client
+            .backend
+            .scratchpad_put(repaired, payment_option)
+            .await
+        {
+            Ok(result) => Ok(result),
+            Err(e) => Err(eyre!(e)),
+        },
+    )
+    .await;
+
+    match result {
+        Ok(_) => {
+            let (cost_in_ant, cost_in_arb_eth) = match spends {
+                Ok(spends) => spends.get_spend_strings().await,
+                Err(e) => {
+                    println!("DEBUG {rest_operation} error: unable to report Spends - {e}");
+                    ("unkown".to_string(), "unknown".to_string())
+                }
+            };
+            MutateResult {
+                rest_operation,
+                dweb_type,
+                status_code: StatusCode::OK.as_u16(),
+                status_message: "success".to_string(),
+                cost_in_ant,
+                cost_in_arb_eth,
+                network_address: scratchpad_address.to_hex(),
+                ..Default::default()
+            }
+            .response(rest_handler)
+        }
+
+        Err(e) => {
+            let status_message = format!("failed to repair Scratchpad on the network - {e}");
+            println!("DEBUG {status_message}");
+            MutateResult {
+                rest_operation,
+                dweb_type,
+                status_code: StatusCode::BAD_GATEWAY.as_u16(),
+                status_message,
+                ..Default::default()
+            }
+            .response(rest_handler)
+        }
+    }
+}
+
 /// Create a new public Scratchpad (offline)
 fn create_public_scratchpad(
     scratchpad_secret: &SecretKey,
@@ -1204,6 +1471,13 @@ fn create_public_scratchpad(
     )
 }
 
+/// Query parameters for getting a Scratchpad by address
+#[derive(Deserialize)]
+pub struct ResolveQueryParams {
+    /// How to choose between the versions of a forked Scratchpad: counter, signature, all or merge
+    resolve: Option<String>,
+}
+
 /// A representation of the Autonomi Scratchpad for web clients
 ///
 /// Exactly one target is allowed, so make sure unused targets are empty strings
@@ -1215,6 +1489,26 @@ pub struct DwebScratchpad {
     encrypted_data: Vec<u8>,
     unencrypted_data: Vec<u8>,
     counter: u64,
+    /// The number of versions found if the Scratchpad is forked, otherwise 0
+    #[serde(default)]
+    fork_count: u64,
+    /// How the version returned was chosen if the Scratchpad is forked (counter, signature, all or merge)
+    #[serde(default)]
+    fork_resolution: String,
+    /// Every version of a forked Scratchpad, returned for resolve=all or resolve=merge
+    #[serde(default)]
+    forks: Vec<DwebScratchpadVersion>,
+}
+
+/// One version of a forked Scratchpad
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct DwebScratchpadVersion {
+    counter: u64,
+    /// The signature as hex, which identifies this version
+    signature: String,
+    data_encoding: u64,
+    encrypted_data: Vec<u8>,
+    unencrypted_data: Vec<u8>,
 }
 
 impl Default for DwebScratchpad {
@@ -1226,6 +1520,91 @@ impl Default for DwebScratchpad {
             data_encoding: 0,
             encrypted_data: Vec::<u8>::new(),
             unencrypted_data: Vec::<u8>::new(),
+            fork_count: 0,
+            fork_resolution: "".to_string(),
+            forks: Vec::new(),
+        }
+    }
+}
+
+impl DwebScratchpad {
+    /// Make the response to a GET, reporting any fork
+    ///
+    /// content returns the (encrypted_data, unencrypted_data) of a version as given to the app
+    fn from_resolved(
+        dweb_type: DwebType,
+        scratchpad_address: &ScratchpadAddress,
+        resolved: &ResolvedScratchpad,
+        resolution: ForkResolution,
+        content: impl Fn(&Scratchpad) -> (Vec<u8>, Vec<u8>),
+    ) -> DwebScratchpad {
+        let mut dweb_scratchpad = DwebScratchpad {
+            dweb_type,
+            scratchpad_address: scratchpad_address.to_hex(),
+            ..Default::default()
+        };
+
+        match &resolved.scratchpad {
+            Some(scratchpad) => {
+                let (encrypted_data, unencrypted_data) = content(scratchpad);
+                dweb_scratchpad.data_encoding = scratchpad.data_encoding();
+                dweb_scratchpad.counter = scratchpad.counter();
+                dweb_scratchpad.encrypted_data = encrypted_data;
+                dweb_scratchpad.unencrypted_data = unencrypted_data;
+            }
+            None => dweb_scratchpad.counter = highest_counter(&resolved.forks),
         }
+
+        if resolved.is_forked() {
+            dweb_scratchpad.fork_count = resolved.forks.len() as u64;
+            dweb_scratchpad.fork_resolution = resolution.as_str().to_string();
+            if resolution.returns_versions() {
+                dweb_scratchpad.forks = resolved
+                    .forks
+                    .iter()
+                    .map(|scratchpad| {
+                        let (encrypted_data, unencrypted_data) = content(scratchpad);
+                        DwebScratchpadVersion {
+                            counter: scratchpad.counter(),
+                            signature: signature_hex(scratchpad),
+                            data_encoding: scratchpad.data_encoding(),
+                            encrypted_data,
+                            unencrypted_data,
+                        }
+                    })
+                    .collect();
+            }
+        }
+        dweb_scratchpad
+    }
+
+    /// The JSON response, which is 409 CONFLICT if no version was chosen so the app must merge them
+    fn response(
+        &self,
+        resolved: &ResolvedScratchpad,
+        rest_operation: &str,
+        rest_handler: &str,
+    ) -> HttpResponse {
+        let json = match serde_json::to_string(&self) {
+            Ok(json) => json,
+            Err(e) => {
+                return make_error_response_page(
+                    Some(StatusCode::INTERNAL_SERVER_ERROR),
+                    &mut HttpResponse::NotFound(),
+                    rest_operation.to_string(),
+                    &format!("{rest_handler} failed to encode JSON result - {e}"),
+                )
+            }
+        };
+
+        // println!("DEBUG DwebScratchpad as JSON: {json:?}");
+
+        let mut response = match resolved.scratchpad {
+            Some(_) => HttpResponse::Ok(),
+            None => HttpResponse::Conflict(),
+        };
+        response
+            .insert_header(ContentType(mime::APPLICATION_JSON))
+            .body(json)
     }
 }
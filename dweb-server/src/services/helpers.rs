@@ -21,25 +21,31 @@ use actix_web::{
 };
 use color_eyre::eyre::{eyre, Result};
 
-use dweb::web::name::validate_dweb_name;
+use dweb::client::DwebClient;
+use dweb::files::directory::{validate_version_tag, Tree};
+use dweb::helpers::convert::tuple_from_address_or_name;
+use dweb::history::{History, HistoryAddress};
+use dweb::web::name::{split_branch_selector, validate_dweb_name};
 
 pub const AS_NAME_NONE: &str = "anonymous";
 
 /// parse dweb address_or_name url with an 'as name'
 ///
-///     [v{version}/]{address_or_name}{remote_path}
+///     [v{version-or-tag}[--{branch}]/]{address_or_name}{remote_path}
 ///
 /// returns a tuple of:
 ///     Option<u64> // version if present
+///     Option<String> // version tag if present
 ///
 /// Note:
-///     version is an optional integer (u64)
+///     version is an optional integer (u64), or a tag recorded in the version's VersionManifest
+///     branch is an optional branch of the History, in which case the address returned is of the branch
 ///     address_or_name is the site to visit
 ///     remote_path is the resource to load from the site
 ///
 pub fn parse_versioned_path_params(
     params: &String,
-) -> Result<(Option<u64>, String, String, String)> {
+) -> Result<(Option<u64>, Option<String>, String, String, String)> {
     // Parse params manually so we can support with and without version
     println!("DEBUG parse_versioned_path_params_with_as_name() {params}");
 
@@ -51,36 +57,45 @@ pub fn parse_versioned_path_params(
     let second_rest = String::from(rest);
 
     println!("1:{first} 2: {second} r: {rest}");
+    let (first, branch) = split_branch_selector(first)?;
 
     // If it validates as a DWEB-NAME it can't be a version (because they start with two alphabetic characters)
 
-    let (version, address_or_name, remote_path) = match parse_version_string(&first) {
-        Ok(version) => (version, second, second_rest),
-        Err(_) => (None, first, first_rest),
+    let (version, version_tag, address_or_name, remote_path) = match parse_version_string(&first) {
+        Ok(version) => (version, None, second, second_rest),
+        Err(_) => match parse_version_tag_string(&first) {
+            // A tag looks like a DWEB-NAME so only accept it if followed by an address or name
+            Ok(tag) if !is_address_or_name(first) && is_address_or_name(second) => {
+                (None, Some(tag), second, second_rest)
+            }
+            _ => (None, None, first, first_rest),
+        },
     };
 
-    println!("version:{version:?} as_name: {AS_NAME_NONE}, address_or_name: {address_or_name} remote_path: {remote_path}");
+    let address_or_name = address_or_name_for_branch(address_or_name, &branch)?;
+    println!("version:{version:?} version_tag:{version_tag:?} branch:{branch:?} as_name: {AS_NAME_NONE}, address_or_name: {address_or_name} remote_path: {remote_path}");
     Ok((
         version,
+        version_tag,
         AS_NAME_NONE.to_string(),
-        address_or_name.to_string(),
+        address_or_name,
         remote_path,
     ))
 }
 
 /// Parse the path part of a /dweb-open-as URL, which in full is:
 ///
-/// url: http://127.0.0.1:<PORT>/[v{version}/]/{as_name}/{address_or_name}{remote_path}
+/// url: http://127.0.0.1:<PORT>/[v{version-or-tag}[--{branch}]/]/{as_name}/{address_or_name}{remote_path}
 ///
 /// Note:
-///     version is an optional integer (u64)
+///     version is an optional integer (u64), or a tag recorded in the version's VersionManifest
 ///     as_name must either be a DWEB-NAME to register, or 'anomymous'
 ///     address_or_name is the site to visit
 ///     remote_path is the resource to load from the site
 ///
 pub fn parse_versioned_path_params_with_as_name(
     params: &String,
-) -> Result<(Option<u64>, String, String, String)> {
+) -> Result<(Option<u64>, Option<String>, String, String, String)> {
     // Parse params manually so we can support with and without version
     println!("DEBUG parse_versioned_path_params() {params}");
 
@@ -94,37 +109,61 @@ pub fn parse_versioned_path_params_with_as_name(
     let third_rest = String::from(rest);
 
     println!("1:{first} 2: {second}  3: {third} r: {rest}");
+    let (first, branch) = split_branch_selector(first)?;
 
     // If it validates as a DWEB-NAME it can't be a version (because they start with two alphabetic characters)
 
-    let (version, as_name, address_or_name, remote_path) = match parse_version_string(&first) {
-        Ok(version) => {
-            println!("BINGO 1");
-            (version, second, third, third_rest)
-        }
-        Err(_) => match validate_dweb_name(first) {
-            Ok(_as_name) => {
-                println!("BINGO 2");
-                (None, first, second, second_rest)
-            }
-            Err(_) => {
-                println!("BINGO 3");
-                let msg = format!("/dweb-open-as parameters not valid: '{params}'");
-                println!("DEBUG {msg}");
-                return Err(eyre!(msg));
+    let (version, version_tag, as_name, address_or_name, remote_path) =
+        match parse_version_string(&first) {
+            Ok(version) => {
+                println!("BINGO 1");
+                (version, None, second, third, third_rest)
             }
-        },
-    };
+            Err(_) => match parse_version_tag_string(&first) {
+                // A tag looks like a DWEB-NAME so only accept it if followed by an as_name and an address or name
+                Ok(tag) if validate_dweb_name(second).is_ok() && is_address_or_name(third) => {
+                    println!("BINGO 4");
+                    (None, Some(tag), second, third, third_rest)
+                }
+                _ => match validate_dweb_name(first) {
+                    Ok(_as_name) => {
+                        println!("BINGO 2");
+                        (None, None, first, second, second_rest)
+                    }
+                    Err(_) => {
+                        println!("BINGO 3");
+                        let msg = format!("/dweb-open-as parameters not valid: '{params}'");
+                        println!("DEBUG {msg}");
+                        return Err(eyre!(msg));
+                    }
+                },
+            },
+        };
 
-    println!("version:{version:?} as_name: {as_name} address_or_name: {address_or_name} remote_path: {remote_path}");
+    let address_or_name = address_or_name_for_branch(address_or_name, &branch)?;
+    println!("version:{version:?} version_tag:{version_tag:?} branch:{branch:?} as_name: {as_name} address_or_name: {address_or_name} remote_path: {remote_path}");
     Ok((
         version,
+        version_tag,
         as_name.to_string(),
-        address_or_name.to_string(),
+        address_or_name,
         remote_path,
     ))
 }
 
+// If a branch is given, return the address of that branch of the History at address_or_name
+fn address_or_name_for_branch(address_or_name: &str, branch: &Option<String>) -> Result<String> {
+    match branch {
+        Some(branch) => match tuple_from_address_or_name(address_or_name) {
+            (Some(history_address), _) => Ok(history_address.branch(branch).to_hex()),
+            _ => Err(eyre!(
+                "branch '{branch}' can only be used with a History, not '{address_or_name}'"
+            )),
+        },
+        None => Ok(address_or_name.to_string()),
+    }
+}
+
 /// Parse a string and if valid return an Option<u64>
 ///
 /// Valid version strings consist of a 'v' (or 'V') followed by an optional integer.
@@ -147,6 +186,42 @@ pub fn parse_version_string(version_str: &str) -> Result<Option<u64>> {
     }
 }
 
+/// Parse a string and if valid return a version tag
+///
+/// Valid version tag strings consist of a 'v' (or 'V') followed by a tag.
+/// In orther words: v<TAG>, where TAG is valid according to dweb::files::directory::validate_version_tag()
+pub fn parse_version_tag_string(version_str: &str) -> Result<String> {
+    if version_str.starts_with("v") || version_str.starts_with("V") {
+        let tag = version_str[1..].to_string();
+        validate_version_tag(&tag)?;
+        Ok(tag)
+    } else {
+        Err(eyre!("invalid version tag: '{version_str}'"))
+    }
+}
+
+fn is_address_or_name(address_or_name: &str) -> bool {
+    let (history_address, archive_address) = tuple_from_address_or_name(address_or_name);
+    history_address.is_some() || archive_address.is_some()
+}
+
+/// Get the version of a History<Tree> which has the given tag in its VersionManifest
+pub async fn version_from_tag(
+    client: &DwebClient,
+    history_address: HistoryAddress,
+    tag: &str,
+) -> Result<u64> {
+    let mut history = History::<Tree>::from_history_address(
+        client.clone(),
+        history_address,
+        client.api_control.ignore_pointers,
+        0,
+    )
+    .await?;
+
+    Tree::history_version_for_tag(client, &mut history, tag).await
+}
+
 pub(crate) fn make_error_response_page(
     status_code: Option<StatusCode>,
     response_builder: &mut HttpResponseBuilder,
@@ -174,3 +249,22 @@ pub(crate) fn make_error_response_page(
         .insert_header(header::ContentType(mime::TEXT_HTML))
         .body(body)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn version_tag_strings_are_parsed() {
+        assert_eq!(parse_version_tag_string("vrelease").unwrap(), "release");
+        assert_eq!(parse_version_tag_string("Vrc-1").unwrap(), "rc-1");
+        assert!(parse_version_tag_string("release").is_err());
+        assert!(parse_version_tag_string("v").is_err());
+        assert!(parse_version_tag_string("v1").is_err());
+        assert!(parse_version_tag_string("vrc--staging").is_err());
+
+        // A version number is never taken as a tag
+        assert_eq!(parse_version_string("v12").unwrap(), Some(12));
+        assert!(parse_version_string("vrelease").is_err());
+    }
+}
@@ -0,0 +1,304 @@
+/*
+Copyright (c) 2025- Mark Hughes
+
+This program is free software: you can redistribute it and/or modify
+it under the terms of the GNU Affero General Public License as published by
+the Free Software Foundation, either version 3 of the License, or
+(at your option) any later version.
+
+This program is distributed in the hope that it will be useful,
+but WITHOUT ANY WARRANTY; without even the implied warranty of
+MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+GNU Affero General Public License for more details.
+
+You should have received a copy of the GNU Affero General Public License
+along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! A background task for the main dweb server which keeps the pointers of
+//! owned Histories in sync with the head of their graph.
+//!
+//! Autonomi pointers can lag the graph (see 'dweb heal-history'). This task
+//! periodically loads each History published from this device (see
+//! dweb::cache::owned_histories) by walking its graph forward from the most recent
+//! entry known locally (see dweb::cache::history_head), and when the pointer differs
+//! from the head it heals it. Healing with pointer_update() is free and
+//! always used when possible. Healing with pointer_put() has a cost so is limited
+//! by a budget for each run of the server.
+//!
+//! Configured using environment variables, or [server] heal_interval and heal_max_puts in dweb.toml:
+//!
+//!     DWEB_HEAL_INTERVAL   seconds between passes (default 900, 0 disables healing)
+//!     DWEB_HEAL_MAX_PUTS   maximum number of paid pointer_put() heals (default 3)
+//!
+//! The outcome of each pass is available from the /dweb-0/history-healer API.
+
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use actix_web::rt::time::sleep;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use autonomi::SecretKey;
+
+use dweb::cache::owned_histories::owned_history_names;
+use dweb::client::DwebClient;
+use dweb::config::config;
+use dweb::files::directory::Tree;
+use dweb::history::{History, PointerHealth};
+
+// Delay the first pass so it doesn't compete with startup
+const HEAL_STARTUP_DELAY_SECS: u64 = 60;
+
+/// The state and most recent outcomes of the History pointer healer
+#[derive(Clone, Default, Serialize, Deserialize, ToSchema)]
+pub struct HistoryHealerStatus {
+    /// False if disabled, or if no owner secret key is available
+    pub enabled: bool,
+    /// Seconds between passes
+    pub interval_secs: u64,
+    /// Maximum number of paid pointer_put() heals for this run of the server
+    pub max_puts: u32,
+    /// Number of paid pointer_put() heals used so far
+    pub puts_used: u32,
+    /// Number of passes completed
+    pub passes: u64,
+    /// Time the most recent pass completed (seconds since UNIX epoch)
+    pub last_pass: Option<u64>,
+    /// The outcome for each History in the most recent pass
+    pub histories: Vec<HistoryHealOutcome>,
+}
+
+/// The outcome of checking the pointer of one History
+#[derive(Clone, Serialize, Deserialize, ToSchema)]
+pub struct HistoryHealOutcome {
+    /// The NAME used when the History was published
+    pub name: String,
+    pub history_address: Option<String>,
+    /// The pointer counter before any healing
+    pub pointer_counter: Option<u64>,
+    /// The index of the head entry found by walking the graph
+    pub head_index: Option<u64>,
+    /// None if the History could not be checked (see error)
+    pub health: Option<PointerHealth>,
+    /// Details of any error
+    pub error: Option<String>,
+}
+
+impl HistoryHealerStatus {
+    /// True if the budget allows another paid pointer_put() heal
+    pub fn put_allowed(&self) -> bool {
+        self.puts_used < self.max_puts
+    }
+}
+
+pub static HISTORY_HEALER_STATUS: LazyLock<Mutex<HistoryHealerStatus>> =
+    LazyLock::new(|| Mutex::new(HistoryHealerStatus::default()));
+
+/// Get a copy of the healer status for reporting
+pub fn history_healer_status() -> HistoryHealerStatus {
+    match HISTORY_HEALER_STATUS.lock() {
+        Ok(status) => status.clone(),
+        Err(_) => HistoryHealerStatus::default(),
+    }
+}
+
+/// Run the healer until the server exits. Intended to be spawned by the main server.
+pub async fn run_history_healer(client: DwebClient) {
+    let interval_secs = config().heal_interval();
+    let max_puts = config().heal_max_puts();
+
+    if interval_secs == 0 {
+        println!("History pointer healer is disabled (DWEB_HEAL_INTERVAL is 0)");
+        return;
+    }
+
+    let owner_secret_key = match dweb::helpers::get_app_secret_key() {
+        Ok(secret_key) => secret_key,
+        Err(e) => {
+            println!("History pointer healer is disabled - {e}");
+            return;
+        }
+    };
+
+    if let Ok(mut status) = HISTORY_HEALER_STATUS.lock() {
+        status.enabled = true;
+        status.interval_secs = interval_secs;
+        status.max_puts = max_puts;
+    }
+    println!(
+        "History pointer healer will check owned Histories every {interval_secs}s (max paid heals: {max_puts})"
+    );
+
+    sleep(Duration::from_secs(HEAL_STARTUP_DELAY_SECS)).await;
+    loop {
+        heal_owned_histories(&client, &owner_secret_key).await;
+        sleep(Duration::from_secs(interval_secs)).await;
+    }
+}
+
+async fn heal_owned_histories(client: &DwebClient, owner_secret_key: &SecretKey) {
+    let names = owned_history_names();
+    println!(
+        "DEBUG heal_owned_histories() checking {} Histories",
+        names.len()
+    );
+
+    let mut outcomes = Vec::<HistoryHealOutcome>::new();
+    for name in names {
+        let allow_put = match HISTORY_HEALER_STATUS.lock() {
+            Ok(status) => status.put_allowed(),
+            Err(_) => false,
+        };
+        let outcome = heal_owned_history(client, owner_secret_key, &name, allow_put).await;
+        if outcome.health == Some(PointerHealth::HealedByPut) {
+            if let Ok(mut status) = HISTORY_HEALER_STATUS.lock() {
+                status.puts_used = status.puts_used + 1;
+            }
+        }
+        outcomes.push(outcome);
+    }
+
+    if let Ok(mut status) = HISTORY_HEALER_STATUS.lock() {
+        status.passes = status.passes + 1;
+        status.last_pass = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|duration| duration.as_secs());
+        status.histories = outcomes;
+    }
+}
+
+async fn heal_owned_history(
+    client: &DwebClient,
+    owner_secret_key: &SecretKey,
+    name: &String,
+    allow_put: bool,
+) -> HistoryHealOutcome {
+    let mut outcome = HistoryHealOutcome {
+        name: name.clone(),
+        history_address: None,
+        pointer_counter: None,
+        head_index: None,
+        health: None,
+        error: None,
+    };
+
+    // Ignore the pointer so the head is found by walking the graph. The walk starts
+    // from the pointer or the recorded head hint, whichever is further ahead, so only
+    // entries added since the last pass are fetched.
+    let mut history = match History::<Tree>::from_name(
+        client.clone(),
+        owner_secret_key.clone(),
+        name.clone(),
+        true,
+        0,
+    )
+    .await
+    {
+        Ok(history) => history,
+        Err(e) => {
+            outcome.error = Some(format!("failed to load History - {e}"));
+            return outcome;
+        }
+    };
+
+    outcome.history_address = Some(history.history_address().to_hex());
+    outcome.pointer_counter = Some(history.pointer_counter());
+    outcome.head_index = Some(history.num_entries().saturating_sub(1));
+
+    match history
+        .check_and_heal_pointer(owner_secret_key.clone(), allow_put)
+        .await
+    {
+        Ok(health) => {
+            if health != PointerHealth::InSync {
+                println!("History '{name}' pointer: {health:?}");
+            }
+            outcome.health = Some(health);
+        }
+        Err(e) => {
+            println!("History '{name}' failed to heal pointer - {e}");
+            outcome.error = Some(e.to_string());
+        }
+    }
+    outcome
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use autonomi::files::archive_public::ArchiveAddress;
+    use autonomi::XorName;
+
+    use dweb::backend::{DwebBackend, MemoryBackend};
+    use dweb::client::DwebClientConfig;
+    use dweb::helpers::graph_entry::{create_graph_entry, get_derivation_from_graph_entry};
+
+    use super::*;
+
+    #[test]
+    fn put_budget_is_respected() {
+        let mut status = HistoryHealerStatus {
+            max_puts: 2,
+            ..Default::default()
+        };
+        assert!(status.put_allowed());
+        status.puts_used = 2;
+        assert!(!status.put_allowed());
+    }
+
+    #[actix_web::test]
+    async fn lagging_pointer_is_healed() {
+        dweb::cache::history_head::set_history_heads_file_path(None);
+        let backend = Arc::new(MemoryBackend::new());
+        let client = DwebClient::with_backend(backend.clone(), &DwebClientConfig::default());
+        let owner = SecretKey::random();
+        let name = "healer".to_string();
+
+        let (_, mut history) =
+            History::<Tree>::create_online(client.clone(), name.clone(), owner.clone())
+                .await
+                .unwrap();
+        let trove_address = ArchiveAddress::new(XorName::from_content(b"version 1"));
+        history
+            .publish_new_version(owner.clone(), &trove_address)
+            .await
+            .unwrap();
+
+        let outcome = heal_owned_history(&client, &owner, &name, false).await;
+        assert_eq!(outcome.health, Some(PointerHealth::InSync));
+
+        // Add entries without updating the pointer, leaving it two behind the head
+        let history_secret_key =
+            History::<Tree>::history_main_secret_key(owner.clone()).derive_child(name.as_bytes());
+        let mut head = history.get_head_entry().await.unwrap().unwrap();
+        for version in 2..=3 {
+            let derivation = get_derivation_from_graph_entry(&head).unwrap();
+            let content = format!("version {version}");
+            let trove_address = ArchiveAddress::new(XorName::from_content(content.as_bytes()));
+            let entry =
+                create_graph_entry(&history_secret_key, Some(&head), &derivation, trove_address)
+                    .await
+                    .unwrap();
+            backend
+                .graph_entry_put(entry.clone(), client.payment_option())
+                .await
+                .unwrap();
+            head = entry;
+        }
+
+        let outcome = heal_owned_history(&client, &owner, &name, false).await;
+        assert_eq!(outcome.pointer_counter, Some(1));
+        assert_eq!(outcome.head_index, Some(3));
+        assert_eq!(outcome.health, Some(PointerHealth::NeedsPut));
+
+        let outcome = heal_owned_history(&client, &owner, &name, true).await;
+        assert_eq!(outcome.health, Some(PointerHealth::HealedByPut));
+
+        let outcome = heal_owned_history(&client, &owner, &name, false).await;
+        assert_eq!(outcome.health, Some(PointerHealth::InSync));
+    }
+}
@@ -0,0 +1,295 @@
+/*
+ Copyright (c) 2025- Mark Hughes
+
+ This program is free software: you can redistribute it and/or modify
+ it under the terms of the GNU Affero General Public License as published by
+ the Free Software Foundation, either version 3 of the License, or
+ (at your option) any later version.
+
+ This program is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY; without even the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU Affero General Public License for more details.
+
+ You should have received a copy of the GNU Affero General Public License
+ along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! A registry of the listeners spawned by the main dweb server.
+//!
+//! The main server starts a listener on a new port for each directory/website opened
+//! (see /dweb-open). Each is registered here with its StopHandle so that it can be
+//! listed and stopped using the /dweb-0/listeners API, stopped when the main server
+//! exits, and stopped once it has been idle for a while so that a long running server
+//! doesn't accumulate listeners which are no longer used.
+//!
+//! Configured using the environment variable, or [server] listener_idle in dweb.toml:
+//!
+//!     DWEB_LISTENER_IDLE   seconds without a request before a listener is stopped (default 1800, 0 disables)
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use actix_web::rt::time::sleep;
+use actix_web::web::Data;
+
+use dweb::cache::directory_with_port::{
+    forget_directory_version_with_port, DirectoryVersionWithPort,
+};
+use dweb::client::DwebClient;
+use dweb::config::config;
+use dweb::web::server::ListenerInfo;
+
+use crate::services::init_dweb_server_non_blocking;
+use crate::StopHandle;
+
+// Never check less often than this, however short the idle timeout
+const MAX_REAP_INTERVAL_SECS: u64 = 60;
+
+struct Listener {
+    directory_version: DirectoryVersionWithPort,
+    stop_handle: Data<StopHandle>,
+    /// Time of the most recent request (seconds since UNIX epoch)
+    last_request: AtomicU64,
+}
+
+static LISTENERS: LazyLock<Mutex<HashMap<u16, Listener>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Spawn a listener for directory_version on its port and register it
+pub(crate) fn spawn_listener(client: DwebClient, directory_version: DirectoryVersionWithPort) {
+    let port = directory_version.port;
+    let stop_handle = register_listener(directory_version.clone());
+
+    let mut client_config = client.client_config.clone();
+    client_config.port = Some(port);
+    std::thread::spawn(move || {
+        let archive_address = directory_version.archive_address;
+        let _ = init_dweb_server_non_blocking(
+            &client_config,
+            Some(client),
+            Some(stop_handle.clone()),
+            Some(directory_version),
+        );
+
+        // Whether stopped or failed to start, a new listener is needed next time it is opened
+        unregister_listener(port, &stop_handle);
+        forget_directory_version_with_port(archive_address);
+        println!("DEBUG listener on port {port} has exited");
+    });
+}
+
+// Register a listener for directory_version, returning the StopHandle for its server
+fn register_listener(directory_version: DirectoryVersionWithPort) -> Data<StopHandle> {
+    let stop_handle = Data::new(StopHandle::default());
+    if let Ok(mut listeners) = LISTENERS.lock() {
+        listeners.insert(
+            directory_version.port,
+            Listener {
+                directory_version,
+                stop_handle: stop_handle.clone(),
+                last_request: AtomicU64::new(now_secs()),
+            },
+        );
+    }
+    stop_handle
+}
+
+// Remove the listener on port unless the port has already been reused by another listener
+fn unregister_listener(port: u16, stop_handle: &Data<StopHandle>) {
+    if let Ok(mut listeners) = LISTENERS.lock() {
+        let is_ours = match listeners.get(&port) {
+            Some(listener) => Arc::ptr_eq(&*listener.stop_handle, &**stop_handle),
+            None => false,
+        };
+        if is_ours {
+            listeners.remove(&port);
+        }
+    }
+}
+
+/// Note that the listener on port has handled a request
+pub(crate) fn record_request(port: u16) {
+    if let Ok(listeners) = LISTENERS.lock() {
+        if let Some(listener) = listeners.get(&port) {
+            listener.last_request.store(now_secs(), Ordering::Relaxed);
+        }
+    }
+}
+
+/// Describe each registered listener, ordered by port
+pub(crate) fn listener_infos() -> Vec<ListenerInfo> {
+    let now = now_secs();
+    let mut infos: Vec<ListenerInfo> = match LISTENERS.lock() {
+        Ok(listeners) => listeners
+            .values()
+            .map(|listener| {
+                let mut info = listener.directory_version.listener_info();
+                let last_request = listener.last_request.load(Ordering::Relaxed);
+                info.idle_secs = Some(now.saturating_sub(last_request));
+                info
+            })
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    infos.sort_by_key(|info| info.port);
+    infos
+}
+
+/// Stop the listener on port gracefully. Returns false if there is no listener on port
+pub(crate) fn stop_listener(port: u16) -> bool {
+    let stop_handle = match LISTENERS.lock() {
+        Ok(listeners) => listeners
+            .get(&port)
+            .map(|listener| listener.stop_handle.clone()),
+        Err(_) => None,
+    };
+    match stop_handle {
+        Some(stop_handle) => {
+            println!("Stopping listener on port {port}");
+            stop_handle.stop(true);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Stop every listener gracefully, returning their ports
+pub(crate) fn stop_all_listeners() -> Vec<u16> {
+    let mut ports: Vec<u16> = match LISTENERS.lock() {
+        Ok(listeners) => listeners.keys().copied().collect(),
+        Err(_) => Vec::new(),
+    };
+    ports.sort();
+    ports.retain(|port| stop_listener(*port));
+    ports
+}
+
+/// Stop listeners which have been idle for longer than DWEB_LISTENER_IDLE seconds. Intended
+/// to be spawned by the main server.
+pub async fn run_listener_reaper() {
+    let idle_secs = config().listener_idle();
+    if idle_secs == 0 {
+        println!("Idle listeners will not be stopped (DWEB_LISTENER_IDLE is 0)");
+        return;
+    }
+
+    let interval_secs = idle_secs.min(MAX_REAP_INTERVAL_SECS);
+    loop {
+        sleep(Duration::from_secs(interval_secs)).await;
+        for port in idle_listeners(idle_secs, now_secs()) {
+            println!("Listener on port {port} has been idle for over {idle_secs}s");
+            stop_listener(port);
+        }
+    }
+}
+
+fn idle_listeners(idle_secs: u64, now: u64) -> Vec<u16> {
+    match LISTENERS.lock() {
+        Ok(listeners) => listeners
+            .iter()
+            .filter(|(_, listener)| {
+                now.saturating_sub(listener.last_request.load(Ordering::Relaxed)) > idle_secs
+            })
+            .map(|(port, _)| *port)
+            .collect(),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn now_secs() -> u64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(duration) => duration.as_secs(),
+        Err(_) => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use autonomi::client::files::archive_public::ArchiveAddress;
+    use autonomi::XorName;
+
+    use dweb::backend::MemoryBackend;
+    use dweb::client::DwebClientConfig;
+    use dweb::files::archive::DualArchive;
+    use dweb::files::directory::Tree;
+
+    // The registry is shared by all tests so each test uses its own ports
+    async fn directory_version(port: u16) -> DirectoryVersionWithPort {
+        let client =
+            DwebClient::with_backend(Arc::new(MemoryBackend::new()), &DwebClientConfig::default());
+        let archive_address = ArchiveAddress::new(XorName::from_content(&port.to_be_bytes()));
+        let tree = Tree::from_dual_archive(&client, DualArchive::new()).await;
+        DirectoryVersionWithPort::new(port, None, None, archive_address, tree)
+    }
+
+    fn set_last_request(port: u16, last_request: u64) {
+        let listeners = LISTENERS.lock().unwrap();
+        listeners[&port]
+            .last_request
+            .store(last_request, Ordering::Relaxed);
+    }
+
+    fn is_registered(port: u16) -> bool {
+        LISTENERS.lock().unwrap().contains_key(&port)
+    }
+
+    #[actix_web::test]
+    async fn listeners_idle_for_longer_than_the_cutoff_are_found() {
+        let (idle_port, active_port, boundary_port) = (41001, 41002, 41003);
+        for port in [idle_port, active_port, boundary_port] {
+            register_listener(directory_version(port).await);
+        }
+        let now = 100_000;
+        set_last_request(idle_port, now - 61);
+        set_last_request(active_port, now - 10);
+        set_last_request(boundary_port, now - 60);
+
+        let idle = idle_listeners(60, now);
+        assert!(idle.contains(&idle_port));
+        assert!(!idle.contains(&active_port));
+        assert!(!idle.contains(&boundary_port));
+
+        // A request resets the idle time
+        record_request(idle_port);
+        assert!(!idle_listeners(60, now_secs()).contains(&idle_port));
+
+        // A clock which has gone backwards doesn't make a listener idle
+        set_last_request(active_port, now + 1000);
+        assert!(!idle_listeners(60, now).contains(&active_port));
+
+        for port in [idle_port, active_port, boundary_port] {
+            let stop_handle = LISTENERS.lock().unwrap()[&port].stop_handle.clone();
+            unregister_listener(port, &stop_handle);
+        }
+    }
+
+    #[actix_web::test]
+    async fn stop_handles_are_registered_by_port() {
+        let port = 41011;
+        assert!(!stop_listener(port));
+
+        let first = register_listener(directory_version(port).await);
+        assert!(is_registered(port));
+        assert!(listener_infos().iter().any(|info| info.port == port
+            && info.idle_secs.is_some()
+            && info.history_address.is_none()));
+
+        // Stopping leaves the listener registered until its server exits
+        assert!(stop_listener(port));
+        assert!(is_registered(port));
+
+        // An exiting listener doesn't remove a newer listener on the same port
+        let second = register_listener(directory_version(port).await);
+        unregister_listener(port, &first);
+        assert!(is_registered(port));
+
+        unregister_listener(port, &second);
+        assert!(!is_registered(port));
+        assert!(!stop_listener(port));
+    }
+}
@@ -14,3 +14,421 @@
  You should have received a copy of the GNU Affero General Public License
  along with this program. If not, see <https://www.gnu.org/licenses/>.
 */
+
+//! An HTTP API for rclone backup, sync and mount of Autonomi storage
+//!
+//! Each rclone remote is a History of private Trees owned by the server's app secret key,
+//! at <code>/dweb-0/rclone/{REMOTE}/</code>. The History is created by the first commit
+//! if it doesn't exist. Changes are staged and published as a new version once no write
+//! has been made for a while, or by POST /dweb-0/rclone-commit/{REMOTE}, so each
+//! 'rclone sync' produces a version which can be restored by reading from
+//! <code>/dweb-0/rclone/{REMOTE}@{VERSION}/</code>.
+//!
+//! Reading follows 'rclone serve http', so a remote can be used with the rclone http backend:
+//!
+//!   GET  {DIRECTORY}/               HTML index with a link for each entry (directories end with '/')
+//!   GET  {DIRECTORY}/?format=json   JSON list of RcloneEntry
+//!   HEAD {FILE}                     size (Content-Length) and modification time (Last-Modified)
+//!   GET  {FILE}                     content, supporting a single byte Range
+//!   GET  {PATH}?stat                JSON RcloneEntry for a file or directory
+//!
+//! Writing:
+//!
+//!   PUT    {FILE}                   write the request body, creating missing directories
+//!   DELETE {PATH}                   remove a file or an empty directory (all its contents with ?purge)
+//!   POST   {DIRECTORY}?mkdir        create a directory and any missing parents
+//!   POST   {PATH}?copy_to={PATH}    server-side copy without downloading or uploading content
+//!   POST   {PATH}?move_to={PATH}    server-side move
+
+use actix_web::{
+    http::{header, header::ContentType, Method, StatusCode},
+    post, web,
+    web::Data,
+    HttpRequest, HttpResponse, HttpResponseBuilder,
+};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+use dweb::client::DwebClient;
+use dweb::files::tree_fs::{TreeFs, TreeFsContent, TreeFsMetadata};
+
+use crate::services::api_dweb::v0::MutateResult;
+use crate::services::tree_fs_mounts::{
+    add_commit_error, commit_mount_response, get_mount, record_write,
+};
+use crate::services::webdav::{http_date, parent_path, percent_encode, xml_escape};
+
+/// Route for rclone remotes, under dweb::api::DWEB_API_ROUTE
+pub const RCLONE_ROUTE: &str = "/rclone";
+
+/// A file or directory as returned by ?format=json and ?stat
+#[derive(Serialize, Deserialize, ToSchema)]
+pub struct RcloneEntry {
+    pub name: String,
+    /// Full path from the root of the remote
+    pub path: String,
+    pub is_dir: bool,
+    /// Size in bytes (0 for a directory)
+    pub size: u64,
+    /// Modification time as seconds since the UNIX epoch (0 for a directory)
+    pub modified: u64,
+    /// True for a file written since the last version was published
+    pub is_staged: bool,
+}
+
+impl RcloneEntry {
+    fn new(path: &str, metadata: &TreeFsMetadata) -> RcloneEntry {
+        RcloneEntry {
+            name: path.rsplit('/').next().unwrap_or("").to_string(),
+            path: path.to_string(),
+            is_dir: metadata.is_dir,
+            size: metadata.len,
+            modified: metadata.modified,
+            is_staged: metadata.is_staged,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub struct RcloneQueryParams {
+    format: Option<String>,
+    stat: Option<String>,
+    mkdir: Option<String>,
+    purge: Option<String>,
+    copy_to: Option<String>,
+    move_to: Option<String>,
+}
+
+/// The actix resource path for rclone requests
+pub fn rclone_resource_path() -> String {
+    format!(
+        "{}{RCLONE_ROUTE}/{{remote}}{{path:.*}}",
+        dweb::api::DWEB_API_ROUTE
+    )
+}
+
+/// Handle every method for a path within a remote
+pub async fn rclone_handler(
+    request: HttpRequest,
+    body: web::Bytes,
+    query_params: web::Query<RcloneQueryParams>,
+    client: Data<DwebClient>,
+) -> HttpResponse {
+    let method = request.method().clone();
+    println!("DEBUG rclone {method} {}", request.path());
+
+    let remote = request.match_info().query("remote").to_string();
+    let path = request.match_info().query("path").to_string();
+    let path = if path.is_empty() {
+        "/".to_string()
+    } else {
+        path
+    };
+
+    let mount = match get_mount(&client, &remote, true).await {
+        Ok(mount) => mount,
+        Err(e) => {
+            return HttpResponse::NotFound().body(format!("failed to open remote '{remote}' - {e}"))
+        }
+    };
+    let mut mount = mount.lock().await;
+
+    let response = match method {
+        Method::GET | Method::HEAD => {
+            if query_params.stat.is_some() {
+                mount
+                    .fs
+                    .metadata(&path)
+                    .map(|metadata| json_response(&RcloneEntry::new(&path, &metadata)))
+            } else {
+                match get(&mount.fs, &request, &remote, &path, &query_params) {
+                    Ok(GetResponse::File { response, content }) => {
+                        // Release the mount so that other requests don't wait while the
+                        // content is fetched from the network
+                        let commit_error = mount.commit_error.clone();
+                        drop(mount);
+                        let response = file_response(response, content, &request).await;
+                        return add_commit_error(response, &commit_error);
+                    }
+                    Ok(GetResponse::Done(response)) => Ok(response),
+                    Err(e) => Err(e),
+                }
+            }
+        }
+        Method::PUT => {
+            let fs = &mut mount.fs;
+            match fs.create_dir_all(&parent_path(&path)) {
+                Ok(()) => fs
+                    .write(&path, body)
+                    .map(|_| HttpResponse::Created().finish()),
+                Err(e) => Err(e),
+            }
+        }
+        Method::DELETE => delete(&mut mount.fs, &path, query_params.purge.is_some()),
+        Method::POST => {
+            let fs = &mut mount.fs;
+            if query_params.mkdir.is_some() {
+                fs.create_dir_all(&path)
+                    .map(|_| HttpResponse::Created().finish())
+            } else if let Some(copy_to) = &query_params.copy_to {
+                fs.create_dir_all(&parent_path(copy_to))
+                    .and_then(|_| fs.copy(&path, copy_to))
+                    .map(|_| HttpResponse::Created().finish())
+            } else if let Some(move_to) = &query_params.move_to {
+                fs.create_dir_all(&parent_path(move_to))
+                    .and_then(|_| fs.rename(&path, move_to))
+                    .map(|_| HttpResponse::Created().finish())
+            } else {
+                Ok(HttpResponse::BadRequest().body("expected ?mkdir, ?copy_to or ?move_to"))
+            }
+        }
+        _ => Ok(HttpResponse::MethodNotAllowed().finish()),
+    };
+
+    let is_write = method == Method::PUT || method == Method::DELETE || method == Method::POST;
+    let response = match response {
+        Ok(response) => {
+            if is_write && response.status().is_success() {
+                record_write(&remote, &mut mount);
+            }
+            response
+        }
+        Err(e) => HttpResponseBuilder::new(crate::services::webdav::status_for_io_error(&e))
+            .body(e.to_string()),
+    };
+    add_commit_error(response, &mount.commit_error)
+}
+
+/// Publish changes made to an rclone remote
+///
+/// Uploads files written to the remote and publishes them as a new version of its History.
+/// Changes are also published automatically once no writes have been made for a while,
+/// so call this at the end of a sync to make the version available sooner.
+///
+/// url: <code>/dweb-0/rclone-commit/{REMOTE}</code>
+#[utoipa::path(
+    post,
+    params(
+        ("remote" = String, Path, description = "the name of the rclone remote (a History owned by the server's app secret key)")),
+    responses(
+        (status = StatusCode::CREATED, description = "A MutateResult with the cost and the version published.", body = MutateResult),
+        (status = StatusCode::NOT_FOUND, description = "The remote has not been accessed"),
+        (status = StatusCode::BAD_GATEWAY, description = "Autonomi network error"),
+    ),
+    tags = ["Dweb"],
+)]
+#[post("/rclone-commit/{remote}")]
+pub async fn rclone_commit_post(request: HttpRequest, remote: web::Path<String>) -> HttpResponse {
+    println!("DEBUG {}", request.path());
+    commit_mount_response(
+        &remote.into_inner(),
+        "/rclone-commit POST",
+        "rclone_commit_post()",
+    )
+    .await
+}
+
+// The response to a GET or HEAD, which for a file may need content from the network
+enum GetResponse {
+    Done(HttpResponse),
+    File {
+        response: HttpResponseBuilder,
+        content: TreeFsContent,
+    },
+}
+
+fn get(
+    fs: &TreeFs,
+    request: &HttpRequest,
+    remote: &str,
+    path: &str,
+    query_params: &RcloneQueryParams,
+) -> std::io::Result<GetResponse> {
+    let metadata = fs.metadata(path)?;
+    if metadata.is_dir {
+        let entries: Vec<RcloneEntry> = fs
+            .read_dir(path)?
+            .iter()
+            .map(|entry| RcloneEntry::new(&entry.path, &entry.metadata))
+            .collect();
+        if query_params.format.as_deref() == Some("json") {
+            return Ok(GetResponse::Done(json_response(&entries)));
+        }
+        return Ok(GetResponse::Done(
+            HttpResponse::Ok()
+                .insert_header(ContentType::html())
+                .body(html_index(remote, path, &entries)),
+        ));
+    }
+
+    let mut response = HttpResponse::Ok();
+    response
+        .insert_header((
+            header::CONTENT_TYPE,
+            metadata
+                .content_type
+                .clone()
+                .unwrap_or(mime::APPLICATION_OCTET_STREAM.to_string()),
+        ))
+        .insert_header((header::LAST_MODIFIED, http_date(metadata.modified)))
+        .insert_header((header::ACCEPT_RANGES, "bytes"));
+    if request.method() == Method::HEAD {
+        return Ok(GetResponse::Done(
+            response
+                .insert_header((header::CONTENT_LENGTH, metadata.len))
+                .finish(),
+        ));
+    }
+
+    Ok(GetResponse::File {
+        response,
+        content: fs.content(path)?,
+    })
+}
+
+// Fetch the content of a file and respond with it, or the part given by a Range header
+async fn file_response(
+    mut response: HttpResponseBuilder,
+    content: TreeFsContent,
+    request: &HttpRequest,
+) -> HttpResponse {
+    let content = match content.read().await {
+        Ok(content) => content,
+        Err(e) => {
+            return HttpResponseBuilder::new(crate::services::webdav::status_for_io_error(&e))
+                .body(e.to_string())
+        }
+    };
+
+    // The range is checked against the content, in case it differs from the metadata
+    let len = content.len() as u64;
+    match request.headers().get(header::RANGE) {
+        Some(range) => match parse_range(range.to_str().unwrap_or(""), len) {
+            Some((start, end)) => response
+                .status(StatusCode::PARTIAL_CONTENT)
+                .insert_header((header::CONTENT_RANGE, format!("bytes {start}-{end}/{len}")))
+                .body(content.slice(start as usize..=end as usize)),
+            None => HttpResponse::RangeNotSatisfiable()
+                .insert_header((header::CONTENT_RANGE, format!("bytes */{len}")))
+                .finish(),
+        },
+        None => response.body(content),
+    }
+}
+
+fn delete(fs: &mut TreeFs, path: &str, purge: bool) -> std::io::Result<HttpResponse> {
+    let metadata = fs.metadata(path)?;
+    if metadata.is_dir && !purge && !fs.read_dir(path)?.is_empty() {
+        return Ok(HttpResponse::Conflict().body("directory is not empty (use ?purge)"));
+    }
+    fs.remove(path)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// A listing in the style of 'rclone serve http', which the rclone http backend can read
+fn html_index(remote: &str, path: &str, entries: &[RcloneEntry]) -> String {
+    let base_href = format!(
+        "{}{RCLONE_ROUTE}/{}",
+        dweb::api::DWEB_API_ROUTE,
+        percent_encode(remote)
+    );
+    let mut html = format!(
+        "<!DOCTYPE html><html><head><title>{}</title></head><body><h1>{}</h1><ul>",
+        xml_escape(path),
+        xml_escape(path)
+    );
+    for entry in entries {
+        let slash = if entry.is_dir { "/" } else { "" };
+        html = html
+            + &format!(
+                "<li><a href=\"{base_href}{}{slash}\">{}{slash}</a></li>",
+                percent_encode(&entry.path),
+                xml_escape(&entry.name)
+            );
+    }
+    html + "</ul></body></html>"
+}
+
+fn json_response<T: Serialize>(value: &T) -> HttpResponse {
+    match serde_json::to_string(value) {
+        Ok(json) => HttpResponse::Ok()
+            .insert_header(ContentType(mime::APPLICATION_JSON))
+            .body(json),
+        Err(e) => HttpResponse::InternalServerError().body(format!("failed to encode JSON - {e}")),
+    }
+}
+
+/// Parse a Range header for content of size len, returning the first and last bytes
+///
+/// Only a single range is supported. Returns None if the range cannot be satisfied.
+fn parse_range(range: &str, len: u64) -> Option<(u64, u64)> {
+    let range = range.trim().strip_prefix("bytes=")?;
+    if range.contains(',') || len == 0 {
+        return None;
+    }
+    let (start, end) = range.split_once('-')?;
+    let (start, end) = if start.is_empty() {
+        // The final bytes
+        let suffix = end.parse::<u64>().ok()?;
+        if suffix == 0 {
+            return None;
+        }
+        (len.saturating_sub(suffix), len - 1)
+    } else {
+        let start = start.parse::<u64>().ok()?;
+        let end = if end.is_empty() {
+            len - 1
+        } else {
+            end.parse::<u64>().ok()?.min(len - 1)
+        };
+        (start, end)
+    };
+    if start > end || start >= len {
+        return None;
+    }
+    Some((start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test;
+
+    use super::*;
+
+    #[actix_web::test]
+    async fn ranges_outside_the_content_are_not_satisfiable() {
+        let content = TreeFsContent::Staged(web::Bytes::from("0123456789"));
+        let request = test::TestRequest::get()
+            .insert_header((header::RANGE, "bytes=5-"))
+            .to_http_request();
+        let response = file_response(HttpResponse::Ok(), content, &request).await;
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        let body = actix_web::body::to_bytes(response.into_body())
+            .await
+            .unwrap();
+        assert_eq!(body, web::Bytes::from("56789"));
+
+        // Metadata may claim more than the content holds
+        let content = TreeFsContent::Staged(web::Bytes::from("0123"));
+        let request = test::TestRequest::get()
+            .insert_header((header::RANGE, "bytes=5-9"))
+            .to_http_request();
+        let response = file_response(HttpResponse::Ok(), content, &request).await;
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(
+            response.headers().get(header::CONTENT_RANGE).unwrap(),
+            "bytes */4"
+        );
+    }
+
+    #[test]
+    fn byte_ranges_are_parsed() {
+        assert_eq!(parse_range("bytes=0-9", 100), Some((0, 9)));
+        assert_eq!(parse_range("bytes=90-", 100), Some((90, 99)));
+        assert_eq!(parse_range("bytes=-10", 100), Some((90, 99)));
+        assert_eq!(parse_range("bytes=50-500", 100), Some((50, 99)));
+        assert_eq!(parse_range("bytes=100-", 100), None);
+        assert_eq!(parse_range("bytes=0-1,5-6", 100), None);
+        assert_eq!(parse_range("items=0-1", 100), None);
+    }
+}
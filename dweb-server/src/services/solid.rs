@@ -14,3 +14,574 @@
  You should have received a copy of the GNU Affero General Public License
  along with this program. If not, see <https://www.gnu.org/licenses/>.
 */
+
+//! A minimal Solid Pod using Autonomi for storage
+//!
+//! Each pod is a History of Trees owned by the server's app secret key at
+//! <code>/dweb-0/solid/{POD}/</code>, created by the first commit if it doesn't exist.
+//! This implements the Linked Data Platform (LDP) subset of the Solid Protocol used
+//! by most Solid apps:
+//!
+//!   GET/HEAD {CONTAINER}/     LDP Basic Container listing, as Turtle or JSON-LD
+//!   GET/HEAD {RESOURCE}       resource content
+//!   PUT      {RESOURCE}       create or replace a resource, creating missing containers
+//!   PUT      {CONTAINER}/     create a container
+//!   POST     {CONTAINER}/     create a resource named using the Slug header, or a container
+//!                             if the Link header has rel="type" ldp:BasicContainer
+//!   DELETE   {PATH}           delete a resource or an empty container
+//!
+//! Container listings and the WebID profile are served as Turtle or JSON-LD by content
+//! negotiation. Other resources are returned as stored, with a content type from
+//! their extension, or text/turtle if there is none.
+//!
+//! The WebID of a pod is <code>/dweb-0/solid/{POD}/profile/card#me</code>. Unless a
+//! profile has been written to the pod, one is generated which includes the public key
+//! of the owner.
+//!
+//! Access control (WAC/ACP) is not implemented: the server is local, so anything with
+//! access to it has full access to its pods. Changes are staged and published as a new
+//! version of the History as for WebDAV (see tree_fs_mounts).
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use actix_web::{
+    http::{header, Method, StatusCode},
+    post, web,
+    web::Data,
+    HttpRequest, HttpResponse, HttpResponseBuilder,
+};
+use serde_json::{json, Value};
+
+use dweb::client::DwebClient;
+use dweb::files::tree_fs::{TreeFs, TreeFsDirEntry};
+
+use crate::services::api_dweb::v0::MutateResult;
+use crate::services::helpers::*;
+use crate::services::tree_fs_mounts::{
+    add_commit_error, commit_mount_response, get_mount, record_write,
+};
+use crate::services::webdav::{http_date, parent_path, percent_encode, status_for_io_error};
+
+/// Route for Solid pods, under dweb::api::DWEB_API_ROUTE
+pub const SOLID_ROUTE: &str = "/solid";
+
+/// Path of the WebID profile document within a pod
+pub const SOLID_PROFILE_PATH: &str = "/profile/card";
+
+const TURTLE: &str = "text/turtle";
+const JSON_LD: &str = "application/ld+json";
+
+const LDP: &str = "http://www.w3.org/ns/ldp#";
+const LINK_CONTAINER: &str = "<http://www.w3.org/ns/ldp#BasicContainer>; rel=\"type\", <http://www.w3.org/ns/ldp#Container>; rel=\"type\", <http://www.w3.org/ns/ldp#Resource>; rel=\"type\"";
+const LINK_RESOURCE: &str = "<http://www.w3.org/ns/ldp#Resource>; rel=\"type\"";
+const ALLOW: &str = "OPTIONS, GET, HEAD, PUT, POST, DELETE";
+
+/// The actix resource path for Solid requests
+pub fn solid_resource_path() -> String {
+    format!(
+        "{}{SOLID_ROUTE}/{{pod}}{{path:.*}}",
+        dweb::api::DWEB_API_ROUTE
+    )
+}
+
+/// Handle every method for a path within a pod
+pub async fn solid_handler(
+    request: HttpRequest,
+    body: web::Bytes,
+    client: Data<DwebClient>,
+) -> HttpResponse {
+    let method = request.method().clone();
+    println!("DEBUG Solid {method} {}", request.path());
+
+    let pod = request.match_info().query("pod").to_string();
+    let path = request.match_info().query("path").to_string();
+    let path = if path.is_empty() {
+        "/".to_string()
+    } else {
+        path
+    };
+    let is_container = path.ends_with('/');
+
+    if method == Method::OPTIONS {
+        return HttpResponse::NoContent()
+            .insert_header((header::ALLOW, ALLOW))
+            .insert_header(("Accept-Post", "*/*"))
+            .insert_header(("Accept-Put", "*/*"))
+            .insert_header((
+                header::LINK,
+                if is_container {
+                    LINK_CONTAINER
+                } else {
+                    LINK_RESOURCE
+                },
+            ))
+            .finish();
+    }
+
+    let mount = match get_mount(&client, &pod, true).await {
+        Ok(mount) => mount,
+        Err(e) => {
+            return make_error_response_page(
+                Some(StatusCode::NOT_FOUND),
+                &mut HttpResponse::NotFound(),
+                format!("Solid {method}"),
+                &format!("failed to open pod '{pod}' - {e}"),
+            )
+        }
+    };
+    let mut mount = mount.lock().await;
+    let pod_url = pod_url(&request, &pod);
+
+    let response = match method {
+        Method::GET | Method::HEAD => get(&mount.fs, &request, &pod_url, &path).await,
+        Method::PUT => put(&mut mount.fs, &path, body),
+        Method::POST => post_to_container(&mut mount.fs, &request, &pod_url, &path, body),
+        Method::DELETE => delete(&mut mount.fs, &path),
+        _ => Ok(HttpResponse::MethodNotAllowed()
+            .insert_header((header::ALLOW, ALLOW))
+            .finish()),
+    };
+
+    let is_write = method == Method::PUT || method == Method::POST || method == Method::DELETE;
+    let response = match response {
+        Ok(response) => {
+            if is_write && response.status().is_success() {
+                record_write(&pod, &mut mount);
+            }
+            response
+        }
+        Err(e) => HttpResponseBuilder::new(status_for_io_error(&e)).body(e.to_string()),
+    };
+    add_commit_error(response, &mount.commit_error)
+}
+
+/// Publish changes made to a Solid pod
+///
+/// Uploads resources written to the pod and publishes them as a new version of its History.
+/// Changes are also published automatically once no writes have been made for a while,
+/// so this is only needed to publish them sooner.
+///
+/// url: <code>/dweb-0/solid-commit/{POD}</code>
+#[utoipa::path(
+    post,
+    params(
+        ("pod" = String, Path, description = "the name of the pod (a History owned by the server's app secret key)")),
+    responses(
+        (status = StatusCode::CREATED, description = "A MutateResult with the cost and the version published.", body = MutateResult),
+        (status = StatusCode::NOT_FOUND, description = "The pod has not been accessed"),
+        (status = StatusCode::BAD_GATEWAY, description = "Autonomi network error"),
+    ),
+    tags = ["Dweb"],
+)]
+#[post("/solid-commit/{pod}")]
+pub async fn solid_commit_post(request: HttpRequest, pod: web::Path<String>) -> HttpResponse {
+    println!("DEBUG {}", request.path());
+    commit_mount_response(
+        &pod.into_inner(),
+        "/solid-commit POST",
+        "solid_commit_post()",
+    )
+    .await
+}
+
+async fn get(
+    fs: &TreeFs,
+    request: &HttpRequest,
+    pod_url: &str,
+    path: &str,
+) -> std::io::Result<HttpResponse> {
+    let head_only = request.method() == Method::HEAD;
+    let use_json_ld = prefers_json_ld(accept(request));
+
+    if path == SOLID_PROFILE_PATH && !fs.exists(path) {
+        let body = match dweb::helpers::get_app_secret_key() {
+            Ok(secret_key) => {
+                profile_document(pod_url, &secret_key.public_key().to_hex(), use_json_ld)
+            }
+            Err(e) => {
+                return Ok(HttpResponse::InternalServerError()
+                    .body(format!("failed to get the pod owner's key - {e}")))
+            }
+        };
+        return Ok(rdf_response(LINK_RESOURCE, use_json_ld, body, head_only));
+    }
+
+    let metadata = fs.metadata(path)?;
+    if metadata.is_dir {
+        // Absolute so the listing is the same whether or not the request has a trailing '/'
+        let container_url = format!("{pod_url}{}/", percent_encode(path.trim_end_matches('/')));
+        let entries = fs.read_dir(path)?;
+        let body = if use_json_ld {
+            container_json_ld(&container_url, &entries)
+        } else {
+            container_turtle(&container_url, &entries)
+        };
+        return Ok(rdf_response(LINK_CONTAINER, use_json_ld, body, head_only));
+    }
+
+    let mut response = HttpResponse::Ok();
+    response
+        .insert_header((
+            header::CONTENT_TYPE,
+            metadata.content_type.clone().unwrap_or(TURTLE.to_string()),
+        ))
+        .insert_header((header::LAST_MODIFIED, http_date(metadata.modified)))
+        .insert_header((header::LINK, LINK_RESOURCE))
+        .insert_header((header::ALLOW, ALLOW));
+    if head_only {
+        return Ok(response
+            .insert_header((header::CONTENT_LENGTH, metadata.len))
+            .finish());
+    }
+    let content = fs.read(path).await?;
+    Ok(response.body(content))
+}
+
+fn put(fs: &mut TreeFs, path: &str, body: web::Bytes) -> std::io::Result<HttpResponse> {
+    if path.ends_with('/') {
+        if fs.exists(path) {
+            return Ok(HttpResponse::Conflict().body("container already exists"));
+        }
+        fs.create_dir_all(path)?;
+        return Ok(HttpResponse::Created().finish());
+    }
+
+    let existed = fs.exists(path);
+    fs.create_dir_all(&parent_path(path))?;
+    fs.write(path, body)?;
+    if existed {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Ok(HttpResponse::Created().finish())
+    }
+}
+
+fn post_to_container(
+    fs: &mut TreeFs,
+    request: &HttpRequest,
+    pod_url: &str,
+    path: &str,
+    body: web::Bytes,
+) -> std::io::Result<HttpResponse> {
+    let metadata = fs.metadata(path)?;
+    if !metadata.is_dir {
+        return Ok(HttpResponse::MethodNotAllowed()
+            .insert_header((header::ALLOW, "OPTIONS, GET, HEAD, PUT, DELETE"))
+            .finish());
+    }
+
+    let create_container = request
+        .headers()
+        .get_all(header::LINK)
+        .filter_map(|link| link.to_str().ok())
+        .any(|link| {
+            link.contains(&format!("{LDP}BasicContainer"))
+                || link.contains(&format!("{LDP}Container"))
+        });
+
+    let slug = request
+        .headers()
+        .get("Slug")
+        .and_then(|slug| slug.to_str().ok())
+        .unwrap_or("");
+    let extension = if create_container {
+        None
+    } else {
+        extension_for_content_type(content_type(request))
+    };
+    let name = resource_name(slug, extension, now());
+
+    let container = path.trim_end_matches('/');
+    let mut new_path = format!("{container}/{name}");
+    let mut suffix = 1;
+    while fs.exists(&new_path) {
+        new_path = format!("{container}/{}", add_suffix(&name, suffix));
+        suffix = suffix + 1;
+    }
+
+    if create_container {
+        fs.create_dir_all(&new_path)?;
+        new_path = new_path + "/";
+    } else {
+        fs.write(&new_path, body)?;
+    }
+    Ok(HttpResponse::Created()
+        .insert_header((
+            header::LOCATION,
+            format!("{pod_url}{}", percent_encode(&new_path)),
+        ))
+        .finish())
+}
+
+fn delete(fs: &mut TreeFs, path: &str) -> std::io::Result<HttpResponse> {
+    if path == "/" {
+        return Ok(
+            HttpResponse::MethodNotAllowed().body("the root container of a pod cannot be deleted")
+        );
+    }
+    let metadata = fs.metadata(path)?;
+    if metadata.is_dir && !fs.read_dir(path)?.is_empty() {
+        return Ok(HttpResponse::Conflict().body("container is not empty"));
+    }
+    fs.remove(path)?;
+    Ok(HttpResponse::NoContent().finish())
+}
+
+fn rdf_response(link: &str, use_json_ld: bool, body: String, head_only: bool) -> HttpResponse {
+    let mut response = HttpResponse::Ok();
+    response
+        .insert_header((
+            header::CONTENT_TYPE,
+            if use_json_ld { JSON_LD } else { TURTLE },
+        ))
+        .insert_header((header::LINK, link))
+        .insert_header((header::ALLOW, ALLOW))
+        .insert_header(("Accept-Post", "*/*"))
+        .insert_header((header::VARY, "Accept"));
+    if head_only {
+        return response
+            .insert_header((header::CONTENT_LENGTH, body.len()))
+            .finish();
+    }
+    response.body(body)
+}
+
+/// The URL of the root container of a pod, without the trailing '/'
+fn pod_url(request: &HttpRequest, pod: &str) -> String {
+    let connection = request.connection_info();
+    format!(
+        "{}://{}{}{SOLID_ROUTE}/{}",
+        connection.scheme(),
+        connection.host(),
+        dweb::api::DWEB_API_ROUTE,
+        percent_encode(pod)
+    )
+}
+
+fn container_turtle(container_url: &str, entries: &[TreeFsDirEntry]) -> String {
+    let mut turtle = format!(
+        "@prefix ldp: <http://www.w3.org/ns/ldp#>.\n\
+         @prefix stat: <http://www.w3.org/ns/posix/stat#>.\n\n\
+         <{container_url}> a ldp:BasicContainer, ldp:Container, ldp:Resource"
+    );
+    if !entries.is_empty() {
+        let contains: Vec<String> = entries
+            .iter()
+            .map(|entry| format!("<{}>", entry_reference(container_url, entry)))
+            .collect();
+        turtle = turtle + ";\n    ldp:contains " + &contains.join(", ");
+    }
+    turtle = turtle + ".\n";
+
+    for entry in entries {
+        if entry.metadata.is_dir {
+            turtle = turtle
+                + &format!(
+                    "\n<{}> a ldp:BasicContainer, ldp:Container, ldp:Resource.\n",
+                    entry_reference(container_url, entry)
+                );
+        } else {
+            turtle = turtle
+                + &format!(
+                    "\n<{}> a ldp:Resource;\n    stat:size {};\n    stat:mtime {}.\n",
+                    entry_reference(container_url, entry),
+                    entry.metadata.len,
+                    entry.metadata.modified
+                );
+        }
+    }
+    turtle
+}
+
+fn container_json_ld(container_url: &str, entries: &[TreeFsDirEntry]) -> String {
+    let contains: Vec<Value> = entries
+        .iter()
+        .map(|entry| {
+            if entry.metadata.is_dir {
+                json!({
+                    "@id": entry_reference(container_url, entry),
+                    "@type": ["ldp:BasicContainer", "ldp:Container", "ldp:Resource"],
+                })
+            } else {
+                json!({
+                    "@id": entry_reference(container_url, entry),
+                    "@type": ["ldp:Resource"],
+                    "stat:size": entry.metadata.len,
+                    "stat:mtime": entry.metadata.modified,
+                })
+            }
+        })
+        .collect();
+    json!({
+        "@context": {
+            "ldp": LDP,
+            "stat": "http://www.w3.org/ns/posix/stat#",
+        },
+        "@id": container_url,
+        "@type": ["ldp:BasicContainer", "ldp:Container", "ldp:Resource"],
+        "ldp:contains": contains,
+    })
+    .to_string()
+}
+
+/// A WebID profile for the owner of a pod
+fn profile_document(pod_url: &str, public_key_hex: &str, use_json_ld: bool) -> String {
+    if use_json_ld {
+        return json!({
+            "@context": {
+                "foaf": "http://xmlns.com/foaf/0.1/",
+                "solid": "http://www.w3.org/ns/solid/terms#",
+                "pim": "http://www.w3.org/ns/pim/space#",
+                "dweb": "https://codeberg.org/happybeing/dweb#",
+            },
+            "@id": "#me",
+            "@type": ["foaf:Person"],
+            "pim:storage": { "@id": format!("{pod_url}/") },
+            "solid:storage": { "@id": format!("{pod_url}/") },
+            "dweb:publicKey": public_key_hex,
+        })
+        .to_string();
+    }
+    format!(
+        "@prefix foaf: <http://xmlns.com/foaf/0.1/>.\n\
+         @prefix solid: <http://www.w3.org/ns/solid/terms#>.\n\
+         @prefix pim: <http://www.w3.org/ns/pim/space#>.\n\
+         @prefix dweb: <https://codeberg.org/happybeing/dweb#>.\n\n\
+         <> a foaf:PersonalProfileDocument;\n    foaf:primaryTopic <#me>.\n\n\
+         <#me> a foaf:Person;\n    pim:storage <{pod_url}/>;\n    solid:storage <{pod_url}/>;\n    dweb:publicKey \"{public_key_hex}\".\n"
+    )
+}
+
+// With a trailing '/' for a container
+fn entry_reference(container_url: &str, entry: &TreeFsDirEntry) -> String {
+    let slash = if entry.metadata.is_dir { "/" } else { "" };
+    format!("{container_url}{}{slash}", percent_encode(&entry.name))
+}
+
+fn accept(request: &HttpRequest) -> &str {
+    request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .unwrap_or("")
+}
+
+fn content_type(request: &HttpRequest) -> &str {
+    request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok())
+        .unwrap_or("")
+}
+
+/// True if an Accept header prefers JSON-LD to Turtle. Turtle wins a tie.
+fn prefers_json_ld(accept: &str) -> bool {
+    let mut turtle_quality = 0.0;
+    let mut json_ld_quality = 0.0;
+    for media_range in accept.split(',') {
+        let mut parts = media_range.split(';');
+        let media_type = parts.next().unwrap_or("").trim();
+        let mut quality = 1.0;
+        for parameter in parts {
+            if let Some(q) = parameter.trim().strip_prefix("q=") {
+                quality = q.parse::<f32>().unwrap_or(0.0);
+            }
+        }
+        if media_type == TURTLE {
+            turtle_quality = quality;
+        } else if media_type == JSON_LD || media_type == "application/json" {
+            json_ld_quality = f32::max(json_ld_quality, quality);
+        }
+    }
+    json_ld_quality > turtle_quality
+}
+
+fn extension_for_content_type(content_type: &str) -> Option<&'static str> {
+    match content_type.split(';').next().unwrap_or("").trim() {
+        TURTLE => Some("ttl"),
+        JSON_LD => Some("jsonld"),
+        "application/json" => Some("json"),
+        "text/plain" => Some("txt"),
+        "text/html" => Some("html"),
+        "text/markdown" => Some("md"),
+        "image/png" => Some("png"),
+        "image/jpeg" => Some("jpg"),
+        "image/gif" => Some("gif"),
+        "image/svg+xml" => Some("svg"),
+        "application/pdf" => Some("pdf"),
+        _ => None,
+    }
+}
+
+/// Make a name for a resource created by POST from its Slug header
+///
+/// Characters other than letters, digits, '-', '_' and '.' are replaced by '-'. Without
+/// a usable slug the name is made from the time. The extension for the content
+/// type is added unless the name already has one.
+fn resource_name(slug: &str, extension: Option<&str>, time: u64) -> String {
+    let slug: String = slug
+        .trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.' {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    let slug = slug.trim_matches('.');
+    let name = if slug.is_empty() {
+        format!("{time}")
+    } else {
+        slug.to_string()
+    };
+    match extension {
+        Some(extension) if !name.contains('.') => format!("{name}.{extension}"),
+        _ => name,
+    }
+}
+
+// Insert -N before the extension of name
+fn add_suffix(name: &str, suffix: u32) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, extension)) => format!("{stem}-{suffix}.{extension}"),
+        None => format!("{name}-{suffix}"),
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_is_negotiated() {
+        assert!(!prefers_json_ld(""));
+        assert!(!prefers_json_ld("*/*"));
+        assert!(!prefers_json_ld("text/turtle, application/ld+json"));
+        assert!(prefers_json_ld("application/ld+json"));
+        assert!(prefers_json_ld(
+            "text/turtle;q=0.5, application/ld+json;q=0.9"
+        ));
+        assert!(!prefers_json_ld(
+            "text/turtle;q=1.0, application/ld+json;q=0.9"
+        ));
+    }
+
+    #[test]
+    fn names_are_made_from_slugs() {
+        assert_eq!(resource_name("my note", Some("ttl"), 7), "my-note.ttl");
+        assert_eq!(resource_name("photo.png", Some("jpg"), 7), "photo.png");
+        assert_eq!(resource_name("../..", None, 7), "-");
+        assert_eq!(resource_name("", Some("ttl"), 7), "7.ttl");
+        assert_eq!(add_suffix("my-note.ttl", 2), "my-note-2.ttl");
+        assert_eq!(add_suffix("notes", 1), "notes-1");
+    }
+}
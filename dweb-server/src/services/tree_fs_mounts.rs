@@ -0,0 +1,370 @@
+/*
+ Copyright (c) 2025- Mark Hughes
+
+ This program is free software: you can redistribute it and/or modify
+ it under the terms of the GNU Affero General Public License as published by
+ the Free Software Foundation, either version 3 of the License, or
+ (at your option) any later version.
+
+ This program is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY; without even the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU Affero General Public License for more details.
+
+ You should have received a copy of the GNU Affero General Public License
+ along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! A TreeFs for each History accessed by the file APIs (WebDAV, rclone and Solid)
+//!
+//! A mount is named by one of:
+//!
+//!   NAME              the most recent version of a History owned by the server's app secret key (read and write)
+//!   NAME@VERSION      a version of an owned History (read only)
+//!   ADDRESS[@VERSION] the most recent or a given version of any History (read only)
+//!
+//! The APIs share mounts, so changes made by one are seen by the others. Writes are staged
+//! in the TreeFs and published as a new version of the History on request or once no
+//! write has been made for IDLE_COMMIT_SECS. A failed idle commit is tried again after a
+//! delay which doubles with each failure, up to MAX_IDLE_COMMIT_RETRY_SECS. Until a commit
+//! succeeds, responses to requests for the mount include the error in a COMMIT_ERROR_HEADER.
+//!
+//! A mount is only locked while its TreeFs is read or changed in memory. Commits upload a
+//! copy of the TreeFs, so requests are not held up while content is uploaded.
+
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::Duration;
+
+use actix_web::{
+    http::{
+        header::{HeaderName, HeaderValue},
+        StatusCode,
+    },
+    HttpResponse,
+};
+use autonomi::AttoTokens;
+use color_eyre::eyre::{eyre, Result};
+use futures_util::lock::Mutex as AsyncMutex;
+
+use dweb::client::DwebClient;
+use dweb::files::directory::Tree;
+use dweb::files::tree_fs::TreeFs;
+use dweb::helpers::convert::str_to_history_address;
+use dweb::history::History;
+use dweb::storage::DwebType;
+use dweb::token::format_tokens_as_attos;
+
+use crate::services::api_dweb::v0::MutateResult;
+use crate::services::helpers::*;
+
+/// Staged writes are committed once there have been no writes for this long
+pub const IDLE_COMMIT_SECS: u64 = 60;
+
+/// The longest delay before a failed idle commit is tried again
+pub const MAX_IDLE_COMMIT_RETRY_SECS: u64 = 3600;
+
+/// Response header giving the error from the last commit of a mount, if it failed
+pub const COMMIT_ERROR_HEADER: &str = "Dweb-Commit-Error";
+
+const MOUNT_VERSION_SEPARATOR: char = '@';
+
+pub(crate) struct TreeFsMount {
+    pub fs: TreeFs,
+    /// Incremented by each write so an idle commit can tell if there has been another
+    pub writes: u64,
+    /// Held while committing so that only one commit of the mount runs at a time
+    committing: Arc<AsyncMutex<()>>,
+    /// The error from the last commit, cleared when a commit succeeds
+    pub commit_error: Option<String>,
+}
+
+impl TreeFsMount {
+    fn new(fs: TreeFs) -> TreeFsMount {
+        TreeFsMount {
+            fs,
+            writes: 0,
+            committing: Arc::new(AsyncMutex::new(())),
+            commit_error: None,
+        }
+    }
+}
+
+pub(crate) type SharedMount = Arc<AsyncMutex<TreeFsMount>>;
+
+/// MOUNTS holds a TreeFs for each mount accessed
+///
+/// A writeable mount is loaded once, so changes made to the History by
+/// other means are not seen until the server is restarted.
+static MOUNTS: LazyLock<Mutex<HashMap<String, SharedMount>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// Get the mount, opening it if this is the first access
+///
+/// If create_if_missing is true, a NAME which is not an existing History is
+/// mounted as an empty tree and the History is created by the first commit.
+pub(crate) async fn get_mount(
+    client: &DwebClient,
+    mount_name: &str,
+    create_if_missing: bool,
+) -> Result<SharedMount> {
+    if let Some(mount) = find_mount(mount_name) {
+        return Ok(mount);
+    }
+
+    let fs = open_mount(client, mount_name, create_if_missing).await?;
+    let mount = Arc::new(AsyncMutex::new(TreeFsMount::new(fs)));
+    match MOUNTS.lock() {
+        // If another request opened it first, use that
+        Ok(mut mounts) => Ok(mounts
+            .entry(mount_name.to_string())
+            .or_insert(mount)
+            .clone()),
+        Err(_) => Ok(mount),
+    }
+}
+
+/// Mount a TreeFs which has already been opened, replacing any mount with the same name
+#[cfg(test)]
+pub(crate) fn insert_mount(mount_name: &str, fs: TreeFs) {
+    let mount = Arc::new(AsyncMutex::new(TreeFsMount::new(fs)));
+    if let Ok(mut mounts) = MOUNTS.lock() {
+        mounts.insert(mount_name.to_string(), mount);
+    }
+}
+
+/// Get the mount if it has been opened
+pub(crate) fn find_mount(mount_name: &str) -> Option<SharedMount> {
+    match MOUNTS.lock() {
+        Ok(mounts) => mounts.get(mount_name).cloned(),
+        Err(_) => None,
+    }
+}
+
+async fn open_mount(
+    client: &DwebClient,
+    mount_name: &str,
+    create_if_missing: bool,
+) -> Result<TreeFs> {
+    let (name, version) = parse_mount(mount_name)?;
+
+    if version.is_none() && str_to_history_address(&name).is_err() {
+        let app_secret_key = dweb::helpers::get_app_secret_key()?;
+        return match TreeFs::open(client, app_secret_key.clone(), &name).await {
+            Ok(fs) => Ok(fs),
+            Err(e) => {
+                if create_if_missing {
+                    println!("DEBUG mounting '{name}' as a new History - {e}");
+                    Ok(TreeFs::create(client, app_secret_key, &name))
+                } else {
+                    Err(e)
+                }
+            }
+        };
+    }
+
+    let mut history = match str_to_history_address(&name) {
+        Ok(history_address) => {
+            History::<Tree>::from_history_address(client.clone(), history_address, false, 0).await?
+        }
+        Err(_) => {
+            History::<Tree>::from_name(
+                client.clone(),
+                dweb::helpers::get_app_secret_key()?,
+                name.clone(),
+                false,
+                0,
+            )
+            .await?
+        }
+    };
+    let archive_address = history
+        .get_version_entry_value(version.unwrap_or(0), false)
+        .await?;
+    let tree = Tree::from_archive_address(client, archive_address).await?;
+    Ok(TreeFs::read_only(client, mount_name, &tree.archive))
+}
+
+/// Note a successful write to mount and schedule a commit for when writes stop
+pub(crate) fn record_write(mount_name: &str, mount: &mut TreeFsMount) {
+    mount.writes = mount.writes + 1;
+    schedule_idle_commit(mount_name, mount.writes, IDLE_COMMIT_SECS);
+}
+
+// Commit after delay_secs if there have been no more writes, retrying with a longer delay
+// if the commit fails. A further write schedules its own commit, which replaces this one.
+fn schedule_idle_commit(mount_name: &str, writes: u64, delay_secs: u64) {
+    let mount_name = mount_name.to_string();
+    actix_web::rt::spawn(async move {
+        actix_web::rt::time::sleep(Duration::from_secs(delay_secs)).await;
+        let mount = match find_mount(&mount_name) {
+            Some(mount) => mount,
+            None => return,
+        };
+        let is_idle = {
+            let mount = mount.lock().await;
+            mount.writes == writes && mount.fs.has_pending_changes()
+        };
+        if !is_idle {
+            return;
+        }
+
+        match commit_mount(&mount).await {
+            Ok((_cost, version)) => {
+                println!("DEBUG committed '{mount_name}' as version {version}")
+            }
+            Err(e) => {
+                let retry_secs = next_idle_commit_delay(delay_secs);
+                println!(
+                    "DEBUG failed to commit '{mount_name}', will try again in {retry_secs}s - {e}"
+                );
+                schedule_idle_commit(&mount_name, writes, retry_secs);
+            }
+        }
+    });
+}
+
+// The delay before trying again after an idle commit which waited delay_secs has failed
+fn next_idle_commit_delay(delay_secs: u64) -> u64 {
+    delay_secs.saturating_mul(2).min(MAX_IDLE_COMMIT_RETRY_SECS)
+}
+
+/// Commit a copy of the mount's TreeFs, so that the mount is not locked while uploading
+///
+/// Writes made during the commit remain pending for the next commit.
+pub(crate) async fn commit_mount(mount: &SharedMount) -> Result<(AttoTokens, u64)> {
+    let committing = mount.lock().await.committing.clone();
+    let _committing = committing.lock().await;
+
+    let snapshot = mount.lock().await.fs.clone();
+    let mut committed = snapshot.clone();
+    let result = committed.commit().await;
+    let mut mount = mount.lock().await;
+    mount.fs.merge_commit(&snapshot, &committed);
+    match &result {
+        Ok(_) => mount.commit_error = None,
+        // Not an error worth reporting if there was nothing to commit
+        Err(e) if snapshot.has_pending_changes() => mount.commit_error = Some(e.to_string()),
+        Err(_) => {}
+    }
+    result
+}
+
+/// Add a COMMIT_ERROR_HEADER to response if the last commit of the mount failed
+pub(crate) fn add_commit_error(
+    mut response: HttpResponse,
+    commit_error: &Option<String>,
+) -> HttpResponse {
+    if let Some(commit_error) = commit_error {
+        // Header values are limited to visible ASCII
+        let value: String = commit_error
+            .chars()
+            .map(|c| if c.is_ascii_graphic() { c } else { ' ' })
+            .collect();
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::try_from(COMMIT_ERROR_HEADER),
+            HeaderValue::from_str(&value),
+        ) {
+            response.headers_mut().insert(name, value);
+        }
+    }
+    response
+}
+
+/// Commit a mount and respond with a MutateResult giving the cost and version published
+pub(crate) async fn commit_mount_response(
+    name: &str,
+    rest_operation: &str,
+    rest_handler: &str,
+) -> HttpResponse {
+    let mount = match find_mount(name) {
+        Some(mount) => mount,
+        None => {
+            return make_error_response_page(
+                Some(StatusCode::NOT_FOUND),
+                &mut HttpResponse::NotFound(),
+                rest_operation.to_string(),
+                &format!("No changes to commit for '{name}'"),
+            )
+        }
+    };
+
+    let result = match commit_mount(&mount).await {
+        Ok((cost, version)) => MutateResult {
+            dweb_type: DwebType::History,
+            rest_operation: rest_operation.to_string(),
+            status_code: StatusCode::CREATED.as_u16(),
+            status_message: format!("success - version {version}"),
+            cost_in_ant: format_tokens_as_attos(cost.as_atto()),
+            object_name: name.to_string(),
+            ..Default::default()
+        },
+        Err(e) => MutateResult {
+            dweb_type: DwebType::History,
+            rest_operation: rest_operation.to_string(),
+            status_code: StatusCode::BAD_GATEWAY.as_u16(),
+            status_message: format!("failed to commit changes to '{name}' - {e}"),
+            object_name: name.to_string(),
+            ..Default::default()
+        },
+    };
+    result.response(rest_handler)
+}
+
+/// Parse a mount name into a History NAME or ADDRESS and an optional version
+fn parse_mount(mount_name: &str) -> Result<(String, Option<u64>)> {
+    match mount_name.rsplit_once(MOUNT_VERSION_SEPARATOR) {
+        Some((name, version)) => match version.parse::<u64>() {
+            Ok(version) if version > 0 && !name.is_empty() => Ok((name.to_string(), Some(version))),
+            _ => Err(eyre!("invalid version in '{mount_name}'")),
+        },
+        None if !mount_name.is_empty() => Ok((mount_name.to_string(), None)),
+        None => Err(eyre!("missing History NAME or ADDRESS")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mount_names_are_parsed() {
+        assert_eq!(parse_mount("notes").unwrap(), ("notes".to_string(), None));
+        assert_eq!(
+            parse_mount("notes@3").unwrap(),
+            ("notes".to_string(), Some(3))
+        );
+        assert!(parse_mount("notes@0").is_err());
+        assert!(parse_mount("@3").is_err());
+    }
+
+    #[test]
+    fn idle_commit_retries_back_off() {
+        assert_eq!(
+            next_idle_commit_delay(IDLE_COMMIT_SECS),
+            2 * IDLE_COMMIT_SECS
+        );
+        assert_eq!(
+            next_idle_commit_delay(MAX_IDLE_COMMIT_RETRY_SECS / 2 + 1),
+            MAX_IDLE_COMMIT_RETRY_SECS
+        );
+        assert_eq!(
+            next_idle_commit_delay(MAX_IDLE_COMMIT_RETRY_SECS),
+            MAX_IDLE_COMMIT_RETRY_SECS
+        );
+    }
+
+    #[test]
+    fn commit_errors_are_added_to_responses() {
+        let response = add_commit_error(
+            HttpResponse::Ok().finish(),
+            &Some("failed\nto upload".to_string()),
+        );
+        assert_eq!(
+            response.headers().get(COMMIT_ERROR_HEADER).unwrap(),
+            "failed to upload"
+        );
+        let response = add_commit_error(HttpResponse::Ok().finish(), &None);
+        assert!(response.headers().get(COMMIT_ERROR_HEADER).is_none());
+    }
+}
@@ -0,0 +1,636 @@
+/*
+ Copyright (c) 2025- Mark Hughes
+
+ This program is free software: you can redistribute it and/or modify
+ it under the terms of the GNU Affero General Public License as published by
+ the Free Software Foundation, either version 3 of the License, or
+ (at your option) any later version.
+
+ This program is distributed in the hope that it will be useful,
+ but WITHOUT ANY WARRANTY; without even the implied warranty of
+ MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ GNU Affero General Public License for more details.
+
+ You should have received a copy of the GNU Affero General Public License
+ along with this program. If not, see <https://www.gnu.org/licenses/>.
+*/
+
+//! WebDAV access to the files of a History<Tree>
+//!
+//! Each History is a WebDAV share at <code>/dweb-0/webdav/{MOUNT}/</code> where MOUNT is:
+//!
+//!   NAME              the most recent version of a History owned by the server's app secret key (read and write)
+//!   NAME@VERSION      a version of an owned History (read only)
+//!   ADDRESS[@VERSION] the most recent or a given version of any History (read only)
+//!
+//! Collections are the directories of the Tree. Writes are staged in a TreeFs and
+//! published as a new version of the History either by POST /dweb-0/webdav-commit/{NAME}
+//! or automatically once no write has been made for tree_fs_mounts::IDLE_COMMIT_SECS.
+//!
+//! The mount is released before file content is fetched from the network, so a slow
+//! GET does not hold up other requests to the share.
+//!
+//! Locking is not implemented. LOCK returns a token which is never checked, which is
+//! enough for clients which insist on locking before writing.
+
+use std::io::ErrorKind;
+use std::time::{Duration, UNIX_EPOCH};
+
+use actix_web::{
+    http::{
+        header::{self, ContentType, HttpDate},
+        Method, StatusCode,
+    },
+    post, web,
+    web::Data,
+    HttpRequest, HttpResponse, HttpResponseBuilder,
+};
+
+use dweb::client::DwebClient;
+use dweb::files::tree_fs::{TreeFs, TreeFsContent, TreeFsMetadata};
+
+use crate::services::api_dweb::v0::MutateResult;
+use crate::services::helpers::*;
+use crate::services::tree_fs_mounts::{
+    add_commit_error, commit_mount_response, get_mount, record_write,
+};
+
+/// Route for WebDAV shares, under dweb::api::DWEB_API_ROUTE
+pub const WEBDAV_ROUTE: &str = "/webdav";
+
+/// The actix resource path for WebDAV requests
+pub fn webdav_resource_path() -> String {
+    format!(
+        "{}{WEBDAV_ROUTE}/{{mount}}{{path:.*}}",
+        dweb::api::DWEB_API_ROUTE
+    )
+}
+
+/// Handle every WebDAV method for a path within a mount
+pub async fn webdav_handler(
+    request: HttpRequest,
+    body: web::Bytes,
+    client: Data<DwebClient>,
+) -> HttpResponse {
+    let method = request.method().clone();
+    println!("DEBUG WebDAV {method} {}", request.path());
+
+    let mount_name = request.match_info().query("mount").to_string();
+    let path = request.match_info().query("path").to_string();
+    let path = if path.is_empty() {
+        "/".to_string()
+    } else {
+        path
+    };
+
+    if method == Method::OPTIONS {
+        return HttpResponse::Ok()
+            .insert_header(("DAV", "1, 2"))
+            .insert_header((
+                header::ALLOW,
+                "OPTIONS, GET, HEAD, PUT, DELETE, PROPFIND, PROPPATCH, MKCOL, COPY, MOVE, LOCK, UNLOCK",
+            ))
+            .finish();
+    }
+
+    let mount = match get_mount(&client, &mount_name, false).await {
+        Ok(mount) => mount,
+        Err(e) => {
+            return make_error_response_page(
+                Some(StatusCode::NOT_FOUND),
+                &mut HttpResponse::NotFound(),
+                format!("WebDAV {method}"),
+                &format!("failed to open '{mount_name}' - {e}"),
+            )
+        }
+    };
+    let mut mount = mount.lock().await;
+    let base_href = format!(
+        "{}{WEBDAV_ROUTE}/{}",
+        dweb::api::DWEB_API_ROUTE,
+        percent_encode(&mount_name)
+    );
+
+    let response = match method.as_str() {
+        "GET" | "HEAD" => match get(&mount.fs, &path, &base_href, method == Method::HEAD) {
+            Ok(GetResponse::File {
+                mut response,
+                content,
+            }) => {
+                // Release the mount so that other requests don't wait while the
+                // content is fetched from the network
+                let commit_error = mount.commit_error.clone();
+                drop(mount);
+                let response = match content.read().await {
+                    Ok(content) => response.body(content),
+                    Err(e) => HttpResponseBuilder::new(status_for_io_error(&e)).body(e.to_string()),
+                };
+                return add_commit_error(response, &commit_error);
+            }
+            Ok(GetResponse::Done(response)) => Ok(response),
+            Err(e) => Err(e),
+        },
+        "PROPFIND" => propfind(&mount.fs, &path, &base_href, depth(&request)),
+        "PROPPATCH" => proppatch(&mount.fs, &path, &base_href),
+        "PUT" => {
+            let existed = mount.fs.exists(&path);
+            mount.fs.write(&path, body).map(|_| {
+                if existed {
+                    HttpResponse::NoContent().finish()
+                } else {
+                    HttpResponse::Created().finish()
+                }
+            })
+        }
+        "MKCOL" => {
+            if mount.fs.exists(&path) {
+                Ok(HttpResponse::MethodNotAllowed().finish())
+            } else {
+                match mount.fs.metadata(&parent_path(&path)) {
+                    Ok(metadata) if metadata.is_dir => mount
+                        .fs
+                        .create_dir_all(&path)
+                        .map(|_| HttpResponse::Created().finish()),
+                    _ => Ok(HttpResponse::Conflict().finish()),
+                }
+            }
+        }
+        "DELETE" => mount
+            .fs
+            .remove(&path)
+            .map(|_| HttpResponse::NoContent().finish()),
+        "MOVE" | "COPY" => copy_or_move(
+            &mut mount.fs,
+            &request,
+            &path,
+            &base_href,
+            method.as_str() == "COPY",
+        ),
+        "LOCK" => Ok(lock(&path, &base_href)),
+        "UNLOCK" => Ok(HttpResponse::NoContent().finish()),
+        _ => Ok(HttpResponse::MethodNotAllowed().finish()),
+    };
+
+    let is_write = matches!(
+        method.as_str(),
+        "PUT" | "MKCOL" | "DELETE" | "MOVE" | "COPY"
+    );
+    let response = match response {
+        Ok(response) => {
+            if is_write && response.status().is_success() {
+                record_write(&mount_name, &mut mount);
+            }
+            response
+        }
+        Err(e) => HttpResponseBuilder::new(status_for_io_error(&e)).body(e.to_string()),
+    };
+    add_commit_error(response, &mount.commit_error)
+}
+
+/// Commit changes made over WebDAV
+///
+/// Uploads files written to the WebDAV share for the History NAME and publishes them as a
+/// new version. Changes are also committed automatically once no writes have been made for
+/// a while, so this is only needed to publish them sooner.
+///
+/// url: <code>/dweb-0/webdav-commit/{NAME}</code>
+#[utoipa::path(
+    post,
+    params(
+        ("name" = String, Path, description = "NAME of a History owned by the server's app secret key, opened using WebDAV")),
+    responses(
+        (status = StatusCode::CREATED, description = "A MutateResult with the cost and the version published.", body = MutateResult),
+        (status = StatusCode::NOT_FOUND, description = "No changes have been made using WebDAV to the History NAME"),
+        (status = StatusCode::BAD_GATEWAY, description = "Autonomi network error"),
+    ),
+    tags = ["Dweb"],
+)]
+#[post("/webdav-commit/{name}")]
+pub async fn webdav_commit_post(request: HttpRequest, name: web::Path<String>) -> HttpResponse {
+    println!("DEBUG {}", request.path());
+    commit_mount_response(
+        &name.into_inner(),
+        "/webdav-commit POST",
+        "webdav_commit_post()",
+    )
+    .await
+}
+
+// The response to a GET or HEAD, which for a file may need content from the network
+enum GetResponse {
+    Done(HttpResponse),
+    File {
+        response: HttpResponseBuilder,
+        content: TreeFsContent,
+    },
+}
+
+fn get(fs: &TreeFs, path: &str, base_href: &str, head_only: bool) -> std::io::Result<GetResponse> {
+    let metadata = fs.metadata(path)?;
+    if metadata.is_dir {
+        // A simple listing for browsers
+        let mut html = String::from("<html><body><ul>");
+        for entry in fs.read_dir(path)? {
+            let slash = if entry.metadata.is_dir { "/" } else { "" };
+            html = html
+                + &format!(
+                    "<li><a href=\"{base_href}{}{slash}\">{}{slash}</a></li>",
+                    percent_encode(&entry.path),
+                    xml_escape(&entry.name)
+                );
+        }
+        html = html + "</ul></body></html>";
+        return Ok(GetResponse::Done(
+            HttpResponse::Ok()
+                .insert_header(ContentType::html())
+                .body(html),
+        ));
+    }
+
+    let content_type = metadata
+        .content_type
+        .clone()
+        .unwrap_or(mime::APPLICATION_OCTET_STREAM.to_string());
+    let mut response = HttpResponse::Ok();
+    response
+        .insert_header((header::CONTENT_TYPE, content_type))
+        .insert_header((header::LAST_MODIFIED, http_date(metadata.modified)));
+    if head_only {
+        return Ok(GetResponse::Done(
+            response
+                .insert_header((header::CONTENT_LENGTH, metadata.len))
+                .finish(),
+        ));
+    }
+    Ok(GetResponse::File {
+        response,
+        content: fs.content(path)?,
+    })
+}
+
+fn propfind(
+    fs: &TreeFs,
+    path: &str,
+    base_href: &str,
+    depth: Option<u32>,
+) -> std::io::Result<HttpResponse> {
+    let metadata = fs.metadata(path)?;
+    let mut responses = vec![propfind_response(base_href, path, &metadata)];
+    if metadata.is_dir && depth != Some(0) {
+        add_propfind_children(fs, path, base_href, depth, &mut responses)?;
+    }
+    Ok(multistatus(responses))
+}
+
+// Add a response for each entry in directory, recursing while depth allows
+fn add_propfind_children(
+    fs: &TreeFs,
+    directory: &str,
+    base_href: &str,
+    depth: Option<u32>,
+    responses: &mut Vec<String>,
+) -> std::io::Result<()> {
+    for entry in fs.read_dir(directory)? {
+        responses.push(propfind_response(base_href, &entry.path, &entry.metadata));
+        // depth None is "infinity"
+        if entry.metadata.is_dir && depth != Some(1) {
+            add_propfind_children(fs, &entry.path, base_href, depth, responses)?;
+        }
+    }
+    Ok(())
+}
+
+fn propfind_response(base_href: &str, path: &str, metadata: &TreeFsMetadata) -> String {
+    let name = path.rsplit('/').next().unwrap_or("");
+    let mut props = format!("<D:displayname>{}</D:displayname>", xml_escape(name));
+    if metadata.is_dir {
+        props = props + "<D:resourcetype><D:collection/></D:resourcetype>";
+    } else {
+        props = props
+            + &format!(
+                "<D:resourcetype/><D:getcontentlength>{}</D:getcontentlength><D:getlastmodified>{}</D:getlastmodified>",
+                metadata.len,
+                http_date(metadata.modified)
+            );
+        if let Some(content_type) = &metadata.content_type {
+            props = props
+                + &format!(
+                    "<D:getcontenttype>{}</D:getcontenttype>",
+                    xml_escape(content_type)
+                );
+        }
+    }
+    format!(
+        "<D:response><D:href>{}</D:href><D:propstat><D:prop>{props}</D:prop><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>",
+        href(base_href, path, metadata.is_dir)
+    )
+}
+
+// Properties cannot be set, but clients which set them after PUT expect success
+fn proppatch(fs: &TreeFs, path: &str, base_href: &str) -> std::io::Result<HttpResponse> {
+    let metadata = fs.metadata(path)?;
+    Ok(multistatus(vec![format!(
+        "<D:response><D:href>{}</D:href><D:propstat><D:prop/><D:status>HTTP/1.1 200 OK</D:status></D:propstat></D:response>",
+        href(base_href, path, metadata.is_dir)
+    )]))
+}
+
+fn copy_or_move(
+    fs: &mut TreeFs,
+    request: &HttpRequest,
+    path: &str,
+    base_href: &str,
+    is_copy: bool,
+) -> std::io::Result<HttpResponse> {
+    let destination = match request.headers().get("Destination") {
+        Some(destination) => destination.to_str().unwrap_or(""),
+        None => return Ok(HttpResponse::BadRequest().body("missing Destination header")),
+    };
+    let destination = match destination_path(destination, base_href) {
+        Some(destination) => destination,
+        // Moving between shares is not supported
+        None => return Ok(HttpResponse::BadGateway().finish()),
+    };
+
+    let overwrite = match request.headers().get("Overwrite") {
+        Some(overwrite) => overwrite.as_bytes() != b"F",
+        None => true,
+    };
+    let existed = fs.exists(&destination);
+    if existed {
+        if !overwrite {
+            return Ok(HttpResponse::PreconditionFailed().finish());
+        }
+        fs.remove(&destination)?;
+    }
+
+    if is_copy {
+        fs.copy(path, &destination)?;
+    } else {
+        fs.rename(path, &destination)?;
+    }
+    if existed {
+        Ok(HttpResponse::NoContent().finish())
+    } else {
+        Ok(HttpResponse::Created().finish())
+    }
+}
+
+fn lock(path: &str, base_href: &str) -> HttpResponse {
+    let token = format!(
+        "opaquelocktoken:{}",
+        std::time::SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_nanos())
+            .unwrap_or(0)
+    );
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?>\
+        <D:prop xmlns:D=\"DAV:\"><D:lockdiscovery><D:activelock>\
+        <D:locktype><D:write/></D:locktype><D:lockscope><D:exclusive/></D:lockscope>\
+        <D:depth>infinity</D:depth><D:timeout>Second-3600</D:timeout>\
+        <D:locktoken><D:href>{token}</D:href></D:locktoken>\
+        <D:lockroot><D:href>{}</D:href></D:lockroot>\
+        </D:activelock></D:lockdiscovery></D:prop>",
+        href(base_href, path, false)
+    );
+    HttpResponse::Ok()
+        .insert_header(("Lock-Token", format!("<{token}>")))
+        .insert_header(ContentType(mime::TEXT_XML))
+        .body(body)
+}
+
+fn multistatus(responses: Vec<String>) -> HttpResponse {
+    let body = format!(
+        "<?xml version=\"1.0\" encoding=\"utf-8\"?><D:multistatus xmlns:D=\"DAV:\">{}</D:multistatus>",
+        responses.concat()
+    );
+    HttpResponse::build(StatusCode::MULTI_STATUS)
+        .insert_header(ContentType(mime::TEXT_XML))
+        .body(body)
+}
+
+// The Depth header, where None means infinity
+fn depth(request: &HttpRequest) -> Option<u32> {
+    match request.headers().get("Depth") {
+        Some(depth) => match depth.to_str().unwrap_or("infinity") {
+            "0" => Some(0),
+            "1" => Some(1),
+            _ => None,
+        },
+        None => None,
+    }
+}
+
+pub(crate) fn status_for_io_error(error: &std::io::Error) -> StatusCode {
+    match error.kind() {
+        ErrorKind::NotFound => StatusCode::NOT_FOUND,
+        ErrorKind::AlreadyExists => StatusCode::METHOD_NOT_ALLOWED,
+        ErrorKind::NotADirectory | ErrorKind::IsADirectory => StatusCode::CONFLICT,
+        ErrorKind::FileTooLarge => StatusCode::INSUFFICIENT_STORAGE,
+        ErrorKind::ReadOnlyFilesystem | ErrorKind::PermissionDenied | ErrorKind::InvalidInput => {
+            StatusCode::FORBIDDEN
+        }
+        _ => StatusCode::BAD_GATEWAY,
+    }
+}
+
+/// The path within this share of a Destination header, or None if it is not in this share
+fn destination_path(destination: &str, base_href: &str) -> Option<String> {
+    // Remove any scheme and host
+    let destination = match destination.find("://") {
+        Some(scheme_end) => {
+            let after_scheme = &destination[scheme_end + 3..];
+            &after_scheme[after_scheme.find('/')?..]
+        }
+        None => destination,
+    };
+    let destination = percent_decode(destination);
+    let base = percent_decode(base_href);
+    let path = destination.strip_prefix(&base)?;
+    if path.is_empty() {
+        Some("/".to_string())
+    } else if path.starts_with('/') {
+        Some(path.trim_end_matches('/').to_string())
+    } else {
+        None
+    }
+}
+
+pub(crate) fn parent_path(path: &str) -> String {
+    match path.trim_end_matches('/').rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(position) => path[..position].to_string(),
+    }
+}
+
+fn href(base_href: &str, path: &str, is_dir: bool) -> String {
+    let path = percent_encode(path.trim_end_matches('/'));
+    if is_dir {
+        format!("{base_href}{path}/")
+    } else {
+        format!("{base_href}{path}")
+    }
+}
+
+pub(crate) fn http_date(seconds_since_epoch: u64) -> String {
+    HttpDate::from(UNIX_EPOCH + Duration::from_secs(seconds_since_epoch)).to_string()
+}
+
+/// Percent encode a path, leaving '/' and unreserved characters
+pub(crate) fn percent_encode(path: &str) -> String {
+    let mut encoded = String::new();
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' => {
+                encoded.push(byte as char)
+            }
+            _ => encoded = encoded + &format!("%{byte:02X}"),
+        }
+    }
+    encoded
+}
+
+fn percent_decode(encoded: &str) -> String {
+    let bytes = encoded.as_bytes();
+    let mut decoded = Vec::<u8>::new();
+    let mut index = 0;
+    while index < bytes.len() {
+        if bytes[index] == b'%' && index + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[index + 1..index + 3]).unwrap_or("");
+            if let Ok(byte) = u8::from_str_radix(hex, 16) {
+                decoded.push(byte);
+                index = index + 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[index]);
+        index = index + 1;
+    }
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+pub(crate) fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use actix_web::{test, App};
+    use autonomi::SecretKey;
+
+    use dweb::backend::MemoryBackend;
+    use dweb::client::DwebClientConfig;
+
+    use super::*;
+    use crate::services::tree_fs_mounts::insert_mount;
+
+    #[test]
+    fn destinations_are_parsed() {
+        let base_href = "/dweb-0/webdav/notes";
+        assert_eq!(
+            destination_path(
+                "http://127.0.0.1:5537/dweb-0/webdav/notes/a%20b/c.txt",
+                base_href
+            ),
+            Some("/a b/c.txt".to_string())
+        );
+        assert_eq!(
+            destination_path("/dweb-0/webdav/notes/dir/", base_href),
+            Some("/dir".to_string())
+        );
+        assert_eq!(destination_path("/dweb-0/webdav/other/a", base_href), None);
+        assert_eq!(destination_path("/dweb-0/webdav/notesx/a", base_href), None);
+    }
+
+    #[test]
+    fn paths_are_encoded_for_hrefs() {
+        assert_eq!(percent_encode("/a b/ü.txt"), "/a%20b/%C3%BC.txt");
+        assert_eq!(percent_decode("/a%20b/%C3%BC.txt"), "/a b/ü.txt");
+        assert_eq!(
+            href("/dweb-0/webdav/n", "/a b", true),
+            "/dweb-0/webdav/n/a%20b/"
+        );
+        assert_eq!(parent_path("/a/b"), "/a");
+        assert_eq!(parent_path("/a"), "/");
+    }
+
+    fn webdav_request(method: &str, mount: &str, path: &str) -> test::TestRequest {
+        test::TestRequest::default()
+            .method(Method::from_bytes(method.as_bytes()).unwrap())
+            .uri(&format!("/dweb-0/webdav/{mount}{path}"))
+    }
+
+    #[actix_web::test]
+    async fn changes_are_staged_and_committed() {
+        dweb::cache::history_head::set_history_heads_file_path(None);
+        let client =
+            DwebClient::with_backend(Arc::new(MemoryBackend::new()), &DwebClientConfig::default());
+        let owner = SecretKey::random();
+        let mount = "webdav-test";
+        insert_mount(mount, TreeFs::create(&client, owner.clone(), mount));
+
+        let app = test::init_service(
+            App::new()
+                .app_data(Data::new(client.clone()))
+                .route(&webdav_resource_path(), web::route().to(webdav_handler))
+                .service(web::scope(dweb::api::DWEB_API_ROUTE).service(webdav_commit_post)),
+        )
+        .await;
+
+        let put = webdav_request("PUT", mount, "/a.txt").set_payload("hello");
+        let response = test::call_service(&app, put.to_request()).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let put = webdav_request("PUT", mount, "/a.txt").set_payload("hello again");
+        let response = test::call_service(&app, put.to_request()).await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let mkcol = webdav_request("MKCOL", mount, "/docs");
+        let response = test::call_service(&app, mkcol.to_request()).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let destination = format!("http://localhost/dweb-0/webdav/{mount}/docs/b.txt");
+        let move_request =
+            webdav_request("MOVE", mount, "/a.txt").insert_header(("Destination", destination));
+        let response = test::call_service(&app, move_request.to_request()).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let propfind = webdav_request("PROPFIND", mount, "/").insert_header(("Depth", "infinity"));
+        let response = test::call_service(&app, propfind.to_request()).await;
+        assert_eq!(response.status(), StatusCode::MULTI_STATUS);
+        let body = String::from_utf8(test::read_body(response).await.to_vec()).unwrap();
+        assert!(body.contains(&format!("<D:href>/dweb-0/webdav/{mount}/docs/</D:href>")));
+        assert!(body.contains(&format!(
+            "<D:href>/dweb-0/webdav/{mount}/docs/b.txt</D:href>"
+        )));
+        assert!(body.contains("<D:getcontentlength>11</D:getcontentlength>"));
+        assert!(!body.contains("a.txt"));
+
+        let get = webdav_request("GET", mount, "/docs/b.txt");
+        let response = test::call_service(&app, get.to_request()).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            test::read_body(response).await,
+            web::Bytes::from("hello again")
+        );
+
+        let missing = webdav_request("PROPFIND", mount, "/a.txt");
+        let response = test::call_service(&app, missing.to_request()).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let commit = test::TestRequest::post().uri(&format!("/dweb-0/webdav-commit/{mount}"));
+        let response = test::call_service(&app, commit.to_request()).await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let fs = TreeFs::open(&client, owner, mount).await.unwrap();
+        assert_eq!(
+            fs.read("/docs/b.txt").await.unwrap(),
+            web::Bytes::from("hello again")
+        );
+    }
+}
@@ -19,7 +19,7 @@ use actix_web::{get, web::Data, HttpRequest, HttpResponse, Responder};
 use qstring::QString;
 
 use dweb::cache::directory_with_port::*;
-use dweb::files::directory::Tree;
+use dweb::files::directory::{Tree, VersionManifest};
 use dweb::history::History;
 
 use super::make_error_response_page;
@@ -53,7 +53,7 @@ pub async fn dweb_info(
 ) -> impl Responder {
     println!("DEBUG dweb_info()...");
 
-    let directory_version = if our_directory_version.is_some() {
+    let mut directory_version = if our_directory_version.is_some() {
         our_directory_version.as_ref().clone().unwrap()
     } else {
         return make_error_response_page(
@@ -134,6 +134,11 @@ pub async fn dweb_info(
             ("unkown".to_string(), "unknown".to_string())
         };
 
+    // Annotations are only fetched when needed (see Tree::load_version_manifest())
+    directory_version
+        .directory_tree
+        .load_version_manifest(&client)
+        .await;
     make_dweb_info_response(&directory_version, &pointer_max_version, &graph_max_version)
 }
 
@@ -158,6 +163,8 @@ fn make_dweb_info_response(
             ArchiveAddress: {}<br/>
             Current version: {}<br/>
             <br/>
+            {}
+            <br/>
             Max version from pointer: {}<br/>
             Max version from graph: {}<br/>
             <br/>
@@ -170,6 +177,7 @@ fn make_dweb_info_response(
             directory_version.history_address.unwrap().to_hex(),
             directory_version.archive_address.to_hex(),
             version_str,
+            make_version_manifest_html(directory_version.directory_tree.version_manifest.as_ref()),
             pointer_max_version,
             graph_max_version,
         )
@@ -188,3 +196,28 @@ fn make_dweb_info_response(
 
     HttpResponse::Ok().body(body)
 }
+
+fn make_version_manifest_html(version_manifest: Option<&VersionManifest>) -> String {
+    match version_manifest {
+        Some(manifest) => format!(
+            "
+            Message: {}<br/>
+            Author: {}<br/>
+            Published: {}<br/>
+            Tags: {}<br/>",
+            html_escape(&manifest.message.clone().unwrap_or_default()),
+            html_escape(&manifest.author.clone().unwrap_or_default()),
+            manifest.published_string(),
+            html_escape(&manifest.tags.join(", ")),
+        ),
+        None => "No annotations recorded for this version<br/>".to_string(),
+    }
+}
+
+fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
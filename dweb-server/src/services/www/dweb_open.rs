@@ -23,7 +23,7 @@ use dweb::helpers::convert::tuple_from_address_or_name;
 use dweb::web::fetch::response_redirect;
 
 use crate::services::helpers::*;
-use crate::services::init_dweb_server_non_blocking;
+use crate::services::listeners::spawn_listener;
 
 use super::make_error_response_page;
 
@@ -31,11 +31,13 @@ use super::make_error_response_page;
 ///
 /// Path parameters refer to the required version and dweb History:
 ///
-///     [v{VERSION-NUMBER}/]{ADDRESS-OR-NAME}/{REMOTE-PATH}
+///     [v{VERSION-NUMBER-OR-TAG}[--{BRANCH}]/]{ADDRESS-OR-NAME}/{REMOTE-PATH}
 ///
-/// url: <code>http://127.0.0.1:5537/dweb-open/[v<VERSION-NUMBER>/]<ADDRESS-OR-NAME><REMOTE-PATH></code>
+/// url: <code>http://127.0.0.1:5537/dweb-open/[v<VERSION-NUMBER-OR-TAG>[--<BRANCH>]/]<ADDRESS-OR-NAME><REMOTE-PATH></code>
 ///
-/// VERSION-NUMBER      Optional version when ADDRESS-OR-NAME refers to a <code>History<Tree></code>
+/// VERSION-NUMBER-OR-TAG   Optional version, or a tag given when the version was published, when ADDRESS-OR-NAME refers to a <code>History<Tree></code>
+///
+/// BRANCH              Optional named branch of the History, such as 'staging'. The version is then a version of the branch, and 'v--staging' opens its most recent version
 ///
 /// ADDRESS-OR-NAME     A hexadecimal address or a short name referring to a History or PublicArchive
 ///
@@ -95,7 +97,7 @@ pub async fn dweb_open(
         ),
     tags = ["Linking"],
     params(
-        ("VERSION-NUMBER" = Option<u64>, description = "Optional version (integer > 0) of the History<Tree>"),
+        ("VERSION-NUMBER" = Option<String>, description = "Optional version (integer > 0) or version tag of the History<Tree>, optionally followed by '--' and the name of a branch"),
         ("DWEB-NAME", description = "The short name to register for the HISTORY-ADDRESS"),
         ("HISTORY-ADDRESS", description = "A hexadecimal address or a short name referring to a content History"),
         ("REMOTE-PATH" = Option<String>, description = "Optional path to the resource you wish to open. Must begin with \"/\"")
@@ -139,12 +141,12 @@ pub async fn handle_dweb_open(
     client: Data<dweb::client::DwebClient>,
     _our_directory_version: Data<Option<DirectoryVersionWithPort>>,
     _is_local_network: Data<bool>,
-    decoded_params: &(Option<u64>, String, String, String),
+    decoded_params: &(Option<u64>, Option<String>, String, String, String),
 ) -> HttpResponse {
     println!("DEBUG handle_dweb_open()...");
 
-    let (version, as_name, address_or_name, remote_path) = decoded_params;
-    let version = version.clone();
+    let (version, version_tag, as_name, address_or_name, remote_path) = decoded_params;
+    let mut version = version.clone();
 
     let (history_address, archive_address) = tuple_from_address_or_name(&address_or_name);
     if history_address.is_none() && archive_address.is_none() {
@@ -159,6 +161,31 @@ pub async fn handle_dweb_open(
     let client = client.into_inner().as_ref().clone();
     let client_clone = client.clone();
 
+    if let Some(version_tag) = version_tag {
+        let history_address = match history_address {
+            Some(history_address) => history_address,
+            None => {
+                return make_error_response_page(
+                    None,
+                    &mut HttpResponse::BadRequest(),
+                    "/dweb-open error".to_string(),
+                    &format!("Version tag '{version_tag}' can only be used with a History"),
+                );
+            }
+        };
+        version = match version_from_tag(&client, history_address, version_tag).await {
+            Ok(version) => Some(version),
+            Err(e) => {
+                return make_error_response_page(
+                    None,
+                    &mut HttpResponse::NotFound(),
+                    "/dweb-open error".to_string(),
+                    &format!("{e}. Address: {address_or_name}"),
+                );
+            }
+        };
+    }
+
     // TODO Check if we are the handler using our_directory_version
 
     // Look for an existing handler
@@ -174,16 +201,7 @@ pub async fn handle_dweb_open(
             let directory_version_clone = directory_version.clone();
             if !from_cache {
                 // Not in the cache so spawn a server to handle it
-                let mut client_config = client.client_config.clone();
-                client_config.port = Some(directory_version.port);
-                std::thread::spawn(move || {
-                    let _ = init_dweb_server_non_blocking(
-                        &client_config,
-                        Some(client.clone()),
-                        None,
-                        Some(directory_version.clone()),
-                    );
-                });
+                spawn_listener(client.clone(), directory_version);
 
                 // Register a valid 'as_name' unless:
                 // - the as_name given is AS_NAME_NONE ('anonymous')
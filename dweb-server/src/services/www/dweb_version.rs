@@ -25,12 +25,15 @@ use super::make_error_response_page;
 
 /// Open the specified VERSION of the current site. The first version is 1.
 ///
-/// To open the most recent version use VERSION 'latest'.
+/// To open the most recent version use VERSION 'latest'. VERSION can also be a tag
+/// given when the version was published.
 ///
 /// Examples (replace <PORT> with the port currently in the address bar):
 ///
 /// Switch to version 3: <code>http://127.0.0.1:<PORT-NUMBER>/dweb-version/3</code>
 ///
+/// Switch to the version tagged 'release-2': <code>http://127.0.0.1:<PORT-NUMBER>/dweb-version/release-2</code>
+///
 /// Switch to most recent: <code>http://127.0.0.1:<PORT-NUMBER>/dweb-version/latest</code>
 #[utoipa::path(
     responses(
@@ -39,7 +42,7 @@ use super::make_error_response_page;
         ),
     tags = ["Manual"],
     params(
-        ("version" = Option<String>, description = "A version (integer > 0), or 'latest' for the most recent version"),
+        ("version" = Option<String>, description = "A version (integer > 0), a version tag, or 'latest' for the most recent version"),
     )
 )]
 #[get("/dweb-version/{version}")]